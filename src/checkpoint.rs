@@ -0,0 +1,167 @@
+//! Checkpoints of a scripted simulation, so that a long run can be resumed
+//! where it left off.
+//!
+//! A checkpoint holds the simulation sequence, how far the simulator got
+//! through it, and the state of every ring member, as one JSON object.
+
+use std::fs;
+use std::path::Path;
+
+use anyhow::{bail, Error, Result};
+
+use crate::json::Value;
+use crate::record::{field_bool, field_str, field_usize};
+use crate::{Expect, MemberStatus, SimSeq, State};
+
+const FORMAT_NAME: &str = "ring-checkpoint";
+
+/// Version of the checkpoint layout. Bump this whenever it changes.
+const FORMAT_VERSION: u64 = 2;
+
+#[derive(Debug)]
+pub struct Checkpoint {
+    /// The sequence being followed.
+    pub seq: SimSeq,
+    /// The first action of the sequence that hasn't been completed.
+    pub step: usize,
+    /// The member the interrupted step's election was started from, if the
+    /// checkpoint was taken while it was held.
+    pub electing: Option<usize>,
+    /// The coordinator according to the simulator.
+    pub coord_id: usize,
+    /// How many elections each ring member was asked to start so far.
+    pub initiated: Vec<usize>,
+    pub members: Vec<MemberStatus>,
+}
+
+impl Checkpoint {
+    /// Write the checkpoint to `path`, replacing any earlier one only once
+    /// it has been written in full.
+    pub fn save(&self, path: &Path) -> Result<()> {
+        let members = self.members.iter()
+            .map(|m| Value::object([
+                ("id", m.id.into()),
+                ("active", m.active.into()),
+                ("coord_id", m.coord_id.into()),
+                ("state", format!("{:?}", m.state).into()),
+            ]))
+            .collect();
+
+        let checkpoint = Value::object([
+            ("format", FORMAT_NAME.into()),
+            ("version", FORMAT_VERSION.into()),
+            ("ring_size", self.members.len().into()),
+            ("step", self.step.into()),
+            ("electing", self.electing.map(Value::from).unwrap_or(Value::Null)),
+            ("coord_id", self.coord_id.into()),
+            ("initiated", numbers(self.initiated.iter().copied())),
+            ("members", Value::Array(members)),
+            ("actions", Value::Array(
+                self.seq.actions.iter().map(|a| a.to_string().into()).collect()
+            )),
+        ]);
+
+        let tmp = path.with_extension("tmp");
+        fs::write(&tmp, format!("{}\n", checkpoint))?;
+        fs::rename(&tmp, path)?;
+        Ok(())
+    }
+
+    pub fn load(path: &Path) -> Result<Self> {
+        let contents = match fs::read_to_string(path) {
+            Ok(c) => c,
+            Err(e) => bail!("Error reading checkpoint: {}", e),
+        };
+
+        let v = Value::parse(&contents)?;
+
+        if v.get("format").and_then(Value::as_str) != Some(FORMAT_NAME) {
+            bail!("{} is not a ring checkpoint", path.display());
+        }
+
+        let version = v.get("version").and_then(Value::as_u64);
+
+        if version != Some(FORMAT_VERSION) {
+            bail!(
+                "Checkpoint has format version {:?}, but this build reads \
+                version {}", version, FORMAT_VERSION
+            );
+        }
+
+        let ring_size = field_usize(&v, "ring_size")?;
+
+        let numbers = |key: &str| -> Result<Vec<usize>> {
+            v.get(key)
+                .and_then(Value::as_array)
+                .ok_or(Error::msg(format!("Missing field {}", key)))?
+                .iter()
+                .map(|n| {
+                    n.as_u64()
+                        .map(|n| n as usize)
+                        .ok_or(Error::msg(format!("Invalid number in {}", key)))
+                })
+                .collect()
+        };
+
+        let members = v.get("members")
+            .and_then(Value::as_array)
+            .ok_or(Error::msg("Missing field members"))?
+            .iter()
+            .map(|m| {
+                let state = match field_str(m, "state")? {
+                    "Follower" => State::Follower,
+                    "Participant" => State::Participant,
+                    "Coordinator" => State::Coordinator,
+                    s => bail!("Unknown member state {}", s),
+                };
+
+                Ok(MemberStatus {
+                    id: field_usize(m, "id")?,
+                    label: None,
+                    active: field_bool(m, "active")?,
+                    coord_id: field_usize(m, "coord_id")?,
+                    state,
+                    down: Vec::new(),
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        if members.len() != ring_size {
+            bail!("Checkpoint has {} members, not {}", members.len(), ring_size);
+        }
+
+        let actions = v.get("actions")
+            .and_then(Value::as_array)
+            .ok_or(Error::msg("Missing field actions"))?
+            .iter()
+            .map(|a| a.as_str().ok_or(Error::msg("Invalid action")).and_then(str::parse))
+            .collect::<Result<Vec<_>>>()?;
+
+        let seq = SimSeq::from_actions(ring_size, actions, Expect::default())?;
+
+        let step = field_usize(&v, "step")?;
+
+        if step > seq.actions.len() {
+            bail!("Checkpoint is at step {}, past the end of its sequence", step);
+        }
+
+        let initiated = numbers("initiated")?;
+
+        if initiated.len() != ring_size {
+            bail!("Checkpoint has a count per member missing");
+        }
+
+        Ok(Self {
+            seq,
+            step,
+            electing: v.get("electing").and_then(Value::as_u64).map(|n| n as usize),
+            coord_id: field_usize(&v, "coord_id")?,
+            initiated,
+            members,
+        })
+    }
+}
+
+fn numbers(ns: impl Iterator<Item = usize>) -> Value {
+    Value::Array(ns.map(Value::from).collect())
+}
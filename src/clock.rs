@@ -0,0 +1,114 @@
+//! The clock a simulation keeps time by.
+//!
+//! Under the real clock, waits take as long as they say. Under the mock
+//! clock, they advance virtual time and return at once, so a long scenario
+//! runs in however long its elections take. Either way, waiting on another
+//! thread to answer still takes real time, since that thread runs in it,
+//! unless the [`Scheduler`](crate::sched::Scheduler) keeps time for every
+//! thread at once.
+
+use std::fmt;
+use std::ops::Deref;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use crate::sync::{thread, Mutex};
+
+pub trait Clock: fmt::Debug + Send + Sync {
+    fn now(&self) -> Instant;
+
+    /// Let `duration` pass.
+    fn sleep(&self, duration: Duration);
+
+    /// The time waiting on another thread to answer is measured by, real
+    /// time unless every thread keeps this clock's.
+    fn ring_now(&self) -> Instant {
+        Instant::now()
+    }
+
+    /// Let `duration` pass without blocking, as a task sharing its thread
+    /// with others must, returning when by [`ring_now`](Self::ring_now) the
+    /// task may carry on.
+    fn pass(&self, duration: Duration) -> Instant {
+        self.ring_now() + duration
+    }
+
+    /// How long has passed since `earlier`.
+    fn elapsed(&self, earlier: Instant) -> Duration {
+        self.now().saturating_duration_since(earlier)
+    }
+}
+
+/// Time as the system keeps it.
+#[derive(Debug)]
+pub struct RealClock;
+
+impl Clock for RealClock {
+    fn now(&self) -> Instant {
+        Instant::now()
+    }
+
+    fn sleep(&self, duration: Duration) {
+        thread::sleep(duration);
+    }
+}
+
+/// Virtual time, which only passes when someone sleeps.
+#[derive(Debug)]
+pub struct MockClock {
+    start: Instant,
+    elapsed: Mutex<Duration>,
+}
+
+impl MockClock {
+    pub fn new() -> Self {
+        Self { start: Instant::now(), elapsed: Mutex::new(Duration::ZERO) }
+    }
+}
+
+impl Default for MockClock {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Clock for MockClock {
+    fn now(&self) -> Instant {
+        self.start + *self.elapsed.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn sleep(&self, duration: Duration) {
+        let mut elapsed = self.elapsed.lock().unwrap_or_else(|e| e.into_inner());
+        *elapsed += duration;
+    }
+
+    fn pass(&self, duration: Duration) -> Instant {
+        self.sleep(duration);
+        self.ring_now()
+    }
+}
+
+/// A clock shared by the simulator and every ring member, the real one
+/// unless told otherwise.
+#[derive(Debug, Clone)]
+pub struct SharedClock(Arc<dyn Clock>);
+
+impl SharedClock {
+    pub fn new(clock: impl Clock + 'static) -> Self {
+        Self(Arc::new(clock))
+    }
+}
+
+impl Default for SharedClock {
+    fn default() -> Self {
+        Self::new(RealClock)
+    }
+}
+
+impl Deref for SharedClock {
+    type Target = dyn Clock;
+
+    fn deref(&self) -> &Self::Target {
+        &*self.0
+    }
+}
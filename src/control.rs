@@ -0,0 +1,115 @@
+//! A tiny HTTP server driving a running simulation, for scripts and
+//! dashboards to inject faults into the ring while it runs.
+//!
+//! `POST` to an action's words as a path, such as `/toggle/3`,
+//! `/kill/2/from/1`, `/leave/4` or `/assert-coordinator/4`, has the
+//! simulator take it as if given at the repl, and `POST /election` holds
+//! one from the configured origin. `POST /pause` and `POST /resume` pause and resume the
+//! ring, and `GET /status` returns what state each member was last in,
+//! which the ring keeps up even while paused. Answers are JSON.
+
+use std::net::TcpStream;
+
+use anyhow::Result;
+
+use crate::event::{Event, SharedSink, Source};
+use crate::http::{self, error};
+use crate::json::Value;
+use crate::pause::Pause;
+use crate::sync::channel::Sender;
+use crate::Action;
+
+/// What the server drives the ring with.
+#[derive(Debug, Clone)]
+pub struct Control {
+    /// How many members the ring has, to turn down actions naming others.
+    pub ring_size: usize,
+    /// The member `POST /election` starts the election from.
+    pub origin: usize,
+    /// Where to send the actions asked for, such as to the simulator of
+    /// a run driven by [`Driver::Interactive`](crate::Driver::Interactive).
+    pub actions: Sender<Action>,
+    /// What pauses the ring, which should be the run's.
+    pub pause: Pause,
+    pub events: SharedSink,
+}
+
+impl Control {
+    /// Serve the control API at `addr`, such as `127.0.0.1:8080`.
+    pub fn serve(self, addr: &str) -> Result<()> {
+        http::serve(addr, "control", move |stream| self.answer(stream))
+    }
+
+    fn answer(&self, mut stream: TcpStream) -> Result<()> {
+        let (method, path) = http::request(&stream)?;
+
+        let (status, body) = match (method.as_str(), path.as_str()) {
+            ("GET", "/status") => ("200 OK", self.status()),
+            ("GET", _) => ("404 Not Found", error("not found")),
+            ("POST", "/pause") => {
+                if !self.pause.is_paused() {
+                    self.pause.pause();
+                    self.events.emit(Source::Main, &Event::Paused);
+                }
+
+                ("200 OK", Value::object([("paused", true.into())]))
+            }
+            ("POST", "/resume") => {
+                if self.pause.is_paused() {
+                    self.pause.resume();
+                    self.events.emit(Source::Main, &Event::Resumed);
+                }
+
+                ("200 OK", Value::object([("paused", false.into())]))
+            }
+            ("POST", path) => self.act(path),
+            _ => ("405 Method Not Allowed", error("method not allowed")),
+        };
+
+        http::respond(&mut stream, status, "application/json", &body.to_string())
+    }
+
+    /// Have the simulator take the action spelled out by `path`.
+    fn act(&self, path: &str) -> (&'static str, Value) {
+        let words = path.trim_matches('/').split('/').collect::<Vec<_>>();
+
+        let action = match words[..] {
+            ["election"] => Ok(Action::Election { origin: self.origin }),
+            _ => words.join(" ").parse::<Action>(),
+        };
+
+        let action = match action.and_then(|action| action.within(self.ring_size)) {
+            Ok(action) => action,
+            Err(e) => return ("400 Bad Request", error(&e.to_string())),
+        };
+
+        // The simulator is gone once the run has ended.
+        let name = action.to_string();
+
+        match self.actions.send(action) {
+            Ok(()) => ("202 Accepted", Value::object([("action", name.into())])),
+            Err(_) => ("410 Gone", error("the run has ended")),
+        }
+    }
+
+    fn status(&self) -> Value {
+        let members = self.pause.statuses().iter()
+            .map(|m| Value::object([
+                ("id", m.id.into()),
+                ("label", match &m.label {
+                    Some(label) => label.as_str().into(),
+                    None => Value::Null,
+                }),
+                ("active", m.active.into()),
+                ("coord_id", m.coord_id.into()),
+                ("state", format!("{:?}", m.state).into()),
+            ]))
+            .collect();
+
+        Value::object([
+            ("paused", self.pause.is_paused().into()),
+            ("members", Value::Array(members)),
+        ])
+    }
+}
+
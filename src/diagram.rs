@@ -0,0 +1,169 @@
+//! Sequence diagrams of a run, for reports and slides: a lifeline for the
+//! simulator and one for every ring member, and an arrow for every message
+//! one sent another, in the order they were sent, written for Mermaid or
+//! PlantUML to draw.
+//!
+//! A message lost on its way is drawn as such. Left out are the
+//! simulator's asking members for their status and the coordinator's
+//! heartbeats and lease renewals, which would drown the elections out.
+
+use std::fmt::Write;
+use std::path::Path;
+use std::sync::Arc;
+
+use crate::sync::Mutex;
+use crate::{Msg, SimMsg};
+
+/// What a diagram is written for.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Format {
+    #[default]
+    Mermaid,
+    PlantUml,
+}
+
+impl Format {
+    /// The format a diagram at `path` is written in: PlantUML if it's named
+    /// `.puml` or `.plantuml`, and Mermaid otherwise.
+    pub fn of(path: &Path) -> Self {
+        match path.extension().and_then(|e| e.to_str()) {
+            Some("puml" | "plantuml") => Format::PlantUml,
+            _ => Format::Mermaid,
+        }
+    }
+}
+
+/// Whose lifeline an arrow starts or ends at.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Party {
+    Sim,
+    Member(usize),
+}
+
+impl Party {
+    fn name(self) -> String {
+        match self {
+            Party::Sim => "sim".to_string(),
+            Party::Member(id) => format!("m{}", id),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Arrow {
+    from: Party,
+    to: Party,
+    kind: &'static str,
+    lost: bool,
+}
+
+/// The messages sent during a run, as they're sent. Clones draw the same
+/// diagram.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Diagram {
+    arrows: Arc<Mutex<Vec<Arrow>>>,
+}
+
+impl Diagram {
+    /// Note ring member `from` sending `msg` to member `to`, which loses
+    /// it on the way if `lost`.
+    pub(crate) fn sent(&self, from: usize, to: usize, msg: &Msg, lost: bool) {
+        if !matches!(msg, Msg::LeaseRenewal { .. }) {
+            self.push(Arrow {
+                from: Party::Member(from),
+                to: Party::Member(to),
+                kind: msg.kind(),
+                lost,
+            });
+        }
+    }
+
+    /// Note the simulator sending `msg` to ring member `to`.
+    pub(crate) fn sim_sent(&self, to: usize, msg: &Msg) {
+        if !matches!(msg, Msg::SimStatus) {
+            self.push(Arrow {
+                from: Party::Sim,
+                to: Party::Member(to),
+                kind: msg.kind(),
+                lost: false,
+            });
+        }
+    }
+
+    /// Note ring member `from` sending `msg` to the simulator.
+    pub(crate) fn sent_sim(&self, from: usize, msg: &SimMsg) {
+        if !matches!(msg, SimMsg::Status(_) | SimMsg::Heartbeat { .. }) {
+            self.push(Arrow {
+                from: Party::Member(from),
+                to: Party::Sim,
+                kind: msg.kind(),
+                lost: false,
+            });
+        }
+    }
+
+    fn push(&self, arrow: Arrow) {
+        self.arrows.lock().unwrap_or_else(|e| e.into_inner()).push(arrow);
+    }
+
+    /// The diagram of a ring of `ring_size` members, in `format`.
+    pub(crate) fn render(&self, ring_size: usize, format: Format) -> String {
+        let arrows = self.arrows.lock().unwrap_or_else(|e| e.into_inner());
+        let parties = [Party::Sim].into_iter().chain((0..ring_size).map(Party::Member));
+        let mut out = String::new();
+
+        // Writing to a string can't fail.
+        match format {
+            Format::Mermaid => {
+                out.push_str("sequenceDiagram\n");
+
+                for party in parties {
+                    match party {
+                        Party::Sim => out.push_str("    participant sim\n"),
+                        Party::Member(id) => {
+                            let _ = writeln!(out, "    participant {} as {}", party.name(), id);
+                        }
+                    }
+                }
+
+                for a in arrows.iter() {
+                    let arrow = match a.lost {
+                        true => "-x",
+                        false => "->>",
+                    };
+
+                    let _ = writeln!(
+                        out, "    {}{}{}: {}", a.from.name(), arrow, a.to.name(), a.kind
+                    );
+                }
+            }
+            Format::PlantUml => {
+                out.push_str("@startuml\n");
+
+                for party in parties {
+                    match party {
+                        Party::Sim => out.push_str("participant sim\n"),
+                        Party::Member(id) => {
+                            let _ = writeln!(out, "participant \"{}\" as {}", id, party.name());
+                        }
+                    }
+                }
+
+                for a in arrows.iter() {
+                    let arrow = match a.lost {
+                        true => "->x",
+                        false => "->",
+                    };
+
+                    let _ = writeln!(
+                        out, "{} {} {} : {}", a.from.name(), arrow, a.to.name(), a.kind
+                    );
+                }
+
+                out.push_str("@enduml\n");
+            }
+        }
+
+        out
+    }
+}
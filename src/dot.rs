@@ -0,0 +1,155 @@
+//! Graphs of a run's ring, for Graphviz to draw: a node for every member,
+//! showing the state it ended in, and an edge for every pair of members one
+//! of which sent the other messages, showing how many.
+//!
+//! Edges around the ring are always drawn, even if nothing was sent along
+//! them, and any others dashed. A graph can also be taken after every
+//! election, of the state the ring was left in and the messages sent during
+//! it, to follow who was coordinator when.
+
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use crate::sync::Mutex;
+use crate::State;
+
+/// What a graph shows of a ring member.
+#[derive(Debug, Clone)]
+pub(crate) struct Node {
+    pub(crate) id: usize,
+    pub(crate) label: Option<String>,
+    pub(crate) active: bool,
+    pub(crate) crashed: bool,
+    /// The role it plays, if it was asked.
+    pub(crate) state: Option<State>,
+}
+
+#[derive(Debug, Default)]
+struct Counts {
+    /// How many messages each member sent each other over the whole run.
+    run: BTreeMap<(usize, usize), usize>,
+    /// How many since the last election's graph was taken.
+    election: BTreeMap<(usize, usize), usize>,
+    /// The graphs taken after every election, in order.
+    elections: Vec<String>,
+}
+
+/// The messages ring members sent each other during a run, as they're
+/// sent. Clones count the same messages.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Dot {
+    counts: Arc<Mutex<Counts>>,
+}
+
+impl Dot {
+    /// Count ring member `from` sending a message to member `to`.
+    pub(crate) fn sent(&self, from: usize, to: usize) {
+        let mut counts = self.counts.lock().unwrap_or_else(|e| e.into_inner());
+        *counts.run.entry((from, to)).or_default() += 1;
+        *counts.election.entry((from, to)).or_default() += 1;
+    }
+
+    /// Take the graph of election `number`, won by `winner` if anyone, of
+    /// the `nodes` it left behind and the messages sent since the last one.
+    pub(crate) fn election(&self, number: usize, winner: Option<usize>, nodes: &[Node]) {
+        let mut counts = self.counts.lock().unwrap_or_else(|e| e.into_inner());
+        let sent = std::mem::take(&mut counts.election);
+
+        let title = match winner {
+            Some(id) => format!("election {}, won by {}", number, id),
+            None => format!("election {}, won by no one", number),
+        };
+
+        let graph = render(&title, winner, nodes, &sent);
+        counts.elections.push(graph);
+    }
+
+    /// The graph of the whole run, of the `nodes` it ended with and
+    /// coordinator `coord_id`.
+    pub(crate) fn run(&self, coord_id: usize, nodes: &[Node]) -> String {
+        let counts = self.counts.lock().unwrap_or_else(|e| e.into_inner());
+        render("run", Some(coord_id), nodes, &counts.run)
+    }
+
+    /// The graphs taken after every election, each with the path next to
+    /// `path` to write it to: `ring-1.dot` for the first of `ring.dot`.
+    pub(crate) fn elections(&self, path: &Path) -> Vec<(PathBuf, String)> {
+        let counts = self.counts.lock().unwrap_or_else(|e| e.into_inner());
+        let stem = path.file_stem().and_then(|s| s.to_str()).unwrap_or("ring");
+
+        counts.elections.iter()
+            .enumerate()
+            .map(|(i, graph)| {
+                let name = match path.extension().and_then(|e| e.to_str()) {
+                    Some(ext) => format!("{}-{}.{}", stem, i + 1, ext),
+                    None => format!("{}-{}", stem, i + 1),
+                };
+
+                (path.with_file_name(name), graph.clone())
+            })
+            .collect()
+    }
+}
+
+/// The graph titled `title` of `nodes`, `coord_id` among them, which sent
+/// each other messages as `sent` counts.
+fn render(
+    title: &str,
+    coord_id: Option<usize>,
+    nodes: &[Node],
+    sent: &BTreeMap<(usize, usize), usize>,
+) -> String {
+    let n = nodes.len();
+    let mut out = String::new();
+
+    // Writing to a string can't fail.
+    out.push_str("digraph ring {\n");
+    out.push_str("    layout = circo;\n");
+    let _ = writeln!(out, "    label = \"{}\";", title);
+    out.push_str("    node [shape = circle, style = filled, fillcolor = white];\n");
+
+    for node in nodes {
+        let name = match &node.label {
+            Some(label) => format!("{} ({})", node.id, label),
+            None => node.id.to_string(),
+        };
+
+        let (annotation, colour) = match (node.crashed, node.active, node.state) {
+            (true, _, _) => ("crashed", "tomato"),
+            (false, false, _) => ("down", "lightgrey"),
+            (false, true, Some(State::Coordinator)) => ("coordinator", "gold"),
+            (false, true, Some(State::Participant)) => ("participant", "lightblue"),
+            (false, true, Some(State::Follower)) => ("follower", "white"),
+            (false, true, None) => ("up", "white"),
+        };
+
+        let shape = match coord_id == Some(node.id) {
+            true => ", shape = doublecircle",
+            false => "",
+        };
+
+        let _ = writeln!(
+            out, "    m{} [label = \"{}\\n{}\", fillcolor = {}{}];",
+            node.id, name, annotation, colour, shape
+        );
+    }
+
+    for from in 0..n {
+        let to = (from + 1) % n;
+        let count = sent.get(&(from, to)).copied().unwrap_or_default();
+        let _ = writeln!(out, "    m{} -> m{} [label = \"{}\"];", from, to, count);
+    }
+
+    for (&(from, to), count) in sent {
+        if to != (from + 1) % n {
+            let _ = writeln!(
+                out, "    m{} -> m{} [label = \"{}\", style = dashed];", from, to, count
+            );
+        }
+    }
+
+    out.push_str("}\n");
+    out
+}
@@ -0,0 +1,1020 @@
+//! What happens during a simulation, as ring members and the simulator
+//! report it.
+//!
+//! Everything the ring has to say is an [`Event`] emitted to the
+//! simulation's [`Sink`], so that a library user can take it in as it
+//! happens rather than read it back out of the output. The [`Console`] sink,
+//! the default, prints every event as a line of its own, and the
+//! [`JsonLines`] sink writes it as a JSON object of its own instead, and
+//! the [`Tracing`] sink hands it to the `tracing` crate.
+
+use std::collections::HashMap;
+use std::fmt;
+use std::fs::File;
+use std::io::{self, LineWriter, Write};
+use std::ops::Deref;
+use std::path::Path;
+use std::sync::Arc;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use anyhow::{bail, Error, Result};
+
+use crate::clock::SharedClock;
+use crate::json::Value;
+use crate::sync::Mutex;
+use crate::{Msg, Partition, ProcessingDelay, SimMsg};
+
+/// How events are written.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Format {
+    /// A line of text, as the console shows it.
+    #[default]
+    Text,
+    /// A JSON object on a line of its own.
+    Jsonl,
+    /// An event of the `tracing` crate, for whatever subscriber is set.
+    Tracing,
+}
+
+impl std::str::FromStr for Format {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "text" => Ok(Self::Text),
+            "jsonl" => Ok(Self::Jsonl),
+            "tracing" => Ok(Self::Tracing),
+            _ => bail!("Unknown log format {}", s),
+        }
+    }
+}
+
+/// Who emitted an event.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Source {
+    /// The ring member with this id.
+    Member(usize),
+    Sim,
+    /// Whatever builds the ring and tears it down.
+    Main,
+}
+
+impl fmt::Display for Source {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Source::Member(id) => write!(f, "{}", id),
+            Source::Sim => write!(f, "sim"),
+            Source::Main => write!(f, "main"),
+        }
+    }
+}
+
+/// Something that happened during a simulation.
+#[derive(Debug, PartialEq)]
+pub enum Event {
+    // Building and tearing down the ring.
+    /// There's no terminal to draw the dashboard on.
+    NoTerminal,
+    RingCreated,
+    /// The emitter is done with the simulation.
+    Done,
+
+    // The simulator.
+    Waiting(Duration),
+    /// Waiting whole seconds between a sequence's toggles.
+    WaitingSecs(u64),
+    ElectionStarted { origin: usize },
+    /// The election started from `origin` had no result within `timeout`.
+    ElectionStuck { origin: usize, timeout: Duration },
+    /// The ring elected `id` in the election `initiator` started.
+    ElectionWon { id: usize, initiator: usize, epoch: usize },
+    SimToggled { id: usize, attempt: usize },
+    ToggleUnacknowledged(usize),
+    /// The simulator received a message it wasn't waiting for.
+    Ignored(SimMsg),
+    /// The coordinator missed too many heartbeats.
+    PresumedDead(usize),
+    /// The coordinator went down, and is left for the members watching it
+    /// to find so.
+    AwaitingDetection(usize),
+    /// The ring elected the coordinator a sequence expected.
+    Asserted(usize),
+    /// An assertion other than the coordinator's held.
+    AssertionHeld(String),
+    /// An assertion given interactively failed, as reported.
+    AssertionFailed(String),
+    /// A sequence meant to kill or recover `id`, which already was.
+    AlreadyToggled { id: usize, active: bool },
+    /// A member was spawned to join the ring.
+    JoiningRing(usize),
+    /// A member was retired from the ring.
+    LeavingRing(usize),
+    /// A sequence meant `id` to join the ring or leave it, or to toggle it
+    /// once it had left, while it was in the ring as `present` says.
+    AlreadyInRing { id: usize, present: bool },
+    /// Member `id`'s thread failed for `reason`, and it was respawned.
+    MemberCrashed { id: usize, reason: String },
+    /// A sequence had member `id` take `delay` over each message from now
+    /// on.
+    Slowing { id: usize, delay: ProcessingDelay },
+    /// A sequence gave member `id` election priority `priority` from now
+    /// on.
+    Reprioritizing { id: usize, priority: u64 },
+    /// A sequence had coordinator `id` step down, handing over to another.
+    SteppingDown(usize),
+    /// A sequence meant coordinator `id` to step down, with no other active
+    /// member to hand over to.
+    NoHandover(usize),
+    /// A sequence cut the ring into groups of members out of each other's
+    /// reach.
+    Partitioning(Partition),
+    /// A sequence made the ring whole again.
+    Healing,
+    /// A sequence ended at the given step, skipping any after it.
+    Ended(usize),
+    /// Ctrl-C was pressed, ending the run at the step it was on.
+    Interrupted,
+    /// The ring was paused, holding every delivery until resumed.
+    Paused,
+    Resumed,
+    Restarting,
+    Checkpointed(usize),
+    InvariantsHold,
+    /// The ring is not settled, so an election is held.
+    Unsettled(String),
+    NotSettled(String),
+    FailedSteps(usize),
+    VotesCast(Vec<usize>),
+    AuditFailed(String),
+    PublishFailed(String),
+    DashboardFailed(String),
+    ExportedTraces,
+    ExportFailed(String),
+    SentEnd,
+
+    // A ring member.
+    Received(Msg),
+    /// The ring member stopped on an error.
+    Stopped(String),
+    Crashed(String),
+    Stopping,
+    SentStatus,
+    /// The sim toggled the member active or inactive.
+    Toggled(bool),
+    SentToggle,
+    SentToggleForward,
+    ToggleUnreachable(usize),
+    Pinged(usize),
+    AnsweredPing(usize),
+    /// A member answered a ping.
+    Active(usize),
+    Inactive(usize),
+    PingTimeout { id: usize, timeout: Duration },
+    /// A member answered a ping only once it was retried, so is slow
+    /// rather than down.
+    Slow { id: usize, attempt: u32 },
+    /// The coordinator the member watches left `missed` pings in a row
+    /// unanswered.
+    MissedPing { id: usize, missed: u32 },
+    /// The member, as coordinator, sent a renewal of its lease around the
+    /// ring.
+    RenewingLease,
+    /// The coordinator the member follows renewed its lease.
+    LeaseRenewed(usize),
+    /// The lease of the coordinator the member follows ran out unrenewed,
+    /// so the member starts an election.
+    LeaseExpired(usize),
+    /// The member found the coordinator gone, so holds a pre-vote on
+    /// whether most of the ring does too before starting an election.
+    PreVoting(usize),
+    /// The member, asked in `origin`'s pre-vote, found the coordinator
+    /// gone if it `agrees`.
+    PreVoted { origin: usize, agrees: bool },
+    /// The member's pre-vote came back around with `agreed` of the
+    /// `polled` members asked finding the coordinator gone, most of them,
+    /// so it starts an election.
+    PreVoteWon { agreed: usize, polled: usize },
+    /// The member's pre-vote came back around with too few of the members
+    /// asked finding the coordinator gone to start an election.
+    PreVoteLost { agreed: usize, polled: usize },
+    /// The member's pre-vote on the coordinator came back around once it
+    /// no longer followed it.
+    StalePreVote(usize),
+    Rescanning,
+    /// The member routes around a member that didn't answer from now on,
+    /// telling the ring to as well.
+    DeclaredDown(usize),
+    /// The member heard another is down, so routes around it.
+    MemberDown(usize),
+    /// The member heard another is up after all, so routes through it
+    /// again.
+    MemberUp(usize),
+    /// The member heard it was found down, so tells the ring it's up.
+    Refuting(usize),
+    /// The member came back up, so routes through every member again and
+    /// tells the ring to route through it.
+    Rejoined,
+    /// The simulator told the member another left the ring, so it's passed
+    /// by.
+    MemberLeft(usize),
+    /// The simulator told the member another joined the ring.
+    MemberJoined(usize),
+    /// The member restarted, having forgotten the coordinator it followed.
+    Restarted,
+    /// The member's thread failed, and it started over as if restarted.
+    Respawned,
+    /// The simulator told the member to take this long over each message
+    /// from another from now on.
+    Slowed(ProcessingDelay),
+    /// The simulator gave the member this election priority from now on.
+    Reprioritized(u64),
+    /// The simulator told the member, as coordinator, to step down, so it
+    /// starts an election it stands in no part of.
+    HandingOver,
+    Sent(usize),
+    /// A fault lost a message on its way to `to`.
+    Dropped { msg: Msg, to: usize },
+    /// A partition cut a message to `to` off.
+    Blocked { msg: Msg, to: usize },
+    /// A fault delivered a message to `to` twice.
+    Duplicated { msg: Msg, to: usize },
+    /// A fault held a message to `to` back, delivering it after the next
+    /// one sent.
+    Reordered { msg: Msg, to: usize },
+    /// The coordinator is down, so the member starts an election.
+    DetectedDown(usize),
+    /// The member restarted knowing no coordinator, so starts an election
+    /// to find one.
+    SeekingCoordinator,
+    /// The member dropped an election whose votes and keys, as many as
+    /// given, don't fit the ring, as from a peer sending garbage.
+    MisfitElection { votes: usize, keys: usize },
+    /// The member was asked to start an election while inactive.
+    InactiveForElection,
+    HandedElection(usize),
+    NoneToHandTo,
+    ForwardingElection,
+    NoneToForwardTo,
+    /// The member joined the election, casting its vote in a ring
+    /// election.
+    Joined,
+    AlreadyVoted(usize),
+    /// The member dropped the election `initiator` started in `epoch`,
+    /// having voted in the one `into` started alongside it.
+    ElectionAbsorbed { initiator: usize, epoch: usize, into: usize },
+    ElectionEnded { initiator: usize, epoch: usize },
+    /// The member ignored a result naming `id` coordinator in `epoch`,
+    /// having heard of the later epoch `latest`.
+    StaleResult { id: usize, epoch: usize, latest: usize },
+    /// The member follows the coordinator it learned of.
+    CoordinatorElected(usize),
+    SentResultForward,
+    SentResult(usize),
+    SentResultToSim,
+    ForwardedCandidate(usize),
+    /// A Hirschberg–Sinclair candidate probing further.
+    Probing { hops: usize },
+    SwallowedProbe(usize),
+    BeatenBy(usize),
+    RepliedToProbe(usize),
+    StaleProbe,
+    StaleReply,
+    AnsweredElection(usize),
+    Challenged(Vec<usize>),
+    /// A higher member answered the member's challenge.
+    Answered,
+    Unanswered,
+    Acknowledged { acks: usize, of: usize },
+    ToldCoordinator(usize),
+    Outranks(usize),
+}
+
+impl fmt::Display for Event {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Event::NoTerminal => write!(f, "not attached to a terminal, so no dashboard"),
+            Event::RingCreated => write!(f, "election ring created"),
+            Event::Done => write!(f, "done"),
+
+            Event::Waiting(duration) => write!(f, "waiting for {:?}", duration),
+            Event::WaitingSecs(secs) => write!(f, "waiting for {}s", secs),
+            Event::ElectionStarted { origin } => write!(f, "election started from {}", origin),
+            Event::ElectionStuck { origin, timeout } => {
+                write!(f, "election started from {} had no result within {:?}", origin, timeout)
+            }
+            Event::ElectionWon { id, initiator, epoch: 0 } => {
+                write!(f, "{} won the election started by {}", id, initiator)
+            }
+            Event::ElectionWon { id, initiator, epoch } => {
+                write!(f, "{} won the election started by {}, in epoch {}", id, initiator, epoch)
+            }
+            Event::SimToggled { id, attempt } => {
+                write!(f, "toggled {} (attempt {})", id, attempt)
+            }
+            Event::ToggleUnacknowledged(id) => {
+                write!(f, "toggle of {} was not acknowledged", id)
+            }
+            Event::Ignored(msg) => write!(f, "ignoring {:?}", msg),
+            Event::PresumedDead(id) => write!(f, "coordinator {} presumed dead", id),
+            Event::AwaitingDetection(id) => {
+                write!(f, "coordinator {} is down, waiting for the ring to find out", id)
+            }
+            Event::Asserted(id) => write!(f, "coordinator {} as asserted", id),
+            Event::AssertionHeld(assertion) => write!(f, "{} holds", assertion),
+            Event::AssertionFailed(report) => write!(f, "{}", report),
+            Event::AlreadyToggled { id, active: true } => write!(f, "{} is already up", id),
+            Event::AlreadyToggled { id, active: false } => write!(f, "{} is already down", id),
+            Event::JoiningRing(id) => write!(f, "{} joins the ring", id),
+            Event::LeavingRing(id) => write!(f, "{} leaves the ring", id),
+            Event::AlreadyInRing { id, present: true } => {
+                write!(f, "{} is already in the ring", id)
+            }
+            Event::AlreadyInRing { id, present: false } => write!(f, "{} is not in the ring", id),
+            Event::MemberCrashed { id, reason } => {
+                write!(f, "{} crashed and was respawned: {}", id, reason)
+            }
+            Event::Slowing { id, delay } if *delay == ProcessingDelay::default() => {
+                write!(f, "{} takes no time over messages from now on", id)
+            }
+            Event::Slowing { id, delay } => {
+                write!(f, "{} takes {} over each message from now on", id, delay)
+            }
+            Event::Reprioritizing { id, priority } => {
+                write!(f, "{} stands with priority {} from now on", id, priority)
+            }
+            Event::SteppingDown(id) => write!(f, "coordinator {} steps down", id),
+            Event::NoHandover(id) => {
+                write!(f, "coordinator {} has no active member to hand over to", id)
+            }
+            Event::Partitioning(partition) => write!(f, "partitioning the ring into {}", partition),
+            Event::Healing => write!(f, "healing the partition"),
+            Event::Ended(step) => write!(f, "sequence ended at step {}", step),
+            Event::Interrupted => write!(f, "interrupted, ending the run"),
+            Event::Paused => write!(f, "paused"),
+            Event::Resumed => write!(f, "resumed"),
+            Event::Restarting => write!(f, "restarting the interrupted election"),
+            Event::Checkpointed(step) => write!(f, "checkpointed step {}", step),
+            Event::InvariantsHold => write!(f, "invariants hold"),
+            Event::Unsettled(reason) => write!(f, "{}, holding an election", reason),
+            Event::NotSettled(reason) => write!(f, "ring did not settle: {}", reason),
+            Event::FailedSteps(failures) => write!(f, "{} steps failed to settle", failures),
+            Event::VotesCast(voters) => write!(f, "votes cast by {:?}", voters),
+            Event::AuditFailed(reason) => write!(f, "audit failed: {}", reason),
+            Event::PublishFailed(e) => write!(f, "could not publish status: {}", e),
+            Event::DashboardFailed(e) => write!(f, "could not update the dashboard: {}", e),
+            Event::ExportedTraces => write!(f, "exported traces"),
+            Event::ExportFailed(e) => write!(f, "could not export traces: {}", e),
+            Event::SentEnd => write!(f, "sent end signal"),
+
+            Event::Received(msg) => write!(f, "received {:?}", msg),
+            Event::Stopped(e) => write!(f, "stopped: {}", e),
+            Event::Crashed(reason) => write!(f, "crashed: {}", reason),
+            Event::Stopping => write!(f, "will now stop"),
+            Event::SentStatus => write!(f, "sent status to sim"),
+            Event::Toggled(active) => write!(f, "active = {}", active),
+            Event::SentToggle => write!(f, "sent toggle to sim"),
+            Event::SentToggleForward => write!(f, "sent toggle forward"),
+            Event::ToggleUnreachable(id) => {
+                write!(f, "toggle target {} is not in the ring", id)
+            }
+            Event::Pinged(id) => write!(f, "pinged {}", id),
+            Event::AnsweredPing(id) => write!(f, "answered ping from {}", id),
+            Event::Active(id) => write!(f, "{} is active", id),
+            Event::Inactive(id) => write!(f, "{} is inactive", id),
+            Event::PingTimeout { id, timeout } => {
+                write!(f, "no answer from {} within {:?}", id, timeout)
+            }
+            Event::Slow { id, attempt } => {
+                write!(f, "{} is slow, answering only attempt {}", id, attempt)
+            }
+            Event::MissedPing { id, missed } => {
+                write!(f, "coordinator {} missed {} ping(s) in a row", id, missed)
+            }
+            Event::RenewingLease => write!(f, "renewing lease"),
+            Event::LeaseRenewed(id) => write!(f, "coordinator {} renewed its lease", id),
+            Event::LeaseExpired(id) => {
+                write!(f, "lease of coordinator {} expired, starting election", id)
+            }
+            Event::PreVoting(id) => {
+                write!(f, "coordinator {} seems gone, holding a pre-vote", id)
+            }
+            Event::PreVoted { origin, agrees: true } => {
+                write!(f, "agreed in {}'s pre-vote that the coordinator is gone", origin)
+            }
+            Event::PreVoted { origin, agrees: false } => {
+                write!(f, "disagreed in {}'s pre-vote that the coordinator is gone", origin)
+            }
+            Event::PreVoteWon { agreed, polled } => {
+                write!(f, "pre-vote carried, {} of {} agreeing, starting election", agreed, polled)
+            }
+            Event::PreVoteLost { agreed, polled } => {
+                write!(f, "pre-vote failed, only {} of {} agreeing", agreed, polled)
+            }
+            Event::StalePreVote(id) => {
+                write!(f, "pre-vote on coordinator {} came back once no longer followed", id)
+            }
+            Event::Rescanning => write!(f, "no active member found, scanning again"),
+            Event::DeclaredDown(id) => write!(f, "declared {} down, routing around it", id),
+            Event::MemberDown(id) => write!(f, "heard {} is down, routing around it", id),
+            Event::MemberUp(id) => write!(f, "heard {} is up, routing through it", id),
+            Event::Refuting(id) => write!(f, "{} declared this member down, refuting", id),
+            Event::Rejoined => write!(f, "back up, rejoining the ring"),
+            Event::MemberLeft(id) => write!(f, "{} left the ring, passing it by", id),
+            Event::MemberJoined(id) => write!(f, "{} joined the ring, passing through it", id),
+            Event::Restarted => write!(f, "restarted, forgetting the coordinator"),
+            Event::Respawned => write!(f, "respawned with fresh state"),
+            Event::Slowed(delay) if *delay == ProcessingDelay::default() => {
+                write!(f, "no longer slowed")
+            }
+            Event::Slowed(delay) => write!(f, "slowed to take {} over each message", delay),
+            Event::Reprioritized(priority) => write!(f, "stands with priority {}", priority),
+            Event::HandingOver => write!(f, "stepping down, handing over to another coordinator"),
+            Event::Sent(id) => write!(f, "sent message to {}", id),
+            Event::Dropped { msg, to } => write!(f, "dropped {:?} to {}", msg, to),
+            Event::Blocked { msg, to } => {
+                write!(f, "blocked {:?} to {} across the partition", msg, to)
+            }
+            Event::Duplicated { msg, to } => write!(f, "duplicated {:?} to {}", msg, to),
+            Event::Reordered { msg, to } => {
+                write!(f, "reordered {:?} to {} after the next message", msg, to)
+            }
+            Event::DetectedDown(id) => {
+                write!(f, "detected coordinator {} down, starting election", id)
+            }
+            Event::SeekingCoordinator => {
+                write!(f, "knows no coordinator since restarting, starting election")
+            }
+            Event::MisfitElection { votes, keys } => {
+                write!(
+                    f, "dropped election with {} votes and {} keys, not one per member",
+                    votes, keys
+                )
+            }
+            Event::InactiveForElection => {
+                write!(f, "received election from sim, but am inactive!")
+            }
+            Event::HandedElection(id) => write!(f, "handed election to {}", id),
+            Event::NoneToHandTo => write!(f, "no active member to hand election to"),
+            Event::ForwardingElection => write!(f, "forwarding election"),
+            Event::NoneToForwardTo => write!(f, "no active member to forward election to"),
+            Event::Joined => write!(f, "joined election"),
+            Event::AlreadyVoted(id) => write!(f, "{} has already voted", id),
+            Event::ElectionAbsorbed { initiator, epoch, into } => write!(
+                f, "absorbed the election started by {} in epoch {} into {}'s",
+                initiator, epoch, into
+            ),
+            Event::ElectionEnded { initiator, epoch: 0 } => {
+                write!(f, "election started by {} ended", initiator)
+            }
+            Event::ElectionEnded { initiator, epoch } => {
+                write!(f, "election started by {} ended, in epoch {}", initiator, epoch)
+            }
+            Event::StaleResult { id, epoch, latest } => write!(
+                f, "ignored stale result of epoch {} naming {}, having heard of epoch {}",
+                epoch, id, latest
+            ),
+            Event::CoordinatorElected(id) => write!(f, "{} won the election", id),
+            Event::SentResultForward => write!(f, "sent result forward"),
+            Event::SentResult(id) => write!(f, "sent result to {}", id),
+            Event::SentResultToSim => write!(f, "sent result to sim"),
+            Event::ForwardedCandidate(id) => write!(f, "forwarded candidate {}", id),
+            Event::Probing { hops } => write!(f, "probing {} members each way", hops),
+            Event::SwallowedProbe(id) => write!(f, "swallowed probe from {}", id),
+            Event::BeatenBy(id) => write!(f, "beaten by {}", id),
+            Event::RepliedToProbe(id) => write!(f, "replied to probe from {}", id),
+            Event::StaleProbe => write!(f, "ignored probe from an earlier election"),
+            Event::StaleReply => write!(f, "ignored reply from another election"),
+            Event::AnsweredElection(id) => write!(f, "answered election from {}", id),
+            Event::Challenged(higher) => write!(f, "challenged {:?}", higher),
+            Event::Answered => write!(f, "was answered, waiting for the coordinator"),
+            Event::Unanswered => write!(f, "no higher member answered"),
+            Event::Acknowledged { acks, of } => {
+                write!(f, "{} of {} members acknowledged", acks, of)
+            }
+            Event::ToldCoordinator(id) => write!(f, "told {} it is the coordinator", id),
+            Event::Outranks(id) => write!(f, "outranks coordinator {}", id),
+        }
+    }
+}
+
+impl Event {
+    /// The name of the event's variant.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Event::NoTerminal => "NoTerminal",
+            Event::RingCreated => "RingCreated",
+            Event::Done => "Done",
+            Event::Waiting(_) => "Waiting",
+            Event::WaitingSecs(_) => "WaitingSecs",
+            Event::ElectionStarted { .. } => "ElectionStarted",
+            Event::ElectionStuck { .. } => "ElectionStuck",
+            Event::ElectionWon { .. } => "ElectionWon",
+            Event::SimToggled { .. } => "SimToggled",
+            Event::ToggleUnacknowledged(_) => "ToggleUnacknowledged",
+            Event::Ignored(_) => "Ignored",
+            Event::PresumedDead(_) => "PresumedDead",
+            Event::AwaitingDetection(_) => "AwaitingDetection",
+            Event::Asserted(_) => "Asserted",
+            Event::AssertionHeld(_) => "AssertionHeld",
+            Event::AssertionFailed(_) => "AssertionFailed",
+            Event::AlreadyToggled { .. } => "AlreadyToggled",
+            Event::JoiningRing(_) => "JoiningRing",
+            Event::LeavingRing(_) => "LeavingRing",
+            Event::AlreadyInRing { .. } => "AlreadyInRing",
+            Event::MemberCrashed { .. } => "MemberCrashed",
+            Event::Slowing { .. } => "Slowing",
+            Event::Reprioritizing { .. } => "Reprioritizing",
+            Event::SteppingDown(_) => "SteppingDown",
+            Event::NoHandover(_) => "NoHandover",
+            Event::Partitioning(_) => "Partitioning",
+            Event::Healing => "Healing",
+            Event::Ended(_) => "Ended",
+            Event::Interrupted => "Interrupted",
+            Event::Paused => "Paused",
+            Event::Resumed => "Resumed",
+            Event::Restarting => "Restarting",
+            Event::Checkpointed(_) => "Checkpointed",
+            Event::InvariantsHold => "InvariantsHold",
+            Event::Unsettled(_) => "Unsettled",
+            Event::NotSettled(_) => "NotSettled",
+            Event::FailedSteps(_) => "FailedSteps",
+            Event::VotesCast(_) => "VotesCast",
+            Event::AuditFailed(_) => "AuditFailed",
+            Event::PublishFailed(_) => "PublishFailed",
+            Event::DashboardFailed(_) => "DashboardFailed",
+            Event::ExportedTraces => "ExportedTraces",
+            Event::ExportFailed(_) => "ExportFailed",
+            Event::SentEnd => "SentEnd",
+            Event::Received(_) => "Received",
+            Event::Stopped(_) => "Stopped",
+            Event::Crashed(_) => "Crashed",
+            Event::Stopping => "Stopping",
+            Event::SentStatus => "SentStatus",
+            Event::Toggled(_) => "Toggled",
+            Event::SentToggle => "SentToggle",
+            Event::SentToggleForward => "SentToggleForward",
+            Event::ToggleUnreachable(_) => "ToggleUnreachable",
+            Event::Pinged(_) => "Pinged",
+            Event::AnsweredPing(_) => "AnsweredPing",
+            Event::Active(_) => "Active",
+            Event::Inactive(_) => "Inactive",
+            Event::PingTimeout { .. } => "PingTimeout",
+            Event::Slow { .. } => "Slow",
+            Event::MissedPing { .. } => "MissedPing",
+            Event::RenewingLease => "RenewingLease",
+            Event::LeaseRenewed(_) => "LeaseRenewed",
+            Event::LeaseExpired(_) => "LeaseExpired",
+            Event::PreVoting(_) => "PreVoting",
+            Event::PreVoted { .. } => "PreVoted",
+            Event::PreVoteWon { .. } => "PreVoteWon",
+            Event::PreVoteLost { .. } => "PreVoteLost",
+            Event::StalePreVote(_) => "StalePreVote",
+            Event::Rescanning => "Rescanning",
+            Event::DeclaredDown(_) => "DeclaredDown",
+            Event::MemberDown(_) => "MemberDown",
+            Event::MemberUp(_) => "MemberUp",
+            Event::Refuting(_) => "Refuting",
+            Event::Rejoined => "Rejoined",
+            Event::MemberLeft(_) => "MemberLeft",
+            Event::MemberJoined(_) => "MemberJoined",
+            Event::Restarted => "Restarted",
+            Event::Respawned => "Respawned",
+            Event::Slowed(_) => "Slowed",
+            Event::Reprioritized(_) => "Reprioritized",
+            Event::HandingOver => "HandingOver",
+            Event::Sent(_) => "Sent",
+            Event::Dropped { .. } => "Dropped",
+            Event::Blocked { .. } => "Blocked",
+            Event::Duplicated { .. } => "Duplicated",
+            Event::Reordered { .. } => "Reordered",
+            Event::DetectedDown(_) => "DetectedDown",
+            Event::SeekingCoordinator => "SeekingCoordinator",
+            Event::MisfitElection { .. } => "MisfitElection",
+            Event::InactiveForElection => "InactiveForElection",
+            Event::HandedElection(_) => "HandedElection",
+            Event::NoneToHandTo => "NoneToHandTo",
+            Event::ForwardingElection => "ForwardingElection",
+            Event::NoneToForwardTo => "NoneToForwardTo",
+            Event::Joined => "Joined",
+            Event::AlreadyVoted(_) => "AlreadyVoted",
+            Event::ElectionAbsorbed { .. } => "ElectionAbsorbed",
+            Event::ElectionEnded { .. } => "ElectionEnded",
+            Event::StaleResult { .. } => "StaleResult",
+            Event::CoordinatorElected(_) => "CoordinatorElected",
+            Event::SentResultForward => "SentResultForward",
+            Event::SentResult(_) => "SentResult",
+            Event::SentResultToSim => "SentResultToSim",
+            Event::ForwardedCandidate(_) => "ForwardedCandidate",
+            Event::Probing { .. } => "Probing",
+            Event::SwallowedProbe(_) => "SwallowedProbe",
+            Event::BeatenBy(_) => "BeatenBy",
+            Event::RepliedToProbe(_) => "RepliedToProbe",
+            Event::StaleProbe => "StaleProbe",
+            Event::StaleReply => "StaleReply",
+            Event::AnsweredElection(_) => "AnsweredElection",
+            Event::Challenged(_) => "Challenged",
+            Event::Answered => "Answered",
+            Event::Unanswered => "Unanswered",
+            Event::Acknowledged { .. } => "Acknowledged",
+            Event::ToldCoordinator(_) => "ToldCoordinator",
+            Event::Outranks(_) => "Outranks",
+        }
+    }
+
+    /// What the event carries, as a JSON object.
+    fn payload(&self) -> Value {
+        match self {
+            Event::NoTerminal => Value::object([]),
+            Event::RingCreated => Value::object([]),
+            Event::Done => Value::object([]),
+            Event::Waiting(duration) => Value::object([("duration_ms", millis(*duration))]),
+            Event::WaitingSecs(secs) => Value::object([("secs", (*secs).into())]),
+            Event::ElectionStarted { origin } => Value::object([("origin", (*origin).into())]),
+            Event::ElectionStuck { origin, timeout } => Value::object([
+                ("origin", (*origin).into()),
+                ("timeout_ms", millis(*timeout)),
+            ]),
+            Event::ElectionWon { id, initiator, epoch } => Value::object([
+                ("id", (*id).into()),
+                ("initiator", (*initiator).into()),
+                ("epoch", (*epoch).into()),
+            ]),
+            Event::SimToggled { id, attempt } => Value::object([
+                ("id", (*id).into()),
+                ("attempt", (*attempt).into()),
+            ]),
+            Event::ToggleUnacknowledged(id) => Value::object([("id", (*id).into())]),
+            Event::Ignored(msg) => Value::object([("msg", msg.to_json())]),
+            Event::PresumedDead(id) => Value::object([("id", (*id).into())]),
+            Event::AwaitingDetection(id) => Value::object([("id", (*id).into())]),
+            Event::Asserted(id) => Value::object([("id", (*id).into())]),
+            Event::AssertionHeld(assertion) => {
+                Value::object([("assertion", assertion.as_str().into())])
+            }
+            Event::AssertionFailed(report) => Value::object([("report", report.as_str().into())]),
+            Event::AlreadyToggled { id, active } => Value::object([
+                ("id", (*id).into()),
+                ("active", (*active).into()),
+            ]),
+            Event::JoiningRing(id) => Value::object([("id", (*id).into())]),
+            Event::LeavingRing(id) => Value::object([("id", (*id).into())]),
+            Event::AlreadyInRing { id, present } => Value::object([
+                ("id", (*id).into()),
+                ("present", (*present).into()),
+            ]),
+            Event::MemberCrashed { id, reason } => Value::object([
+                ("id", (*id).into()),
+                ("reason", reason.as_str().into()),
+            ]),
+            Event::Slowing { id, delay } => Value::object([
+                ("id", (*id).into()),
+                ("delay", delay.to_string().into()),
+            ]),
+            Event::Reprioritizing { id, priority } => Value::object([
+                ("id", (*id).into()),
+                ("priority", (*priority).into()),
+            ]),
+            Event::SteppingDown(id) => Value::object([("id", (*id).into())]),
+            Event::NoHandover(id) => Value::object([("id", (*id).into())]),
+            Event::Partitioning(partition) => Value::object([(
+                "groups",
+                Value::Array(partition.groups.iter()
+                    .map(|group| Value::Array(group.iter().map(|id| (*id).into()).collect()))
+                    .collect()),
+            )]),
+            Event::Healing => Value::object([]),
+            Event::Ended(step) => Value::object([("step", (*step).into())]),
+            Event::Interrupted | Event::Paused | Event::Resumed => Value::object([]),
+            Event::Restarting => Value::object([]),
+            Event::Checkpointed(step) => Value::object([("step", (*step).into())]),
+            Event::InvariantsHold => Value::object([]),
+            Event::Unsettled(reason) => Value::object([("reason", reason.as_str().into())]),
+            Event::NotSettled(reason) => Value::object([("reason", reason.as_str().into())]),
+            Event::FailedSteps(failures) => Value::object([("failures", (*failures).into())]),
+            Event::VotesCast(voters) => Value::object([("voters", id_array(voters))]),
+            Event::AuditFailed(reason) => Value::object([("reason", reason.as_str().into())]),
+            Event::PublishFailed(error) => Value::object([("error", error.as_str().into())]),
+            Event::DashboardFailed(error) => Value::object([("error", error.as_str().into())]),
+            Event::ExportedTraces => Value::object([]),
+            Event::ExportFailed(error) => Value::object([("error", error.as_str().into())]),
+            Event::SentEnd => Value::object([]),
+            Event::Received(msg) => Value::object([("msg", msg.to_json())]),
+            Event::Stopped(error) => Value::object([("error", error.as_str().into())]),
+            Event::Crashed(reason) => Value::object([("reason", reason.as_str().into())]),
+            Event::Stopping => Value::object([]),
+            Event::SentStatus => Value::object([]),
+            Event::Toggled(active) => Value::object([("active", (*active).into())]),
+            Event::SentToggle => Value::object([]),
+            Event::SentToggleForward => Value::object([]),
+            Event::ToggleUnreachable(id) => Value::object([("id", (*id).into())]),
+            Event::Pinged(id) => Value::object([("id", (*id).into())]),
+            Event::AnsweredPing(id) => Value::object([("id", (*id).into())]),
+            Event::Active(id) => Value::object([("id", (*id).into())]),
+            Event::Inactive(id) => Value::object([("id", (*id).into())]),
+            Event::PingTimeout { id, timeout } => Value::object([
+                ("id", (*id).into()),
+                ("timeout_ms", millis(*timeout)),
+            ]),
+            Event::Slow { id, attempt } => Value::object([
+                ("id", (*id).into()),
+                ("attempt", (*attempt as usize).into()),
+            ]),
+            Event::MissedPing { id, missed } => Value::object([
+                ("id", (*id).into()),
+                ("missed", (*missed as usize).into()),
+            ]),
+            Event::RenewingLease => Value::object([]),
+            Event::LeaseRenewed(id) => Value::object([("id", (*id).into())]),
+            Event::LeaseExpired(id) => Value::object([("id", (*id).into())]),
+            Event::PreVoting(id) => Value::object([("id", (*id).into())]),
+            Event::PreVoted { origin, agrees } => Value::object([
+                ("origin", (*origin).into()),
+                ("agrees", (*agrees).into()),
+            ]),
+            Event::PreVoteWon { agreed, polled } | Event::PreVoteLost { agreed, polled } => {
+                Value::object([("agreed", (*agreed).into()), ("polled", (*polled).into())])
+            }
+            Event::StalePreVote(id) => Value::object([("id", (*id).into())]),
+            Event::Rescanning => Value::object([]),
+            Event::DeclaredDown(id) => Value::object([("id", (*id).into())]),
+            Event::MemberDown(id) => Value::object([("id", (*id).into())]),
+            Event::MemberUp(id) => Value::object([("id", (*id).into())]),
+            Event::Refuting(origin) => Value::object([("origin", (*origin).into())]),
+            Event::Rejoined => Value::object([]),
+            Event::MemberLeft(id) => Value::object([("id", (*id).into())]),
+            Event::MemberJoined(id) => Value::object([("id", (*id).into())]),
+            Event::Restarted => Value::object([]),
+            Event::Respawned => Value::object([]),
+            Event::Slowed(delay) => Value::object([("delay", delay.to_string().into())]),
+            Event::Reprioritized(priority) => Value::object([("priority", (*priority).into())]),
+            Event::HandingOver => Value::object([]),
+            Event::Sent(to) => Value::object([("to", (*to).into())]),
+            Event::Dropped { msg, to }
+            | Event::Blocked { msg, to }
+            | Event::Duplicated { msg, to }
+            | Event::Reordered { msg, to } => Value::object([
+                ("msg", msg.to_json()),
+                ("to", (*to).into()),
+            ]),
+            Event::DetectedDown(coordinator) => Value::object([
+                ("coordinator", (*coordinator).into()),
+            ]),
+            Event::SeekingCoordinator => Value::object([]),
+            Event::MisfitElection { votes, keys } => Value::object([
+                ("votes", (*votes).into()),
+                ("keys", (*keys).into()),
+            ]),
+            Event::InactiveForElection => Value::object([]),
+            Event::HandedElection(to) => Value::object([("to", (*to).into())]),
+            Event::NoneToHandTo => Value::object([]),
+            Event::ForwardingElection => Value::object([]),
+            Event::NoneToForwardTo => Value::object([]),
+            Event::Joined => Value::object([]),
+            Event::AlreadyVoted(id) => Value::object([("id", (*id).into())]),
+            Event::ElectionAbsorbed { initiator, epoch, into } => Value::object([
+                ("initiator", (*initiator).into()),
+                ("epoch", (*epoch).into()),
+                ("into", (*into).into()),
+            ]),
+            Event::ElectionEnded { initiator, epoch } => Value::object([
+                ("initiator", (*initiator).into()),
+                ("epoch", (*epoch).into()),
+            ]),
+            Event::StaleResult { id, epoch, latest } => Value::object([
+                ("id", (*id).into()),
+                ("epoch", (*epoch).into()),
+                ("latest", (*latest).into()),
+            ]),
+            Event::CoordinatorElected(id) => Value::object([("id", (*id).into())]),
+            Event::SentResultForward => Value::object([]),
+            Event::SentResult(to) => Value::object([("to", (*to).into())]),
+            Event::SentResultToSim => Value::object([]),
+            Event::ForwardedCandidate(id) => Value::object([("id", (*id).into())]),
+            Event::Probing { hops } => Value::object([("hops", (*hops).into())]),
+            Event::SwallowedProbe(id) => Value::object([("id", (*id).into())]),
+            Event::BeatenBy(id) => Value::object([("id", (*id).into())]),
+            Event::RepliedToProbe(id) => Value::object([("id", (*id).into())]),
+            Event::StaleProbe => Value::object([]),
+            Event::StaleReply => Value::object([]),
+            Event::AnsweredElection(id) => Value::object([("id", (*id).into())]),
+            Event::Challenged(ids) => Value::object([("ids", id_array(ids))]),
+            Event::Answered => Value::object([]),
+            Event::Unanswered => Value::object([]),
+            Event::Acknowledged { acks, of } => Value::object([
+                ("acks", (*acks).into()),
+                ("of", (*of).into()),
+            ]),
+            Event::ToldCoordinator(id) => Value::object([("id", (*id).into())]),
+            Event::Outranks(coordinator) => Value::object([
+                ("coordinator", (*coordinator).into()),
+            ]),
+        }
+    }
+}
+
+/// A duration in milliseconds, as JSON.
+fn millis(duration: Duration) -> Value {
+    Value::Number(duration.as_secs_f64() * 1000.0)
+}
+
+fn id_array(ids: &[usize]) -> Value {
+    Value::Array(ids.iter().map(|id| (*id).into()).collect())
+}
+
+/// Where events are emitted to.
+pub trait Sink: fmt::Debug + Send + Sync {
+    fn emit(&self, source: Source, event: &Event);
+
+    /// Whether the sink writes to stdout in a format that anything else
+    /// printed there would break, so that that goes to stderr instead.
+    fn holds_stdout(&self) -> bool {
+        false
+    }
+
+    /// Tell the time of events by `clock` from now on, rather than the
+    /// system's, if the sink tells it at all.
+    fn keep_time(&self, _clock: &SharedClock) {}
+}
+
+/// Prints every event on a line of its own, after its source.
+#[derive(Debug)]
+pub struct Console;
+
+impl Sink for Console {
+    fn emit(&self, source: Source, event: &Event) {
+        println!("{}: {}", source, event);
+    }
+}
+
+/// Writes every event as a JSON object on a line of its own, with when it
+/// was emitted in microseconds since the Unix epoch, or since the run
+/// started in virtual time if it keeps that, who emitted it, and the number
+/// of the election the simulator last started, if any:
+///
+/// ```text
+/// {"time":1700000000000000,"node":3,"event":"Pinged","election":2,"payload":{"id":4}}
+/// ```
+///
+/// Lines are written whole, so events from all threads interleave cleanly.
+pub struct JsonLines {
+    out: Mutex<JsonOut>,
+    stdout: bool,
+}
+
+struct JsonOut {
+    writer: Box<dyn Write + Send>,
+    /// How many elections the simulator has started.
+    elections: usize,
+    /// The clock kept instead of the system's, and when by it the run
+    /// started.
+    clock: Option<(SharedClock, Instant)>,
+}
+
+impl fmt::Debug for JsonLines {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("JsonLines").finish_non_exhaustive()
+    }
+}
+
+impl JsonLines {
+    pub fn stdout() -> Self {
+        Self { stdout: true, ..Self::new(Box::new(io::stdout())) }
+    }
+
+    /// Write events to a file at `path`, replacing whatever it held.
+    pub fn create(path: &Path) -> Result<Self> {
+        Ok(Self::new(Box::new(LineWriter::new(File::create(path)?))))
+    }
+
+    pub(crate) fn new(writer: Box<dyn Write + Send>) -> Self {
+        let out = JsonOut { writer, elections: 0, clock: None };
+        Self { out: Mutex::new(out), stdout: false }
+    }
+}
+
+impl Sink for JsonLines {
+    fn emit(&self, source: Source, event: &Event) {
+        let mut out = self.out.lock().unwrap_or_else(|e| e.into_inner());
+
+        if let (Source::Sim, Event::ElectionStarted { .. }) = (source, event) {
+            out.elections += 1;
+        }
+
+        let time = match &out.clock {
+            Some((clock, start)) => clock.elapsed(*start).as_micros() as u64,
+            None => SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|d| d.as_micros() as u64)
+                .unwrap_or_default(),
+        };
+
+        let node = match source {
+            Source::Member(id) => id.into(),
+            source => source.to_string().into(),
+        };
+
+        let election = match out.elections {
+            0 => Value::Null,
+            n => n.into(),
+        };
+
+        let line = Value::object([
+            ("time", time.into()),
+            ("node", node),
+            ("event", event.kind().into()),
+            ("election", election),
+            ("payload", event.payload()),
+        ]);
+
+        // An event log that can't be written to shouldn't stop the ring.
+        let _ = writeln!(out.writer, "{}", line);
+    }
+
+    fn holds_stdout(&self) -> bool {
+        self.stdout
+    }
+
+    fn keep_time(&self, clock: &SharedClock) {
+        let mut out = self.out.lock().unwrap_or_else(|e| e.into_inner());
+        out.clock = Some((clock.clone(), clock.now()));
+    }
+}
+
+/// Hands every event to the subscriber of the `tracing` crate, within the
+/// span of the election the simulator last started, until its result is
+/// in, and a member's within a child span of it for that member. Members
+/// run within spans of their own, too, however they log.
+#[derive(Debug, Default)]
+pub struct Tracing {
+    election: Mutex<Option<ElectionSpans>>,
+}
+
+#[derive(Debug)]
+struct ElectionSpans {
+    election: tracing::Span,
+    /// The spans of the members taking part, by id.
+    members: HashMap<usize, tracing::Span>,
+}
+
+impl Sink for Tracing {
+    fn emit(&self, source: Source, event: &Event) {
+        let mut election = self.election.lock().unwrap_or_else(|e| e.into_inner());
+
+        if let (Source::Sim, Event::ElectionStarted { origin }) = (source, event) {
+            let span = tracing::info_span!(parent: None, "election", origin = *origin);
+            *election = Some(ElectionSpans { election: span, members: HashMap::new() });
+        }
+
+        let span = election.as_mut().map(|spans| match source {
+            Source::Member(id) => spans.members.entry(id)
+                .or_insert_with(|| tracing::info_span!(parent: &spans.election, "member", id))
+                .clone(),
+            _ => spans.election.clone(),
+        });
+
+        if let (Source::Sim, Event::ElectionWon { .. } | Event::ElectionStuck { .. }) =
+            (source, event)
+        {
+            *election = None;
+        }
+
+        drop(election);
+
+        let _entered = span.as_ref().map(tracing::Span::enter);
+        tracing::info!(source = %source, event = event.kind(), "{}", event);
+    }
+}
+
+/// A sink shared by the simulator and every ring member, the console
+/// unless told otherwise.
+#[derive(Debug, Clone)]
+pub struct SharedSink(Arc<dyn Sink>);
+
+impl SharedSink {
+    pub fn new(sink: impl Sink + 'static) -> Self {
+        Self(Arc::new(sink))
+    }
+
+    /// Print `text`, which is no event, to stdout, or to stderr should the
+    /// sink hold stdout, so that, e.g., `jq` can still read the events.
+    pub fn print(&self, text: impl fmt::Display) {
+        match self.holds_stdout() {
+            true => eprint!("{}", text),
+            false => print!("{}", text),
+        }
+    }
+}
+
+impl Default for SharedSink {
+    fn default() -> Self {
+        Self::new(Console)
+    }
+}
+
+impl Deref for SharedSink {
+    type Target = dyn Sink;
+
+    fn deref(&self) -> &Self::Target {
+        &*self.0
+    }
+}
@@ -0,0 +1,111 @@
+//! Just enough of an async runtime, on `std` alone, to run ring members as
+//! tasks: [`block_on`] runs one to the end on the current thread, as each
+//! member's own thread does, and [`run`] runs any number of them on the
+//! current thread between them, as an event loop.
+//!
+//! On threads of their own, members block wherever they wait, so their
+//! tasks are never left pending. On the loop, they only ever wait for a
+//! message to arrive or for time to pass, each through [`until`], which
+//! has nothing to wake them by. The loop polls every task in turn instead,
+//! and naps whenever a whole round of them found nothing they waited for.
+
+use std::cell::Cell;
+use std::future::{self, Future};
+use std::panic::{self, AssertUnwindSafe};
+use std::pin::{pin, Pin};
+use std::task::{Context, Poll, Waker};
+use std::time::Duration;
+
+use crate::sync::channel::{Receiver, TryRecvError};
+use crate::sync::thread;
+
+/// How long the loop naps for once none of its tasks moved on.
+const IDLE: Duration = Duration::from_micros(50);
+
+thread_local! {
+    /// Whether a task on the current thread found what it waited for since
+    /// the loop last looked.
+    static MOVED: Cell<bool> = const { Cell::new(false) };
+}
+
+/// A ring member's life as a task, as it would be on a thread of its own.
+pub(crate) type Task = Pin<Box<dyn Future<Output = ()>>>;
+
+/// What makes a task on the loop's thread, sent there from another, as
+/// those of members joining the ring are.
+pub(crate) type Spawn = Box<dyn FnOnce() -> Task + Send>;
+
+/// Run `future` to the end on the current thread, kept on the heap as
+/// the loop keeps its tasks, for a member's to fit loom's small stacks.
+pub(crate) fn block_on<F: Future>(future: F) -> F::Output {
+    let mut future = Box::pin(future);
+    let mut cx = Context::from_waker(Waker::noop());
+
+    loop {
+        if let Poll::Ready(out) = future.as_mut().poll(&mut cx) {
+            return out;
+        }
+
+        thread::sleep(IDLE);
+    }
+}
+
+/// Run the tasks `spawned` on the current thread until every one has ended
+/// and no more can be spawned.
+pub(crate) fn run(spawned: Receiver<Spawn>) {
+    let mut cx = Context::from_waker(Waker::noop());
+    let mut tasks = Vec::new();
+    let mut open = true;
+
+    loop {
+        while open {
+            match spawned.try_recv() {
+                Ok(spawn) => tasks.push(spawn()),
+                Err(TryRecvError::Empty) => break,
+                Err(TryRecvError::Disconnected) => open = false,
+            }
+        }
+
+        if tasks.is_empty() {
+            match open.then(|| spawned.recv()) {
+                Some(Ok(spawn)) => tasks.push(spawn()),
+                _ => return,
+            }
+        }
+
+        MOVED.set(false);
+        let before = tasks.len();
+        tasks.retain_mut(|task| task.as_mut().poll(&mut cx).is_pending());
+
+        if !MOVED.get() && tasks.len() == before {
+            thread::sleep(IDLE);
+        }
+    }
+}
+
+/// Wait until `ready` gives something, asking it every time the task is
+/// polled.
+pub(crate) fn until<T>(mut ready: impl FnMut() -> Option<T>) -> impl Future<Output = T> {
+    future::poll_fn(move |_| match ready() {
+        Some(out) => {
+            MOVED.set(true);
+            Poll::Ready(out)
+        }
+        None => Poll::Pending,
+    })
+}
+
+/// Run `future`, catching it panicking as [`panic::catch_unwind`] does,
+/// for a member panicking on the loop not to unwind every other with it.
+pub(crate) async fn catch_unwind<F: Future>(future: F) -> std::thread::Result<F::Output> {
+    let mut future = pin!(future);
+
+    future::poll_fn(|cx| {
+        match panic::catch_unwind(AssertUnwindSafe(|| future.as_mut().poll(cx))) {
+            Ok(Poll::Ready(out)) => Poll::Ready(Ok(out)),
+            Ok(Poll::Pending) => Poll::Pending,
+            Err(payload) => Poll::Ready(Err(payload)),
+        }
+    })
+    .await
+}
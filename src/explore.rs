@@ -0,0 +1,103 @@
+//! Model checking a simulation sequence on a small ring: following it over
+//! and over, under every interleaving of messages the deterministic
+//! scheduler can pick, with the ring's invariants checked in each.
+//!
+//! The interleavings are explored depth-first. Every run follows the
+//! choices of the last one up to its last choice with others left untried,
+//! takes the next of those, and the first ready after that. States reached
+//! before by another interleaving aren't explored again.
+
+use std::collections::HashSet;
+use std::fmt;
+use std::sync::Arc;
+
+use anyhow::{bail, Result};
+
+use crate::sched::Scheduler;
+use crate::sync::Mutex;
+use crate::{run, Driver, RingBuilder, SimConfig, SimSeq, SIM_TIMEOUT};
+
+/// How an exploration went.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Exploration {
+    /// How many interleavings were run.
+    pub schedules: usize,
+    /// How many distinct states they went through.
+    pub states: usize,
+    /// Whether every interleaving was explored, rather than stopping at
+    /// the most asked for.
+    pub complete: bool,
+}
+
+impl fmt::Display for Exploration {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} schedules through {} states", self.schedules, self.states)?;
+
+        if !self.complete {
+            write!(f, ", stopping short of exploring them all")?;
+        }
+
+        Ok(())
+    }
+}
+
+impl RingBuilder {
+    /// Follow `seq` under every interleaving, up to `max_schedules` of
+    /// them, starting from the one `schedule` picks the first turns of, and
+    /// fail on the first that breaks the ring's invariants or the
+    /// sequence's assertions, naming its picks.
+    pub fn explore(
+        self, seq: &SimSeq, schedule: Vec<usize>, max_schedules: usize,
+    ) -> Result<Exploration> {
+        if !self.nodes.is_empty() {
+            bail!("Only a ring spawned here can be explored");
+        }
+
+        let visited = Arc::new(Mutex::new(HashSet::new()));
+        let mut prefix = schedule;
+        let mut schedules = 0;
+
+        loop {
+            let sched = Scheduler::exploring(prefix, visited.clone());
+
+            // A ring stuck waiting would stop virtual time for good.
+            let config = SimConfig {
+                verify: true,
+                sim_timeout: Some(self.config.sim_timeout.unwrap_or(SIM_TIMEOUT)),
+                checkpoint: None,
+                oracle: None,
+                sched: Some(sched.clone()),
+                ..self.config.clone()
+            };
+
+            let res = run(Driver::Script(seq.clone()), self.size, None, None, config);
+            schedules += 1;
+            let taken = sched.taken();
+
+            if let Err(e) = res {
+                // The first ready goes next unless told otherwise.
+                let last = taken.iter().rposition(|(pick, _)| *pick != 0).map_or(0, |i| i + 1);
+                let picks = taken[..last.max(1)].iter()
+                    .map(|(pick, _)| pick.to_string())
+                    .collect::<Vec<_>>();
+
+                bail!("Schedule {} (--schedule {}) fails: {}", schedules, picks.join(","), e);
+            }
+
+            let states = visited.lock().unwrap_or_else(|e| e.into_inner()).len();
+
+            // Backtrack to the last choice with others left to try.
+            prefix = match taken.iter().rposition(|(pick, of)| pick + 1 < *of) {
+                Some(i) => taken[..=i].iter()
+                    .enumerate()
+                    .map(|(j, (pick, _))| if j == i { pick + 1 } else { *pick })
+                    .collect(),
+                None => return Ok(Exploration { schedules, states, complete: true }),
+            };
+
+            if schedules >= max_schedules {
+                return Ok(Exploration { schedules, states, complete: false });
+            }
+        }
+    }
+}
@@ -0,0 +1,235 @@
+//! Golden traces, for regression testing the election algorithms: the
+//! events a simulation sequence has the ring emit, run under the
+//! deterministic scheduler so that they come in the same order every run,
+//! compared against a trace checked in from an earlier one.
+//!
+//! A test runs a sequence with [`RingBuilder::trace`] and checks the trace
+//! against its golden file with [`Trace::check`]:
+//!
+//! ```no_run
+//! use std::path::Path;
+//! use ring::{Ring, SimSeq};
+//!
+//! let seq = SimSeq::from_file(Path::new("tests/kill-coordinator.txt"), 5)?;
+//! let trace = Ring::builder().size(5).trace(&seq)?;
+//! trace.check(Path::new("tests/golden/ring.golden"))?;
+//! # Ok::<(), anyhow::Error>(())
+//! ```
+//!
+//! A change meant to change the traces is checked in along with them,
+//! written over the golden files by checking with `RING_BLESS` set.
+
+use std::env;
+use std::fmt;
+use std::fs;
+use std::mem;
+use std::path::Path;
+use std::sync::Arc;
+
+use anyhow::{bail, Error, Result};
+
+use crate::event::{Event, SharedSink, Sink, Source};
+use crate::sync::Mutex;
+use crate::{run, Driver, RingBuilder, SimConfig, SimSeq, SIM_TIMEOUT};
+
+/// Environment variable that has [`Trace::check`] write the golden file
+/// rather than compare against it.
+pub const BLESS_VAR: &str = "RING_BLESS";
+
+/// How many unchanged lines a diff shows around each change.
+const CONTEXT: usize = 2;
+
+/// The events of a run, one line each, as the console prints them.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Trace {
+    lines: Vec<String>,
+}
+
+impl Trace {
+    pub fn lines(&self) -> &[String] {
+        &self.lines
+    }
+
+    /// How this trace differs from `golden`, if at all.
+    pub fn diff(&self, golden: &Trace) -> Option<TraceDiff> {
+        let (old, new) = (&golden.lines[..], &self.lines[..]);
+
+        // Runs mostly differ somewhere in the middle, which keeps the table
+        // below small.
+        let prefix = old.iter().zip(new).take_while(|(a, b)| a == b).count();
+        let suffix = old[prefix..].iter().rev()
+            .zip(new[prefix..].iter().rev())
+            .take_while(|(a, b)| a == b)
+            .count();
+
+        if prefix == old.len() && prefix == new.len() {
+            return None;
+        }
+
+        let (a, b) = (&old[prefix..old.len() - suffix], &new[prefix..new.len() - suffix]);
+
+        // The length of the longest common subsequence of every pair of
+        // suffixes of the two.
+        let mut lcs = vec![vec![0; b.len() + 1]; a.len() + 1];
+
+        for i in (0..a.len()).rev() {
+            for j in (0..b.len()).rev() {
+                lcs[i][j] = match a[i] == b[j] {
+                    true => lcs[i + 1][j + 1] + 1,
+                    false => lcs[i + 1][j].max(lcs[i][j + 1]),
+                };
+            }
+        }
+
+        let mut changes = old[..prefix].iter().cloned().map(Change::Same).collect::<Vec<_>>();
+        let (mut i, mut j) = (0, 0);
+
+        while i < a.len() || j < b.len() {
+            if i < a.len() && j < b.len() && a[i] == b[j] {
+                changes.push(Change::Same(a[i].clone()));
+                (i, j) = (i + 1, j + 1);
+            } else if j == b.len() || (i < a.len() && lcs[i + 1][j] >= lcs[i][j + 1]) {
+                changes.push(Change::Removed(a[i].clone()));
+                i += 1;
+            } else {
+                changes.push(Change::Added(b[j].clone()));
+                j += 1;
+            }
+        }
+
+        changes.extend(old[old.len() - suffix..].iter().cloned().map(Change::Same));
+        Some(TraceDiff { changes })
+    }
+
+    /// Compare this trace against the golden one at `path`, failing with
+    /// how they differ, or write it to `path` instead if [`BLESS_VAR`] is
+    /// set.
+    pub fn check(&self, path: &Path) -> Result<()> {
+        if env::var_os(BLESS_VAR).is_some() {
+            fs::write(path, self.to_string())?;
+            return Ok(());
+        }
+
+        let golden = match fs::read_to_string(path) {
+            Ok(golden) => golden.parse::<Trace>()?,
+            Err(e) => bail!(
+                "Error reading golden trace {}: {} (set {} to write it)",
+                path.display(), e, BLESS_VAR
+            ),
+        };
+
+        match self.diff(&golden) {
+            Some(diff) => bail!(
+                "Trace differs from golden trace {} (set {} to update it):\n{}",
+                path.display(), BLESS_VAR, diff
+            ),
+            None => Ok(()),
+        }
+    }
+}
+
+impl fmt::Display for Trace {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.lines.iter().try_for_each(|line| writeln!(f, "{}", line))
+    }
+}
+
+impl std::str::FromStr for Trace {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        Ok(Self { lines: s.lines().map(str::to_string).collect() })
+    }
+}
+
+/// A line of one trace diffed against another.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Change {
+    Same(String),
+    /// Only in the golden trace.
+    Removed(String),
+    /// Only in the trace diffed against it.
+    Added(String),
+}
+
+/// How two traces differ: the lines of the golden one missing from the
+/// other, marked `-`, and the lines in the other instead, marked `+`, with
+/// a few unchanged lines around them.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TraceDiff {
+    changes: Vec<Change>,
+}
+
+impl fmt::Display for TraceDiff {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let near_change = |i: usize| {
+            let from = i.saturating_sub(CONTEXT);
+            let to = (i + CONTEXT + 1).min(self.changes.len());
+            self.changes[from..to].iter().any(|c| !matches!(c, Change::Same(_)))
+        };
+
+        // Where each change is in the golden trace, counting from 1.
+        let mut line = 1;
+        let mut skipped = true;
+
+        for (i, change) in self.changes.iter().enumerate() {
+            if near_change(i) {
+                if skipped {
+                    writeln!(f, "@@ line {} @@", line)?;
+                    skipped = false;
+                }
+
+                match change {
+                    Change::Same(l) => writeln!(f, "  {}", l)?,
+                    Change::Removed(l) => writeln!(f, "- {}", l)?,
+                    Change::Added(l) => writeln!(f, "+ {}", l)?,
+                }
+            } else {
+                skipped = true;
+            }
+
+            if !matches!(change, Change::Added(_)) {
+                line += 1;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Keeps every event emitted as a line of a trace.
+#[derive(Debug)]
+pub(crate) struct Collect(pub(crate) Arc<Mutex<Vec<String>>>);
+
+impl Sink for Collect {
+    fn emit(&self, source: Source, event: &Event) {
+        let line = format!("{}: {}", source, event);
+        self.0.lock().unwrap_or_else(|e| e.into_inner()).push(line);
+    }
+}
+
+impl RingBuilder {
+    /// Follow `seq` under the deterministic scheduler, picking turns with
+    /// the configured seed, and return the events the run emitted instead
+    /// of emitting them to the configured sink.
+    pub fn trace(self, seq: &SimSeq) -> Result<Trace> {
+        if !self.nodes.is_empty() {
+            bail!("Only a ring spawned here can be traced");
+        }
+
+        let lines = Arc::new(Mutex::new(Vec::new()));
+
+        // A ring stuck waiting would stop virtual time for good.
+        let config = SimConfig {
+            deterministic: true,
+            sim_timeout: Some(self.config.sim_timeout.unwrap_or(SIM_TIMEOUT)),
+            events: SharedSink::new(Collect(lines.clone())),
+            ..self.config
+        };
+
+        run(Driver::Script(seq.clone()), self.size, self.out.as_deref(), None, config)?;
+
+        let mut lines = lines.lock().unwrap_or_else(|e| e.into_inner());
+        Ok(Trace { lines: mem::take(&mut *lines) })
+    }
+}
@@ -0,0 +1,69 @@
+//! What the servers reporting on and driving a running simulation share:
+//! a thread taking their connections one at a time, for as long as the
+//! process lives, and the little of HTTP they speak.
+
+use std::io::Write;
+use std::net::{TcpListener, TcpStream};
+use std::thread;
+use std::time::Duration;
+
+use anyhow::Result;
+
+/// How long a client may take to send its request, so that one sending
+/// none can't hold the server up.
+const READ_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Listen at `addr`, such as `127.0.0.1:8080`, and have `answer` take
+/// every connection, warning of those it fails on as the `name` server.
+pub(crate) fn serve(
+    addr: &str, name: &'static str, answer: impl Fn(TcpStream) -> Result<()> + Send + 'static,
+) -> Result<()> {
+    let listener = TcpListener::bind(addr)?;
+
+    thread::spawn(move || {
+        for stream in listener.incoming().flatten() {
+            let res = stream.set_read_timeout(Some(READ_TIMEOUT))
+                .map_err(Into::into)
+                .and_then(|()| answer(stream));
+
+            if let Err(e) = res {
+                tracing::warn!("{}: could not answer request: {}", name, e);
+            }
+        }
+    });
+
+    Ok(())
+}
+
+/// The method and path of the request `stream` opens with, each empty if
+/// it gives none. The WebSocket server reads its handshake whole instead.
+#[cfg(any(feature = "status-server", feature = "metrics-server", feature = "control-server"))]
+pub(crate) fn request(stream: &TcpStream) -> Result<(String, String)> {
+    use std::io::{BufRead, BufReader};
+
+    let mut line = String::new();
+    BufReader::new(stream).read_line(&mut line)?;
+    let mut parts = line.split_whitespace().map(str::to_string);
+
+    Ok((parts.next().unwrap_or_default(), parts.next().unwrap_or_default()))
+}
+
+/// Answer with `status`, such as `200 OK`, and `body`, as `content_type`,
+/// then hang up.
+pub(crate) fn respond(
+    stream: &mut TcpStream, status: &str, content_type: &str, body: &str,
+) -> Result<()> {
+    write!(
+        stream,
+        "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status, content_type, body.len(), body
+    )?;
+
+    Ok(())
+}
+
+/// A JSON answer giving `reason` for an error.
+#[cfg(any(feature = "status-server", feature = "control-server"))]
+pub(crate) fn error(reason: &str) -> crate::json::Value {
+    crate::json::Value::object([("error", reason.into())])
+}
@@ -0,0 +1,54 @@
+//! Ending a run on Ctrl-C rather than killing it mid-flight: the simulator
+//! stops at its next chance, between actions or during a wait, and the run
+//! ends as any other does, sending every member `SimEnd`, waiting for them
+//! to stop and printing its summary, before failing with [`Interrupted`].
+//!
+//! A run that's checkpointing can be resumed from where it was stopped. A
+//! second Ctrl-C kills the process as before, for a run stuck somewhere it
+//! never gets to stop, such as an election without a `--sim-timeout`.
+
+use std::fmt;
+// Signal handlers run outside whatever a loom model runs.
+use std::sync::atomic::{AtomicBool, Ordering};
+
+static INTERRUPTED: AtomicBool = AtomicBool::new(false);
+
+/// How a run that was interrupted fails, told apart from other errors so
+/// that the process can exit with its own code.
+#[derive(Debug)]
+pub struct Interrupted;
+
+impl fmt::Display for Interrupted {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Interrupted by Ctrl-C")
+    }
+}
+
+impl std::error::Error for Interrupted {}
+
+/// Have Ctrl-C end runs from now on, rather than kill the process, until
+/// pressed a second time. It only kills the process on platforms other than
+/// Unix.
+pub fn catch_ctrl_c() {
+    #[cfg(unix)]
+    // SAFETY: the handler only touches an atomic and resets the signal's
+    // disposition, both of which are async-signal-safe.
+    unsafe {
+        libc::signal(libc::SIGINT, on_sigint as extern "C" fn(libc::c_int) as libc::sighandler_t);
+    }
+}
+
+/// Whether Ctrl-C was pressed since [`catch_ctrl_c`].
+pub fn interrupted() -> bool {
+    INTERRUPTED.load(Ordering::SeqCst)
+}
+
+#[cfg(unix)]
+extern "C" fn on_sigint(_: libc::c_int) {
+    INTERRUPTED.store(true, Ordering::SeqCst);
+
+    // SAFETY: as in `catch_ctrl_c`.
+    unsafe {
+        libc::signal(libc::SIGINT, libc::SIG_DFL);
+    }
+}
@@ -0,0 +1,313 @@
+//! A minimal JSON reader and writer, covering what the simulator's
+//! recordings and reports need.
+
+use std::fmt;
+
+use anyhow::{bail, Result};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    Null,
+    Bool(bool),
+    Number(f64),
+    String(String),
+    Array(Vec<Value>),
+    /// Object members, in the order they were written.
+    Object(Vec<(String, Value)>),
+}
+
+impl Value {
+    /// Parse a single JSON value, rejecting trailing characters.
+    pub fn parse(s: &str) -> Result<Self> {
+        let mut parser = Parser { chars: s.char_indices().peekable(), src: s };
+        let value = parser.value()?;
+        parser.skip_ws();
+
+        if let Some((i, _)) = parser.chars.peek() {
+            bail!("Unexpected trailing characters at {}", i);
+        }
+
+        Ok(value)
+    }
+
+    /// Build an object from its members.
+    pub fn object<const N: usize>(members: [(&str, Value); N]) -> Self {
+        Self::Object(
+            members.into_iter().map(|(k, v)| (k.to_string(), v)).collect()
+        )
+    }
+
+    pub fn get(&self, key: &str) -> Option<&Value> {
+        match self {
+            Self::Object(members) => members.iter()
+                .find(|(k, _)| k == key)
+                .map(|(_, v)| v),
+            _ => None,
+        }
+    }
+
+    pub fn as_bool(&self) -> Option<bool> {
+        match self {
+            Self::Bool(b) => Some(*b),
+            _ => None,
+        }
+    }
+
+    pub fn as_u64(&self) -> Option<u64> {
+        match self {
+            Self::Number(n) if *n >= 0.0 && n.fract() == 0.0 => Some(*n as u64),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Self::String(s) => Some(s),
+            _ => None,
+        }
+    }
+
+    pub fn as_array(&self) -> Option<&[Value]> {
+        match self {
+            Self::Array(items) => Some(items),
+            _ => None,
+        }
+    }
+}
+
+impl From<bool> for Value {
+    fn from(b: bool) -> Self {
+        Self::Bool(b)
+    }
+}
+
+impl From<usize> for Value {
+    fn from(n: usize) -> Self {
+        Self::Number(n as f64)
+    }
+}
+
+impl From<u64> for Value {
+    fn from(n: u64) -> Self {
+        Self::Number(n as f64)
+    }
+}
+
+impl From<&str> for Value {
+    fn from(s: &str) -> Self {
+        Self::String(s.to_string())
+    }
+}
+
+impl From<String> for Value {
+    fn from(s: String) -> Self {
+        Self::String(s)
+    }
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Null => write!(f, "null"),
+            Self::Bool(b) => write!(f, "{}", b),
+            Self::Number(n) => write!(f, "{}", n),
+            Self::String(s) => write_str(f, s),
+            Self::Array(items) => {
+                write!(f, "[")?;
+
+                for (i, item) in items.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ",")?;
+                    }
+
+                    write!(f, "{}", item)?;
+                }
+
+                write!(f, "]")
+            }
+            Self::Object(members) => {
+                write!(f, "{{")?;
+
+                for (i, (k, v)) in members.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ",")?;
+                    }
+
+                    write_str(f, k)?;
+                    write!(f, ":{}", v)?;
+                }
+
+                write!(f, "}}")
+            }
+        }
+    }
+}
+
+fn write_str(f: &mut fmt::Formatter, s: &str) -> fmt::Result {
+    write!(f, "\"")?;
+
+    for c in s.chars() {
+        match c {
+            '"' => write!(f, "\\\"")?,
+            '\\' => write!(f, "\\\\")?,
+            '\n' => write!(f, "\\n")?,
+            '\r' => write!(f, "\\r")?,
+            '\t' => write!(f, "\\t")?,
+            c if (c as u32) < 0x20 => write!(f, "\\u{:04x}", c as u32)?,
+            c => write!(f, "{}", c)?,
+        }
+    }
+
+    write!(f, "\"")
+}
+
+struct Parser<'a> {
+    chars: std::iter::Peekable<std::str::CharIndices<'a>>,
+    src: &'a str,
+}
+
+impl Parser<'_> {
+    fn skip_ws(&mut self) {
+        while let Some((_, c)) = self.chars.peek() {
+            if !c.is_whitespace() {
+                break;
+            }
+
+            self.chars.next();
+        }
+    }
+
+    fn expect(&mut self, expected: char) -> Result<()> {
+        match self.chars.next() {
+            Some((_, c)) if c == expected => Ok(()),
+            Some((i, c)) => bail!("Expected '{}' at {}, found '{}'", expected, i, c),
+            None => bail!("Expected '{}', found end of input", expected),
+        }
+    }
+
+    fn value(&mut self) -> Result<Value> {
+        self.skip_ws();
+
+        match self.chars.peek().copied() {
+            Some((_, '{')) => self.object(),
+            Some((_, '[')) => self.array(),
+            Some((_, '"')) => Ok(Value::String(self.string()?)),
+            Some((_, 't')) => self.literal("true", Value::Bool(true)),
+            Some((_, 'f')) => self.literal("false", Value::Bool(false)),
+            Some((_, 'n')) => self.literal("null", Value::Null),
+            Some((_, c)) if c == '-' || c.is_ascii_digit() => self.number(),
+            Some((i, c)) => bail!("Unexpected '{}' at {}", c, i),
+            None => bail!("Unexpected end of input"),
+        }
+    }
+
+    fn literal(&mut self, word: &str, value: Value) -> Result<Value> {
+        for c in word.chars() {
+            self.expect(c)?;
+        }
+
+        Ok(value)
+    }
+
+    fn number(&mut self) -> Result<Value> {
+        let start = self.chars.peek().map(|(i, _)| *i).unwrap_or(0);
+        let mut end = start;
+
+        while let Some((i, c)) = self.chars.peek().copied() {
+            if !(c.is_ascii_digit() || "+-.eE".contains(c)) {
+                break;
+            }
+
+            end = i + c.len_utf8();
+            self.chars.next();
+        }
+
+        match self.src[start..end].parse() {
+            Ok(n) => Ok(Value::Number(n)),
+            Err(_) => bail!("Invalid number at {}", start),
+        }
+    }
+
+    fn string(&mut self) -> Result<String> {
+        self.expect('"')?;
+        let mut s = String::new();
+
+        loop {
+            match self.chars.next() {
+                Some((_, '"')) => return Ok(s),
+                Some((_, '\\')) => match self.chars.next() {
+                    Some((_, 'n')) => s.push('\n'),
+                    Some((_, 'r')) => s.push('\r'),
+                    Some((_, 't')) => s.push('\t'),
+                    Some((_, 'u')) => {
+                        let mut code = 0;
+
+                        for _ in 0..4 {
+                            let digit = self.chars.next()
+                                .and_then(|(_, c)| c.to_digit(16));
+
+                            match digit {
+                                Some(d) => code = code * 16 + d,
+                                None => bail!("Invalid unicode escape"),
+                            }
+                        }
+
+                        s.push(char::from_u32(code).unwrap_or('\u{fffd}'));
+                    }
+                    Some((_, c)) => s.push(c),
+                    None => bail!("Unterminated string"),
+                },
+                Some((_, c)) => s.push(c),
+                None => bail!("Unterminated string"),
+            }
+        }
+    }
+
+    fn array(&mut self) -> Result<Value> {
+        self.expect('[')?;
+        let mut items = Vec::new();
+        self.skip_ws();
+
+        if let Some((_, ']')) = self.chars.peek() {
+            self.chars.next();
+            return Ok(Value::Array(items));
+        }
+
+        loop {
+            items.push(self.value()?);
+            self.skip_ws();
+
+            match self.chars.next() {
+                Some((_, ',')) => continue,
+                Some((_, ']')) => return Ok(Value::Array(items)),
+                _ => bail!("Expected ',' or ']' in array"),
+            }
+        }
+    }
+
+    fn object(&mut self) -> Result<Value> {
+        self.expect('{')?;
+        let mut members = Vec::new();
+        self.skip_ws();
+
+        if let Some((_, '}')) = self.chars.peek() {
+            self.chars.next();
+            return Ok(Value::Object(members));
+        }
+
+        loop {
+            self.skip_ws();
+            let key = self.string()?;
+            self.skip_ws();
+            self.expect(':')?;
+            members.push((key, self.value()?));
+            self.skip_ws();
+
+            match self.chars.next() {
+                Some((_, ',')) => continue,
+                Some((_, '}')) => return Ok(Value::Object(members)),
+                _ => bail!("Expected ',' or '}}' in object"),
+            }
+        }
+    }
+}
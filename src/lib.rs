@@ -0,0 +1,5599 @@
+//! A ring of members electing a coordinator among themselves, and a
+//! simulator that takes members down and brings them back up to see the
+//! ring elect a new one.
+//!
+//! A ring is built and driven to the end with [`Ring::builder`]:
+//!
+//! ```no_run
+//! use ring::{Driver, Ring};
+//!
+//! let summary = Ring::builder().size(5).run(Driver::Once { origin: 0 })?;
+//! println!("coordinator: {}", summary.coord_id);
+//! # Ok::<(), anyhow::Error>(())
+//! ```
+//!
+//! Everything the ring does along the way is emitted as an
+//! [`event::Event`] to the [`SimConfig::events`] sink, printed to stdout
+//! unless another is given.
+
+use std::any::Any;
+use std::cmp::Reverse;
+use std::collections::{HashMap, VecDeque};
+use std::fmt;
+use std::fs::{self, OpenOptions};
+use std::mem;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use anyhow::{bail, Context, Error, Result};
+use crossbeam::thread;
+use gag::Redirect;
+use tracing::Instrument;
+
+pub mod checkpoint;
+pub mod clock;
+#[cfg(feature = "control-server")]
+pub mod control;
+pub mod diagram;
+mod dot;
+pub mod event;
+mod executor;
+pub mod explore;
+pub mod golden;
+#[cfg(any(
+    feature = "status-server", feature = "metrics-server", feature = "control-server",
+    feature = "ws-server",
+))]
+mod http;
+pub mod interrupt;
+mod json;
+pub mod member;
+#[cfg(feature = "metrics-server")]
+pub mod metrics;
+pub mod oracle;
+pub mod pause;
+pub mod record;
+pub mod render;
+pub mod replay;
+pub mod rng;
+pub mod sched;
+#[cfg(feature = "status-server")]
+pub mod status;
+pub mod step;
+pub mod sync;
+pub mod toml;
+pub mod trace;
+pub mod transport;
+#[cfg(feature = "tui")]
+pub mod tui;
+#[cfg(feature = "ws-server")]
+pub mod ws;
+
+use checkpoint::Checkpoint;
+use clock::SharedClock;
+use diagram::Diagram;
+use dot::{Dot, Node};
+use event::{Event, SharedSink, Source};
+use interrupt::Interrupted;
+use json::Value;
+use member::{Core, Effects, Waiting};
+use oracle::Oracle;
+use pause::Pause;
+use record::Recorder;
+use render::Render;
+use rng::Rng;
+use sched::Scheduler;
+use sync::channel::{bounded, unbounded, Receiver, RecvTimeoutError, Sender, TryRecvError};
+use sync::{AtomicUsize, Mutex, Ordering};
+use trace::Tracer;
+use transport::{unbounded_link, Channels, Link, Protocol, Roster, Transport};
+
+/// How many members a ring has unless told otherwise.
+pub const DEFAULT_RING_SIZE: usize = 3;
+
+/// How long the simulator waits on the ring before giving up, when messages
+/// may be lost.
+pub const SIM_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// Parse a duration such as "250ms", "2s" or "1m", taking bare numbers as
+/// seconds.
+pub fn parse_duration(s: &str) -> Result<Duration> {
+    let (num, unit) = match s.find(|c: char| !c.is_ascii_digit()) {
+        Some(i) => s.split_at(i),
+        None => (s, "s"),
+    };
+
+    let num = match num.parse::<u64>() {
+        Ok(n) => n,
+        Err(_) => bail!("Invalid duration {:?}", s),
+    };
+
+    match unit {
+        "ms" => Ok(Duration::from_millis(num)),
+        "s" => Ok(Duration::from_secs(num)),
+        "m" => Ok(Duration::from_secs(num * 60)),
+        _ => bail!("Invalid duration unit in {:?}", s),
+    }
+}
+
+/// Options controlling how a simulation is run.
+#[derive(Debug, Clone, Default)]
+pub struct SimConfig {
+    /// Assert the ring's invariants after every simulation step.
+    pub verify: bool,
+    /// Where to record every delivered message, if anywhere.
+    pub recorder: Option<Recorder>,
+    /// Seed for every random choice made during the run.
+    pub seed: u64,
+    /// Faults injected into messages between ring members.
+    pub faults: Faults,
+    /// How long the simulator waits for the ring to answer, if not forever.
+    pub sim_timeout: Option<Duration>,
+    /// How long the simulator waits for an election's result before
+    /// reporting it stuck, if not as long as it waits on the ring otherwise.
+    pub election_timeout: Option<Duration>,
+    /// How many messages a ring member's election channel holds, if not
+    /// one. Algorithms whose channels are unbounded ignore it.
+    pub channel_capacity: Option<usize>,
+    /// What the ring members spawned for the run are run on.
+    pub runtime: MemberRuntime,
+    /// Human-readable names of the ring members, by id.
+    pub labels: Vec<String>,
+    /// Election priorities of the ring members, by id.
+    pub priorities: Vec<u64>,
+    /// The election algorithm ring members run.
+    pub algorithm: Algorithm,
+    /// How an election's winner is picked among its voters.
+    pub ranking: Ranking,
+    /// How long each member takes over every message from another, by id,
+    /// if at all: none for members beyond those given.
+    pub processing: Vec<ProcessingDelay>,
+    /// When an election is considered over.
+    pub termination: TerminationRule,
+    /// When the simulator starts an election after a toggle.
+    pub reelect: ReelectPolicy,
+    /// How often the coordinator sends the simulator a heartbeat, if at all,
+    /// for the simulator to find it down by the heartbeats it misses.
+    pub heartbeat: Option<Duration>,
+    /// How followers watch the coordinator themselves, if they do.
+    pub detection: Option<Detection>,
+    /// How long an elected coordinator leads for unless it renews its
+    /// lease around the ring, if it must, followers electing another once
+    /// the lease runs out.
+    pub lease: Option<Duration>,
+    /// Have a follower finding the coordinator gone first hold a pre-vote
+    /// around the ring on whether it is, only electing another should most
+    /// of the members asked find it gone too, lest a flaky link of the
+    /// follower's own have it disrupt the ring.
+    pub pre_vote: bool,
+    /// Have election results carry the votes cast, for the simulator to
+    /// check the winner against.
+    pub audit: bool,
+    /// How long members wait for a ping to be answered.
+    pub backoff: Backoff,
+    /// Have members wait for a ping to be answered as long as the round
+    /// trips they measured to its target took on average, plus this many
+    /// standard deviations, if at all. The backoff's base is waited for
+    /// the first few pings to every member, so should be generous.
+    pub adaptive_timeout: Option<f64>,
+    /// How many times a member scans the ring again before giving up on
+    /// finding an active member to send to.
+    pub send_retries: usize,
+    /// Have members taking part in an election try the active member they
+    /// last found each way around the ring during it first, for as long as
+    /// it keeps answering, rather than ping every member before it.
+    pub cache_successor: bool,
+    /// Have members that find another down tell the rest of the ring, which
+    /// routes around it from then on rather than ping it on every send,
+    /// until it comes back up and says so.
+    pub reconfigure: bool,
+    /// Have a member whose thread fails, erroring out or panicking,
+    /// respawned with fresh state for the run to go on, rather than the
+    /// run given up on.
+    pub respawn: bool,
+    /// Where to trace elections and the messages delivered during them, if
+    /// anywhere.
+    pub tracer: Option<Tracer>,
+    /// What pauses before every message delivered to a ring member, if
+    /// single-stepping.
+    pub stepper: Option<step::Stepper>,
+    /// What pauses and resumes the ring from outside it, if anything.
+    pub pause: Option<pause::Pause>,
+    /// Where to checkpoint the simulation after every step, if anywhere.
+    pub checkpoint: Option<PathBuf>,
+    /// Where to write the run's summary as JSON once it ends, if anywhere.
+    pub stats_out: Option<PathBuf>,
+    /// Where to write a CSV row for every election once the run ends, if
+    /// anywhere.
+    pub metrics_out: Option<PathBuf>,
+    /// Where to write a sequence diagram of the messages sent once the run
+    /// ends, if anywhere, in the format its name calls for.
+    pub diagram_out: Option<PathBuf>,
+    /// Where to write a Graphviz graph of the ring once the run ends, if
+    /// anywhere.
+    pub dot_out: Option<PathBuf>,
+    /// Write a graph of the ring after every election as well, next to
+    /// the run's.
+    pub dot_elections: bool,
+    /// What the simulation keeps time by.
+    pub clock: SharedClock,
+    /// Where ring members and the simulator emit everything that happens.
+    pub events: SharedSink,
+    /// How to picture the ring among the output after every change to it,
+    /// if at all.
+    pub render: Option<Render>,
+    /// The oracle watching the events, to check after every election that
+    /// the active members agree on the coordinator, if asked to.
+    pub oracle: Option<Oracle>,
+    /// Keep virtual time for the whole ring, passing it only once every
+    /// member and the simulator is waiting, instead of only on sleeps.
+    /// Overrides the clock.
+    pub virtual_time: bool,
+    /// Keep virtual time with members and the simulator taking turns, in
+    /// an order picked by the seed, so that a run can be repeated exactly.
+    pub deterministic: bool,
+    /// What the ring's threads wait through, while it's keeping virtual
+    /// time.
+    sched: Option<Scheduler>,
+    /// What ring members sent, while the ring is running.
+    tally: Option<Arc<Tally>>,
+    /// The messages sent for the diagram, while the ring is running.
+    diagram: Option<Diagram>,
+    /// The messages sent for the graphs, while the ring is running.
+    dot: Option<Dot>,
+    /// Where to count every delivered election message, if anywhere.
+    pub counts: Option<Arc<MessageCounts>>,
+    /// Where to publish the state of the ring for the status server, if
+    /// anywhere.
+    #[cfg(feature = "status-server")]
+    pub status: Option<status::Board>,
+    /// Where to count metrics for Prometheus to scrape, if anywhere.
+    #[cfg(feature = "metrics-server")]
+    pub metrics: Option<metrics::Exporter>,
+    /// Draw a live dashboard of the ring in the terminal.
+    #[cfg(feature = "tui")]
+    pub tui: bool,
+    /// The dashboard being drawn, while the ring is running.
+    #[cfg(feature = "tui")]
+    dashboard: Option<tui::Dashboard>,
+}
+
+impl SimConfig {
+    /// The election priority of ring member `id`, 0 if it wasn't given
+    /// one.
+    fn priority(&self, id: usize) -> u64 {
+        self.priorities.get(id).copied().unwrap_or(0)
+    }
+
+    /// Give ring member `id` election priority `priority` from now on.
+    fn set_priority(&mut self, id: usize, priority: u64) {
+        if self.priorities.len() <= id {
+            self.priorities.resize(id + 1, 0);
+        }
+
+        self.priorities[id] = priority;
+    }
+
+    /// How long the coordinator going down may take the members watching
+    /// it to find out, if they watch it.
+    fn watch_window(&self) -> Option<Duration> {
+        let detection = self.detection.map(|detection| detection.window());
+        let lease = self.lease.map(|lease| lease * 2);
+        detection.max(lease)
+    }
+
+    /// The member an election among the `active` members of a ring would
+    /// elect, if any is. A ranking by when members were last seen alive is
+    /// taken to favor the lowest id, as it does with all seen at once.
+    pub(crate) fn elects(&self, active: &[bool]) -> Option<usize> {
+        let keys = (0..active.len())
+            .map(|id| match self.algorithm {
+                Algorithm::Ring => self.ranking_key(id).unwrap_or(0),
+                _ => id as u64,
+            })
+            .collect::<Vec<_>>();
+
+        rank(active, &keys)
+    }
+
+    /// The key ring member `id` is ranked by in an election, unless only
+    /// the member knows it.
+    fn ranking_key(&self, id: usize) -> Option<u64> {
+        match self.ranking {
+            Ranking::Priority => Some(self.priority(id)),
+            Ranking::LowestId => Some(0),
+            Ranking::HighestId => Some(id as u64),
+            Ranking::Recency => None,
+        }
+    }
+}
+
+/// How many election messages of each type were delivered to ring members.
+#[derive(Debug, Default)]
+pub struct MessageCounts {
+    pings: AtomicUsize,
+    pongs: AtomicUsize,
+    elections: AtomicUsize,
+    results: AtomicUsize,
+    answers: AtomicUsize,
+}
+
+impl MessageCounts {
+    /// How many messages of each type were counted so far.
+    fn totals(&self) -> [(&'static str, usize); 5] {
+        [
+            ("pings", &self.pings),
+            ("pongs", &self.pongs),
+            ("elections", &self.elections),
+            ("results", &self.results),
+            ("answers", &self.answers),
+        ]
+        .map(|(name, counter)| (name, counter.load(Ordering::Relaxed)))
+    }
+
+    fn count(&self, msg: &Msg) {
+        let counter = match msg {
+            Msg::Ping { .. } => &self.pings,
+            Msg::Pong => &self.pongs,
+            Msg::Election { .. } | Msg::Candidate { .. } | Msg::Probe { .. }
+            | Msg::BullyElection { .. } => &self.elections,
+            Msg::ElectionResult { .. } | Msg::Coordinator { .. } => &self.results,
+            Msg::Answer | Msg::Reply { .. } => &self.answers,
+            _ => return,
+        };
+
+        counter.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+impl fmt::Display for MessageCounts {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let names = [
+            "pings", "pongs", "election forwards", "result forwards", "answers",
+        ];
+        let mut total = 0;
+
+        for (name, (_, n)) in names.into_iter().zip(self.totals()) {
+            total += n;
+            writeln!(f, "{:17}  {}", name, n)?;
+        }
+
+        writeln!(f, "{:17}  {}", "total", total)
+    }
+}
+
+/// What every ring member sent over a run, for its summary.
+#[derive(Debug)]
+struct Tally {
+    members: Mutex<Vec<MemberStats>>,
+    /// The last election message a ring member sent on: who sent it, to
+    /// whom, and its type.
+    forwarded: Mutex<Option<(usize, usize, &'static str)>>,
+}
+
+impl Tally {
+    fn new(ring_size: usize) -> Self {
+        Self {
+            members: Mutex::new(vec![MemberStats::default(); ring_size]),
+            forwarded: Mutex::new(None),
+        }
+    }
+
+    /// Note `msg` as sent on by ring member `from` to `to`, if it's part of
+    /// an election.
+    fn forwarded(&self, from: usize, to: usize, msg: &Msg) {
+        if !msg.is_sim() && !matches!(msg, Msg::Ping { .. } | Msg::Pong) {
+            let mut forwarded = self.forwarded.lock().unwrap_or_else(|e| e.into_inner());
+            *forwarded = Some((from, to, msg.kind()));
+        }
+    }
+
+    /// The last election message sent on since this was last asked.
+    fn take_forwarded(&self) -> Option<(usize, usize, &'static str)> {
+        self.forwarded.lock().unwrap_or_else(|e| e.into_inner()).take()
+    }
+
+    /// Count `msg` as sent by ring member `id`.
+    fn sent(&self, id: usize, msg: &Msg) {
+        let mut members = self.members.lock().unwrap_or_else(|e| e.into_inner());
+        let sent = &mut members[id].sent;
+
+        match sent.iter_mut().find(|(kind, _)| *kind == msg.kind()) {
+            Some((_, n)) => *n += 1,
+            None => sent.push((msg.kind(), 1)),
+        }
+    }
+
+    /// How many messages ring members have sent so far, all told.
+    fn total(&self) -> usize {
+        let members = self.members.lock().unwrap_or_else(|e| e.into_inner());
+        members.iter().flat_map(|m| &m.sent).map(|(_, n)| n).sum()
+    }
+
+    /// Count a ping by ring member `id` as unanswered.
+    fn ping_timeout(&self, id: usize) {
+        let mut members = self.members.lock().unwrap_or_else(|e| e.into_inner());
+        members[id].ping_timeouts += 1;
+    }
+
+    /// What each ring member sent so far, by id, its messages by type.
+    fn members(&self) -> Vec<MemberStats> {
+        let mut members = self.members.lock().unwrap_or_else(|e| e.into_inner()).clone();
+
+        for member in &mut members {
+            member.sent.sort();
+        }
+
+        members
+    }
+}
+
+/// What a ring member sent over a run.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct MemberStats {
+    /// How many messages of each type it sent other ring members.
+    pub sent: Vec<(&'static str, usize)>,
+    /// How many of its pings weren't answered within their timeout.
+    pub ping_timeouts: usize,
+}
+
+/// How one of the simulator's elections went.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ElectionStats {
+    /// The ring member it was started from.
+    pub initiator: usize,
+    /// The ring member it elected, unless the simulator gave up on it.
+    pub winner: Option<usize>,
+    /// When it started and ended by the simulation's clock, since the run
+    /// started.
+    pub start: Duration,
+    pub end: Duration,
+    /// How many messages ring members sent meanwhile, if they were
+    /// spawned here.
+    pub messages: Option<usize>,
+    /// How many ring members were active when it started.
+    pub active: usize,
+}
+
+impl ElectionStats {
+    /// The columns of [`Self::csv_row`].
+    pub const CSV_HEADER: &'static str =
+        "election,start_ms,end_ms,initiator,winner,messages,active";
+
+    /// The election as a row of CSV, numbered `n`, leaving out whatever
+    /// isn't known.
+    pub fn csv_row(&self, n: usize) -> String {
+        let known = |v: Option<usize>| v.map(|v| v.to_string()).unwrap_or_default();
+
+        format!(
+            "{},{:.3},{:.3},{},{},{},{}",
+            n, self.start.as_secs_f64() * 1000.0, self.end.as_secs_f64() * 1000.0,
+            self.initiator, known(self.winner), known(self.messages), self.active
+        )
+    }
+}
+
+/// How a ring member retries a ping before declaring its target inactive:
+/// each attempt waits `multiplier` times longer than the last, up to
+/// `attempts` attempts.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Backoff {
+    pub base: Duration,
+    pub multiplier: u32,
+    pub attempts: u32,
+}
+
+impl Default for Backoff {
+    /// A single 1 millisecond attempt.
+    fn default() -> Self {
+        Self { base: Duration::from_millis(1), multiplier: 2, attempts: 1 }
+    }
+}
+
+impl std::str::FromStr for Backoff {
+    type Err = Error;
+
+    /// Parse a backoff given as BASE,MULTIPLIER,ATTEMPTS, e.g. "10ms,2,3".
+    fn from_str(s: &str) -> Result<Self> {
+        let parts = s.split(',').collect::<Vec<_>>();
+
+        let (base, multiplier, attempts) = match parts[..] {
+            [base, multiplier, attempts] => (base, multiplier, attempts),
+            _ => bail!("Backoff must be given as BASE,MULTIPLIER,ATTEMPTS"),
+        };
+
+        let backoff = Self {
+            base: parse_duration(base)?,
+            multiplier: multiplier.parse()
+                .map_err(|_| Error::msg(format!("Invalid backoff multiplier {:?}", multiplier)))?,
+            attempts: attempts.parse()
+                .map_err(|_| Error::msg(format!("Invalid backoff attempts {:?}", attempts)))?,
+        };
+
+        if backoff.attempts == 0 {
+            bail!("Backoff needs at least one attempt");
+        }
+
+        // Every attempt waits longer than the last.
+        if backoff.multiplier < 2 {
+            bail!("Backoff needs a multiplier of at least 2");
+        }
+
+        Ok(backoff)
+    }
+}
+
+/// How ring members watch the coordinator themselves: every follower pings
+/// it every `interval`, and starts an election of its own once `misses`
+/// pings in a row have gone unanswered, rather than wait on the simulator
+/// to elect another when it takes the coordinator down.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Detection {
+    pub interval: Duration,
+    pub misses: u32,
+}
+
+impl Detection {
+    /// How long the coordinator going down may take to be found out.
+    pub fn window(&self) -> Duration {
+        self.interval * (self.misses + 1)
+    }
+}
+
+impl std::str::FromStr for Detection {
+    type Err = Error;
+
+    /// Parse detection given as INTERVAL[,MISSES], e.g. "50ms,3", with as
+    /// many misses as the simulator allows the coordinator's heartbeat by
+    /// default.
+    fn from_str(s: &str) -> Result<Self> {
+        let (interval, misses) = match s.split_once(',') {
+            Some((interval, misses)) => (interval, misses.parse()?),
+            None => (s, HEARTBEAT_MISSES),
+        };
+
+        let detection = Self { interval: parse_duration(interval)?, misses };
+
+        if detection.interval.is_zero() || detection.misses == 0 {
+            bail!("Detection needs a nonzero interval and at least one miss");
+        }
+
+        Ok(detection)
+    }
+}
+
+/// When an election is considered over, and so who ends it.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum TerminationRule {
+    /// The election message travels the whole ring and ends when it
+    /// returns to its originator, the first member to have voted in it.
+    #[default]
+    ReturnToOrigin,
+    /// The election ends at the last active member to vote, which finds
+    /// that its next active member has already voted. This saves the
+    /// final trip back to the originator.
+    AllVoted,
+}
+
+impl std::str::FromStr for TerminationRule {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "origin" => Ok(Self::ReturnToOrigin),
+            "all-voted" => Ok(Self::AllVoted),
+            _ => bail!("Unknown termination rule {}", s),
+        }
+    }
+}
+
+/// The election algorithm ring members run.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Algorithm {
+    /// An election message travels the ring, collecting the votes of every
+    /// active member, and the voter ranked first wins.
+    #[default]
+    Ring,
+    /// A member challenges every member with a higher id and wins unless
+    /// one of them answers, taking the election over, so that the highest
+    /// active id wins.
+    Bully,
+    /// A single candidate id travels the ring, replaced by the id of every
+    /// active member higher than it, and the highest active id wins once
+    /// its candidacy makes it around.
+    ChangRoberts,
+    /// Every member stands as a candidate, probing ever further both ways
+    /// around the ring, twice as far each phase, until a higher id beats
+    /// it. The highest active id wins once its probe makes it around.
+    HirschbergSinclair,
+}
+
+impl std::str::FromStr for Algorithm {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "ring" => Ok(Self::Ring),
+            "bully" => Ok(Self::Bully),
+            "chang-roberts" => Ok(Self::ChangRoberts),
+            "hirschberg-sinclair" => Ok(Self::HirschbergSinclair),
+            _ => bail!("Unknown election algorithm {}", s),
+        }
+    }
+}
+
+/// How an election's winner is ranked first among its voters, ties going
+/// to the lowest id.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Ranking {
+    /// The voter with the highest priority, so the lowest id unless the
+    /// members were given priorities.
+    #[default]
+    Priority,
+    /// The voter with the lowest id, whatever the priorities.
+    LowestId,
+    /// The voter with the highest id, as in the textbook ring algorithm.
+    HighestId,
+    /// The voter most recently confirmed alive, by answering a ping or
+    /// sending a heartbeat.
+    Recency,
+}
+
+impl std::str::FromStr for Ranking {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "priority" => Ok(Self::Priority),
+            "lowest-id" => Ok(Self::LowestId),
+            "highest-id" => Ok(Self::HighestId),
+            "recency" => Ok(Self::Recency),
+            _ => bail!("Unknown election ranking {}", s),
+        }
+    }
+}
+
+/// When the simulator starts an election after toggling a ring member.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ReelectPolicy {
+    /// Only when the coordinator has gone down.
+    #[default]
+    CoordinatorOnly,
+    /// After every toggle, to compare the cost of churn.
+    Any,
+}
+
+impl std::str::FromStr for ReelectPolicy {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "coordinator-only" => Ok(Self::CoordinatorOnly),
+            "any" => Ok(Self::Any),
+            _ => bail!("Unknown re-election policy {}", s),
+        }
+    }
+}
+
+/// What the ring members spawned for a run are run on.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum MemberRuntime {
+    /// A thread of each member's own.
+    #[default]
+    Threads,
+    /// One event loop running every member as a task, on a single thread
+    /// however large the ring.
+    EventLoop,
+}
+
+impl std::str::FromStr for MemberRuntime {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "threads" => Ok(Self::Threads),
+            "event-loop" => Ok(Self::EventLoop),
+            _ => bail!("Unknown member runtime {}", s),
+        }
+    }
+}
+
+/// Faults injected into the messages ring members send each other.
+///
+/// Messages from the simulator are never affected.
+#[derive(Debug, Clone, Default)]
+pub struct Faults {
+    /// Probability of silently dropping a message.
+    pub drop_rate: f64,
+    /// Upper bound of the random delay before a message is sent.
+    pub max_delay: Duration,
+    /// How long every message takes on its way, if it isn't delivered at
+    /// once.
+    pub latency: Option<Latency>,
+    /// How long messages take from one member to another, by the ids of
+    /// both, in place of `latency` between them.
+    pub link_latencies: HashMap<(usize, usize), Latency>,
+    /// Probability of silently dropping a message from one member to
+    /// another, by the ids of both, in place of `drop_rate` between them.
+    pub link_drop_rates: HashMap<(usize, usize), f64>,
+    /// Probability of delivering a message twice.
+    pub duplicate_rate: f64,
+    /// Probability of holding a message back to go after the next one the
+    /// same member sends, swapping the two.
+    pub reorder_rate: f64,
+    /// The partition members' messages can't cross, set by the simulator.
+    pub cut: Cut,
+}
+
+impl Faults {
+    /// Faults used by chaos runs unless overridden.
+    pub fn chaos() -> Self {
+        Self { drop_rate: 0.02, max_delay: Duration::from_millis(2), ..Self::default() }
+    }
+
+    /// How long messages from member `from` to member `to` take on their
+    /// way, if it isn't delivered at once.
+    fn latency(&self, from: usize, to: usize) -> Option<Latency> {
+        self.link_latencies.get(&(from, to)).copied().or(self.latency)
+    }
+
+    /// Probability of silently dropping a message from member `from` to
+    /// member `to`.
+    fn drop_rate(&self, from: usize, to: usize) -> f64 {
+        self.link_drop_rates.get(&(from, to)).copied().unwrap_or(self.drop_rate)
+    }
+
+    /// Whether any message may be dropped.
+    pub fn lossy(&self) -> bool {
+        self.drop_rate > 0.0 || self.link_drop_rates.values().any(|p| *p > 0.0)
+    }
+}
+
+/// How long a message takes on its way, picked at random for each one.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Latency {
+    /// Anything from `min` to `max`, all equally likely.
+    Uniform { min: Duration, max: Duration },
+    /// Normally distributed around `mean`, never less than nothing.
+    Normal { mean: Duration, stddev: Duration },
+    /// At least `scale`, with a long tail the longer the lower `shape` is.
+    Pareto { scale: Duration, shape: f64 },
+}
+
+impl Latency {
+    /// How long the next message takes, picked with `rng`.
+    fn pick(&self, rng: &mut Rng) -> Duration {
+        match *self {
+            Latency::Uniform { min, max } => {
+                let spread = (max - min).as_micros() as u64;
+                min + Duration::from_micros(rng.below(spread + 1))
+            }
+            Latency::Normal { mean, stddev } => {
+                // Box–Muller, with the first draw kept off zero.
+                let (u, v) = (1.0 - rng.unit(), rng.unit());
+                let z = (-2.0 * u.ln()).sqrt() * (2.0 * std::f64::consts::PI * v).cos();
+                capped(mean.as_secs_f64() + z * stddev.as_secs_f64())
+            }
+            Latency::Pareto { scale, shape } => {
+                let u = 1.0 - rng.unit();
+                capped(scale.as_secs_f64() * u.powf(-1.0 / shape))
+            }
+        }
+    }
+}
+
+/// `secs` seconds, at least nothing and at most [`MAX_LATENCY`].
+fn capped(secs: f64) -> Duration {
+    match Duration::try_from_secs_f64(secs.max(0.0)) {
+        Ok(duration) => duration.min(MAX_LATENCY),
+        Err(_) => MAX_LATENCY,
+    }
+}
+
+impl std::str::FromStr for Latency {
+    type Err = Error;
+
+    /// Parse a latency given as uniform:MIN:MAX, normal:MEAN:STDDEV or
+    /// pareto:SCALE:SHAPE, e.g. "normal:5ms:1ms".
+    fn from_str(s: &str) -> Result<Self> {
+        let latency = match s.split(':').collect::<Vec<_>>()[..] {
+            ["uniform", min, max] => {
+                let (min, max) = (parse_duration(min)?, parse_duration(max)?);
+
+                if min > max {
+                    bail!("Latency {:?} must not start after it ends", s);
+                }
+
+                Latency::Uniform { min, max }
+            }
+            ["normal", mean, stddev] => Latency::Normal {
+                mean: parse_duration(mean)?,
+                stddev: parse_duration(stddev)?,
+            },
+            ["pareto", scale, shape] => {
+                let shape = match shape.parse::<f64>() {
+                    Ok(shape) if shape >= 1.0 => shape,
+                    Ok(_) => bail!("Pareto shape in {:?} must be at least 1, or it has no mean", s),
+                    _ => bail!("Invalid Pareto shape {:?} in {:?}", shape, s),
+                };
+
+                Latency::Pareto { scale: parse_duration(scale)?, shape }
+            }
+            _ => bail!(
+                "Latency must be given as uniform:MIN:MAX, normal:MEAN:STDDEV or \
+                pareto:SCALE:SHAPE, not {:?}", s
+            ),
+        };
+
+        Ok(latency)
+    }
+}
+
+/// How long a ring member takes over each message from another member: a
+/// time picked at random between `min` and `max` every time, so always the
+/// same if they are.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ProcessingDelay {
+    pub min: Duration,
+    pub max: Duration,
+}
+
+impl ProcessingDelay {
+    /// How long the next message takes, picked with `rng` unless fixed.
+    fn pick(&self, rng: &mut Rng) -> Duration {
+        if self.min == self.max {
+            return self.min;
+        }
+
+        let spread = (self.max - self.min).as_micros() as u64;
+        self.min + Duration::from_micros(rng.below(spread + 1))
+    }
+}
+
+impl std::str::FromStr for ProcessingDelay {
+    type Err = Error;
+
+    /// Parse a delay given as a duration, such as "10ms", or as the range
+    /// MIN..MAX it's picked from, such as "5ms..20ms".
+    fn from_str(s: &str) -> Result<Self> {
+        let (min, max) = match s.split_once("..") {
+            Some((min, max)) => (parse_duration(min)?, parse_duration(max)?),
+            None => (parse_duration(s)?, parse_duration(s)?),
+        };
+
+        if min > max {
+            bail!("Processing delay {:?} must not start after it ends", s);
+        }
+
+        Ok(Self { min, max })
+    }
+}
+
+impl fmt::Display for ProcessingDelay {
+    /// The delay as [`ProcessingDelay`]'s `FromStr` parses it.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.min == self.max {
+            true => write!(f, "{}", duration_str(self.min)),
+            false => write!(f, "{}..{}", duration_str(self.min), duration_str(self.max)),
+        }
+    }
+}
+
+/// Groups of ring members cut off from each other, each able to reach only
+/// those in the same group. Members named in no group make up another.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Partition {
+    pub groups: Vec<Vec<usize>>,
+}
+
+impl Partition {
+    /// Cut the ring into `groups`, unless none is given or one names a
+    /// member another does.
+    pub fn new(groups: Vec<Vec<usize>>) -> Result<Self> {
+        if groups.is_empty() {
+            bail!("Partition names no group of members");
+        }
+
+        let mut named = groups.concat();
+        named.sort();
+
+        if let Some(pair) = named.windows(2).find(|pair| pair[0] == pair[1]) {
+            bail!("Partition names member {} in more than one group", pair[0]);
+        }
+
+        Ok(Self { groups })
+    }
+}
+
+impl std::str::FromStr for Partition {
+    type Err = Error;
+
+    /// Parse groups given as ids between braces, such as "{0,1} {2,3,4}".
+    fn from_str(s: &str) -> Result<Self> {
+        let mut groups = Vec::new();
+        let mut rest = s.trim();
+
+        while !rest.is_empty() {
+            let (group, after) = match rest.strip_prefix('{').and_then(|r| r.split_once('}')) {
+                Some(split) => split,
+                None => bail!("Invalid partition {:?}, such as {{0,1}} {{2,3,4}}", s),
+            };
+
+            let group = group.split(',')
+                .map(|id| match id.trim().parse() {
+                    Ok(id) => Ok(id),
+                    Err(_) => bail!("Invalid member {:?} in partition {:?}", id.trim(), s),
+                })
+                .collect::<Result<Vec<usize>>>()?;
+
+            groups.push(group);
+            rest = after.trim_start();
+        }
+
+        Self::new(groups).map_err(|e| Error::msg(format!("{} in {:?}", e, s)))
+    }
+}
+
+impl fmt::Display for Partition {
+    /// The partition as [`Partition`]'s `FromStr` parses it.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (i, group) in self.groups.iter().enumerate() {
+            let ids = group.iter().map(|id| id.to_string()).collect::<Vec<_>>();
+
+            match i {
+                0 => write!(f, "{{{}}}", ids.join(","))?,
+                _ => write!(f, " {{{}}}", ids.join(","))?,
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// The partition the ring is cut by, if any, as the simulator sets it for
+/// every member to check its messages against. Clones share the same one.
+#[derive(Debug, Clone, Default)]
+pub struct Cut {
+    /// The group each member is in, by id, or none while the ring is whole.
+    sides: Arc<Mutex<Vec<usize>>>,
+}
+
+impl Cut {
+    /// Cut a ring of `ring_size` members as `partition` says.
+    fn split(&self, partition: &Partition, ring_size: usize) {
+        let mut sides = vec![partition.groups.len(); ring_size];
+
+        for (side, group) in partition.groups.iter().enumerate() {
+            for id in group {
+                sides[*id] = side;
+            }
+        }
+
+        *self.sides.lock().unwrap_or_else(|e| e.into_inner()) = sides;
+    }
+
+    /// Make the ring whole again.
+    fn heal(&self) {
+        self.sides.lock().unwrap_or_else(|e| e.into_inner()).clear();
+    }
+
+    /// Whether messages from member `from` to member `to` are cut off.
+    fn separates(&self, from: usize, to: usize) -> bool {
+        let sides = self.sides.lock().unwrap_or_else(|e| e.into_inner());
+
+        match (sides.get(from), sides.get(to)) {
+            (Some(a), Some(b)) => a != b,
+            _ => false,
+        }
+    }
+}
+
+/// `duration` as sequence files write it, such as `2s` or `250ms`.
+fn duration_str(duration: Duration) -> String {
+    match duration.subsec_nanos() {
+        0 => format!("{}s", duration.as_secs()),
+        _ => format!("{}ms", duration.as_millis()),
+    }
+}
+
+/// What the simulator does to the ring.
+pub enum Driver {
+    /// Follow a simulation sequence.
+    Script(SimSeq),
+    /// Follow the rest of a checkpointed simulation sequence.
+    Resume(Checkpoint),
+    /// Toggle random members for the given duration.
+    Chaos { duration: Duration },
+    /// Hold a single election from ring member `origin`.
+    Once { origin: usize },
+    /// Take each action as it's given, until no more are, carrying on
+    /// past assertions that fail.
+    Interactive(Receiver<Action>),
+}
+
+/// What a simulation ended with.
+#[derive(Debug)]
+pub struct Summary {
+    /// The coordinator according to the simulator.
+    pub coord_id: usize,
+    /// How many elections the simulator started.
+    pub elections: usize,
+    /// How many of them each ring member started.
+    pub initiated: Vec<usize>,
+    /// The ring members that crashed, and why.
+    pub crashed: Vec<(usize, String)>,
+    /// How many of the elections were started although the coordinator
+    /// was up.
+    pub forced: usize,
+    /// The epoch of the election the coordinator won, 0 if the algorithm
+    /// numbers none.
+    pub epoch: usize,
+    /// How each election held this run went, in order.
+    pub results: Vec<ElectionStats>,
+    /// What each ring member spawned here sent, by id.
+    pub members: Vec<MemberStats>,
+    /// How long the run took.
+    pub wall_time: Duration,
+    /// How long the run took by the simulation's clock.
+    pub ring_time: Duration,
+}
+
+impl Summary {
+    fn to_json(&self) -> Value {
+        let results = self.results.iter()
+            .map(|e| Value::object([
+                ("initiator", e.initiator.into()),
+                ("winner", e.winner.map(Value::from).unwrap_or(Value::Null)),
+                ("start_ms", Value::Number(e.start.as_secs_f64() * 1000.0)),
+                ("end_ms", Value::Number(e.end.as_secs_f64() * 1000.0)),
+                ("messages", e.messages.map(Value::from).unwrap_or(Value::Null)),
+                ("active", e.active.into()),
+            ]))
+            .collect();
+
+        let members = self.members.iter()
+            .enumerate()
+            .map(|(id, m)| Value::object([
+                ("id", id.into()),
+                (
+                    "sent",
+                    Value::Object(
+                        m.sent.iter().map(|(kind, n)| (kind.to_string(), (*n).into())).collect()
+                    ),
+                ),
+                ("ping_timeouts", m.ping_timeouts.into()),
+            ]))
+            .collect();
+
+        let crashed = self.crashed.iter()
+            .map(|(id, reason)| Value::object([
+                ("id", (*id).into()),
+                ("reason", reason.as_str().into()),
+            ]))
+            .collect();
+
+        Value::object([
+            ("coordinator", self.coord_id.into()),
+            ("elections", self.elections.into()),
+            ("initiated", Value::Array(self.initiated.iter().map(|n| (*n).into()).collect())),
+            ("forced", self.forced.into()),
+            ("epoch", self.epoch.into()),
+            ("results", Value::Array(results)),
+            ("members", Value::Array(members)),
+            ("crashed", Value::Array(crashed)),
+            ("wall_time_ms", Value::Number(self.wall_time.as_secs_f64() * 1000.0)),
+            ("ring_time_ms", Value::Number(self.ring_time.as_secs_f64() * 1000.0)),
+        ])
+    }
+}
+
+impl fmt::Display for Summary {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.epoch {
+            0 => writeln!(
+                f, "sim: coordinator {} after {} elections",
+                self.coord_id, self.elections
+            )?,
+            epoch => writeln!(
+                f, "sim: coordinator {} of epoch {} after {} elections",
+                self.coord_id, epoch, self.elections
+            )?,
+        }
+
+        let initiated = self.initiated.iter()
+            .enumerate()
+            .map(|(id, n)| format!("{} by {}", n, id))
+            .collect::<Vec<_>>();
+
+        writeln!(f, "sim: elections started: {}", initiated.join(", "))?;
+
+        if !self.results.is_empty() {
+            let winners = self.results.iter()
+                .map(|e| match e.winner {
+                    Some(id) => format!("{} from {}", id, e.initiator),
+                    None => format!("none from {}", e.initiator),
+                })
+                .collect::<Vec<_>>();
+
+            writeln!(f, "sim: winners: {}", winners.join(", "))?;
+        }
+
+        if self.forced > 0 {
+            writeln!(
+                f, "sim: {} of them started with the coordinator still up",
+                self.forced
+            )?;
+        }
+
+        for (id, member) in self.members.iter().enumerate() {
+            let sent = member.sent.iter()
+                .map(|(kind, n)| format!("{} {}", n, kind))
+                .collect::<Vec<_>>();
+
+            match sent.is_empty() {
+                true => writeln!(f, "sim: member {} sent nothing", id)?,
+                false => writeln!(f, "sim: member {} sent {}", id, sent.join(", "))?,
+            }
+        }
+
+        if !self.members.is_empty() {
+            let timeouts = self.members.iter()
+                .enumerate()
+                .map(|(id, m)| format!("{} by {}", m.ping_timeouts, id))
+                .collect::<Vec<_>>();
+
+            writeln!(f, "sim: pings timed out: {}", timeouts.join(", "))?;
+        }
+
+        for (id, reason) in &self.crashed {
+            writeln!(f, "sim: member {} crashed: {}", id, reason)?;
+        }
+
+        writeln!(
+            f, "sim: took {:.3?}, {:.3?} by the ring's clock",
+            self.wall_time, self.ring_time
+        )
+    }
+}
+
+/// A ring of members electing a coordinator among themselves, driven by
+/// the simulator.
+#[derive(Debug)]
+pub struct Ring;
+
+impl Ring {
+    pub fn builder() -> RingBuilder {
+        RingBuilder::default()
+    }
+}
+
+/// Builds a ring, leaving out whatever isn't needed: by default the ring
+/// has [`DEFAULT_RING_SIZE`] members, runs with the default configuration
+/// and prints to stdout.
+#[derive(Debug)]
+pub struct RingBuilder {
+    size: usize,
+    config: SimConfig,
+    out: Option<PathBuf>,
+    nodes: Vec<String>,
+    protocol: Protocol,
+}
+
+impl Default for RingBuilder {
+    fn default() -> Self {
+        Self {
+            size: DEFAULT_RING_SIZE,
+            config: SimConfig::default(),
+            out: None,
+            nodes: Vec::new(),
+            protocol: Protocol::default(),
+        }
+    }
+}
+
+impl RingBuilder {
+    /// How many members the ring has.
+    pub fn size(mut self, size: usize) -> Self {
+        self.size = size;
+        self
+    }
+
+    pub fn config(mut self, config: SimConfig) -> Self {
+        self.config = config;
+        self
+    }
+
+    /// Where to send everything the ring prints instead of stdout.
+    pub fn output(mut self, out: impl Into<PathBuf>) -> Self {
+        self.out = Some(out.into());
+        self
+    }
+
+    /// Drive members already running as their own processes, listening
+    /// at `addrs` by id, instead of spawning the ring. The ring has as many
+    /// members as there are addresses.
+    pub fn nodes(mut self, addrs: Vec<String>) -> Self {
+        self.size = addrs.len();
+        self.nodes = addrs;
+        self
+    }
+
+    /// How to reach the members given with [`Self::nodes`].
+    pub fn protocol(mut self, protocol: Protocol) -> Self {
+        self.protocol = protocol;
+        self
+    }
+
+    /// Spawn the ring, let `driver` drive it to the end, and tear it down.
+    pub fn run(self, driver: Driver) -> Result<Summary> {
+        if self.size == 0 {
+            bail!("A ring needs at least one member");
+        }
+
+        if !self.nodes.is_empty() && matches!(driver, Driver::Resume(_)) {
+            bail!("Only a ring spawned here can resume a checkpoint");
+        }
+
+        if !self.nodes.is_empty() && matches!(&driver, Driver::Script(seq) if seq.pauses()) {
+            bail!("Only a ring spawned here can be paused");
+        }
+
+        if !self.nodes.is_empty() && matches!(&driver, Driver::Script(seq) if seq.partitions()) {
+            bail!("Only a ring spawned here can be partitioned");
+        }
+
+        if !self.nodes.is_empty() && (self.config.virtual_time || self.config.deterministic) {
+            bail!("Only a ring spawned here can keep virtual time");
+        }
+
+        let nodes = match self.nodes.is_empty() {
+            true => None,
+            false => Some((&self.nodes[..], self.protocol)),
+        };
+
+        run(driver, self.size, self.out.as_deref(), nodes, self.config)
+    }
+}
+
+/// Build a fresh ring, drive it to the end, and tear it down.
+fn run(
+    driver: Driver, ring_size: usize, out: Option<&Path>,
+    nodes: Option<(&[String], Protocol)>, config: SimConfig,
+) -> Result<Summary> {
+    // If an output file path was specified, redirect stdout to that.
+    let redirect = match out {
+        Some(out_path) => {
+            let log = OpenOptions::new()
+                .truncate(true)
+                .read(true)
+                .create(true)
+                .write(true)
+                .open(out_path)
+                .with_context(|| format!("cannot open {}", out_path.display()))?;
+
+            let redirect = Redirect::stdout(log)
+                .with_context(|| format!("cannot redirect output to {}", out_path.display()))?;
+
+            Some(redirect)
+        }
+        None => None,
+    };
+
+    // A resumed ring starts out as it was checkpointed.
+    let config = match config.render {
+        Some(Render::Ascii) => {
+            let (active, coord_id) = match &driver {
+                Driver::Resume(checkpoint) => (
+                    checkpoint.members.iter().map(|m| m.active).collect(),
+                    checkpoint.coord_id,
+                ),
+                _ => (vec![true; ring_size], 0),
+            };
+
+            let ascii = render::Ascii::new(config.events.clone(), active, coord_id);
+            SimConfig { events: SharedSink::new(ascii), ..config }
+        }
+        None => config,
+    };
+
+    // Draw the dashboard for this run only, falling back to plain output
+    // when there's no terminal to draw it on.
+    #[cfg(feature = "tui")]
+    let config = match config.tui {
+        true => {
+            let dashboard = tui::Dashboard::start()?;
+
+            let events = match &dashboard {
+                Some(dashboard) => SharedSink::new(dashboard.watch(config.events.clone())),
+                None => {
+                    config.events.emit(Source::Main, &Event::NoTerminal);
+                    config.events.clone()
+                }
+            };
+
+            SimConfig { dashboard, events, ..config }
+        }
+        false => config,
+    };
+
+    #[cfg(feature = "tui")]
+    let dashboard = config.dashboard.clone();
+
+    let events = config.events.clone();
+
+    // Members hold their deliveries through whatever pauses the ring, be
+    // it the sequence or whoever gives the actions.
+    let pauses = match &driver {
+        Driver::Script(seq) | Driver::Resume(Checkpoint { seq, .. }) => seq.pauses(),
+        Driver::Interactive(_) => true,
+        Driver::Chaos { .. } | Driver::Once { .. } => false,
+    };
+
+    let config = match (pauses, config.pause.is_none()) {
+        (true, true) => SimConfig { pause: Some(Pause::default()), ..config },
+        _ => config,
+    };
+
+    // Messages cut off by a partition can leave the simulator waiting on
+    // the ring forever, as lost ones can.
+    let partitions = matches!(
+        &driver,
+        Driver::Script(seq) | Driver::Resume(Checkpoint { seq, .. }) if seq.partitions()
+    );
+
+    let config = match (partitions, config.sim_timeout) {
+        (true, None) => SimConfig { sim_timeout: Some(SIM_TIMEOUT), ..config },
+        _ => config,
+    };
+
+    // Only members spawned here can be counted.
+    let tally = nodes.is_none().then(|| Arc::new(Tally::new(ring_size)));
+    let diagram = config.diagram_out.is_some().then(Diagram::default);
+    let dot = config.dot_out.is_some().then(Dot::default);
+    let config = SimConfig { tally, diagram, dot, ..config };
+
+    // Have every thread keep the scheduler's time for this run, unless
+    // it's exploring with a scheduler of its own.
+    let config = match (config.sched.clone(), config.virtual_time || config.deterministic) {
+        (Some(sched), _) => SimConfig { clock: SharedClock::new(sched), ..config },
+        (None, true) => {
+            let sched = match config.deterministic {
+                true => Scheduler::deterministic(config.seed),
+                false => Scheduler::new(),
+            };
+
+            SimConfig { clock: SharedClock::new(sched.clone()), sched: Some(sched), ..config }
+        }
+        (None, false) => config,
+    };
+
+    // Taking turns blocks every member but the one whose turn it is, and
+    // members on the loop can't block without holding up the others.
+    if config.sched.is_some() && config.runtime == MemberRuntime::EventLoop {
+        bail!("Members on the event loop can't take turns in virtual time");
+    }
+
+    // Events are timed as the ring keeps time, so runs taking turns log
+    // alike.
+    if config.sched.is_some() {
+        config.events.keep_time(&config.clock);
+    }
+
+    // Create a link for each ring member: a bounded channel for the
+    // election and an unbounded one for the simulator's control messages.
+    // Nobody may be held up sending while keeping virtual time, as time
+    // would stop for them, nor on the event loop, as the whole loop would.
+    let (ss, rs): (Vec<_>, Vec<_>) = (0..ring_size)
+        .map(|_| match config.sched.is_some() || config.runtime == MemberRuntime::EventLoop {
+            true => unbounded_link(),
+            false => Link::new(config.algorithm, config.channel_capacity),
+        })
+        .unzip();
+
+    // Create a channel for the simulator.
+    let (sim_s, sim_r) = match config.sched {
+        Some(_) => unbounded(),
+        None => bounded(1),
+    };
+
+    // Every member and the simulator waits through the scheduler, if any.
+    let (parts, sim_part): (Vec<_>, _) = match &config.sched {
+        Some(sched) => {
+            let parts = rs.iter()
+                .map(|r| {
+                    let r = r.clone();
+                    Some(sched.join(move || r.len()))
+                })
+                .collect();
+            let sim_r = sim_r.clone();
+
+            (parts, Some(sched.join(move || sim_r.len())))
+        }
+        None => (vec![None; rs.len()], None),
+    };
+
+    // Members running elsewhere are reached over their links instead of
+    // being spawned.
+    let (rs, forwarders) = match nodes {
+        Some((addrs, protocol)) => (Vec::new(), protocol.dial(addrs, rs, sim_s.clone())?),
+        None => (rs, Vec::new()),
+    };
+
+    #[cfg(feature = "tui")]
+    if let Some(dashboard) = &config.dashboard {
+        dashboard.count(rs.clone());
+    }
+
+    // Members resuming a checkpoint start from their checkpointed state.
+    let initial = match &driver {
+        Driver::Resume(checkpoint) => checkpoint.members.clone(),
+        _ => Vec::new(),
+    };
+
+    // Each member's links to the others, for the simulator to add those
+    // joining the ring to and take those leaving it out of.
+    let peers = (0..ring_size)
+        .map(|i| -> Roster {
+            let links = ss.iter().cloned().enumerate().filter(|(j, _)| *j != i).collect();
+            Arc::new(Mutex::new(links))
+        })
+        .collect::<Vec<_>>();
+
+    // Members run on an event loop are sent to it, those joining the ring
+    // later included, to be made into tasks on its thread.
+    let (tasks, loop_r) = match config.runtime {
+        MemberRuntime::EventLoop => {
+            let (tasks, loop_r) = unbounded();
+            (Some(tasks), Some(loop_r))
+        }
+        MemberRuntime::Threads => (None, None),
+    };
+
+    let spawner = nodes.is_none().then(|| Spawner {
+        peers: peers.clone(),
+        sim_s: sim_s.clone(),
+        threads: Vec::new(),
+        tasks: tasks.clone(),
+    });
+
+    // Spawn a thread for each ring member, or one for the event loop they
+    // all run on, and one for the controller. Each ring member receives
+    // on its channel and sends on the next's.
+    let (res, joined) = thread::scope(|scope| {
+        for (i, ((r, part), peers)) in rs.into_iter().zip(parts).zip(peers).enumerate() {
+            let transport = Channels::shared(peers, ring_size, r);
+            let sim_s = sim_s.clone();
+            let config = config.clone();
+            let status = initial.get(i).cloned();
+            let member = move || run_member(i, transport, sim_s, config, status, part);
+
+            match &tasks {
+                Some(tasks) => {
+                    let _ = tasks.send(Box::new(move || Box::pin(member()) as executor::Task));
+                }
+                None => {
+                    scope.spawn(move |_| executor::block_on(member()));
+                }
+            }
+        }
+
+        // The loop ends once the simulator can spawn no more members.
+        drop(tasks);
+
+        if let Some(loop_r) = loop_r {
+            scope.spawn(move |_| executor::run(loop_r));
+        }
+
+        config.events.emit(Source::Main, &Event::RingCreated);
+
+        scope
+            .spawn(move |_| {
+                let _part = sim_part.zip(config.sched.clone())
+                    .map(|(part, sched)| sched.enter(part));
+
+                let coord_id = config.elects(&vec![true; ss.len()]).unwrap_or_default();
+                let mut sim = Simulator::new(ss, sim_r, coord_id, config, spawner);
+                let res = sim.run(driver);
+                (res, sim.spawner.map_or(Vec::new(), |spawner| spawner.threads))
+            })
+            .join()
+            .unwrap()
+    })
+    .unwrap();
+
+    // Members that joined the ring stop once told to, as the rest did.
+    for member in joined {
+        let _ = member.join();
+    }
+
+    // Let the simulator's last messages reach members running elsewhere.
+    for forwarder in forwarders {
+        let _ = forwarder.join();
+    }
+
+    events.emit(Source::Main, &Event::Done);
+
+    #[cfg(feature = "tui")]
+    if let Some(dashboard) = dashboard {
+        dashboard.close()?;
+    }
+
+    if let Some(redirect_) = redirect {
+        redirect_.into_inner();
+    }
+
+    res
+}
+
+/// Run ring member `id` over `transport`, starting out as `status` says if
+/// given, until told to stop, as participant `part` of the scheduler if
+/// there is one, and let the simulator know through `sim_s` should it
+/// crash.
+///
+/// Should the member fail before being told to stop and the ring respawn
+/// members, it's supervised: run again over the same transport with fresh
+/// state, once the simulator has heard of it.
+///
+/// The member runs as a task, for a thread of its own to block on or an
+/// event loop to run alongside the others.
+async fn run_member(
+    id: usize, transport: Channels, sim_s: Sender<SimMsg>, config: SimConfig,
+    status: Option<MemberStatus>, part: Option<usize>,
+) {
+    let crash_s = sim_s.clone();
+    let events = config.events.clone();
+    let respawn = config.respawn;
+
+    // Only stop waiting through the scheduler once the simulator has heard
+    // of a crash.
+    let _part = part.zip(config.sched.clone())
+        .map(|(part, sched)| sched.enter(part));
+
+    let transport = match config.sched.clone() {
+        Some(sched) => transport.scheduled(sched),
+        None => transport,
+    };
+
+    let mut member = RingMember::builder()
+        .id(id)
+        .transport(transport)
+        .sim(sim_s)
+        .config(config)
+        .build();
+
+    if let Some(status) = &status {
+        member.restore(status);
+    }
+
+    loop {
+        // Catch a panicking member here so that it doesn't unwind the whole
+        // ring, and let the simulator know instead.
+        let res = executor::catch_unwind(member.run_async()).await;
+
+        let (reason, panicked) = match res {
+            Ok(Ok(())) => return,
+            Ok(Err(e)) => (e.to_string(), false),
+            Err(payload) => (panic_reason(payload), true),
+        };
+
+        match panicked {
+            true => events.emit(Source::Member(id), &Event::Crashed(reason.clone())),
+            false => events.emit(Source::Member(id), &Event::Stopped(reason.clone())),
+        }
+
+        // The simulator may already be gone, and a member that was told
+        // to stop has nothing left to do.
+        if respawn && !member.core.ended {
+            if crash_s.send(SimMsg::Respawned { id, reason }).is_err() {
+                return;
+            }
+
+            member.respawn();
+            continue;
+        }
+
+        if panicked {
+            let _ = crash_s.send(SimMsg::Crashed { id, reason });
+        }
+
+        return;
+    }
+}
+
+/// The message a panic was raised with.
+fn panic_reason(payload: Box<dyn Any + Send>) -> String {
+    match payload.downcast::<String>() {
+        Ok(s) => *s,
+        Err(payload) => match payload.downcast::<&str>() {
+            Ok(s) => s.to_string(),
+            Err(_) => "unknown panic".to_string(),
+        },
+    }
+}
+
+/// How many times the simulator queries the ring for a consistent
+/// snapshot before giving up.
+const SNAPSHOT_ATTEMPTS: usize = 5;
+
+/// How many heartbeats in a row the coordinator may miss before the
+/// simulator presumes it dead.
+const HEARTBEAT_MISSES: u32 = 3;
+
+/// How many times the simulator sends a toggle before giving up on it
+/// being acknowledged.
+const TOGGLE_ATTEMPTS: usize = 3;
+
+/// How many elections the simulator attempts before giving up on
+/// settling the ring.
+const SETTLE_ATTEMPTS: usize = 3;
+
+/// The least a ring member waits for a ping to be answered, however quick
+/// the round trips it measured: the default for a ring run on channels.
+const MIN_PING_TIMEOUT: Duration = Duration::from_millis(1);
+
+/// How many times a coordinator renews its lease within each lease, for a
+/// renewal or two to be slow without the lease running out.
+const RENEWALS_PER_LEASE: u32 = 3;
+
+/// How many round trips to a member must be measured before they're told
+/// how long to wait for it by.
+const MIN_ROUND_TRIPS: u32 = 3;
+
+/// How long a ring member waits before scanning the ring again after
+/// finding no active member on it.
+const RESCAN_DELAY: Duration = Duration::from_millis(10);
+
+/// How often the simulator looks for the next action given interactively
+/// while there's none.
+const ACTION_POLL: Duration = Duration::from_millis(50);
+
+/// How often the simulator wakes up from a wait to see if the run was
+/// interrupted.
+const INTERRUPT_POLL: Duration = Duration::from_millis(100);
+
+/// The longest a message takes on its way, however long its latency's
+/// tail, so that an extreme draw holds it up for as long as the simulator
+/// would wait on it rather than overflows.
+const MAX_LATENCY: Duration = SIM_TIMEOUT;
+
+/// The simulator, which toggles ring members active/inactive and starts
+/// elections when the coordinator goes down.
+struct Simulator {
+    ss: Vec<Link<Sender<Msg>>>,
+    sim_r: Receiver<SimMsg>,
+    coord_id: usize,
+    config: SimConfig,
+    /// Invariant violations reported by ring members since the last check.
+    violations: Vec<String>,
+    /// How many elections each ring member was asked to start.
+    initiated: Vec<usize>,
+    /// The ring members that crashed, and why.
+    crashed: Vec<(usize, String)>,
+    /// The members respawned since the ring last settled, for elections to
+    /// be held from them once it does.
+    respawned: Vec<usize>,
+    /// How many toggles have been sent, numbering each one.
+    toggles: usize,
+    /// How many elections were started although the coordinator was up.
+    forced: usize,
+    /// The epoch of the latest result taken, so that any from before it
+    /// are ignored.
+    epoch: usize,
+    /// When the coordinator was last heard from.
+    last_beat: Instant,
+    /// How each election held so far went.
+    results: Vec<ElectionStats>,
+    /// Which ring members are active, as they last acknowledged.
+    active: Vec<bool>,
+    /// Which ring members left the ring.
+    left: Vec<bool>,
+    /// What spawns members joining the ring, unless its members run
+    /// elsewhere.
+    spawner: Option<Spawner>,
+    /// When the run started, in real time and by the clock.
+    started: (Instant, Instant),
+}
+
+/// What the simulator spawns ring members joining the ring with.
+#[derive(Debug)]
+struct Spawner {
+    /// Every member's links to the others, by id.
+    peers: Vec<Roster>,
+    sim_s: Sender<SimMsg>,
+    /// The threads of the members that joined, to wait for once they're
+    /// told to stop.
+    threads: Vec<std::thread::JoinHandle<()>>,
+    /// Where to send members to run as tasks instead, if on an event loop.
+    tasks: Option<Sender<executor::Spawn>>,
+}
+
+/// Where the ring stood as an election opened, to tell what it took.
+#[derive(Debug, Clone, Copy)]
+struct Opened {
+    start: Instant,
+    /// How many messages ring members had sent, if counted.
+    sent: Option<usize>,
+    /// How many members were active.
+    active: usize,
+}
+
+impl Simulator {
+    fn new(
+        ss: Vec<Link<Sender<Msg>>>, sim_r: Receiver<SimMsg>, coord_id: usize,
+        config: SimConfig, spawner: Option<Spawner>,
+    ) -> Self {
+        Self {
+            last_beat: config.clock.now(),
+            started: (Instant::now(), config.clock.now()),
+            results: Vec::new(),
+            active: vec![true; ss.len()],
+            left: vec![false; ss.len()],
+            initiated: vec![0; ss.len()],
+            ss, sim_r, coord_id, config, spawner, violations: Vec::new(),
+            crashed: Vec::new(), respawned: Vec::new(), toggles: 0,
+            forced: 0, epoch: 0,
+        }
+    }
+
+    fn emit(&self, event: Event) {
+        self.config.events.emit(Source::Sim, &event);
+    }
+
+    /// Send `msg` to ring member `id`.
+    fn send(&self, id: usize, msg: Msg) -> Result<()> {
+        if let Some(diagram) = &self.config.diagram {
+            diagram.sim_sent(id, &msg);
+        }
+
+        self.ss[id].send(msg)?;
+        Ok(())
+    }
+
+    fn run(&mut self, driver: Driver) -> Result<Summary> {
+        // The ring is up before its first step, so show it as it starts.
+        self.export();
+        self.publish();
+
+        let res = match driver {
+            Driver::Script(seq) => self.follow(&seq, 0),
+            Driver::Resume(checkpoint) => self.resume(checkpoint),
+            Driver::Chaos { duration } => self.chaos(duration),
+            Driver::Once { origin } => self.elect(origin),
+            Driver::Interactive(actions) => self.interact(actions),
+        };
+
+        // A collector being down shouldn't fail the run itself.
+        if let Some(tracer) = &self.config.tracer {
+            match tracer.export() {
+                Ok(()) => self.emit(Event::ExportedTraces),
+                Err(e) => self.emit(Event::ExportFailed(e.to_string())),
+            }
+        }
+
+        // Members can only be asked how they ended before they're stopped.
+        let nodes = match &self.config.dot {
+            Some(_) => self.nodes(res.is_ok()),
+            None => Vec::new(),
+        };
+
+        // Tell every member to stop, skipping those that crashed, left the
+        // ring or have otherwise already stopped.
+        for id in 0..self.ss.len() {
+            if !self.left[id] && !self.crashed.iter().any(|(c, _)| *c == id) {
+                let _ = self.send(id, Msg::SimEnd);
+            }
+        }
+
+        self.emit(Event::SentEnd);
+
+        let summary = Summary {
+            coord_id: self.coord_id,
+            elections: self.initiated.iter().sum(),
+            initiated: self.initiated.clone(),
+            crashed: self.crashed.clone(),
+            forced: self.forced,
+            epoch: self.epoch,
+            results: self.results.clone(),
+            members: match &self.config.tally {
+                Some(tally) => tally.members(),
+                None => Vec::new(),
+            },
+            wall_time: self.started.0.elapsed(),
+            ring_time: self.config.clock.now().saturating_duration_since(self.started.1),
+        };
+
+        self.config.events.print(&summary);
+
+        // Write the summary even if the run failed, to show how far it got.
+        if let Some(path) = &self.config.stats_out {
+            fs::write(path, format!("{}\n", summary.to_json()))?;
+        }
+
+        if let Some(path) = &self.config.metrics_out {
+            let rows = summary.results.iter()
+                .enumerate()
+                .map(|(i, e)| e.csv_row(i + 1) + "\n")
+                .collect::<String>();
+
+            fs::write(path, format!("{}\n{}", ElectionStats::CSV_HEADER, rows))?;
+        }
+
+        if let (Some(path), Some(diagram)) = (&self.config.diagram_out, &self.config.diagram) {
+            fs::write(path, diagram.render(self.ss.len(), diagram::Format::of(path)))?;
+        }
+
+        if let (Some(path), Some(dot)) = (&self.config.dot_out, &self.config.dot) {
+            fs::write(path, dot.run(self.coord_id, &nodes))?;
+
+            for (path, graph) in dot.elections(path) {
+                fs::write(path, graph)?;
+            }
+        }
+
+        self.emit(Event::Done);
+        res.map(|_| summary)
+    }
+
+    /// Take each action of the simulation sequence from action `start` on,
+    /// checking the ring's invariants after every change if asked to.
+    fn follow(&mut self, seq: &SimSeq, start: usize) -> Result<()> {
+        for (step, action) in seq.actions.iter().enumerate().skip(start) {
+            // Nothing is done to a paused ring until it's resumed.
+            if let Some(pause) = &self.config.pause {
+                pause.hold();
+            }
+
+            self.interrupted()?;
+
+            let (id, origin, active) = match *action {
+                Action::Wait(duration) => {
+                    match duration.subsec_nanos() {
+                        0 => self.emit(Event::WaitingSecs(duration.as_secs())),
+                        _ => self.emit(Event::Waiting(duration)),
+                    }
+
+                    self.wait(duration)?;
+                    self.checkpoint(seq, step + 1, None)?;
+                    continue;
+                }
+                Action::Pause(duration) => {
+                    self.pause(duration)?;
+                    self.checkpoint(seq, step + 1, None)?;
+                    continue;
+                }
+                Action::Toggle { id, origin } => (id, origin, None),
+                Action::Kill { id, origin } => (id, origin, Some(false)),
+                Action::Recover { id, origin } => (id, origin, Some(true)),
+                Action::Crash { id, origin } => (id, origin, Some(false)),
+                Action::Restart(id) => {
+                    if self.left[id] {
+                        self.emit(Event::AlreadyInRing { id, present: false });
+                        self.checkpoint(seq, step + 1, None)?;
+                        continue;
+                    }
+
+                    if self.active[id] {
+                        self.emit(Event::AlreadyToggled { id, active: true });
+                        self.checkpoint(seq, step + 1, None)?;
+                        continue;
+                    }
+
+                    // The member comes back up knowing nothing of the ring's
+                    // elections, and finds the coordinator by holding one.
+                    self.send(id, Msg::SimRestart)?;
+                    self.toggle(id)?;
+                    self.checkpoint(seq, step, Some(id))?;
+                    self.elect(id)?;
+                    self.settled(seq, step)?;
+                    continue;
+                }
+                Action::Join { id, origin } | Action::Leave { id, origin } => {
+                    let joining = matches!(action, Action::Join { .. });
+
+                    // Joining a member that's in the ring or taking one out
+                    // that isn't leaves the ring as it is.
+                    if self.left[id] != joining {
+                        self.emit(Event::AlreadyInRing { id, present: !self.left[id] });
+                        self.checkpoint(seq, step + 1, None)?;
+                        continue;
+                    }
+
+                    if self.spawner.is_none() {
+                        bail!(
+                            "{}: {} needs a ring spawned here, not run elsewhere",
+                            seq.at(step), action
+                        );
+                    }
+
+                    if !joining && self.left.iter().filter(|left| !**left).count() < 3 {
+                        bail!(
+                            "{}: {} leaves a single member in the ring, with none to \
+                            pass messages to", seq.at(step), action
+                        );
+                    }
+
+                    // A member joins down, to be brought up as any other,
+                    // and leaves once taken down as any other.
+                    if joining {
+                        self.join(id)?;
+                    }
+
+                    if self.active[id] != joining {
+                        self.change(seq, step, id, origin)?;
+                    }
+
+                    if !joining {
+                        self.leave(id)?;
+                    }
+
+                    self.settled(seq, step)?;
+                    continue;
+                }
+                Action::Slow { id, delay } => {
+                    match self.left[id] {
+                        true => self.emit(Event::AlreadyInRing { id, present: false }),
+                        false => {
+                            self.send(id, Msg::SimSlow { delay })?;
+                            self.emit(Event::Slowing { id, delay });
+                        }
+                    }
+
+                    self.checkpoint(seq, step + 1, None)?;
+                    continue;
+                }
+                Action::Priority { id, priority } => {
+                    match self.left[id] {
+                        true => self.emit(Event::AlreadyInRing { id, present: false }),
+                        false => {
+                            // Kept here too, for the audit and any member
+                            // respawned.
+                            self.config.set_priority(id, priority);
+                            self.send(id, Msg::SimPriority { priority })?;
+                            self.emit(Event::Reprioritizing { id, priority });
+                        }
+                    }
+
+                    self.checkpoint(seq, step + 1, None)?;
+                    continue;
+                }
+                Action::Partition(ref partition) => {
+                    if self.spawner.is_none() {
+                        bail!(
+                            "{}: {} needs a ring spawned here, not run elsewhere",
+                            seq.at(step), action
+                        );
+                    }
+
+                    self.config.faults.cut.split(partition, self.ss.len());
+                    self.emit(Event::Partitioning(partition.clone()));
+                    self.checkpoint(seq, step + 1, None)?;
+                    continue;
+                }
+                Action::Heal => {
+                    self.config.faults.cut.heal();
+                    self.emit(Event::Healing);
+                    self.checkpoint(seq, step + 1, None)?;
+                    continue;
+                }
+                Action::Election { origin } => {
+                    self.checkpoint(seq, step, Some(origin))?;
+                    self.elect(origin)?;
+                    self.settled(seq, step)?;
+                    continue;
+                }
+                Action::Concurrent { origins } => {
+                    self.checkpoint(seq, step, Some(origins[0]))?;
+                    self.elect_from(&origins)?;
+                    self.settled(seq, step)?;
+                    continue;
+                }
+                Action::StepDown => {
+                    let coord_id = self.coord_id;
+
+                    let others = (0..self.ss.len())
+                        .any(|id| id != coord_id && self.active[id] && !self.left[id]);
+
+                    if !others {
+                        self.emit(Event::NoHandover(coord_id));
+                        self.checkpoint(seq, step + 1, None)?;
+                        continue;
+                    }
+
+                    // Picked back up, the coordinator is asked to step down
+                    // all over again.
+                    self.checkpoint(seq, step, None)?;
+                    self.step_down()?;
+                    self.settled(seq, step)?;
+                    continue;
+                }
+                Action::AssertCoordinator(_) | Action::AssertActive(_)
+                | Action::AssertElections(..) => {
+                    self.assert(seq, step)?;
+                    self.checkpoint(seq, step + 1, None)?;
+                    continue;
+                }
+                Action::End => {
+                    self.emit(Event::Ended(step + 1));
+                    return Ok(());
+                }
+            };
+
+            // A member that left the ring can't be changed, and killing a
+            // member that's down or recovering one that's up leaves the
+            // ring as it is.
+            if self.left[id] {
+                self.emit(Event::AlreadyInRing { id, present: false });
+                self.checkpoint(seq, step + 1, None)?;
+                continue;
+            }
+
+            if active.is_some_and(|active| self.active[id] == active) {
+                self.emit(Event::AlreadyToggled { id, active: self.active[id] });
+                self.checkpoint(seq, step + 1, None)?;
+                continue;
+            }
+
+            self.change(seq, step, id, origin)?;
+            self.settled(seq, step)?;
+        }
+
+        Ok(())
+    }
+
+    /// Toggle ring member `id` as action `step` of the sequence does,
+    /// electing from `origin` should it take the coordinator down or the
+    /// ring be asked to elect after every change.
+    fn change(&mut self, seq: &SimSeq, step: usize, id: usize, origin: usize) -> Result<()> {
+        // Nobody would be left to vote in the election that follows.
+        if self.active[id] && self.active.iter().filter(|a| **a).count() == 1 {
+            bail!(
+                "{}: taking {} down leaves no active member to elect a coordinator",
+                seq.at(step), id
+            );
+        }
+
+        let active = self.toggle(id)?;
+        let lost = id == self.coord_id && !active;
+
+        let elect = if lost {
+            true
+        } else if self.config.reelect == ReelectPolicy::Any {
+            self.forced += 1;
+            true
+        } else {
+            false
+        };
+
+        if elect {
+            self.checkpoint(seq, step, Some(origin))?;
+
+            // Members watching the coordinator find it down themselves, and
+            // the simulator does by its heartbeat when they don't.
+            match (self.config.watch_window(), self.config.heartbeat, lost) {
+                (Some(window), _, true) => self.detected(window)?,
+                (None, Some(interval), true) => self.silenced(interval)?,
+                _ => self.elect(origin)?,
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Spawn ring member `id`, which left the ring, to join it again, down
+    /// and following the coordinator, and let the others know.
+    fn join(&mut self, id: usize) -> Result<()> {
+        let on_loop = self.config.runtime == MemberRuntime::EventLoop;
+        let (link, r) = match self.config.sched.is_some() || on_loop {
+            true => unbounded_link(),
+            false => Link::new(self.config.algorithm, self.config.channel_capacity),
+        };
+
+        let part = self.config.sched.as_ref().map(|sched| {
+            let r = r.clone();
+            sched.join(move || r.len())
+        });
+
+        let status = self.out_of_ring(id);
+        let left = self.left.clone();
+        let spawner = self.spawner.as_mut().ok_or(Error::msg("No ring to join"))?;
+
+        let links = (0..left.len())
+            .filter(|other| *other != id && !left[*other])
+            .map(|other| (other, self.ss[other].clone()))
+            .collect();
+
+        spawner.peers[id] = Arc::new(Mutex::new(links));
+
+        for other in (0..left.len()).filter(|other| *other != id && !left[*other]) {
+            let mut links = spawner.peers[other].lock().unwrap_or_else(|e| e.into_inner());
+            links.insert(id, link.clone());
+        }
+
+        let transport = Channels::shared(spawner.peers[id].clone(), self.ss.len(), r);
+        let (sim_s, config) = (spawner.sim_s.clone(), self.config.clone());
+
+        let member = move || run_member(id, transport, sim_s, config, Some(status), part);
+
+        match &spawner.tasks {
+            Some(tasks) => tasks
+                .send(Box::new(move || Box::pin(member()) as executor::Task))
+                .map_err(|_| Error::msg("The event loop has ended"))?,
+            None => spawner.threads.push(std::thread::spawn(move || executor::block_on(member()))),
+        }
+
+        self.ss[id] = link;
+        self.left[id] = false;
+        self.emit(Event::JoiningRing(id));
+
+        // The member passes by those that left, and the rest through it.
+        for other in (0..self.ss.len()).filter(|other| *other != id) {
+            match self.left[other] {
+                true => self.send(id, Msg::SimLeft { id: other })?,
+                false => self.send(other, Msg::SimJoined { id })?,
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Retire ring member `id`, which is down, from the ring, once every
+    /// other member knows to pass it by.
+    fn leave(&mut self, id: usize) -> Result<()> {
+        for other in (0..self.ss.len()).filter(|other| *other != id && !self.left[*other]) {
+            self.send(other, Msg::SimLeft { id })?;
+        }
+
+        // Every member answers only once it's done with what came before.
+        self.status()?;
+
+        if let Some(spawner) = &self.spawner {
+            for peers in &spawner.peers {
+                peers.lock().unwrap_or_else(|e| e.into_inner()).remove(&id);
+            }
+        }
+
+        self.send(id, Msg::SimEnd)?;
+        self.left[id] = true;
+        self.emit(Event::LeavingRing(id));
+        Ok(())
+    }
+
+    /// The status of ring member `id` while out of the ring: down, and
+    /// following whoever the simulator takes for the coordinator.
+    fn out_of_ring(&self, id: usize) -> MemberStatus {
+        MemberStatus {
+            id,
+            label: self.config.labels.get(id).cloned(),
+            active: false,
+            coord_id: self.coord_id,
+            state: State::Follower,
+            down: Vec::new(),
+        }
+    }
+
+    /// The first ring member from `id` on, ringwise, that hasn't left the
+    /// ring.
+    fn present_from(&self, id: usize) -> usize {
+        let n = self.ss.len();
+        (0..n).map(|i| (id + i) % n).find(|i| !self.left[*i]).unwrap_or(id)
+    }
+
+    /// Take each action from `actions` as it comes, reporting assertions
+    /// that fail rather than stopping at them, until `actions` is dropped
+    /// or one ends the run.
+    fn interact(&mut self, actions: Receiver<Action>) -> Result<()> {
+        let mut seq = SimSeq::from_actions(self.ss.len(), Vec::new(), Expect::default())?;
+
+        loop {
+            // Keep an eye on the coordinator's heartbeat in between.
+            let action = match actions.try_recv() {
+                Ok(action) => action,
+                Err(TryRecvError::Empty) => {
+                    self.wait(ACTION_POLL)?;
+                    continue;
+                }
+                Err(TryRecvError::Disconnected) => return Ok(()),
+            };
+
+            if let Some(id) = action.ids().into_iter().find(|id| *id >= self.ss.len()) {
+                bail!("{} names member {}, which isn't in the ring", action, id);
+            }
+
+            let ends = action == Action::End;
+            seq.actions.push(action);
+
+            match self.follow(&seq, seq.actions.len() - 1) {
+                Err(e) if e.is::<AssertionFailed>() => {
+                    self.emit(Event::AssertionFailed(e.to_string().trim_end().to_string()));
+                }
+                res => res?,
+            }
+
+            if ends {
+                return Ok(());
+            }
+        }
+    }
+
+    /// Finish an action that changed the ring, checking its invariants if
+    /// asked to and saving how far the sequence got.
+    fn settled(&mut self, seq: &SimSeq, step: usize) -> Result<()> {
+        // Members respawned meanwhile know no more of the coordinator than
+        // restarted ones do, so find it the same way.
+        for id in std::mem::take(&mut self.respawned) {
+            if self.active[id] && !self.left[id] {
+                self.elect(id)?;
+            }
+        }
+
+        if self.config.verify {
+            self.check()?;
+        }
+
+        self.publish();
+        self.checkpoint(seq, step + 1, None)
+    }
+
+    /// Check the assertion action `step` of the sequence makes, failing
+    /// with [`AssertionFailed`] if it doesn't hold.
+    fn assert(&mut self, seq: &SimSeq, step: usize) -> Result<()> {
+        let snapshot = self.snapshot()?;
+        let elections = self.initiated.iter().sum();
+
+        let failed = match seq.actions[step] {
+            Action::AssertCoordinator(expected) => {
+                let mut followed = snapshot.members.iter()
+                    .filter(|m| m.active)
+                    .map(|m| m.coord_id)
+                    .collect::<Vec<_>>();
+
+                followed.sort();
+                followed.dedup();
+
+                (followed != [expected]).then(|| format!(
+                    "expected coordinator {}, but the ring follows {:?}", expected, followed
+                ))
+            }
+            Action::AssertActive(id) => {
+                (!snapshot.members[id].active).then(|| format!("expected {} to be active", id))
+            }
+            Action::AssertElections(cmp, count) => (!cmp.holds(elections, count)).then(|| {
+                format!("expected {} {} elections, but the ring held {}", cmp, count, elections)
+            }),
+            _ => None,
+        };
+
+        if let Some(failed) = failed {
+            let report = format!("Assertion failed at {}: {}", seq.at(step), failed);
+            return Err(AssertionFailed(format!("{}\n{}", report, snapshot)).into());
+        }
+
+        match &seq.actions[step] {
+            Action::AssertCoordinator(expected) => self.emit(Event::Asserted(*expected)),
+            action => self.emit(Event::AssertionHeld(action.to_string())),
+        }
+
+        Ok(())
+    }
+
+    /// Pick a checkpointed simulation back up, holding the election it was
+    /// interrupted in again from the start.
+    fn resume(&mut self, checkpoint: Checkpoint) -> Result<()> {
+        self.coord_id = checkpoint.coord_id;
+        self.initiated = checkpoint.initiated;
+        self.active = checkpoint.members.iter().map(|m| m.active).collect();
+        self.export();
+        let mut start = checkpoint.step;
+
+        if let Some(origin) = checkpoint.electing {
+            self.emit(Event::Restarting);
+            self.elect(origin)?;
+
+            if self.config.verify {
+                self.check()?;
+            }
+
+            start += 1;
+            self.checkpoint(&checkpoint.seq, start, None)?;
+        }
+
+        self.follow(&checkpoint.seq, start)
+    }
+
+    /// Let the metrics exporter know the coordinator and how many ring
+    /// members are active, if it's up.
+    fn export(&self) {
+        #[cfg(feature = "metrics-server")]
+        if let Some(metrics) = &self.config.metrics {
+            metrics.ring(self.coord_id, self.active.iter().filter(|a| **a).count());
+        }
+    }
+
+    /// Publish the state of the ring to the status server and the
+    /// dashboard, whichever are up. A ring too unsettled to be snapshot
+    /// keeps its last published state.
+    fn publish(&mut self) {
+        #[cfg(feature = "status-server")]
+        if let Some(board) = self.config.status.clone() {
+            match self.snapshot() {
+                Ok(snapshot) => board.publish(snapshot, self.initiated.clone()),
+                Err(e) => self.emit(Event::PublishFailed(e.to_string())),
+            }
+        }
+
+        #[cfg(feature = "tui")]
+        if let Some(dashboard) = self.config.dashboard.clone() {
+            match self.snapshot() {
+                Ok(snapshot) => dashboard.publish(snapshot),
+                Err(e) => self.emit(Event::DashboardFailed(e.to_string())),
+            }
+        }
+    }
+
+    /// Save how far the simulation got through `seq`, along with the state
+    /// of the ring, if asked to.
+    fn checkpoint(
+        &mut self, seq: &SimSeq, step: usize, electing: Option<usize>
+    ) -> Result<()> {
+        let path = match &self.config.checkpoint {
+            Some(path) => path.clone(),
+            None => return Ok(()),
+        };
+
+        let checkpoint = Checkpoint {
+            seq: seq.clone(),
+            step,
+            electing,
+            coord_id: self.coord_id,
+            initiated: self.initiated.clone(),
+            members: self.snapshot()?.members,
+        };
+
+        checkpoint.save(&path)?;
+        self.emit(Event::Checkpointed(step));
+        Ok(())
+    }
+
+    /// Toggle random ring members until `duration` has passed, with the
+    /// invariants checked after every step, and report whether the ring
+    /// converged to a single valid coordinator.
+    fn chaos(&mut self, duration: Duration) -> Result<()> {
+        let mut rng = Rng::new(self.config.seed);
+        let mut failures = 0;
+        let start = self.config.clock.now();
+
+        while self.config.clock.elapsed(start) < duration {
+            let wait = Duration::from_millis(rng.below(100));
+            self.emit(Event::Waiting(wait));
+            self.wait(wait)?;
+
+            let id = rng.below(self.ss.len() as u64) as usize;
+
+            // Elections need at least one active member to vote.
+            if self.active[id] && self.active.iter().filter(|a| **a).count() == 1 {
+                continue;
+            }
+
+            let active = self.toggle(id)?;
+            let lost = id == self.coord_id && !active;
+
+            // Members watching the coordinator find it down themselves.
+            let res = match (self.config.watch_window(), lost) {
+                (Some(window), true) => self.detected(window),
+                _ => Ok(()),
+            };
+
+            if let Err(e) = res.and_then(|_| self.settle(&mut rng)) {
+                self.emit(Event::NotSettled(e.to_string()));
+                failures += 1;
+            }
+
+            self.publish();
+        }
+
+        self.emit(Event::FailedSteps(failures));
+
+        match self.settle(&mut rng) {
+            Ok(()) => {
+                self.config.events.print(format_args!(
+                    "chaos: PASS, converged to coordinator {} (seed {})\n",
+                    self.coord_id, self.config.seed
+                ));
+
+                Ok(())
+            }
+            Err(e) => {
+                if let Ok(snapshot) = self.snapshot() {
+                    self.config.events.print(snapshot);
+                }
+
+                bail!(
+                    "chaos: FAIL, did not converge: {} \
+                    (reproduce with --seed {})", e, self.config.seed
+                )
+            }
+        }
+    }
+
+    /// Hold elections from random members until the ring's invariants hold,
+    /// giving up after a few attempts.
+    fn settle(&mut self, rng: &mut Rng) -> Result<()> {
+        let mut res = self.check();
+
+        for _ in 0..SETTLE_ATTEMPTS {
+            let e = match &res {
+                Ok(()) => return Ok(()),
+                Err(e) => e,
+            };
+
+            self.emit(Event::Unsettled(e.to_string()));
+            let origin = rng.below(self.ss.len() as u64) as usize;
+            res = self.elect(origin).and_then(|_| self.check());
+        }
+
+        res
+    }
+
+    /// Toggle a ring member, returning whether it is now active.
+    ///
+    /// The toggle is sent again if its target doesn't acknowledge it in
+    /// time, since it may have been lost on the way around the ring.
+    fn toggle(&mut self, id: usize) -> Result<bool> {
+        self.toggles += 1;
+        let seq = self.toggles;
+
+        for attempt in 1..=TOGGLE_ATTEMPTS {
+            self.send(self.present_from(0), Msg::SimToggle { id, hops: 0, seq })?;
+            self.emit(Event::SimToggled { id, attempt });
+
+            // Wait for toggle confirmation.
+            while let Some(msg) = self.recv_for_timeout()? {
+                match msg {
+                    SimMsg::ConfirmToggle { id: t_id, active, seq: t_seq }
+                        if t_id == id && t_seq == seq =>
+                    {
+                        self.active[id] = active;
+                        self.export();
+                        return Ok(active);
+                    }
+                    SimMsg::Unreachable { id: t_id } if t_id == id => {
+                        bail!("Toggle target {} is not in the ring", id);
+                    }
+                    msg => self.emit(Event::Ignored(msg)),
+                }
+            }
+
+            self.emit(Event::ToggleUnacknowledged(id));
+        }
+
+        bail!(
+            "Toggle of {} was not acknowledged after {} attempts",
+            id, TOGGLE_ATTEMPTS
+        );
+    }
+
+    /// Let `duration` pass, keeping an eye on the coordinator's heartbeat if
+    /// it sends one, and electing a new one should it go silent.
+    fn wait(&mut self, duration: Duration) -> Result<()> {
+        let mut paused = self.config.pause.as_ref().map_or(Duration::ZERO, Pause::paused_for);
+
+        let interval = match self.config.heartbeat {
+            Some(interval) => interval,
+            None => {
+                let mut left = duration;
+
+                // Whatever of the wait passed paused is waited again.
+                loop {
+                    self.sleep(left)?;
+                    left = self.paused_since(&mut paused);
+
+                    if left.is_zero() {
+                        return Ok(());
+                    }
+                }
+            }
+        };
+
+        let mut deadline = self.config.clock.now() + duration;
+
+        loop {
+            // Time spent paused counts neither towards the wait nor against
+            // the coordinator, whose heartbeats were held meanwhile.
+            let more = self.paused_since(&mut paused);
+            deadline += more;
+            self.last_beat += more;
+            self.interrupted()?;
+
+            let now = self.config.clock.now();
+
+            if self.config.clock.elapsed(self.last_beat) > interval * HEARTBEAT_MISSES {
+                self.emit(Event::PresumedDead(self.coord_id));
+                self.elect((self.coord_id + 1) % self.ss.len())?;
+            }
+
+            if now >= deadline {
+                return Ok(());
+            }
+
+            let timeout = (deadline - now).min(interval);
+
+            if let Some(msg) = self.recv_within(Some(timeout))? {
+                self.emit(Event::Ignored(msg));
+            }
+        }
+    }
+
+    /// Start an election from ring member `origin`, or the next one should
+    /// it have left the ring, and wait for its result.
+    fn elect(&mut self, origin: usize) -> Result<()> {
+        self.elect_from(&[origin])
+    }
+
+    /// Start an election from each of ring members `origins` at once, or
+    /// the next one should it have left the ring, all numbered alike, and
+    /// wait for their result.
+    ///
+    /// Ring and Hirschberg–Sinclair elections absorb any started alongside
+    /// them, so end in a single result, while every Chang–Roberts and bully
+    /// election started ends in its own.
+    fn elect_from(&mut self, origins: &[usize]) -> Result<()> {
+        let (algorithm, ring_size) = (self.config.algorithm, self.ss.len());
+
+        self.hold(origins, |origin, election| {
+            Msg::opening(algorithm, origin, ring_size, election)
+        })
+    }
+
+    /// Have the coordinator step down, starting an election it stands in
+    /// no part of, and wait for its result.
+    fn step_down(&mut self) -> Result<()> {
+        let coord_id = self.coord_id;
+        self.emit(Event::SteppingDown(coord_id));
+        self.hold(&[coord_id], |_, epoch| Msg::SimStepDown { epoch })
+    }
+
+    /// Start an election from each of ring members `origins`, or the next
+    /// should it have left the ring, by sending it the message `opening`
+    /// makes of it and the election's number, and wait for their result.
+    fn hold(
+        &mut self, origins: &[usize], opening: impl Fn(usize, usize) -> Msg,
+    ) -> Result<()> {
+        let mut started = Vec::new();
+
+        for origin in origins.iter().map(|origin| self.present_from(*origin)) {
+            if !started.contains(&origin) {
+                started.push(origin);
+            }
+        }
+
+        let first = started[0];
+
+        if let Some(tracer) = &self.config.tracer {
+            tracer.start_election(first)?;
+        }
+
+        let election = self.initiated.iter().sum::<usize>() + 1;
+        let opened = self.open();
+        let timeout = self.config.election_timeout.or(self.config.sim_timeout);
+
+        for &origin in &started {
+            self.send(origin, opening(origin, election))?;
+            self.initiated[origin] += 1;
+
+            #[cfg(feature = "metrics-server")]
+            if let Some(metrics) = &self.config.metrics {
+                metrics.election();
+            }
+            self.emit(Event::ElectionStarted { origin });
+        }
+
+        let results = match self.config.algorithm {
+            Algorithm::ChangRoberts | Algorithm::Bully => started.len(),
+            _ => 1,
+        };
+
+        let mut res = Ok(self.coord_id);
+
+        for _ in 0..results {
+            res = match self.result_within(timeout) {
+                Ok(Some((id, _))) => Ok(id),
+                Ok(None) => Err(self.stuck(first, timeout.unwrap_or_default())),
+                Err(e) => Err(e),
+            };
+
+            if res.is_err() {
+                break;
+            }
+        }
+
+        self.close(first, opened, res)
+    }
+
+    /// Wait for the members watching the coordinator, which just went down,
+    /// to find it so and elect another of their own accord.
+    ///
+    /// Unless the ring has an election timeout of its own, the election is
+    /// given as long as finding the coordinator down may take, `window`.
+    fn detected(&mut self, window: Duration) -> Result<()> {
+        let down = self.coord_id;
+        let opened = self.open();
+
+        let timeout = window + self.config.election_timeout.or(self.config.sim_timeout)
+            .unwrap_or(window);
+
+        self.emit(Event::AwaitingDetection(down));
+
+        // An election nobody started is filed under the coordinator that
+        // went down.
+        let (initiator, res) = match self.result_within(Some(timeout)) {
+            Ok(Some((id, initiator))) => {
+                self.initiated[initiator] += 1;
+                (initiator, Ok(id))
+            }
+            Ok(None) => {
+                let e = Error::msg(format!(
+                    "No member found coordinator {} down and elected another within {:?}",
+                    down, timeout
+                ));
+
+                (down, Err(e))
+            }
+            Err(e) => (down, Err(e)),
+        };
+
+        self.close(initiator, opened, res)
+    }
+
+    /// Wait for the coordinator, gone down, to miss enough heartbeats sent
+    /// every `interval` to be presumed dead and replaced.
+    fn silenced(&mut self, interval: Duration) -> Result<()> {
+        let down = self.coord_id;
+        self.wait(interval * (HEARTBEAT_MISSES + 1))?;
+
+        if self.coord_id == down {
+            bail!("Coordinator {} went down but was never presumed dead", down);
+        }
+
+        Ok(())
+    }
+
+    /// Note where the ring stands as an election opens.
+    fn open(&self) -> Opened {
+        if let Some(tally) = &self.config.tally {
+            tally.take_forwarded();
+        }
+
+        Opened {
+            start: self.config.clock.now(),
+            sent: self.config.tally.as_ref().map(|tally| tally.total()),
+            active: self.active.iter().filter(|a| **a).count(),
+        }
+    }
+
+    /// Wait up to `timeout` for an election's result, taking the winner
+    /// on as the coordinator. Returns the winner and who started the
+    /// election, or nothing should no result come in time.
+    fn result_within(&mut self, timeout: Option<Duration>) -> Result<Option<(usize, usize)>> {
+        loop {
+            match self.recv_within(timeout)? {
+                Some(msg @ SimMsg::ElectionResult { epoch, .. }) if epoch < self.epoch => {
+                    self.emit(Event::Ignored(msg));
+                }
+                Some(SimMsg::ElectionResult { id, initiator, votes, epoch }) => {
+                    self.emit(Event::ElectionWon { id, initiator, epoch });
+                    self.epoch = epoch;
+
+                    if let Some(votes) = votes {
+                        self.audit(id, &votes);
+                    }
+
+                    self.coord_id = id;
+                    self.last_beat = self.config.clock.now();
+                    self.export();
+                    return Ok(Some((id, initiator)));
+                }
+                Some(msg) => self.emit(Event::Ignored(msg)),
+                None => return Ok(None),
+            }
+        }
+    }
+
+    /// Record how the election `opened` and started by `initiator` went,
+    /// failing as it did, if it did.
+    fn close(&mut self, initiator: usize, opened: Opened, res: Result<usize>) -> Result<()> {
+        let Opened { start, sent, active } = opened;
+        let since = |t: Instant| t.saturating_duration_since(self.started.1);
+
+        self.results.push(ElectionStats {
+            initiator,
+            winner: res.as_ref().ok().copied(),
+            start: since(start),
+            end: since(self.config.clock.now()),
+            messages: self.config.tally.as_ref()
+                .zip(sent)
+                .map(|(tally, sent)| tally.total() - sent),
+            active,
+        });
+
+        if let Some(tracer) = &self.config.tracer {
+            tracer.end_election(res.as_ref().ok().copied())?;
+        }
+
+        if let (Some(dot), true) = (self.config.dot.clone(), self.config.dot_elections) {
+            let nodes = self.nodes(res.is_ok());
+            dot.election(self.results.len(), res.as_ref().ok().copied(), &nodes);
+        }
+
+        res?;
+
+        // Every member answers the snapshot only once it's done with what
+        // came before, so the oracle has seen all they made of the result.
+        if let Some(oracle) = self.config.oracle.clone() {
+            self.snapshot()?;
+            oracle.check()?;
+        }
+
+        Ok(())
+    }
+
+    /// Report the election started from `origin` as stuck, with no result
+    /// within `timeout`, naming who last sent one of its messages on.
+    fn stuck(&self, origin: usize, timeout: Duration) -> Error {
+        let forwarded = self.config.tally.as_ref().and_then(|tally| tally.take_forwarded());
+
+        let last = match forwarded {
+            Some((from, to, kind)) => format!(", last sent on by {} ({} to {})", from, kind, to),
+            None => String::new(),
+        };
+
+        self.emit(Event::ElectionStuck { origin, timeout });
+        Error::msg(format!(
+            "election started from {} is stuck, with no result within {:?}{}",
+            origin, timeout, last
+        ))
+    }
+
+    /// Log who voted in an election, and under `--verify` set aside a
+    /// violation if its winner isn't the one ranked first among them. Only
+    /// the members know when they were last alive, so the winner of an
+    /// election by recency is only checked to have voted.
+    fn audit(&mut self, winner: usize, votes: &[bool]) {
+        let voters = votes.iter()
+            .enumerate()
+            .filter(|(_, v)| **v)
+            .map(|(i, _)| i)
+            .collect::<Vec<_>>();
+
+        self.emit(Event::VotesCast(voters.clone()));
+
+        let keys = (0..votes.len())
+            .map(|id| self.config.ranking_key(id))
+            .collect::<Option<Vec<_>>>();
+
+        let reason = if !votes.get(winner).copied().unwrap_or(false) {
+            format!("winner {} did not vote in its election {:?}", winner, voters)
+        } else if keys.is_some_and(|keys| rank(votes, &keys) != Some(winner)) {
+            format!("winner {} is not ranked first of the voters {:?}", winner, voters)
+        } else {
+            return;
+        };
+
+        self.emit(Event::AuditFailed(reason.clone()));
+
+        if self.config.verify {
+            self.violations.push(reason);
+        }
+    }
+
+    /// Sleep for `duration`, waking up now and then in real time to fail
+    /// should the run be interrupted meanwhile.
+    fn sleep(&self, duration: Duration) -> Result<()> {
+        // Virtual time goes by too fast to need to.
+        if self.config.sched.is_some() {
+            self.config.clock.sleep(duration);
+            return Ok(());
+        }
+
+        let mut left = duration;
+
+        loop {
+            self.config.clock.sleep(left.min(INTERRUPT_POLL));
+            left = left.saturating_sub(INTERRUPT_POLL);
+            self.interrupted()?;
+
+            if left.is_zero() {
+                return Ok(());
+            }
+        }
+    }
+
+    /// Fail should the run have been interrupted, saying so.
+    fn interrupted(&self) -> Result<()> {
+        if interrupt::interrupted() {
+            self.emit(Event::Interrupted);
+            return Err(Interrupted.into());
+        }
+
+        Ok(())
+    }
+
+    /// How much longer the ring has been paused for than `paused`, once
+    /// it's resumed, moving `paused` on to that.
+    fn paused_since(&self, paused: &mut Duration) -> Duration {
+        match &self.config.pause {
+            Some(pause) => {
+                pause.hold();
+                let before = mem::replace(paused, pause.paused_for());
+                *paused - before
+            }
+            None => Duration::ZERO,
+        }
+    }
+
+    /// Show the state the ring is in, then hold it still for `duration` of
+    /// real time, for it to be looked at meanwhile.
+    fn pause(&mut self, duration: Duration) -> Result<()> {
+        // Members answer nothing while paused.
+        let snapshot = self.snapshot()?;
+        self.config.events.print(snapshot);
+
+        let pause = self.config.pause.clone().unwrap_or_default();
+        pause.pause();
+        self.emit(Event::Paused);
+
+        sync::thread::sleep(duration);
+
+        pause.resume();
+        self.emit(Event::Resumed);
+        Ok(())
+    }
+
+    /// What the graphs show of every ring member: what it says of itself
+    /// if it's to be asked, and what the simulator knows of it otherwise,
+    /// or should the ring not answer.
+    fn nodes(&mut self, ask: bool) -> Vec<Node> {
+        // Members that crashed answer nothing.
+        let members = match ask && self.crashed.is_empty() {
+            true => self.snapshot().ok().map(|snapshot| snapshot.members),
+            false => None,
+        };
+
+        (0..self.ss.len())
+            .map(|id| {
+                let status = members.as_ref().map(|members| &members[id]);
+
+                Node {
+                    id,
+                    label: match status {
+                        Some(s) => s.label.clone(),
+                        None => self.config.labels.get(id).cloned(),
+                    },
+                    active: status.map_or(self.active[id], |s| s.active),
+                    crashed: self.crashed.iter().any(|(c, _)| *c == id),
+                    state: status.map(|s| s.state),
+                }
+            })
+            .collect()
+    }
+
+    /// Take a consistent snapshot of the ring.
+    ///
+    /// Members are asked for their status one after another, so an election
+    /// in flight could change the ring between answers. The ring is queried
+    /// until two rounds of answers agree, which makes the snapshot a view
+    /// the ring was actually in.
+    fn snapshot(&mut self) -> Result<RingSnapshot> {
+        let mut members = self.status()?;
+
+        for _ in 0..SNAPSHOT_ATTEMPTS {
+            let again = self.status()?;
+
+            if again == members {
+                return Ok(RingSnapshot { coord_id: self.coord_id, members });
+            }
+
+            members = again;
+        }
+
+        bail!("the ring kept changing while taking a snapshot")
+    }
+
+    /// Ask every ring member for its status.
+    fn status(&mut self) -> Result<Vec<MemberStatus>> {
+        let mut statuses = Vec::with_capacity(self.ss.len());
+
+        for i in 0..self.ss.len() {
+            if self.left[i] {
+                statuses.push(self.out_of_ring(i));
+                continue;
+            }
+
+            self.send(i, Msg::SimStatus)?;
+
+            loop {
+                match self.recv()? {
+                    SimMsg::Status(status) if status.id == i => {
+                        statuses.push(status);
+                        break;
+                    }
+                    msg => self.emit(Event::Ignored(msg)),
+                }
+            }
+        }
+
+        Ok(statuses)
+    }
+
+    /// Fail with the violated invariant if the ring is not in a valid state.
+    fn check(&mut self) -> Result<()> {
+        if !self.violations.is_empty() {
+            let violations = std::mem::take(&mut self.violations);
+            bail!("invariant violated: {}", violations.join("; "));
+        }
+
+        let snapshot = self.snapshot()?;
+        check_invariants(&snapshot)?;
+        self.emit(Event::InvariantsHold);
+        Ok(())
+    }
+
+    /// Receive the next message from the ring, recording it if asked to
+    /// and setting aside any invariant violations reported along the way.
+    ///
+    /// A crashed member ends the simulation, since the ring can't route
+    /// around it, unless it was respawned.
+    fn recv(&mut self) -> Result<SimMsg> {
+        match self.recv_for_timeout()? {
+            Some(msg) => Ok(msg),
+            None => bail!("timed out waiting for the ring"),
+        }
+    }
+
+    /// Like `recv`, but give up once the simulator's timeout has passed.
+    fn recv_for_timeout(&mut self) -> Result<Option<SimMsg>> {
+        self.recv_within(self.config.sim_timeout)
+    }
+
+    /// Like `recv`, but give up after `timeout`, if given, not counting
+    /// whatever of it passes with the ring paused. Coordinator heartbeats
+    /// are taken note of rather than returned.
+    fn recv_within(&mut self, timeout: Option<Duration>) -> Result<Option<SimMsg>> {
+        // The ring answers in real time, unless every thread keeps virtual
+        // time.
+        let mut deadline = timeout.map(|timeout| self.config.clock.ring_now() + timeout);
+        let mut paused = self.config.pause.as_ref().map(Pause::paused_for);
+
+        loop {
+            let msg = match (&self.config.sched, deadline) {
+                (Some(sched), deadline) => sched::recv_until(sched, &self.sim_r, deadline)?,
+                (None, Some(deadline)) => match self.sim_r.recv_deadline(deadline) {
+                    Ok(msg) => Some(msg),
+                    Err(RecvTimeoutError::Timeout) => None,
+                    Err(e) => return Err(e.into()),
+                },
+                (None, None) => Some(self.sim_r.recv()?),
+            };
+
+            let msg = match (msg, &self.config.pause, paused.zip(deadline)) {
+                (Some(msg), _, _) => msg,
+                (None, Some(pause), Some((before, until))) => {
+                    pause.hold();
+                    let after = pause.paused_for();
+
+                    if after == before {
+                        return Ok(None);
+                    }
+
+                    (deadline, paused) = (Some(until + (after - before)), Some(after));
+                    continue;
+                }
+                (None, _, _) => return Ok(None),
+            };
+
+            if let Some(recorder) = &self.config.recorder {
+                recorder.sim(&msg)?;
+            }
+
+            match msg {
+                SimMsg::Violation { reason } => self.violations.push(reason),
+                SimMsg::Heartbeat { id } => {
+                    if id == self.coord_id {
+                        self.last_beat = self.config.clock.now();
+                    }
+                }
+                SimMsg::Crashed { id, reason } => {
+                    self.crashed.push((id, reason.clone()));
+                    bail!("Member {} crashed: {}", id, reason);
+                }
+                SimMsg::Respawned { id, reason } => {
+                    self.emit(Event::MemberCrashed { id, reason });
+                    self.respawned.push(id);
+                }
+                msg => return Ok(Some(msg)),
+            }
+        }
+    }
+}
+
+/// Check the invariants that must hold whenever the ring is settled,
+/// naming the offending members if any is violated.
+fn check_invariants(snapshot: &RingSnapshot) -> Result<()> {
+    let RingSnapshot { coord_id, members: statuses } = snapshot;
+    let coord_id = *coord_id;
+
+    let coords = statuses.iter()
+        .filter(|s| s.state == State::Coordinator)
+        .map(|s| s.id)
+        .collect::<Vec<_>>();
+
+    if coords.len() > 1 {
+        bail!(
+            "invariant violated: at most one coordinator, \
+            but members {:?} are in the coordinator state", coords
+        );
+    }
+
+    if !statuses[coord_id].active {
+        bail!(
+            "invariant violated: the coordinator is active, \
+            but coordinator {} is inactive", coord_id
+        );
+    }
+
+    let electing = statuses.iter()
+        .filter(|s| s.state == State::Participant)
+        .map(|s| s.id)
+        .collect::<Vec<_>>();
+
+    if !electing.is_empty() {
+        bail!(
+            "invariant violated: no member is still electing once the ring \
+            settled, but members {:?} are participants", electing
+        );
+    }
+
+    let dissenters = statuses.iter()
+        .filter(|s| s.coord_id != coord_id)
+        .map(|s| s.id)
+        .collect::<Vec<_>>();
+
+    if !dissenters.is_empty() {
+        bail!(
+            "invariant violated: all members agree on the coordinator, \
+            but members {:?} do not follow {}", dissenters, coord_id
+        );
+    }
+
+    Ok(())
+}
+
+/// A ring member run on a thread of its own, stepping its [`Core`] through
+/// every message its transport delivers.
+#[derive(Debug)]
+pub struct RingMember {
+    core: Core,
+    runtime: Runtime,
+}
+
+/// What a ring member's core acts through: its transport, the simulator
+/// and the events sink, subject to the configured faults.
+#[derive(Debug)]
+struct Runtime {
+    id: usize,
+    transport: Box<dyn Transport>,
+    sim_s: Sender<SimMsg>,
+    config: SimConfig,
+    rng: Rng,
+    /// When this member last sent the simulator a heartbeat.
+    last_beat: Instant,
+    /// When this member last pinged the coordinator it watches.
+    last_ping: Instant,
+    /// The coordinator this member last pinged, and how many pings in a
+    /// row it left unanswered.
+    missed: (usize, u32),
+    /// The round trips of the pings answered by each member, by id, if
+    /// ping timeouts adapt to them.
+    round_trips: HashMap<usize, RoundTrips>,
+    /// The active member found last counterclockwise and clockwise, and
+    /// during which of the elections this member took part in, if caching
+    /// them.
+    successors: [Option<(usize, usize)>; 2],
+    /// The message held back to go after the next one sent, and who it's
+    /// for, if reordering messages.
+    held: Option<(usize, Msg)>,
+    /// When this member, as coordinator, last renewed its lease.
+    last_renewal: Instant,
+    /// The coordinator this member follows, how many renewals of its
+    /// lease this member had heard of when it last heard one, and when.
+    lease: (usize, usize, Instant),
+    /// The messages this member sent itself, as the only one left to send
+    /// to, which no transport carries, to be received before any other.
+    own: VecDeque<Msg>,
+}
+
+/// What a ring member waits on besides messages.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Timer {
+    /// Sending the simulator a heartbeat, as coordinator.
+    Beat,
+    /// Renewing its lease, as coordinator.
+    Renewal,
+    /// Pinging the coordinator it watches.
+    Ping(Detection),
+    /// The lease of the coordinator it follows running out.
+    Expiry,
+}
+
+/// How long the pings a member answered took to be, running.
+#[derive(Debug, Clone, Copy, Default)]
+struct RoundTrips {
+    count: u32,
+    /// The mean, in seconds.
+    mean: f64,
+    /// The sum of squared differences from the mean, in seconds squared.
+    m2: f64,
+}
+
+impl RoundTrips {
+    fn add(&mut self, rtt: Duration) {
+        let rtt = rtt.as_secs_f64();
+        self.count += 1;
+        let delta = rtt - self.mean;
+        self.mean += delta / self.count as f64;
+        self.m2 += delta * (rtt - self.mean);
+    }
+
+    /// The mean plus `k` standard deviations.
+    fn timeout(&self, k: f64) -> Duration {
+        let stddev = (self.m2 / self.count as f64).sqrt();
+        Duration::from_secs_f64(self.mean + k * stddev)
+    }
+}
+
+/// Builds a ring member, leaving out whatever isn't needed: by default
+/// the member has no peers, reports to a simulator nobody listens to and
+/// is reached through channels nobody sends on.
+#[derive(Debug, Default)]
+pub struct RingMemberBuilder {
+    id: usize,
+    transport: Option<Box<dyn Transport>>,
+    sim_s: Option<Sender<SimMsg>>,
+    config: SimConfig,
+}
+
+impl RingMemberBuilder {
+    pub fn id(mut self, id: usize) -> Self {
+        self.id = id;
+        self
+    }
+
+    /// How the member reaches every other member and is reached by them.
+    pub fn transport(mut self, transport: impl Transport + 'static) -> Self {
+        self.transport = Some(Box::new(transport));
+        self
+    }
+
+    /// Where to send messages for the simulator.
+    pub fn sim(mut self, sim_s: Sender<SimMsg>) -> Self {
+        self.sim_s = Some(sim_s);
+        self
+    }
+
+    pub fn config(mut self, config: SimConfig) -> Self {
+        self.config = config;
+        self
+    }
+
+    /// Build the member, following member 0 as the coordinator.
+    pub fn build(self) -> RingMember {
+        let Self { id, transport, sim_s, config } = self;
+
+        let transport = transport.unwrap_or_else(|| {
+            let (_, link) = Link::new(config.algorithm, config.channel_capacity);
+            Box::new(Channels::new(HashMap::new(), link))
+        });
+
+        let core = Core::new(id, transport.ring_size(), config.clone());
+        let sim_s = sim_s.unwrap_or_else(|| unbounded().0);
+
+        // Give each member its own stream of random choices.
+        let rng = Rng::new(config.seed.wrapping_add(id as u64 + 1));
+
+        let runtime = Runtime {
+            id, transport, sim_s, last_beat: config.clock.now(), last_ping: config.clock.now(),
+            missed: (0, 0), round_trips: HashMap::new(), successors: [None; 2], held: None,
+            last_renewal: config.clock.now(), lease: (0, 0, config.clock.now()), config, rng,
+            own: VecDeque::new(),
+        };
+
+        RingMember { core, runtime }
+    }
+}
+
+impl RingMember {
+    pub fn builder() -> RingMemberBuilder {
+        RingMemberBuilder::default()
+    }
+
+    /// Receive and handle messages until told to stop, within a span of
+    /// the `tracing` crate for this member.
+    pub fn run(&mut self) -> Result<()> {
+        executor::block_on(self.run_async())
+    }
+
+    /// Like `run`, but as a task, for an event loop to run alongside other
+    /// members'. The span is only entered while the task is polled.
+    async fn run_async(&mut self) -> Result<()> {
+        let span = tracing::info_span!("member", id = self.runtime.id);
+        self.handle_messages().instrument(span).await
+    }
+
+    async fn handle_messages(&mut self) -> Result<()> {
+        let Self { core, runtime } = self;
+
+        loop {
+            // Only an active coordinator has heartbeats to send and a lease
+            // to renew, and would otherwise spin waiting for none.
+            let beating = core.state == State::Coordinator && core.sim_active;
+            runtime.waiting(core);
+            runtime.release()?;
+
+            if beating {
+                if let Some(interval) = runtime.config.heartbeat {
+                    runtime.beat(core, interval);
+                }
+
+                if let Some(lease) = runtime.config.lease {
+                    runtime.renew(core, lease).await?;
+                }
+            }
+
+            let msg = match runtime.next_timer(core, beating) {
+                Some((due, timer)) => match runtime.recv_timeout(due).await? {
+                    Some(msg) => msg,
+                    None => {
+                        // Heartbeats and renewals go out as the loop starts
+                        // over.
+                        match timer {
+                            Timer::Ping(detection) => runtime.watch(core, detection).await?,
+                            Timer::Expiry => runtime.expire(core).await?,
+                            Timer::Beat | Timer::Renewal => {}
+                        }
+
+                        continue;
+                    }
+                },
+                None => runtime.recv().await?,
+            };
+
+            runtime.record(&msg)?;
+            runtime.emit(Event::Received(msg.clone()));
+
+            // A slow member takes its time over every message from the ring.
+            if !msg.is_sim() && core.processing != ProcessingDelay::default() {
+                let delay = core.processing.pick(&mut runtime.rng);
+                runtime.sleep(delay).await;
+            }
+
+            let res = core.step(runtime, msg).await?;
+
+            // The simulator may have told this member to stop while it was
+            // waiting on another member, too.
+            if !res || core.ended {
+                break;
+            }
+        }
+
+        runtime.emit(Event::Done);
+        Ok(())
+    }
+
+    /// Take on the state saved in a checkpoint. An election in progress is
+    /// held again from the start, so the member hasn't voted in it yet.
+    pub fn restore(&mut self, status: &MemberStatus) {
+        self.core.restore(status);
+    }
+
+    /// Start over after failing, as a restarted member would, but up if it
+    /// was.
+    fn respawn(&mut self) {
+        self.core.restart();
+        self.runtime.emit(Event::Respawned);
+    }
+}
+
+impl Effects for Runtime {
+    /// Send a message to ring member `id`, subject to the configured faults.
+    ///
+    /// This covers toggles forwarded on the simulator's behalf, which the
+    /// simulator resends if they're lost.
+    fn send_to(&mut self, id: usize, msg: Msg) -> Waiting<'_, ()> {
+        Box::pin(async move {
+            let faults = &self.config.faults;
+
+            if !faults.max_delay.is_zero() {
+                let max = faults.max_delay.as_micros() as u64;
+                let delay = Duration::from_micros(self.rng.below(max + 1));
+                self.sleep(delay).await;
+            }
+
+            // The message is held up as long as it would take on its way, so
+            // arrives no sooner than it would over a network.
+            if let Some(latency) = faults.latency(self.id, id) {
+                let delay = latency.pick(&mut self.rng);
+                self.sleep(delay).await;
+            }
+
+            // A message lost on the way still leaves a stuck election waiting
+            // on whoever sent it.
+            if let Some(tally) = &self.config.tally {
+                tally.forwarded(self.id, id, &msg);
+            }
+
+            // Only the simulator's own messages cross a partition.
+            let cut = !msg.is_sim() && self.config.faults.cut.separates(self.id, id);
+            let dropped = self.rng.chance(self.config.faults.drop_rate(self.id, id));
+
+            if let Some(diagram) = &self.config.diagram {
+                diagram.sent(self.id, id, &msg, cut || dropped);
+            }
+
+            if cut {
+                self.emit(Event::Blocked { msg, to: id });
+                return Ok(());
+            }
+
+            if dropped {
+                self.emit(Event::Dropped { msg, to: id });
+                return Ok(());
+            }
+
+            if let Some(tally) = &self.config.tally {
+                tally.sent(self.id, &msg);
+            }
+
+            if let Some(dot) = &self.config.dot {
+                dot.sent(self.id, id);
+            }
+
+            #[cfg(feature = "metrics-server")]
+            if let Some(metrics) = &self.config.metrics {
+                metrics.sent(self.id, &msg);
+            }
+
+            let (duplicate_rate, reorder_rate) =
+                (self.config.faults.duplicate_rate, self.config.faults.reorder_rate);
+
+            // Only one message is held back at a time, so none is held forever.
+            if reorder_rate > 0.0 && self.held.is_none() && self.rng.chance(reorder_rate) {
+                self.held = Some((id, msg));
+                return Ok(());
+            }
+
+            if duplicate_rate > 0.0 && self.rng.chance(duplicate_rate) {
+                self.emit(Event::Duplicated { msg: msg.clone(), to: id });
+                self.deliver(id, msg.clone())?;
+            }
+
+            self.deliver(id, msg)?;
+
+            if let Some((to, msg)) = self.held.take() {
+                self.emit(Event::Reordered { msg: msg.clone(), to });
+                self.deliver(to, msg)?;
+            }
+
+            Ok(())
+        })
+    }
+
+    fn send_sim(&mut self, msg: SimMsg) -> Result<()> {
+        if let Some(diagram) = &self.config.diagram {
+            diagram.sent_sim(self.id, &msg);
+        }
+
+        self.sim_s.send(msg)?;
+        Ok(())
+    }
+
+    fn emit(&mut self, event: Event) {
+        self.config.events.emit(Source::Member(self.id), &event);
+    }
+
+    /// Find the first active member by pinging each in turn, scanning the
+    /// ring again up to `send_retries` times should no member answer.
+    ///
+    /// Should successors be cached, the member found last in the election
+    /// this member takes part in is pinged first, and scanning only starts
+    /// again should it not answer. Members are only toggled between
+    /// elections, so none can have come back up before it meanwhile.
+    ///
+    /// Should the ring be reconfigured, members this one already heard are
+    /// down aren't pinged at all, and those found down on the last scan are
+    /// declared so.
+    fn active_toward<'a>(&'a mut self, core: &'a mut Core, clockwise: bool) -> Waiting<'a, usize> {
+        Box::pin(async move {
+            let slot = clockwise as usize;
+            let electing = core.state == State::Participant;
+
+            if let Some((joined, id)) = self.successors[slot].take() {
+                let timeout = self.ping_timeout(id);
+
+                if electing && joined == core.joined && self.ping(core, id, timeout).await? {
+                    self.successors[slot] = Some((joined, id));
+                    self.emit(Event::Active(id));
+                    return Ok(id);
+                }
+            }
+
+            for scan in 0..=self.config.send_retries {
+                if scan > 0 {
+                    self.emit(Event::Rescanning);
+                    self.sleep(RESCAN_DELAY).await;
+                }
+
+                let mut range = core.ring().collect::<Vec<_>>();
+                let mut down = Vec::new();
+
+                if !clockwise {
+                    range.reverse();
+                }
+
+                for i in range {
+                    let mut timeout = self.ping_timeout(i);
+
+                    for attempt in 1..=self.config.backoff.attempts {
+                        if self.ping(core, i, timeout).await? {
+                            if attempt > 1 {
+                                self.emit(Event::Slow { id: i, attempt });
+                            }
+
+                            if self.config.cache_successor {
+                                self.successors[slot] = Some((core.joined, i));
+                            }
+
+                            self.emit(Event::Active(i));
+                            self.declare(core, &down, Some(i)).await?;
+                            return Ok(i);
+                        }
+
+                        if attempt < self.config.backoff.attempts {
+                            self.emit(Event::PingTimeout { id: i, timeout });
+                            timeout = timeout.saturating_mul(self.config.backoff.multiplier);
+                        }
+                    }
+
+                    self.emit(Event::Inactive(i));
+                    down.push(i);
+                }
+
+                if scan == self.config.send_retries {
+                    self.declare(core, &down, None).await?;
+                }
+            }
+
+            bail!("No response")
+        })
+    }
+
+    fn await_answers<'a>(&'a mut self, core: &'a mut Core, count: usize) -> Waiting<'a, usize> {
+        Box::pin(async move {
+            let mut answers = 0;
+            let mut timeout = self.config.backoff.base;
+
+            for _ in 0..self.config.backoff.attempts {
+                // The ring answers in real time, unless every thread keeps
+                // virtual time.
+                let deadline = self.config.clock.ring_now() + timeout;
+
+                while answers < count && core.state == State::Participant {
+                    let left = deadline.saturating_duration_since(self.config.clock.ring_now());
+                    self.waiting(core);
+                    self.release()?;
+
+                    let msg = match self.recv_timeout(left).await? {
+                        Some(msg) => msg,
+                        None => break,
+                    };
+
+                    self.record(&msg)?;
+
+                    if let Msg::Answer = msg {
+                        answers += 1;
+                    } else {
+                        core.step(self, msg).await?;
+                    }
+                }
+
+                if answers == count || core.state != State::Participant {
+                    break;
+                }
+
+                timeout = timeout.saturating_mul(self.config.backoff.multiplier);
+            }
+
+            Ok(answers)
+        })
+    }
+
+    fn reaches<'a>(&'a mut self, core: &'a mut Core, id: usize) -> Waiting<'a, bool> {
+        Box::pin(async move {
+            self.ping(core, id, self.ping_timeout(id)).await
+        })
+    }
+
+    fn alive_micros(&self) -> u64 {
+        alive_micros(&self.config)
+    }
+}
+
+impl Runtime {
+    /// Let the simulator know that this member, as coordinator, is still up,
+    /// if `interval` has passed since it last did.
+    fn beat(&mut self, core: &mut Core, interval: Duration) {
+        if core.state != State::Coordinator || !core.sim_active
+            || self.config.clock.elapsed(self.last_beat) < interval
+        {
+            return;
+        }
+
+        // The simulator is never kept waiting on a heartbeat, so one it's
+        // too busy to receive is skipped.
+        let _ = self.sim_s.try_send(SimMsg::Heartbeat { id: self.id });
+        self.last_beat = self.config.clock.now();
+        core.last_alive = alive_micros(&self.config);
+    }
+
+    /// Renew this member's lease as coordinator around the ring, if long
+    /// enough has passed since it last did to renew it a few times within
+    /// `lease`.
+    async fn renew(&mut self, core: &mut Core, lease: Duration) -> Result<()> {
+        if self.config.clock.elapsed(self.last_renewal) < lease / RENEWALS_PER_LEASE {
+            return Ok(());
+        }
+
+        self.last_renewal = self.config.clock.now();
+        core.renew_lease(self).await
+    }
+
+    /// The first of what `core` waits on besides messages to come due, and
+    /// how long until it does, if it waits on anything. An active
+    /// coordinator is `beating`.
+    fn next_timer(&mut self, core: &Core, beating: bool) -> Option<(Duration, Timer)> {
+        let mut timers = Vec::new();
+
+        if beating {
+            if let Some(interval) = self.config.heartbeat {
+                let due = interval.saturating_sub(self.config.clock.elapsed(self.last_beat));
+                timers.push((due, Timer::Beat));
+            }
+
+            if let Some(lease) = self.config.lease {
+                let due = (lease / RENEWALS_PER_LEASE)
+                    .saturating_sub(self.config.clock.elapsed(self.last_renewal));
+
+                timers.push((due, Timer::Renewal));
+            }
+        } else if core.watches() {
+            if let Some(detection) = self.config.detection {
+                let due = detection.interval
+                    .saturating_sub(self.config.clock.elapsed(self.last_ping));
+
+                timers.push((due, Timer::Ping(detection)));
+            }
+
+            if let Some(lease) = self.config.lease {
+                self.track_lease(core);
+                let due = lease.saturating_sub(self.config.clock.elapsed(self.lease.2));
+                timers.push((due, Timer::Expiry));
+            }
+        }
+
+        timers.into_iter().min_by_key(|(due, _)| *due)
+    }
+
+    /// Take the lease of the coordinator `core` follows to run from now,
+    /// should it have been renewed since last looked at, or the member
+    /// follow another coordinator, which has a lease of its own.
+    fn track_lease(&mut self, core: &Core) {
+        let (coord_id, renewals) = (core.coord_id(), core.renewals);
+
+        if (self.lease.0, self.lease.1) != (coord_id, renewals) {
+            self.lease = (coord_id, renewals, self.config.clock.now());
+        }
+    }
+
+    /// Start an election of this member's own, or a pre-vote on holding
+    /// one, the lease of the coordinator it follows having run out
+    /// unrenewed.
+    async fn expire(&mut self, core: &mut Core) -> Result<()> {
+        // The next lease runs from now, lest the election be started over
+        // and over.
+        self.lease.2 = self.config.clock.now();
+        self.emit(Event::LeaseExpired(core.coord_id()));
+        core.suspect(self).await
+    }
+
+    /// Ping the coordinator this member watches, starting an election of
+    /// its own, or a pre-vote on holding one, should the coordinator have
+    /// left `detection.misses` pings in a row unanswered.
+    async fn watch(&mut self, core: &mut Core, detection: Detection) -> Result<()> {
+        let coord_id = core.coord_id();
+        self.last_ping = self.config.clock.now();
+
+        // A coordinator this member hasn't pinged yet has missed nothing.
+        if self.missed.0 != coord_id {
+            self.missed = (coord_id, 0);
+        }
+
+        let answered = self.ping(core, coord_id, detection.interval).await?;
+
+        // Whatever arrived while waiting may have changed who to watch,
+        // or started an election already.
+        if answered || !core.watches() || core.coord_id() != coord_id {
+            self.missed.1 = 0;
+            return Ok(());
+        }
+
+        self.missed.1 += 1;
+        self.emit(Event::MissedPing { id: coord_id, missed: self.missed.1 });
+
+        if self.missed.1 >= detection.misses {
+            self.missed.1 = 0;
+            core.suspect(self).await?;
+        }
+
+        Ok(())
+    }
+
+    /// Have `core` route around the members found `down` from now on, if
+    /// reconfiguring the ring, telling the rest of it through `next`.
+    async fn declare(
+        &mut self, core: &mut Core, down: &[usize], next: Option<usize>,
+    ) -> Result<()> {
+        if self.config.reconfigure {
+            for &id in down {
+                core.declare_down(self, id, next).await?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// How long to wait for ring member `id` to answer a first ping.
+    fn ping_timeout(&self, id: usize) -> Duration {
+        match (self.config.adaptive_timeout, self.round_trips.get(&id)) {
+            (Some(k), Some(rtts)) if rtts.count >= MIN_ROUND_TRIPS => {
+                rtts.timeout(k).max(MIN_PING_TIMEOUT)
+            }
+            _ => self.config.backoff.base,
+        }
+    }
+
+    /// Ping ring member `id`, returning whether it answered within
+    /// `timeout`.
+    async fn ping(&mut self, core: &mut Core, id: usize, timeout: Duration) -> Result<bool> {
+        let sent = self.config.clock.ring_now();
+        self.send_to(id, Msg::Ping { s_id: self.id }).await?;
+        self.emit(Event::Pinged(id));
+
+        // Wait again for a response after handling an unexpected message
+        // if one was received.
+        loop {
+            self.waiting(core);
+            self.release()?;
+
+            let msg = match self.recv_timeout(timeout).await? {
+                Some(msg) => msg,
+                None => {
+                    if let Some(tally) = &self.config.tally {
+                        tally.ping_timeout(self.id);
+                    }
+
+                    #[cfg(feature = "metrics-server")]
+                    if let Some(metrics) = &self.config.metrics {
+                        metrics.ping_failed(self.id);
+                    }
+
+                    return Ok(false);
+                }
+            };
+
+            self.record(&msg)?;
+
+            if let Msg::Pong = msg {
+                if self.config.adaptive_timeout.is_some() {
+                    let rtt = self.config.clock.ring_now().saturating_duration_since(sent);
+                    self.round_trips.entry(id).or_default().add(rtt);
+                }
+
+                return Ok(true);
+            }
+
+            core.step(self, msg).await?;
+        }
+    }
+
+    /// Receive the next message within `timeout`, recording giving up on it
+    /// if recording, so that a replay gives up at the same point.
+    ///
+    /// Whatever of `timeout` passed with the ring paused is waited again.
+    async fn recv_timeout(&mut self, mut timeout: Duration) -> Result<Option<Msg>> {
+        if let Some(msg) = self.own.pop_front() {
+            return Ok(Some(msg));
+        }
+
+        let msg = loop {
+            let paused = self.config.pause.as_ref().map(Pause::paused_for);
+            let msg = self.transport_recv(Some(timeout)).await?;
+
+            if let (None, Some(pause), Some(paused)) = (&msg, &self.config.pause, paused) {
+                pause.hold();
+                timeout = pause.paused_for() - paused;
+
+                if !timeout.is_zero() {
+                    continue;
+                }
+            }
+
+            break msg;
+        };
+
+        if let (None, Some(recorder)) = (&msg, &self.config.recorder) {
+            recorder.timeout(self.id)?;
+        }
+
+        Ok(msg)
+    }
+
+    /// Receive the next message, however long it takes.
+    async fn recv(&mut self) -> Result<Msg> {
+        match self.own.pop_front() {
+            Some(msg) => Ok(msg),
+            None => self.transport_recv(None).await?.ok_or(Error::msg("Woken without a message")),
+        }
+    }
+
+    /// Receive the next message over the transport, giving up after
+    /// `timeout` if given. On an event loop, the transport is only asked
+    /// for one already there, the loop running other members until one is.
+    async fn transport_recv(&self, timeout: Option<Duration>) -> Result<Option<Msg>> {
+        if self.config.runtime == MemberRuntime::Threads {
+            return match timeout {
+                Some(timeout) => self.transport.recv_timeout(timeout),
+                None => self.transport.recv().map(Some),
+            };
+        }
+
+        // The ring answers in real time, as the loop runs in it.
+        let deadline = timeout.map(|timeout| self.config.clock.ring_now() + timeout);
+
+        executor::until(|| match self.transport.recv_timeout(Duration::ZERO) {
+            Ok(None) if deadline.is_none_or(|d| self.config.clock.ring_now() < d) => None,
+            res => Some(res),
+        })
+        .await
+    }
+
+    /// Let `duration` pass, as by the clock. On an event loop, the other
+    /// members run meanwhile.
+    async fn sleep(&self, duration: Duration) {
+        if self.config.runtime == MemberRuntime::Threads {
+            return self.config.clock.sleep(duration);
+        }
+
+        let until = self.config.clock.pass(duration);
+        executor::until(|| (self.config.clock.ring_now() >= until).then_some(())).await
+    }
+
+    /// Hand `msg` to ring member `id`, keeping it to receive next if that's
+    /// this member, as when it's alone in the ring.
+    fn deliver(&mut self, id: usize, msg: Msg) -> Result<()> {
+        if id != self.id {
+            return self.transport.send_to(id, msg);
+        }
+
+        self.own.push_back(msg);
+        Ok(())
+    }
+
+    /// Send the message held back to be reordered, if any, since no other
+    /// will be sent before this member waits for one. It's late, but never
+    /// lost.
+    fn release(&mut self) -> Result<()> {
+        match self.held.take() {
+            Some((to, msg)) => self.deliver(to, msg),
+            None => Ok(()),
+        }
+    }
+
+    /// Let the stepper and whatever pauses the ring know what state this
+    /// member waits for a message in.
+    fn waiting(&self, core: &Core) {
+        if let Some(stepper) = &self.config.stepper {
+            stepper.waiting(core.status());
+        }
+
+        if let Some(pause) = &self.config.pause {
+            pause.waiting(core.status());
+        }
+    }
+
+    /// Hold a message delivered to this member while the ring is paused,
+    /// and pause before it if single-stepping, then record it, if
+    /// recording, and count it, if counting.
+    fn record(&self, msg: &Msg) -> Result<()> {
+        if let Some(pause) = &self.config.pause {
+            pause.hold();
+        }
+
+        if let Some(stepper) = &self.config.stepper {
+            stepper.deliver(self.id, msg)?;
+        }
+
+        if let Some(counts) = &self.config.counts {
+            counts.count(msg);
+        }
+
+        if let Some(tracer) = &self.config.tracer {
+            tracer.message(self.id, msg)?;
+        }
+
+        match &self.config.recorder {
+            Some(recorder) => recorder.member(self.id, msg),
+            None => Ok(()),
+        }
+    }
+}
+
+/// The voter in `body` an election elects: the one with the highest
+/// ranking key, and the lowest id among those with the same key.
+fn rank(body: &[bool], keys: &[u64]) -> Option<usize> {
+    (0..body.len())
+        .filter(|i| body[*i])
+        .max_by_key(|i| (keys[*i], Reverse(*i)))
+}
+
+/// Microseconds since the Unix epoch, comparable across ring members.
+fn unix_micros() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_micros() as u64)
+        .unwrap_or_default()
+}
+
+/// When a member is seen alive: at `unix_micros`, or at the scheduler's
+/// virtual time, if it keeps one, for a repeated run to rank alike.
+fn alive_micros(config: &SimConfig) -> u64 {
+    match &config.sched {
+        Some(sched) => sched.micros(),
+        None => unix_micros(),
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Msg {
+    Ping { s_id: usize },
+    Pong,
+    /// An election, carrying the ranking key of every member that voted in
+    /// it, such as its priority, so that whoever ends it ranks the voters
+    /// as any member would, and the epoch it was numbered, carried on into
+    /// its result so that members can tell stale results from current ones.
+    Election {
+        body: Vec<bool>,
+        keys: Vec<u64>,
+        initiator: usize,
+        epoch: usize,
+    },
+    /// The election's winner, carrying the votes cast in it if auditing.
+    ElectionResult {
+        id: usize,
+        initiator: usize,
+        votes: Option<Vec<bool>>,
+        epoch: usize,
+    },
+    /// A Chang–Roberts election started by `initiator`, carrying the
+    /// highest id it has met so far as its candidate.
+    Candidate { id: usize, initiator: usize },
+    /// A probe of Hirschberg–Sinclair candidate `id` in election `election`,
+    /// started by `initiator`, sent `hops` members clockwise or the other
+    /// way round so far, of the `2^phase` it goes.
+    Probe {
+        id: usize,
+        election: usize,
+        phase: u32,
+        hops: usize,
+        clockwise: bool,
+        initiator: usize,
+    },
+    /// A reply to candidate `id`'s probe, on its way back to it clockwise
+    /// or the other way round.
+    Reply { id: usize, election: usize, clockwise: bool },
+    /// A bully election started by `initiator`, challenging a member with
+    /// a higher id than member `s_id` to answer it.
+    BullyElection { s_id: usize, initiator: usize },
+    /// Answers a bully election, letting its sender know that a member
+    /// with a higher id is up, or acknowledges its coordinator.
+    Answer,
+    /// Announces member `id` the winner of the bully election started by
+    /// `initiator`.
+    Coordinator { id: usize, initiator: usize },
+    /// Tells the ring that member `origin` found member `id` down, on its
+    /// way around from `origin`, `hops` members so far.
+    MemberDown { id: usize, origin: usize, hops: usize },
+    /// Tells the ring that member `id` is up after all, on its way around
+    /// from it, `hops` members so far.
+    MemberUp { id: usize, hops: usize },
+    /// Coordinator `id` renewing its lease, on its way around the ring from
+    /// it, `hops` members so far.
+    LeaseRenewal { id: usize, hops: usize },
+    /// Asks the ring whether coordinator `coord_id` is gone, on its way
+    /// around from `origin`, `hops` members so far, `agreed` of which and
+    /// `origin` found it so.
+    PreVote { origin: usize, coord_id: usize, agreed: usize, hops: usize },
+    /// Toggle ring member `id`, forwarded `hops` times so far. Resent
+    /// toggles keep their `seq`.
+    SimToggle { id: usize, hops: usize, seq: usize },
+    SimStatus,
+    SimEnd,
+    /// Tells a member that member `id` left the ring, so is passed by.
+    SimLeft { id: usize },
+    /// Tells a member that member `id` joined the ring, so is passed
+    /// through again.
+    SimJoined { id: usize },
+    /// Tells a member it crashed and is restarting, so forgets all it knew
+    /// of the ring's elections.
+    SimRestart,
+    /// Tells a member to take `delay` over each message from another from
+    /// now on.
+    SimSlow { delay: ProcessingDelay },
+    /// Tells a member to stand in elections with `priority` from now on.
+    SimPriority { priority: u64 },
+    /// Tells the coordinator to step down, starting election `epoch` to
+    /// hand over to another member, standing in no part of it itself.
+    SimStepDown { epoch: usize },
+}
+
+impl Msg {
+    /// Whether the message is sent on behalf of the simulator rather than
+    /// being part of the election protocol.
+    fn is_sim(&self) -> bool {
+        matches!(
+            self,
+            Msg::SimToggle { .. } | Msg::SimStatus | Msg::SimEnd | Msg::SimLeft { .. }
+            | Msg::SimJoined { .. } | Msg::SimRestart | Msg::SimSlow { .. }
+            | Msg::SimPriority { .. } | Msg::SimStepDown { .. }
+        )
+    }
+
+    /// The name of the message's variant.
+    fn kind(&self) -> &'static str {
+        match self {
+            Msg::Ping { .. } => "Ping",
+            Msg::Pong => "Pong",
+            Msg::Election { .. } => "Election",
+            Msg::ElectionResult { .. } => "ElectionResult",
+            Msg::Candidate { .. } => "Candidate",
+            Msg::Probe { .. } => "Probe",
+            Msg::Reply { .. } => "Reply",
+            Msg::BullyElection { .. } => "BullyElection",
+            Msg::Answer => "Answer",
+            Msg::Coordinator { .. } => "Coordinator",
+            Msg::MemberDown { .. } => "MemberDown",
+            Msg::MemberUp { .. } => "MemberUp",
+            Msg::LeaseRenewal { .. } => "LeaseRenewal",
+            Msg::PreVote { .. } => "PreVote",
+            Msg::SimToggle { .. } => "SimToggle",
+            Msg::SimStatus => "SimStatus",
+            Msg::SimEnd => "SimEnd",
+            Msg::SimLeft { .. } => "SimLeft",
+            Msg::SimJoined { .. } => "SimJoined",
+            Msg::SimRestart => "SimRestart",
+            Msg::SimSlow { .. } => "SimSlow",
+            Msg::SimPriority { .. } => "SimPriority",
+            Msg::SimStepDown { .. } => "SimStepDown",
+        }
+    }
+
+    /// An election to be started by ring member `initiator` of a ring of
+    /// `ring_size` members, in epoch `epoch`.
+    pub fn election(initiator: usize, ring_size: usize, epoch: usize) -> Self {
+        Self::Election {
+            body: vec![false; ring_size],
+            keys: vec![0; ring_size],
+            initiator,
+            epoch,
+        }
+    }
+
+    /// What starts an election of `algorithm` from ring member `origin` of
+    /// a ring of `ring_size` members, numbered `election` should the
+    /// algorithm number them.
+    pub(crate) fn opening(
+        algorithm: Algorithm, origin: usize, ring_size: usize, election: usize,
+    ) -> Self {
+        match algorithm {
+            Algorithm::Ring => Msg::election(origin, ring_size, election),
+            Algorithm::Bully => Msg::BullyElection { s_id: origin, initiator: origin },
+            Algorithm::ChangRoberts => Msg::Candidate { id: origin, initiator: origin },
+            Algorithm::HirschbergSinclair => Msg::Probe {
+                id: origin,
+                election,
+                phase: 0,
+                hops: 0,
+                clockwise: true,
+                initiator: origin,
+            },
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum SimMsg {
+    /// Acknowledges toggle `seq`, with the member's state after it.
+    ConfirmToggle { id: usize, active: bool, seq: usize },
+    /// A toggle went around the ring without finding its target.
+    Unreachable { id: usize },
+    ElectionResult {
+        id: usize,
+        initiator: usize,
+        votes: Option<Vec<bool>>,
+        epoch: usize,
+    },
+    Status(MemberStatus),
+    Violation { reason: String },
+    /// A ring member's thread panicked.
+    Crashed { id: usize, reason: String },
+    /// A ring member's thread failed for `reason` and was respawned.
+    Respawned { id: usize, reason: String },
+    /// The coordinator is still up.
+    Heartbeat { id: usize },
+}
+
+impl SimMsg {
+    /// The name of the message's variant.
+    fn kind(&self) -> &'static str {
+        match self {
+            SimMsg::ConfirmToggle { .. } => "ConfirmToggle",
+            SimMsg::Unreachable { .. } => "Unreachable",
+            SimMsg::ElectionResult { .. } => "ElectionResult",
+            SimMsg::Status(_) => "Status",
+            SimMsg::Violation { .. } => "Violation",
+            SimMsg::Crashed { .. } => "Crashed",
+            SimMsg::Respawned { .. } => "Respawned",
+            SimMsg::Heartbeat { .. } => "Heartbeat",
+        }
+    }
+}
+
+/// A consistent view of every ring member at one point in time.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RingSnapshot {
+    /// The coordinator according to the simulator.
+    pub coord_id: usize,
+    pub members: Vec<MemberStatus>,
+}
+
+impl fmt::Display for RingSnapshot {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "coordinator: {}", self.coord_id)?;
+
+        for m in &self.members {
+            match &m.label {
+                Some(label) => write!(f, "{} ({})", m.id, label)?,
+                None => write!(f, "{}", m.id)?,
+            }
+
+            write!(
+                f, ": active = {}, coord_id = {}, state = {:?}",
+                m.active, m.coord_id, m.state
+            )?;
+
+            match m.down.is_empty() {
+                true => writeln!(f)?,
+                false => writeln!(f, ", down = {:?}", m.down)?,
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// The role a ring member currently plays in the election.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum State {
+    /// Follows the coordinator it knows of.
+    Follower,
+    /// Has voted in an election that has not ended yet.
+    Participant,
+    /// Believes itself to be the coordinator.
+    Coordinator,
+}
+
+/// A ring member's view of itself, as reported to the simulator.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MemberStatus {
+    pub id: usize,
+    pub label: Option<String>,
+    pub active: bool,
+    pub coord_id: usize,
+    pub state: State,
+    /// The members it routes around, having heard they are down.
+    pub down: Vec<usize>,
+}
+
+/// The most actions a sequence file may hold once its repeats are taken
+/// over, so that a repeat can't run its reader out of memory.
+const MAX_SEQ_ACTIONS: usize = 1_000_000;
+
+/// The `SimSeq` type, which specifies the actions the simulator takes, in
+/// order.
+///
+/// A sequence is classically made of steps, each waiting for some seconds
+/// and then toggling a member active/inactive. Should a toggle take the
+/// coordinator down, the simulator starts the ensuing election from the
+/// toggle's origin. Named actions also take members down or up only if
+/// they aren't already, hold elections outright, check the coordinator,
+/// pause the ring and end the sequence early.
+#[derive(Debug, Clone)]
+pub struct SimSeq {
+    /// What the simulator does, in order.
+    pub actions: Vec<Action>,
+    /// What the scenario says the run should end with.
+    pub expect: Expect,
+    /// The line of its file each action was read from, if the sequence
+    /// was read line by line.
+    pub lines: Vec<usize>,
+}
+
+/// Something the simulator does while following a sequence.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Action {
+    /// Let time pass.
+    Wait(Duration),
+    /// Show the state the ring is in, then hold it still, timers and
+    /// deliveries alike, for the given time.
+    Pause(Duration),
+    /// Toggle member `id` active/inactive, electing from `origin` should
+    /// it take the coordinator down.
+    Toggle { id: usize, origin: usize },
+    /// Take member `id` down unless it already is, as a toggle would.
+    Kill { id: usize, origin: usize },
+    /// Bring member `id` back up unless it already is, as a toggle would.
+    Recover { id: usize, origin: usize },
+    /// Take member `id` down as a kill would, for it to be restarted.
+    Crash { id: usize, origin: usize },
+    /// Bring member `id` back up unless it already is, having forgotten
+    /// all it knew of the ring's elections, then hold an election from it
+    /// for it to find the coordinator again.
+    Restart(usize),
+    /// Spawn member `id`, which left the ring, to join it again, and
+    /// bring it up as a recovery would.
+    Join { id: usize, origin: usize },
+    /// Take member `id` down as a kill would, then retire it from the ring.
+    Leave { id: usize, origin: usize },
+    /// Have member `id` take `delay` over each message from another from
+    /// now on.
+    Slow { id: usize, delay: ProcessingDelay },
+    /// Give member `id` election priority `priority` from now on.
+    Priority { id: usize, priority: u64 },
+    /// Cut the ring as the partition says, so that no member's messages
+    /// reach another group's.
+    Partition(Partition),
+    /// Let members' messages reach any other again.
+    Heal,
+    /// Hold an election from member `origin`.
+    Election { origin: usize },
+    /// Hold an election from each of two members at once.
+    Concurrent { origins: [usize; 2] },
+    /// Have the coordinator step down, handing over to whichever other
+    /// member an election it stands in no part of picks.
+    StepDown,
+    /// Check that the ring follows coordinator `id`.
+    AssertCoordinator(usize),
+    /// Check that member `id` is active.
+    AssertActive(usize),
+    /// Check how many elections the ring has held so far.
+    AssertElections(Cmp, usize),
+    /// Stop following the sequence.
+    End,
+}
+
+impl Action {
+    /// The members the action names.
+    pub fn ids(&self) -> Vec<usize> {
+        match *self {
+            Action::Toggle { id, origin } | Action::Kill { id, origin }
+            | Action::Recover { id, origin } | Action::Crash { id, origin }
+            | Action::Join { id, origin } | Action::Leave { id, origin } => vec![id, origin],
+            Action::Election { origin } => vec![origin],
+            Action::Concurrent { origins } => origins.to_vec(),
+            Action::Restart(id) | Action::Slow { id, .. } | Action::Priority { id, .. }
+            | Action::AssertCoordinator(id)
+            | Action::AssertActive(id) => vec![id],
+            Action::Partition(ref partition) => partition.groups.concat(),
+            Action::Wait(_) | Action::Pause(_) | Action::Heal | Action::StepDown
+            | Action::AssertElections(..) | Action::End => Vec::new(),
+        }
+    }
+
+    /// The action, unless it names a member beyond a ring of `ring_size`.
+    pub fn within(self, ring_size: usize) -> Result<Self> {
+        match self.ids().into_iter().find(|id| *id >= ring_size) {
+            Some(id) => bail!(
+                "member {} isn't in the ring, which only has members 0 to {}",
+                id, ring_size - 1
+            ),
+            None => Ok(self),
+        }
+    }
+
+    /// The action with its election started from `from`, if it may hold
+    /// one after changing a member.
+    fn starting_from(self, from: usize) -> Self {
+        match self {
+            Action::Toggle { id, .. } => Action::Toggle { id, origin: from },
+            Action::Kill { id, .. } => Action::Kill { id, origin: from },
+            Action::Recover { id, .. } => Action::Recover { id, origin: from },
+            Action::Crash { id, .. } => Action::Crash { id, origin: from },
+            Action::Join { id, .. } => Action::Join { id, origin: from },
+            Action::Leave { id, .. } => Action::Leave { id, origin: from },
+            action => action,
+        }
+    }
+}
+
+impl fmt::Display for Action {
+    /// The action as a sequence file line holds it, such as `wait 2s` or
+    /// `kill 3 from 1`.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let (name, id, origin) = match *self {
+            Action::Wait(duration) => return write!(f, "wait {}", duration_str(duration)),
+            Action::Pause(duration) => return write!(f, "pause {}", duration_str(duration)),
+            Action::Toggle { id, origin } => ("toggle", id, origin),
+            Action::Kill { id, origin } => ("kill", id, origin),
+            Action::Recover { id, origin } => ("recover", id, origin),
+            Action::Crash { id, origin } => ("crash", id, origin),
+            Action::Join { id, origin } => ("join", id, origin),
+            Action::Leave { id, origin } => ("leave", id, origin),
+            Action::Restart(id) => return write!(f, "restart {}", id),
+            Action::Slow { id, delay } => return write!(f, "slow {} {}", id, delay),
+            Action::Priority { id, priority } => {
+                return write!(f, "priority {} {}", id, priority);
+            }
+            Action::Partition(ref partition) => return write!(f, "partition {}", partition),
+            Action::Heal => return write!(f, "heal"),
+            Action::Election { origin } => return write!(f, "election {}", origin),
+            Action::Concurrent { origins: [first, second] } => {
+                return write!(f, "concurrent {} {}", first, second);
+            }
+            Action::StepDown => return write!(f, "stepdown"),
+            Action::AssertCoordinator(id) => return write!(f, "assert-coordinator {}", id),
+            Action::AssertActive(id) => return write!(f, "assert-active {}", id),
+            Action::AssertElections(Cmp::Exactly, count) => {
+                return write!(f, "assert-elections {}", count);
+            }
+            Action::AssertElections(cmp, count) => {
+                return write!(f, "assert-elections {} {}", cmp, count);
+            }
+            Action::End => return write!(f, "end"),
+        };
+
+        match origin {
+            0 => write!(f, "{} {}", name, id),
+            _ => write!(f, "{} {} from {}", name, id, origin),
+        }
+    }
+}
+
+impl std::str::FromStr for Action {
+    type Err = Error;
+
+    /// Parse an action as [`Action`]'s `Display` writes it, elections
+    /// after a change starting from member 0 unless given `from` another.
+    fn from_str(s: &str) -> Result<Self> {
+        let words = s.split_whitespace().collect::<Vec<_>>();
+
+        let id = |word: &str| match word.parse() {
+            Ok(id) => Ok(id),
+            Err(_) => bail!("Invalid member {:?} in {:?}", word, s),
+        };
+
+        let change = |id: usize, origin: usize| match words[0] {
+            "toggle" => Action::Toggle { id, origin },
+            "kill" => Action::Kill { id, origin },
+            "crash" => Action::Crash { id, origin },
+            "join" => Action::Join { id, origin },
+            "leave" => Action::Leave { id, origin },
+            _ => Action::Recover { id, origin },
+        };
+
+        if let Some(groups) = s.trim().strip_prefix("partition ") {
+            return Ok(Action::Partition(groups.parse()?));
+        }
+
+        if let Some(bound) = s.trim().strip_prefix("assert-elections") {
+            let (cmp, count) = Cmp::parse(bound)?;
+            return Ok(Action::AssertElections(cmp, count));
+        }
+
+        match words[..] {
+            ["wait", duration] => Ok(Action::Wait(parse_duration(duration)?)),
+            ["pause", duration] => Ok(Action::Pause(parse_duration(duration)?)),
+            ["toggle" | "kill" | "recover" | "crash" | "join" | "leave", member] => {
+                Ok(change(id(member)?, 0))
+            }
+            [
+                "toggle" | "kill" | "recover" | "crash" | "join" | "leave",
+                member, "from", origin,
+            ] => Ok(change(id(member)?, id(origin)?)),
+            ["restart", member] => Ok(Action::Restart(id(member)?)),
+            ["slow", member, delay] => Ok(Action::Slow { id: id(member)?, delay: delay.parse()? }),
+            ["priority", member, priority] => match priority.parse() {
+                Ok(priority) => Ok(Action::Priority { id: id(member)?, priority }),
+                Err(_) => bail!("Invalid priority {:?} in {:?}", priority, s),
+            },
+            ["heal"] => Ok(Action::Heal),
+            ["election", origin] => Ok(Action::Election { origin: id(origin)? }),
+            ["concurrent", first, second] => {
+                Ok(Action::Concurrent { origins: [id(first)?, id(second)?] })
+            }
+            ["stepdown"] => Ok(Action::StepDown),
+            ["assert-coordinator", member] => Ok(Action::AssertCoordinator(id(member)?)),
+            ["assert-active", member] => Ok(Action::AssertActive(id(member)?)),
+            ["end"] => Ok(Action::End),
+            _ => bail!("Unknown action {:?}", s),
+        }
+    }
+}
+
+/// How a count compares to the one an assertion gives.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Cmp {
+    Less,
+    AtMost,
+    Exactly,
+    AtLeast,
+    More,
+}
+
+impl Cmp {
+    /// Whether `n` compares to `bound` as asked.
+    pub fn holds(self, n: usize, bound: usize) -> bool {
+        match self {
+            Cmp::Less => n < bound,
+            Cmp::AtMost => n <= bound,
+            Cmp::Exactly => n == bound,
+            Cmp::AtLeast => n >= bound,
+            Cmp::More => n > bound,
+        }
+    }
+
+    /// Parse a bound such as `<= 3`, or `3` for exactly that many.
+    fn parse(bound: &str) -> Result<(Self, usize)> {
+        let bound = bound.trim();
+
+        let ops = [
+            ("<=", Cmp::AtMost), (">=", Cmp::AtLeast), ("==", Cmp::Exactly),
+            ("<", Cmp::Less), (">", Cmp::More), ("=", Cmp::Exactly),
+        ];
+
+        let (cmp, count) = ops.into_iter()
+            .find_map(|(op, cmp)| bound.strip_prefix(op).map(|count| (cmp, count)))
+            .unwrap_or((Cmp::Exactly, bound));
+
+        match count.trim().parse() {
+            Ok(count) => Ok((cmp, count)),
+            Err(_) => bail!("Invalid bound {:?}, such as <= 3", bound),
+        }
+    }
+}
+
+impl fmt::Display for Cmp {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Cmp::Less => write!(f, "<"),
+            Cmp::AtMost => write!(f, "<="),
+            Cmp::Exactly => write!(f, "=="),
+            Cmp::AtLeast => write!(f, ">="),
+            Cmp::More => write!(f, ">"),
+        }
+    }
+}
+
+impl SimSeq {
+    /// Toggle the coordinator inactive until the last ring member
+    /// is the only one left. Then, toggle its predecessor active before
+    /// toggling the coordinator inactive and then active and so on
+    /// until the first ring member is reached.
+    /// Wait for `wait` between toggles.
+    ///
+    /// E.g.: The toggle order for 0 1 2 is 0 1 1 2 2 0 1 1.
+    pub fn walk_down(ring_size: usize, wait: Duration) -> Self {
+        let last = ring_size.saturating_sub(1);
+        let mut toggles = Vec::with_capacity(last * 4);
+
+        for i in 0..last {
+            toggles.push(i);
+        }
+
+        for i in (0..last).rev() {
+            toggles.push(i);
+            toggles.push(i + 1);
+            toggles.push(i + 1);
+        }
+
+        let waits = vec![wait; toggles.len()];
+        SimSeq::new(ring_size, toggles, waits).unwrap()
+    }
+
+    /// A sequence for a ring of `ring_size` members, of steps each waiting
+    /// for `waits[i]` and then toggling member `toggles[i]`.
+    pub fn new(ring_size: usize, toggles: Vec<usize>, waits: Vec<Duration>) -> Result<Self> {
+        if toggles.len() != waits.len(){
+            bail!("Number of toggles must be equal to the number of waits");
+        }
+
+        let actions = waits.into_iter()
+            .zip(toggles)
+            .flat_map(|(wait, id)| {
+                [Action::Wait(wait), Action::Toggle { id, origin: 0 }]
+            })
+            .collect();
+
+        Self::from_actions(ring_size, actions, Expect::default())
+    }
+
+    /// A sequence for a ring of `ring_size` members taking `actions`.
+    pub fn from_actions(
+        ring_size: usize, actions: Vec<Action>, expect: Expect,
+    ) -> Result<Self> {
+        for (step, action) in actions.iter().enumerate() {
+            if let Some(id) = action.ids().into_iter().find(|id| *id >= ring_size) {
+                bail!(
+                    "Step {} ({}) names member {}, but the ring only has \
+                    members 0 to {}", step + 1, action, id, ring_size - 1
+                );
+            }
+        }
+
+        Ok(Self { actions, expect, lines: Vec::new() })
+    }
+
+    /// Generate a sequence of `steps` random toggles, each waiting between
+    /// `waits.0` and `waits.1` before it, in whole seconds if both are and
+    /// in milliseconds otherwise.
+    ///
+    /// Each step toggles the coordinator with probability `bias` and a
+    /// uniformly random member otherwise, so that a high bias makes for
+    /// many elections. The coordinator is tracked as a ring configured as
+    /// `config` would elect it, whenever it holds an election.
+    pub fn random(
+        rng: &mut Rng, ring_size: usize, steps: usize, bias: f64, waits: (Duration, Duration),
+        config: &SimConfig,
+    ) -> Self {
+        let mut active = vec![true; ring_size];
+        let mut coord_id = config.elects(&active).unwrap_or_default();
+        let mut toggles = Vec::with_capacity(steps);
+        let mut drawn = Vec::with_capacity(steps);
+
+        for _ in 0..steps {
+            let mut id = if rng.chance(bias) {
+                coord_id
+            } else {
+                rng.below(ring_size as u64) as usize
+            };
+
+            // Elections need at least one active member to vote, so bring
+            // another one up instead of taking the last one down.
+            if active[id] && active.iter().filter(|a| **a).count() == 1
+                && ring_size > 1
+            {
+                id = (id + 1 + rng.below(ring_size as u64 - 1) as usize) % ring_size;
+            }
+
+            active[id] = !active[id];
+
+            if (id == coord_id && !active[id]) || config.reelect == ReelectPolicy::Any {
+                coord_id = config.elects(&active).unwrap_or_default();
+            }
+
+            toggles.push(id);
+            drawn.push(random_wait(rng, waits));
+        }
+
+        SimSeq::new(ring_size, toggles, drawn).unwrap()
+    }
+
+    /// Start the election after each change of a member from ring member
+    /// `origin` instead of the one given.
+    pub fn with_origin(mut self, ring_size: usize, origin: usize) -> Result<Self> {
+        if origin >= ring_size {
+            bail!("Election origin {} is not a ring member", origin);
+        }
+
+        self.actions = self.actions.into_iter()
+            .map(|action| action.starting_from(origin))
+            .collect();
+
+        Ok(self)
+    }
+
+    /// Work out which ring members each action leaves active, without
+    /// running the ring, failing on any that takes the last one down.
+    /// Members that left the ring are left as they are by all but joining
+    /// it.
+    pub fn dry_run(&self, ring_size: usize) -> Result<Vec<Vec<bool>>> {
+        let mut active = vec![true; ring_size];
+        let mut present = vec![true; ring_size];
+        let mut after = Vec::with_capacity(self.actions.len());
+
+        for (i, action) in self.actions.iter().enumerate() {
+            match *action {
+                Action::Join { id, .. } if !present[id] => {
+                    (active[id], present[id]) = (true, true);
+                }
+                Action::Toggle { id, .. } if present[id] => active[id] = !active[id],
+                Action::Kill { id, .. } if present[id] => active[id] = false,
+                Action::Recover { id, .. } if present[id] => active[id] = true,
+                Action::Crash { id, .. } if present[id] => active[id] = false,
+                Action::Restart(id) if present[id] => active[id] = true,
+                Action::Leave { id, .. } => (active[id], present[id]) = (false, false),
+                _ => {}
+            }
+
+            if !active.contains(&true) {
+                bail!(
+                    "{}: {} takes the last active member down, leaving none \
+                    to hold elections", self.at(i), action
+                );
+            }
+
+            let leaving = matches!(action, Action::Leave { .. });
+
+            if leaving && present.iter().filter(|p| **p).count() < 2 {
+                bail!(
+                    "{}: {} leaves a single member in the ring, with none to \
+                    pass messages to", self.at(i), action
+                );
+            }
+
+            after.push(active.clone());
+        }
+
+        Ok(after)
+    }
+
+    /// Where action `i` was read from, to point at it.
+    pub fn at(&self, i: usize) -> String {
+        match self.lines.get(i) {
+            Some(line) => format!("line {}", line),
+            None => format!("step {}", i + 1),
+        }
+    }
+
+    /// How many steps the sequence holds, not counting the waits and
+    /// pauses.
+    pub fn steps(&self) -> usize {
+        self.actions.iter().filter(|a| !matches!(a, Action::Wait(_) | Action::Pause(_))).count()
+    }
+
+    /// Whether the sequence pauses the ring anywhere.
+    pub fn pauses(&self) -> bool {
+        self.actions.iter().any(|a| matches!(a, Action::Pause(_)))
+    }
+
+    /// Whether the sequence partitions the ring anywhere.
+    pub fn partitions(&self) -> bool {
+        self.actions.iter().any(|a| matches!(a, Action::Partition(_)))
+    }
+
+    /// Read the simulation sequence from a file, in the structured format
+    /// read by [`SimSeq::from_structured`] if it's named `.toml` or
+    /// `.json`, and in the one read by [`SimSeq::from_lines`] otherwise.
+    pub fn from_file(path: &std::path::Path, ring_size: usize) -> Result<Self> {
+        let contents = match fs::read_to_string(path) {
+            Ok(c) => c,
+            Err(e) => bail!("Error reading file: {}", e),
+        };
+
+        let structured = match path.extension().and_then(|ext| ext.to_str()) {
+            Some("toml") => toml::parse(&contents).map(|entries| {
+                Value::Object(entries.into_iter().map(|(k, v)| (k, v.to_json())).collect())
+            }),
+            Some("json") => Value::parse(&contents),
+            _ => return Self::from_lines(path, &contents, ring_size),
+        };
+
+        structured
+            .and_then(|v| Self::from_structured(&v, ring_size))
+            .map_err(|e| Error::msg(format!("{}: {}", path.display(), e)))
+    }
+
+    /// Read a simulation sequence with a step per entry of its `steps`.
+    /// Each step waits for its `wait`, such as `2` seconds or `"250ms"`,
+    /// then changes a member as it says with `toggle`, `kill`, `recover`,
+    /// `crash`, `join` or `leave`, electing from its `origin`, 0 unless
+    /// given, or restarting one with `restart`, has the member to `slow`
+    /// take as long as it's slowed `by` over each message, such as
+    /// `"10ms"` or `"5ms..20ms"`, gives the member named by `priority` the
+    /// election priority it's given `to`, cuts the ring into the groups of
+    /// members to `partition` it into, such as `[[0, 1], [2, 3, 4]]`, makes
+    /// it whole again if `heal` is true, holds an `election` from a member,
+    /// or one from each of the two `concurrent` ones at once, has the
+    /// coordinator hand over to another if `stepdown` is true, checks the
+    /// coordinator to `assert_coordinator`, that the member `assert_active`
+    /// is and how many elections were held against `assert_elections`,
+    /// such as `3` or `"<= 3"`, and ends the sequence if `end` is true, in
+    /// that order and each only if given.
+    /// An `expect` entry holds the scenario's expected result, as in
+    /// `{ coordinator = 2, elections = 3 }`.
+    pub fn from_structured(v: &Value, ring_size: usize) -> Result<Self> {
+        // Only the keys given are read, so any other is a mistake.
+        let known = |v: &Value, keys: &[&str], what: &str| match v {
+            Value::Object(members) => {
+                match members.iter().find(|(k, _)| !keys.contains(&k.as_str())) {
+                    Some((k, _)) => bail!("Unknown {} {}", what, k),
+                    None => Ok(()),
+                }
+            }
+            _ => bail!("Expected a table for the {}", what),
+        };
+
+        known(v, &["steps", "expect"], "setting")?;
+
+        let steps = v.get("steps")
+            .and_then(Value::as_array)
+            .ok_or(Error::msg("Missing steps"))?;
+
+        let mut actions = Vec::new();
+
+        for (i, step) in steps.iter().enumerate() {
+            let n = i + 1;
+
+            known(step, &[
+                "wait", "pause", "toggle", "kill", "recover", "crash", "restart", "join",
+                "leave", "origin", "slow", "by", "priority", "to", "partition", "heal", "election",
+                "concurrent", "stepdown", "assert_coordinator", "assert_active",
+                "assert_elections", "end",
+            ], "step key")?;
+
+            let id = |key: &str| match step.get(key) {
+                Some(v) => v.as_u64()
+                    .map(|id| Some(id as usize))
+                    .ok_or(Error::msg(format!("Step {}: invalid {}", n, key))),
+                None => Ok(None),
+            };
+
+            match step.get("wait") {
+                Some(Value::String(s)) => actions.push(Action::Wait(parse_duration(s)?)),
+                Some(w) => match w.as_u64() {
+                    Some(secs) => actions.push(Action::Wait(Duration::from_secs(secs))),
+                    None => bail!("Step {}: invalid wait", n),
+                },
+                None => {}
+            }
+
+            match step.get("pause") {
+                Some(Value::String(s)) => actions.push(Action::Pause(parse_duration(s)?)),
+                Some(p) => match p.as_u64() {
+                    Some(secs) => actions.push(Action::Pause(Duration::from_secs(secs))),
+                    None => bail!("Step {}: invalid pause", n),
+                },
+                None => {}
+            }
+
+            let origin = id("origin")?.unwrap_or(0);
+
+            let changes = [
+                id("toggle")?.map(|id| Action::Toggle { id, origin }),
+                id("kill")?.map(|id| Action::Kill { id, origin }),
+                id("recover")?.map(|id| Action::Recover { id, origin }),
+                id("crash")?.map(|id| Action::Crash { id, origin }),
+                id("restart")?.map(Action::Restart),
+                id("join")?.map(|id| Action::Join { id, origin }),
+                id("leave")?.map(|id| Action::Leave { id, origin }),
+            ];
+
+            match &changes.into_iter().flatten().collect::<Vec<_>>()[..] {
+                [] => {}
+                [change] => actions.push(change.clone()),
+                _ => bail!(
+                    "Step {}: only one of toggle, kill, recover, crash, restart, join and \
+                    leave may be given", n
+                ),
+            }
+
+            match (id("slow")?, step.get("by")) {
+                (Some(id), Some(Value::String(by))) => {
+                    actions.push(Action::Slow { id, delay: by.parse()? });
+                }
+                (None, None) => {}
+                (Some(_), _) => bail!("Step {}: slow needs the delay to slow by", n),
+                (None, Some(_)) => bail!("Step {}: by needs a member to slow", n),
+            }
+
+            match (id("priority")?, step.get("to").map(Value::as_u64)) {
+                (Some(id), Some(Some(priority))) => {
+                    actions.push(Action::Priority { id, priority });
+                }
+                (None, None) => {}
+                (Some(_), _) => bail!("Step {}: priority needs the priority to give it", n),
+                (None, Some(_)) => bail!("Step {}: to needs a member to give a priority", n),
+            }
+
+            if let Some(groups) = step.get("partition") {
+                let invalid = || Error::msg(format!(
+                    "Step {}: invalid partition, such as [[0, 1], [2, 3, 4]]", n
+                ));
+
+                let groups = groups.as_array().ok_or_else(invalid)?.iter()
+                    .map(|group| {
+                        group.as_array()
+                            .and_then(|ids| {
+                                ids.iter().map(|id| id.as_u64().map(|id| id as usize)).collect()
+                            })
+                            .ok_or_else(invalid)
+                    })
+                    .collect::<Result<Vec<Vec<_>>>>()?;
+
+                actions.push(Action::Partition(Partition::new(groups)?));
+            }
+
+            match step.get("heal") {
+                Some(heal) if heal.as_bool() == Some(true) => actions.push(Action::Heal),
+                Some(heal) if heal.as_bool().is_none() => bail!("Step {}: invalid heal", n),
+                _ => {}
+            }
+
+            if let Some(origin) = id("election")? {
+                actions.push(Action::Election { origin });
+            }
+
+            if let Some(origins) = step.get("concurrent") {
+                let origins = origins.as_array()
+                    .and_then(|ids| {
+                        ids.iter().map(|id| id.as_u64().map(|id| id as usize)).collect()
+                    })
+                    .and_then(|ids: Vec<_>| <[usize; 2]>::try_from(ids).ok())
+                    .ok_or(Error::msg(format!(
+                        "Step {}: invalid concurrent, two members such as [1, 3]", n
+                    )))?;
+
+                actions.push(Action::Concurrent { origins });
+            }
+
+            match step.get("stepdown") {
+                Some(stepdown) if stepdown.as_bool() == Some(true) => {
+                    actions.push(Action::StepDown);
+                }
+                Some(stepdown) if stepdown.as_bool().is_none() => {
+                    bail!("Step {}: invalid stepdown", n);
+                }
+                _ => {}
+            }
+
+            if let Some(coord_id) = id("assert_coordinator")? {
+                actions.push(Action::AssertCoordinator(coord_id));
+            }
+
+            if let Some(id) = id("assert_active")? {
+                actions.push(Action::AssertActive(id));
+            }
+
+            match step.get("assert_elections") {
+                Some(Value::String(bound)) => {
+                    let (cmp, count) = Cmp::parse(bound)?;
+                    actions.push(Action::AssertElections(cmp, count));
+                }
+                Some(_) => {
+                    let count = id("assert_elections")?.unwrap_or_default();
+                    actions.push(Action::AssertElections(Cmp::Exactly, count));
+                }
+                None => {}
+            }
+
+            match step.get("end") {
+                Some(end) if end.as_bool() == Some(true) => actions.push(Action::End),
+                Some(end) if end.as_bool().is_none() => bail!("Step {}: invalid end", n),
+                _ => {}
+            }
+        }
+
+        let mut expect = Expect::default();
+
+        if let Some(e) = v.get("expect") {
+            known(e, &["coordinator", "elections"], "expectation")?;
+
+            let slots = [
+                ("coordinator", &mut expect.coord_id),
+                ("elections", &mut expect.elections),
+            ];
+
+            for (key, slot) in slots {
+                if let Some(value) = e.get(key) {
+                    *slot = Some(value.as_u64()
+                        .ok_or(Error::msg(format!("Invalid expectation {}", key)))? as usize);
+                }
+            }
+        }
+
+        Self::from_actions(ring_size, actions, expect)
+    }
+
+    /// Read a simulation sequence given line by line, with an action on
+    /// each, such as `kill 2` or `wait 250ms`, as [`Action`] parses it. A
+    /// line with a duration, such as `2` seconds or `250ms`, is a wait, and
+    /// a number after it the member to toggle. Blank lines and anything after a `#` are skipped.
+    /// Lines starting with `expect` hold the scenario's expected result
+    /// and may appear anywhere. An `assert coordinator=N` line checks the
+    /// ring's coordinator, as `assert-coordinator N` does, and
+    /// `assert-active N` and `assert-elections <= N` check that a member is
+    /// active and how many elections were held, and `pause 5s` shows the
+    /// state the ring is in and holds it still that long. A `leave 2` line
+    /// retires a member from the ring and `join 2` has it join again, and
+    /// `crash 2` takes one down for `restart 2` to bring it back up
+    /// having forgotten the ring's elections. A `slow 2 10ms` line has a
+    /// member take 10ms over each message from then on, or anywhere from
+    /// 5ms to 20ms for `slow 2 5ms..20ms`, and a `priority 2 10` line gives
+    /// a member election priority 10 from then on. A `partition {0,1}
+    /// {2,3,4}` line cuts the ring so that no member's messages reach
+    /// another group's, those named in no group making up one more, until
+    /// a `heal` line.
+    /// A `concurrent 1 3` line holds an election from each of two members
+    /// at once, and a `stepdown` line has the coordinator hand over to
+    /// another.
+    /// The lines between `repeat N {` and `}` are taken N times over, and
+    /// repeats may hold others.
+    pub fn from_lines(path: &std::path::Path, contents: &str, ring_size: usize) -> Result<Self> {
+        let mut actions = Vec::new();
+        let mut expect = Expect::default();
+        // Whether the last line was a wait a toggle is due after.
+        let mut waited = false;
+        // How many lines hold a step's wait and how many its toggle, and
+        // the line of the last wait.
+        let (mut waits, mut toggles, mut last_wait) = (0, 0, 0);
+        // The line each repeat being read starts at, how many times it
+        // repeats and the first action it holds.
+        let mut repeats: Vec<(usize, u64, usize)> = Vec::new();
+        let mut lines = Vec::new();
+
+        let at = |n: usize, e: Error| Error::msg(format!("{}, line {}: {}", path.display(), n, e));
+
+        for (n, line) in contents.lines().enumerate() {
+            let n = n + 1;
+            let line = line.split('#').next().unwrap_or_default().trim();
+
+            if line.is_empty() {
+                continue;
+            }
+
+            if waited && (line == "}" || line.starts_with("repeat")) {
+                let e = Error::msg("a repeat can't start or end between a step's wait and toggle");
+                return Err(at(n, e));
+            }
+
+            let action = if let Some(directive) = line.strip_prefix("expect") {
+                expect.parse(directive).map_err(|e| at(n, e))?;
+                continue;
+            } else if let Some(times) = line.strip_prefix("repeat") {
+                match times.trim().strip_suffix('{').map(|t| t.trim().parse()) {
+                    Some(Ok(times)) => repeats.push((n, times, actions.len())),
+                    _ => {
+                        let e = Error::msg(format!("expected repeat N {{, found {:?}", line));
+                        return Err(at(n, e));
+                    }
+                }
+
+                continue;
+            } else if line == "}" {
+                let (opened, times, start) = repeats.pop()
+                    .ok_or_else(|| at(n, Error::msg("} without a repeat to end")))?;
+
+                let body = actions.split_off(start);
+                let body_lines = lines.split_off(start);
+
+                // The repeats this one holds were taken over already, so
+                // count towards its body.
+                let len = usize::try_from(times).ok()
+                    .and_then(|times| body.len().checked_mul(times))
+                    .filter(|len| actions.len() + len <= MAX_SEQ_ACTIONS)
+                    .ok_or_else(|| at(opened, Error::msg(format!(
+                        "repeat {} takes the sequence past {} actions", times, MAX_SEQ_ACTIONS
+                    ))))?;
+
+                actions.extend(body.iter().cycle().take(len).cloned());
+                lines.extend(body_lines.iter().cycle().take(len));
+                continue;
+            } else if let Some(assertion) = line.strip_prefix("assert ") {
+                match assertion.trim().strip_prefix("coordinator=").map(str::parse) {
+                    Some(Ok(id)) => Action::AssertCoordinator(id),
+                    _ => return Err(at(n, Error::msg(format!("invalid assertion {}", line)))),
+                }
+            } else if waited {
+                match line.parse() {
+                    Ok(id) => {
+                        waited = false;
+                        toggles += 1;
+                        Action::Toggle { id, origin: 0 }
+                    }
+                    Err(_) => {
+                        let e = Error::msg("expected the member to toggle after the wait before");
+                        return Err(at(n, e));
+                    }
+                }
+            } else if line.starts_with(|c: char| c.is_ascii_digit()) {
+                waited = true;
+                (waits, last_wait) = (waits + 1, n);
+                Action::Wait(parse_duration(line).map_err(|e| at(n, e))?)
+            } else {
+                line.parse().map_err(|e| at(n, e))?
+            };
+
+            if waited && !matches!(action, Action::Wait(_)) {
+                let e = Error::msg("expected the member to toggle after the wait before");
+                return Err(at(n, e));
+            }
+
+            actions.push(action.within(ring_size).map_err(|e| at(n, e))?);
+            lines.push(n);
+        }
+
+        if let Some((n, _, _)) = repeats.last() {
+            return Err(at(*n, Error::msg("repeat without a } to end it")));
+        }
+
+        if waited {
+            bail!(
+                "{} ends with a wait but no toggle after it, having {} waits but {} \
+                toggles, likely because of line {}: each step is a line with a wait, \
+                such as 2 or 250ms, followed by a line with the member to toggle",
+                path.display(), waits, toggles, last_wait
+            );
+        }
+
+        Ok(SimSeq { lines, ..Self::from_actions(ring_size, actions, expect)? })
+    }
+
+    /// The sequence as a structured TOML file.
+    pub fn to_toml(&self) -> String {
+        let mut out = String::new();
+
+        let expect = [
+            ("coordinator", self.expect.coord_id),
+            ("elections", self.expect.elections),
+        ];
+
+        let expect = expect.into_iter()
+            .filter_map(|(key, v)| v.map(|v| format!("{} = {}", key, v)))
+            .collect::<Vec<_>>();
+
+        if !expect.is_empty() {
+            out += &format!("expect = {{ {} }}\n", expect.join(", "));
+        }
+
+        // A step's wait goes with the action after it.
+        let mut waiting = false;
+
+        for action in &self.actions {
+            if !waiting {
+                if !out.is_empty() {
+                    out.push('\n');
+                }
+
+                out += "[[steps]]\n";
+            }
+
+            waiting = matches!(action, Action::Wait(_));
+
+            let (key, id, origin) = match *action {
+                Action::Wait(duration) => {
+                    out += &format!("wait = \"{}\"\n", &Action::Wait(duration).to_string()[5..]);
+                    continue;
+                }
+                Action::Pause(duration) => {
+                    let pause = Action::Pause(duration).to_string();
+                    out += &format!("pause = \"{}\"\n", &pause[6..]);
+                    continue;
+                }
+                Action::Toggle { id, origin } => ("toggle", id, origin),
+                Action::Kill { id, origin } => ("kill", id, origin),
+                Action::Recover { id, origin } => ("recover", id, origin),
+                Action::Crash { id, origin } => ("crash", id, origin),
+                Action::Join { id, origin } => ("join", id, origin),
+                Action::Leave { id, origin } => ("leave", id, origin),
+                Action::Restart(id) => ("restart", id, 0),
+                Action::Slow { id, delay } => {
+                    out += &format!("slow = {}\nby = \"{}\"\n", id, delay);
+                    continue;
+                }
+                Action::Priority { id, priority } => {
+                    out += &format!("priority = {}\nto = {}\n", id, priority);
+                    continue;
+                }
+                Action::Partition(ref partition) => {
+                    let groups = partition.groups.iter()
+                        .map(|group| format!("{:?}", group))
+                        .collect::<Vec<_>>();
+
+                    out += &format!("partition = [{}]\n", groups.join(", "));
+                    continue;
+                }
+                Action::Heal => {
+                    out += "heal = true\n";
+                    continue;
+                }
+                Action::Election { origin } => ("election", origin, 0),
+                Action::Concurrent { origins } => {
+                    out += &format!("concurrent = {:?}\n", origins);
+                    continue;
+                }
+                Action::StepDown => {
+                    out += "stepdown = true\n";
+                    continue;
+                }
+                Action::AssertCoordinator(id) => ("assert_coordinator", id, 0),
+                Action::AssertActive(id) => ("assert_active", id, 0),
+                Action::AssertElections(Cmp::Exactly, count) => ("assert_elections", count, 0),
+                Action::AssertElections(cmp, count) => {
+                    out += &format!("assert_elections = \"{} {}\"\n", cmp, count);
+                    continue;
+                }
+                Action::End => {
+                    out += "end = true\n";
+                    continue;
+                }
+            };
+
+            out += &format!("{} = {}\n", key, id);
+
+            if origin != 0 {
+                out += &format!("origin = {}\n", origin);
+            }
+        }
+
+        out
+    }
+}
+
+impl fmt::Display for SimSeq {
+    /// The sequence as [`SimSeq::from_lines`] reads it.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.expect.coord_id.is_some() || self.expect.elections.is_some() {
+            write!(f, "expect")?;
+
+            if let Some(coord_id) = self.expect.coord_id {
+                write!(f, " coordinator={}", coord_id)?;
+            }
+
+            if let Some(elections) = self.expect.elections {
+                write!(f, " elections={}", elections)?;
+            }
+
+            writeln!(f)?;
+        }
+
+        for action in &self.actions {
+            writeln!(f, "{}", action)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// A random wait between `min` and `max` included, in whole seconds if both
+/// are and in milliseconds otherwise.
+fn random_wait(rng: &mut Rng, (min, max): (Duration, Duration)) -> Duration {
+    if min.subsec_nanos() == 0 && max.subsec_nanos() == 0 {
+        let secs = max.as_secs() - min.as_secs();
+        return min + Duration::from_secs(rng.below(secs + 1));
+    }
+
+    let millis = (max - min).as_millis() as u64;
+    min + Duration::from_millis(rng.below(millis + 1))
+}
+
+/// A scenario's assertion or expectation that didn't hold, told apart from
+/// other errors so that the process can exit with its own code.
+#[derive(Debug)]
+pub struct AssertionFailed(pub String);
+
+impl fmt::Display for AssertionFailed {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl std::error::Error for AssertionFailed {}
+
+/// The result a scenario expects its run to end with. Anything left
+/// unset isn't checked.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Expect {
+    coord_id: Option<usize>,
+    elections: Option<usize>,
+}
+
+impl Expect {
+    /// Add the settings from an `expect` directive, such as
+    /// `expect coordinator=2 elections=3`.
+    fn parse(&mut self, directive: &str) -> Result<()> {
+        for setting in directive.split_whitespace() {
+            let (slot, value) = match setting.split_once('=') {
+                Some(("coordinator", v)) => (&mut self.coord_id, v),
+                Some(("elections", v)) => (&mut self.elections, v),
+                _ => bail!("Unknown expectation {}", setting),
+            };
+
+            match value.parse() {
+                Ok(n) => *slot = Some(n),
+                Err(_) => bail!("Invalid expectation {}", setting),
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Check a finished run against the expectation.
+    pub fn check(&self, summary: &Summary) -> Result<()> {
+        if let Some(coord_id) = self.coord_id {
+            if coord_id != summary.coord_id {
+                return Err(AssertionFailed(format!(
+                    "Expected coordinator {}, but the ring ended with {}",
+                    coord_id, summary.coord_id
+                )).into());
+            }
+        }
+
+        if let Some(elections) = self.elections {
+            if elections != summary.elections {
+                return Err(AssertionFailed(format!(
+                    "Expected {} elections, but the ring held {}",
+                    elections, summary.elections
+                )).into());
+            }
+        }
+
+        Ok(())
+    }
+}
+
+// Ring members run on threads of their own, outside any loom model.
+#[cfg(all(test, not(loom)))]
+mod tests {
+    use std::{env, process};
+
+    use super::*;
+    use crate::event::JsonLines;
+    use crate::golden::Collect;
+    use crate::record::Format;
+
+    /// The events a ring of `size`, configured as `config`, emits following
+    /// the sequence `lines`.
+    fn trace(size: usize, lines: &str, config: SimConfig) -> Vec<String> {
+        let seq = SimSeq::from_lines(Path::new("test"), lines, size).unwrap();
+        let trace = Ring::builder().size(size).config(config).trace(&seq).unwrap();
+        trace.lines().to_vec()
+    }
+
+    #[test]
+    fn backoff_needs_a_growing_timeout() {
+        assert!("1ms,0,3".parse::<Backoff>().is_err());
+        assert!("1ms,1,3".parse::<Backoff>().is_err());
+        assert!("1ms,2,0".parse::<Backoff>().is_err());
+        assert_eq!(
+            "10ms,2,3".parse::<Backoff>().unwrap(),
+            Backoff { base: Duration::from_millis(10), multiplier: 2, attempts: 3 },
+        );
+    }
+
+    #[test]
+    fn backoff_waits_out_a_member_answering_after_the_first_timeout() {
+        // Member 1 takes 15ms over every ping, so misses a 10ms timeout but
+        // makes the 20ms one after it.
+        let config = |backoff: &str| SimConfig {
+            processing: vec![ProcessingDelay::default(), "15ms".parse().unwrap()],
+            backoff: backoff.parse().unwrap(),
+            ..SimConfig::default()
+        };
+
+        let once = trace(3, "election 0\n", config("10ms,2,1"));
+        assert!(once.contains(&"0: 1 is inactive".to_string()));
+
+        let twice = trace(3, "election 0\n", config("10ms,2,2"));
+        assert!(twice.contains(&"0: no answer from 1 within 10ms".to_string()));
+        assert!(twice.contains(&"0: 1 is slow, answering only attempt 2".to_string()));
+        assert!(!twice.contains(&"0: 1 is inactive".to_string()));
+    }
+
+    #[test]
+    fn an_election_from_the_last_member_elects_the_lowest_active_id() {
+        let cases = [
+            ("election 2\n", "sim: 0 won the election started by 2, in epoch 1"),
+            ("kill 0\nelection 2\n", "sim: 1 won the election started by 2, in epoch 2"),
+            ("kill 0\nkill 1\nelection 2\n", "sim: 2 won the election started by 2, in epoch 3"),
+        ];
+        for (lines, won) in cases {
+            let events = trace(3, lines, SimConfig::default());
+            assert!(events.contains(&won.to_string()), "{:?} gave {:?}", lines, events);
+        }
+    }
+
+    #[test]
+    fn a_lost_toggle_is_sent_again() {
+        // With this seed, the first toggle member 0 forwards to member 1 is
+        // lost and the second isn't.
+        let faults = Faults {
+            link_drop_rates: HashMap::from([((0, 1), 0.5)]),
+            ..Faults::default()
+        };
+        let config = SimConfig { seed: 2, faults, ..SimConfig::default() };
+        let events = trace(3, "kill 2\n", config);
+
+        for line in [
+            "0: dropped SimToggle { id: 2, hops: 1, seq: 1 } to 1",
+            "sim: toggle of 2 was not acknowledged",
+            "sim: toggled 2 (attempt 2)",
+            "2: received SimToggle { id: 2, hops: 2, seq: 1 }",
+        ] {
+            assert!(events.contains(&line.to_string()), "no {:?} in {:?}", line, events);
+        }
+    }
+
+    #[test]
+    fn a_rescan_finds_a_member_whose_ping_was_lost() {
+        // With this seed, member 0's first ping to member 1 is lost and the
+        // one it sends on scanning the ring again isn't.
+        let faults = Faults {
+            link_drop_rates: HashMap::from([((0, 1), 0.5)]),
+            ..Faults::default()
+        };
+        let config = SimConfig { seed: 9, faults, send_retries: 1, ..SimConfig::default() };
+        let events = trace(3, "kill 2\nelection 0\n", config);
+
+        let rescan = events.iter().position(|e| e == "0: no active member found, scanning again");
+        let found = events.iter().position(|e| e == "0: 1 is active");
+        assert!(events.contains(&"0: dropped Ping { s_id: 0 } to 1".to_string()));
+        assert!(rescan.is_some() && found > rescan, "{:?}", events);
+    }
+
+    #[test]
+    fn rescans_give_up_on_a_ring_with_no_other_active_member() {
+        let config = SimConfig { send_retries: 2, ..SimConfig::default() };
+        let events = trace(3, "kill 1\nkill 2\nelection 0\n", config);
+        let count = |line: &str| events.iter().filter(|e| *e == line).count();
+
+        assert_eq!(count("0: no active member found, scanning again"), 2);
+        assert_eq!(count("0: 1 is inactive"), 3);
+        assert_eq!(count("0: 2 is inactive"), 3);
+        assert!(events.contains(&"sim: 0 won the election started by 0, in epoch 1".to_string()));
+    }
+
+    #[test]
+    fn only_the_any_policy_reelects_with_the_coordinator_up() {
+        let won = |reelect| {
+            let config = SimConfig { reelect, ..SimConfig::default() };
+            trace(3, "kill 2\nrecover 2\nkill 0\n", config).into_iter()
+                .filter(|e| e.starts_with("sim: ") && e.contains(" won the election "))
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(won(ReelectPolicy::CoordinatorOnly), [
+            "sim: 1 won the election started by 0, in epoch 1",
+        ]);
+        assert_eq!(won(ReelectPolicy::Any), [
+            "sim: 0 won the election started by 0, in epoch 1",
+            "sim: 0 won the election started by 0, in epoch 2",
+            "sim: 1 won the election started by 0, in epoch 3",
+        ]);
+    }
+
+    #[test]
+    fn a_toggle_after_a_crash_or_leave_of_the_same_member_ends() {
+        let events = trace(3, "crash 1\nkill 1\n", SimConfig::default());
+        assert!(events.contains(&"sim: 1 is already down".to_string()), "{:?}", events);
+
+        let events = trace(3, "leave 1\nkill 1\ncrash 1\n", SimConfig::default());
+        let skipped = events.iter().filter(|e| *e == "sim: 1 is not in the ring").count();
+        assert_eq!(skipped, 2, "{:?}", events);
+    }
+
+    #[test]
+    fn walking_down_toggles_each_coordinator_in_turn() {
+        let wait = Duration::from_millis(5);
+        let walk = |size, toggles: &[usize]| {
+            let seq = SimSeq::new(size, toggles.to_vec(), vec![wait; toggles.len()]).unwrap();
+            assert_eq!(SimSeq::walk_down(size, wait).to_string(), seq.to_string());
+        };
+
+        assert_eq!(
+            SimSeq::walk_down(2, wait).to_string(),
+            "wait 5ms\ntoggle 0\nwait 5ms\ntoggle 0\nwait 5ms\ntoggle 1\nwait 5ms\ntoggle 1\n",
+        );
+        walk(2, &[0, 0, 1, 1]);
+        walk(3, &[0, 1, 1, 2, 2, 0, 1, 1]);
+        walk(5, &[0, 1, 2, 3, 3, 4, 4, 2, 3, 3, 1, 2, 2, 0, 1, 1]);
+        assert_eq!(SimSeq::walk_down(1, wait).steps(), 0);
+    }
+
+    #[test]
+    fn a_coordinator_taken_down_is_found_by_its_missed_heartbeats() {
+        let config = SimConfig {
+            heartbeat: Some(Duration::from_millis(10)),
+            ..SimConfig::default()
+        };
+        let events = trace(3, "kill 0\nassert-coordinator 1\n", config);
+
+        let presumed = events.iter().position(|e| e == "sim: coordinator 0 presumed dead");
+        let won = events.iter()
+            .position(|e| e == "sim: 1 won the election started by 1, in epoch 1");
+        assert!(presumed.is_some() && won > presumed, "{:?}", events);
+    }
+
+    #[test]
+    fn members_on_one_event_loop_elect_as_those_on_threads_do() {
+        // Member 5 joins again as a task spawned on the loop while it runs.
+        let steps = "kill 0\nkill 31\nrecover 0\nleave 5\njoin 5\nkill 0\n";
+        let won = |algorithm, runtime| {
+            let lines = Arc::new(Mutex::new(Vec::new()));
+            let events = SharedSink::new(Collect(lines.clone()));
+            let config = SimConfig { algorithm, runtime, events, ..SimConfig::default() };
+
+            let seq = SimSeq::from_lines(Path::new("test"), steps, 32).unwrap();
+            Ring::builder().size(32).config(config).run(Driver::Script(seq)).unwrap();
+
+            let lines = mem::take(&mut *lines.lock().unwrap_or_else(|e| e.into_inner()));
+            lines.into_iter().filter(|e| e.contains(" won the election ")).collect::<Vec<_>>()
+        };
+
+        for algorithm in [
+            Algorithm::Ring, Algorithm::Bully, Algorithm::ChangRoberts,
+            Algorithm::HirschbergSinclair,
+        ] {
+            let on_loop = won(algorithm, MemberRuntime::EventLoop);
+            assert!(!on_loop.is_empty(), "{:?}", algorithm);
+            assert_eq!(on_loop, won(algorithm, MemberRuntime::Threads), "{:?}", algorithm);
+        }
+    }
+
+    #[test]
+    fn the_event_loop_cannot_take_turns_in_virtual_time() {
+        let config = SimConfig { runtime: MemberRuntime::EventLoop, ..SimConfig::default() };
+        let seq = SimSeq::from_lines(Path::new("test"), "kill 0\n", 3).unwrap();
+        assert!(Ring::builder().size(3).config(config).trace(&seq).is_err());
+    }
+
+    #[test]
+    fn a_silent_coordinator_is_presumed_dead_and_replaced() {
+        // Taking the coordinator down elects another at once, so it's kept
+        // up but busy instead: each of the followers' pings holds it up
+        // many heartbeats long.
+        let config = SimConfig {
+            heartbeat: Some(Duration::from_millis(10)),
+            detection: Some("50ms".parse().unwrap()),
+            ..SimConfig::default()
+        };
+        let events = trace(3, "slow 0 100ms\nwait 300ms\n", config);
+
+        let presumed = events.iter().position(|e| e == "sim: coordinator 0 presumed dead");
+        let won = events.iter()
+            .position(|e| e == "sim: 1 won the election started by 1, in epoch 1");
+        assert!(presumed.is_some() && won > presumed, "{:?}", events);
+    }
+
+    #[test]
+    fn an_unbalanced_sequence_file_points_at_the_step_left_open() {
+        let error = |lines| {
+            SimSeq::from_lines(Path::new("seq.txt"), lines, 3).unwrap_err().to_string()
+        };
+
+        assert!(error("2\n1\n3\n").starts_with(
+            "seq.txt ends with a wait but no toggle after it, having 2 waits but 1 toggles, \
+            likely because of line 3:"
+        ));
+        assert!(error("2\n1\n\n# the last step\n5\n").contains("line 5:"));
+        assert_eq!(
+            error("2\n1\n3\nkill 1\n"),
+            "seq.txt, line 4: expected the member to toggle after the wait before",
+        );
+        assert!(SimSeq::from_lines(Path::new("seq.txt"), "2\n1\n3\n2\n", 3).is_ok());
+    }
+
+    #[test]
+    fn a_repeat_is_taken_over_within_bounds() {
+        let read = |lines| SimSeq::from_lines(Path::new("seq.txt"), lines, 3);
+
+        let seq = read("repeat 2 {\n  repeat 3 {\n    kill 0\n  }\n  recover 0\n}\n").unwrap();
+        assert_eq!(seq.actions.len(), 8);
+        assert_eq!(seq.lines, [3, 3, 3, 5, 3, 3, 3, 5]);
+
+        let e = read("kill 0\nrepeat 99999999999 {\n  kill 0\n}\n").unwrap_err();
+        assert_eq!(
+            e.to_string(),
+            "seq.txt, line 2: repeat 99999999999 takes the sequence past 1000000 actions",
+        );
+
+        let e = read("repeat 1000 {\n  repeat 1001 {\n    kill 0\n  }\n}\n").unwrap_err();
+        assert!(e.to_string().starts_with("seq.txt, line 1: repeat 1000 takes"), "{}", e);
+        assert!(read("repeat 99999999999 {\n}\n").unwrap().actions.is_empty());
+    }
+
+    #[test]
+    fn a_binary_recording_replays_as_the_ndjson_one_does() {
+        let runs = [(Format::Ndjson, "ndjson"), (Format::Binary, "bin")].map(|(format, ext)| {
+            let path = env::temp_dir().join(format!("ring-test-{}.{}", process::id(), ext));
+            let recorder = Recorder::create(&path, format).unwrap();
+            let config = SimConfig { recorder: Some(recorder), ..SimConfig::default() };
+            let ran = trace(3, "kill 0\nelection 2\n", config);
+
+            let records = record::read(&path).unwrap();
+            fs::remove_file(&path).unwrap();
+
+            let replayed = Arc::new(Mutex::new(Vec::new()));
+            let events = SharedSink::new(Collect(replayed.clone()));
+            let config = SimConfig { events, ..SimConfig::default() };
+            Ring::builder().size(3).config(config).replay(&records).unwrap();
+
+            let replayed = mem::take(&mut *replayed.lock().unwrap_or_else(|e| e.into_inner()));
+            (ran, records, replayed)
+        });
+
+        let [(ran, records, replayed), binary] = runs;
+        assert!(!records.is_empty() && !replayed.is_empty());
+        assert_eq!((ran, records, replayed), binary);
+        assert_eq!("bincode".parse::<Format>().unwrap(), Format::Binary);
+    }
+
+    #[test]
+    fn a_bully_coordinator_challenged_after_a_higher_one_recovered_gives_way() {
+        let config = SimConfig {
+            algorithm: Algorithm::Bully, verify: true, ..SimConfig::default()
+        };
+        let lines = "kill 0\nkill 4\nelection 2\nrecover 0\nrecover 4\nelection 1\n";
+        let events = trace(5, lines, config);
+        let won = "sim: 4 won the election started by 1".to_string();
+        assert!(events.contains(&won), "{:?}", events);
+    }
+
+    #[test]
+    fn a_ring_result_goes_around_to_every_member() {
+        for termination in [TerminationRule::ReturnToOrigin, TerminationRule::AllVoted] {
+            let config = SimConfig { termination, verify: true, ..SimConfig::default() };
+            let events = trace(4, "election 0\n", config);
+
+            for id in 0..4 {
+                let elected = format!("{}: 0 won the election", id);
+                assert!(events.contains(&elected), "no {:?} in {:?}", elected, events);
+            }
+
+            assert!(events.contains(&"sim: invariants hold".to_string()), "{:?}", events);
+        }
+    }
+
+    #[test]
+    fn a_ring_of_one_elects_its_only_member() {
+        let algorithms = [
+            Algorithm::Ring, Algorithm::Bully,
+            Algorithm::ChangRoberts, Algorithm::HirschbergSinclair,
+        ];
+
+        for algorithm in algorithms {
+            let config = SimConfig { algorithm, verify: true, ..SimConfig::default() };
+            let events = trace(1, "election 0\n", config);
+
+            assert!(events.contains(&"0: sent result to sim".to_string()), "{:?}", events);
+            assert!(events.contains(&"sim: invariants hold".to_string()), "{:?}", events);
+        }
+
+        let seq = SimSeq::from_lines(Path::new("test"), "kill 0\n", 1).unwrap();
+        let e = Ring::builder().size(1).trace(&seq).unwrap_err();
+        assert!(e.to_string().contains("line 1: taking 0 down leaves no active member"), "{}", e);
+    }
+
+    #[test]
+    fn a_restarted_member_learns_the_coordinator_from_its_election() {
+        let config = SimConfig { verify: true, ..SimConfig::default() };
+        let events = trace(3, "kill 0\ncrash 2\nrestart 2\n", config);
+
+        let seeking = "2: knows no coordinator since restarting, starting election".to_string();
+        assert!(events.contains(&seeking), "{:?}", events);
+        assert!(events.contains(&"2: sent result to sim".to_string()), "{:?}", events);
+        assert_eq!(events.iter().filter(|e| *e == "sim: invariants hold").count(), 3);
+    }
+
+    #[test]
+    fn the_ring_starts_with_the_coordinator_its_election_would_pick() {
+        let elected = |config: SimConfig| {
+            trace(4, "kill 3\n", config).into_iter()
+                .filter(|e| e.starts_with("sim: ") && e.contains(" won the election "))
+                .collect::<Vec<_>>()
+        };
+
+        for algorithm in [Algorithm::Bully, Algorithm::ChangRoberts] {
+            let won = elected(SimConfig { algorithm, ..SimConfig::default() });
+            assert_eq!(won, ["sim: 2 won the election started by 0"]);
+        }
+
+        let highest = SimConfig { ranking: Ranking::HighestId, ..SimConfig::default() };
+        assert_eq!(elected(highest), ["sim: 2 won the election started by 0, in epoch 1"]);
+        assert!(elected(SimConfig::default()).is_empty());
+    }
+
+    #[test]
+    fn a_random_sequence_biased_to_the_coordinator_follows_the_election() {
+        let wait = Duration::from_secs(1);
+        let toggles = |config: SimConfig| {
+            let seq = SimSeq::random(&mut Rng::new(1), 4, 3, 1.0, (wait, wait), &config);
+            seq.to_string()
+        };
+        let expected = |toggles: Vec<usize>| {
+            SimSeq::new(4, toggles, vec![wait; 3]).unwrap().to_string()
+        };
+
+        assert_eq!(toggles(SimConfig::default()), expected(vec![0, 1, 2]));
+        let bully = SimConfig { algorithm: Algorithm::Bully, ..SimConfig::default() };
+        assert_eq!(toggles(bully), expected(vec![3, 2, 1]));
+        let highest = SimConfig { ranking: Ranking::HighestId, ..SimConfig::default() };
+        assert_eq!(toggles(highest), expected(vec![3, 2, 1]));
+    }
+
+    #[test]
+    fn a_long_tailed_latency_is_capped() {
+        let mut rng = Rng::new(1);
+        let pareto = Latency::Pareto { scale: Duration::from_millis(1), shape: 0.01 };
+        let normal = Latency::Normal { mean: Duration::MAX, stddev: Duration::MAX };
+
+        for _ in 0..1000 {
+            assert!(pareto.pick(&mut rng) <= MAX_LATENCY);
+            assert!(normal.pick(&mut rng) <= MAX_LATENCY);
+        }
+
+        assert!("pareto:1ms:0.5".parse::<Latency>().is_err());
+        assert!("pareto:1ms:1.5".parse::<Latency>().is_ok());
+    }
+
+    #[test]
+    fn a_deterministic_event_log_is_timed_by_virtual_time() {
+        let logs = [1, 2].map(|run| {
+            let path = env::temp_dir().join(format!("ring-test-{}-{}.jsonl", process::id(), run));
+            let events = SharedSink::new(JsonLines::create(&path).unwrap());
+            let config = SimConfig { deterministic: true, events, ..SimConfig::default() };
+
+            let seq = SimSeq::from_lines(Path::new("test"), "wait 2s\nkill 0\n", 3).unwrap();
+            Ring::builder().size(3).config(config).run(Driver::Script(seq)).unwrap();
+
+            let log = fs::read_to_string(&path).unwrap();
+            fs::remove_file(&path).unwrap();
+            log
+        });
+
+        assert_eq!(logs[0], logs[1]);
+        assert!(logs[0].starts_with("{\"time\":0,"), "{}", logs[0]);
+        assert!(logs[0].lines().last().unwrap().starts_with("{\"time\":200"), "{}", logs[0]);
+    }
+
+    #[test]
+    #[cfg(feature = "status-server")]
+    fn the_status_server_shows_the_ring_before_its_first_step() {
+        use std::io::{Read, Write};
+        use std::net::{TcpListener, TcpStream};
+
+        let addr = TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap().to_string();
+        let board = status::Board::serve(&addr, Arc::default()).unwrap();
+        let config = SimConfig { status: Some(board), ..SimConfig::default() };
+
+        let seq = SimSeq::from_lines(Path::new("test"), "wait 1s\nkill 0\n", 3).unwrap();
+        let ring = std::thread::spawn(move || {
+            Ring::builder().size(3).config(config).run(Driver::Script(seq)).unwrap()
+        });
+
+        std::thread::sleep(Duration::from_millis(300));
+        let mut stream = TcpStream::connect(&addr).unwrap();
+        stream.write_all(b"GET /status HTTP/1.1\r\n\r\n").unwrap();
+        let mut answer = String::new();
+        stream.read_to_string(&mut answer).unwrap();
+
+        assert!(answer.contains("{\"coordinator\":0,\"members\":["), "{}", answer);
+        ring.join().unwrap();
+    }
+}
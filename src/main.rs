@@ -1,471 +1,1522 @@
 use std::collections::HashMap;
-use std::time::Duration;
+use std::env;
+use std::fs;
+use std::io::{self, BufRead};
+use std::path::{Path, PathBuf};
+use std::process;
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
 use anyhow::{bail, Error, Result};
-use crossbeam::channel::{bounded, Receiver, Sender};
-use crossbeam::thread;
-use gag::Redirect;
+use gag::Gag;
+
+use ring::checkpoint::Checkpoint;
+use ring::clock::{MockClock, SharedClock};
+#[cfg(feature = "control-server")]
+use ring::control::Control;
+use ring::event::{self, Event, JsonLines, SharedSink, Source};
+use ring::interrupt::{self, Interrupted};
+use ring::oracle::Oracle;
+use ring::pause::Pause;
+use ring::record::{self, Record, Recorder};
+use ring::rng::Rng;
+use ring::step::Stepper;
+use ring::sync::channel::{self, Sender};
+use ring::toml;
+use ring::trace::Tracer;
+#[cfg(unix)]
+use ring::transport::Unix;
+use ring::transport::{Protocol, Tcp, Transport, Udp};
+use ring::{
+    parse_duration, Action, Algorithm, AssertionFailed, Backoff, Driver, Faults,
+    MessageCounts, Ranking, Ring, RingBuilder, RingMember, SimConfig, SimMsg, SimSeq,
+    TerminationRule, DEFAULT_RING_SIZE, SIM_TIMEOUT,
+};
+
+/// How long `analyze` waits for each ping to be answered.
+const ANALYZE_PING_TIMEOUT: Duration = Duration::from_millis(50);
+
+/// How long members of a ring of nodes wait for each ping to be answered
+/// unless told otherwise, the default being too short to cross a network.
+const NETWORK_PING_TIMEOUT: Duration = Duration::from_millis(50);
+
+/// How many interleavings `explore` runs unless told otherwise.
+const DEFAULT_MAX_SCHEDULES: usize = 1000;
+
+/// What `ring help`, `--help` or `-h` prints.
+const USAGE: &str = "\
+Usage: ring run [--seq INPUTFILE] [--size N] [--config CONFIGFILE] [-o OUTPUTFILE] [OPTIONS]
+       ring validate INPUTFILE [--size N]
+       ring generate [--random STEPS | --toggles STEPS] [--size N] [--seed SEED]
+       [--wait-range MIN,MAX] [-o OUTPUTFILE (.toml for the structured
+       format)] [OPTIONS]
+       ring [INPUTFILE] [-o OUTPUTFILE] [OPTIONS]
+       ring chaos --duration SECS [-o OUTPUTFILE] [OPTIONS]
+       ring --random STEPS [--bias-coordinator P] [--wait-range MIN,MAX] [OPTIONS]
+       ring --restore CHECKPOINT [OPTIONS]
+       ring --once [OPTIONS]
+       ring run-suite DIR [OPTIONS]
+       ring analyze --fail ID [OPTIONS]
+       ring explore [INPUTFILE] [--size N] [--max-schedules N]
+       [--schedule PICKS (to replay a single run)] [OPTIONS]
+       ring replay RECORDING [--list | OPTIONS (those it was recorded with)]
+       ring diff RECORDING RECORDING
+       ring node --id ID --peers ADDR,... [--loss-rate P] [OPTIONS]
+       ring repl [OPTIONS]
+       ring tui [INPUTFILE] [OPTIONS] (for --tui)
+Options: --ring-size N, --verify, --audit, --record RECORDING, --record-format ndjson|binary
+         (or bincode, for binary; binary by default for a .bin RECORDING),
+         --oracle (to check the coordinator after every election),
+         --origin ID, --seed SEED, --size N (for --ring-size),
+         --config CONFIGFILE (TOML, with an option per key, for
+         the command line to override),
+         --out OUTPUTFILE (for -o), --sim-timeout DURATION,
+         --election-timeout DURATION (to report a stuck election),
+         --channel-capacity N,
+         --runtime threads|event-loop (event-loop to run every member
+         as a task on one thread, instead of on a thread each),
+         --drop-rate P, --link-drop-rate FROM-TO=P,... (for messages
+         from member FROM to member TO, instead of --drop-rate),
+         --duplicate-rate P, --reorder-rate P (of holding a message
+         back to go after the next one the same member sends),
+         --max-delay DURATION,
+         --latency uniform:MIN:MAX|normal:MEAN:STDDEV|pareto:SCALE:SHAPE
+         (for every message to take on its way),
+         --link-latency FROM-TO=LATENCY,... (for messages from member
+         FROM to member TO, instead of --latency), --labels NAME,...,
+         --algorithm ring|bully|chang-roberts|hirschberg-sinclair,
+         --priorities P,... (by id, the highest winning, changed as
+         a sequence's priority actions say),
+         --elect priority|lowest-id|highest-id|recency
+         (priority by default, ties going to the lowest id),
+         --termination origin|all-voted,
+         --reelect-on any|coordinator-only,
+         --heartbeat INTERVAL (for the simulator to find the coordinator
+         down by the heartbeats it misses, rather than at once),
+         --detect INTERVAL[,MISSES] (for followers to ping the
+         coordinator and elect another once it misses MISSES, 3 by
+         default, instead of the simulator),
+         --lease DURATION (for the coordinator to renew its lease
+         around the ring a few times within, and followers to elect
+         another once it runs out, instead of the simulator),
+         --pre-vote (for a follower finding the coordinator gone
+         under --detect or --lease to ask the ring around first,
+         electing another only should most of it find it gone too),
+         --ping-timeout DURATION (for the first attempt at a ping,
+         overriding BASE; 1ms by default, and 50ms between nodes),
+         --ping-retries N (before declaring a member down, overriding
+         ATTEMPTS),
+         --cache-successor (to try the member last found active first
+         during an election),
+         --reconfigure (for members to tell the ring of those they
+         find down, for it to route around them until they're back),
+         --respawn (to run a member whose thread fails again with
+         fresh state, instead of giving up on the run),
+         --processing-delay DELAY,... (for each member by id to take
+         over every message, such as 10ms, or MIN..MAX to pick it
+         from at random every time),
+         --timeout-backoff BASE,MULTIPLIER,ATTEMPTS, --send-retries N,
+         --adaptive-timeout K (to wait for a ping to be answered the
+         mean round trip to its target plus K standard deviations,
+         once a few were measured),
+         --wait DURATION (between the default sequence's toggles),
+         --otel-endpoint URL, --checkpoint CHECKPOINT, --mock-clock,
+         --stats-out PATH (to write the run's summary as JSON),
+         --metrics-out PATH (to write a CSV row per election),
+         --diagram PATH (to write a sequence diagram of the messages
+         sent, in PlantUML for a .puml PATH and Mermaid otherwise),
+         --dot PATH (to write a Graphviz graph of the ring and the
+         messages sent), --dot-elections (to write one per election
+         too, next to PATH),
+         --virtual-time, --deterministic,
+         --step (to pause before every message delivered, implying
+         --deterministic),
+         --log-format text|jsonl|tracing, --log-file PATH (for
+         jsonl, instead of stdout; tracing goes to stderr, as do the
+         transports' and servers' warnings),
+         --render ascii (to print the ring after every change to it),
+         --nodes ADDR,... (to drive members run with node),
+         --transport tcp|udp|unix (between nodes, with HOST:PORT
+         addresses or, for unix, socket paths),
+         --status-addr HOST:PORT (with the status-server feature),
+         --metrics-addr HOST:PORT (with the metrics-server feature),
+         --control-port PORT (to drive the ring over HTTP instead of a
+         sequence, with the control-server feature),
+         --ws-port PORT (to stream every event as JSON over WebSocket,
+         with the ws-server feature),
+         --tui (with the tui feature)
+Exits with 2 if a scenario's assertions or expectations fail, 130 if a
+run is ended early with Ctrl-C, and 1 on any other error.\n";
 
-use std::env;
-use std::fs::{self, OpenOptions};
-use std::path::Path;
+fn main() {
+    // Accept cli arguments, as USAGE describes.
+    let args = env::args().skip(1).collect::<Vec<_>>();
 
-const RING_SIZE: usize = 3;
+    // Warnings, and events under --log-format tracing, keep off stdout.
+    let _ = tracing_subscriber::fmt().with_writer(io::stderr).with_target(false).try_init();
 
+    // Asking for help anywhere gets it, ahead of running anything.
+    if args.first().is_some_and(|arg| arg == "help")
+        || args.iter().any(|arg| arg == "--help" || arg == "-h")
+    {
+        print!("{}", USAGE);
+        return;
+    }
 
-fn main() {
-    // Accept cli arguments.
-    // Usage: BIN [INPUTFILE] -o [OUTPUTFILE]
-    let args: Vec<String> = env::args().collect::<Vec<_>>();
-
-    let in_ = match args.get(1) {
-        Some(arg1) => {
-            match arg1 == "-o" {
-                true => None,
-                false => Some(Path::new(arg1))
-            }
+    let res = match args.first().map(String::as_str) {
+        Some("replay") => match args.get(1) {
+            Some(path) => replay(Path::new(path), &args[2..]),
+            None => Err(Error::msg("Usage: replay RECORDING [--list | OPTIONS]")),
+        },
+        Some("diff") => match (args.get(1), args.get(2)) {
+            (Some(a), Some(b)) => diff(Path::new(a), Path::new(b)),
+            _ => Err(Error::msg("Usage: diff RECORDING RECORDING")),
+        },
+        Some("run") => Opts::parse(&args[1..], false).and_then(run_scenario),
+        Some("validate") => Opts::parse(&args[1..], false).and_then(validate),
+        Some("generate") => Opts::parse(&args[1..], false).and_then(generate),
+        Some("chaos") => Opts::parse(&args[1..], true).and_then(chaos),
+        Some("run-suite") => Opts::parse(&args[1..], false).and_then(run_suite),
+        Some("analyze") => Opts::parse(&args[1..], false).and_then(analyze),
+        Some("explore") => Opts::parse(&args[1..], false).and_then(explore),
+        Some("node") => Opts::parse(&args[1..], false).and_then(node),
+        Some("repl") => Opts::parse(&args[1..], false).and_then(repl),
+        // Short for running with --tui.
+        Some("tui") => {
+            let args = args[1..].iter().cloned().chain(["--tui".to_string()]).collect::<Vec<_>>();
+            Opts::parse(&args, false).and_then(run_scenario)
         }
-        None => None,
+        _ => Opts::parse(&args, false).and_then(run_scenario),
     };
 
-    let out = match args.rsplit(|a| a == "-o").next() {
-        Some(argout) => {
-            if argout.is_empty() {
-                None
-            } else {
-                let bin_name = &args[0];
+    if let Err(e) = res {
+        eprintln!("error: {:#}", e);
 
-                if &argout[0] == bin_name {
-                    None
-                } else {
-                    Some(Path::new(&argout[0]))
+        // Scenarios failing their assertions exit apart from other errors,
+        // for scripts running them to tell the two apart, and runs
+        // interrupted exit as a shell has those killed by Ctrl-C.
+        match e.downcast_ref::<AssertionFailed>() {
+            Some(_) => process::exit(2),
+            None if e.is::<Interrupted>() => process::exit(130),
+            None => process::exit(1),
+        }
+    }
+}
+
+/// Options given on the command line.
+struct Opts {
+    /// Arguments that are not options, such as the input file.
+    positional: Vec<String>,
+    /// The input file given with `--seq`.
+    seq: Option<String>,
+    /// The waits and toggles of a sequence given in a configuration file.
+    steps: Option<(Vec<Duration>, Vec<usize>)>,
+    out: Option<String>,
+    origin: Option<usize>,
+    duration: Option<Duration>,
+    /// How many steps to generate a random sequence with, if asked to.
+    random: Option<usize>,
+    /// The chance that a random step toggles the current coordinator.
+    bias: f64,
+    /// The shortest and longest wait before a random step.
+    wait_range: (Duration, Duration),
+    /// How long to wait between the default sequence's toggles.
+    wait: Option<Duration>,
+    /// The ring member to take down for analysis.
+    fail: Option<usize>,
+    /// How many interleavings to explore at most.
+    max_schedules: usize,
+    /// The turns to start exploring from, if not the first ready each.
+    schedule: Vec<usize>,
+    /// How many members the ring has.
+    ring_size: usize,
+    /// The checkpoint to resume a scenario from, if any.
+    restore: Option<String>,
+    /// Hold a single election instead of following a sequence.
+    once: bool,
+    /// The ring member to run as a node.
+    id: Option<usize>,
+    /// Every member's address, by id, as a node sees them.
+    peers: Vec<String>,
+    /// The addresses of the nodes to drive, by id, instead of spawning
+    /// the ring.
+    nodes: Vec<String>,
+    /// How nodes reach one another.
+    protocol: Protocol,
+    /// The chance that a node loses a message over UDP on purpose.
+    loss_rate: f64,
+    /// The port to serve the control API on, driving the ring instead of
+    /// a sequence.
+    #[cfg(feature = "control-server")]
+    control_port: Option<u16>,
+    config: SimConfig,
+}
+
+impl Opts {
+    fn parse(args: &[String], chaos: bool) -> Result<Self> {
+        // The configuration file's options come first, for those on the
+        // command line to override.
+        let (args, steps) = match args.iter().position(|arg| arg == "--config") {
+            Some(i) => {
+                let path = args.get(i + 1)
+                    .ok_or(Error::msg("--config requires a path"))?;
+
+                let file = ConfigFile::load(Path::new(path))?;
+                let rest = args[..i].iter().chain(&args[i + 2..]).cloned();
+                (file.args.into_iter().chain(rest).collect(), file.steps)
+            }
+            None => (args.to_vec(), None),
+        };
+
+        let mut opts = Self {
+            positional: Vec::new(),
+            seq: None,
+            steps,
+            out: None,
+            origin: None,
+            duration: None,
+            random: None,
+            bias: 0.0,
+            wait_range: (Duration::ZERO, Duration::from_secs(1)),
+            wait: None,
+            fail: None,
+            max_schedules: DEFAULT_MAX_SCHEDULES,
+            schedule: Vec::new(),
+            ring_size: DEFAULT_RING_SIZE,
+            restore: None,
+            once: false,
+            id: None,
+            peers: Vec::new(),
+            nodes: Vec::new(),
+            protocol: Protocol::default(),
+            loss_rate: 0.0,
+            #[cfg(feature = "control-server")]
+            control_port: None,
+            config: SimConfig::default(),
+        };
+
+        let mut seed = None;
+        let mut record = None;
+        let mut record_format = None;
+        let mut ping_timeout = None;
+        let mut ping_retries: Option<u32> = None;
+        let mut log_format = event::Format::default();
+        let mut log_file = None;
+        let mut otel_endpoint = None;
+        let mut mock_clock = false;
+        let mut oracle = false;
+        let mut step = false;
+        #[cfg(feature = "ws-server")]
+        let mut ws_port: Option<u16> = None;
+        let config = &mut opts.config;
+        let mut args = args.iter().cloned();
+
+        if chaos {
+            config.verify = true;
+            config.faults = Faults::chaos();
+            config.sim_timeout = Some(SIM_TIMEOUT);
+        }
+
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "-o" | "--out" => opts.out = args.next(),
+                "--config" => bail!("--config can only be given once, on the command line"),
+                "--seq" => {
+                    opts.seq = Some(
+                        args.next()
+                            .ok_or(Error::msg("--seq requires a path"))?
+                    );
+                }
+                "--verify" => config.verify = true,
+                "--audit" => config.audit = true,
+                "--oracle" => oracle = true,
+                "--heartbeat" => {
+                    let interval = args.next().unwrap_or_default();
+                    config.heartbeat = Some(parse_duration(&interval)?);
+                }
+                "--detect" => config.detection = Some(parse_arg(&arg, args.next())?),
+                "--lease" => {
+                    let lease = parse_duration(&args.next().unwrap_or_default())?;
+
+                    if lease.is_zero() {
+                        bail!("--lease needs a nonzero duration");
+                    }
+
+                    config.lease = Some(lease);
+                }
+                "--pre-vote" => config.pre_vote = true,
+                "--origin" => opts.origin = Some(parse_arg(&arg, args.next())?),
+                "--record" => {
+                    record = Some(
+                        args.next()
+                            .ok_or(Error::msg("--record requires a path"))?
+                    );
+                }
+                #[cfg(feature = "status-server")]
+                "--status-addr" => {
+                    let addr = args.next()
+                        .ok_or(Error::msg("--status-addr requires an address"))?;
+
+                    let counts = config.counts.get_or_insert_with(Default::default);
+                    config.status = Some(ring::status::Board::serve(&addr, counts.clone())?);
+                }
+                #[cfg(not(feature = "status-server"))]
+                "--status-addr" => {
+                    bail!("--status-addr requires the status-server feature");
+                }
+                #[cfg(feature = "control-server")]
+                "--control-port" => opts.control_port = Some(parse_arg(&arg, args.next())?),
+                #[cfg(not(feature = "control-server"))]
+                "--control-port" => {
+                    bail!("--control-port requires the control-server feature");
+                }
+                #[cfg(feature = "ws-server")]
+                "--ws-port" => ws_port = Some(parse_arg(&arg, args.next())?),
+                #[cfg(not(feature = "ws-server"))]
+                "--ws-port" => bail!("--ws-port requires the ws-server feature"),
+                #[cfg(feature = "metrics-server")]
+                "--metrics-addr" => {
+                    let addr = args.next()
+                        .ok_or(Error::msg("--metrics-addr requires an address"))?;
+
+                    config.metrics = Some(ring::metrics::Exporter::serve(&addr)?);
+                }
+                #[cfg(not(feature = "metrics-server"))]
+                "--metrics-addr" => {
+                    bail!("--metrics-addr requires the metrics-server feature");
+                }
+                #[cfg(feature = "tui")]
+                "--tui" => config.tui = true,
+                #[cfg(not(feature = "tui"))]
+                "--tui" => bail!("--tui requires the tui feature"),
+                "--checkpoint" => {
+                    let path = args.next()
+                        .ok_or(Error::msg("--checkpoint requires a path"))?;
+
+                    config.checkpoint = Some(PathBuf::from(path));
+                }
+                "--stats-out" => {
+                    let path = args.next()
+                        .ok_or(Error::msg("--stats-out requires a path"))?;
+
+                    config.stats_out = Some(PathBuf::from(path));
+                }
+                "--metrics-out" => {
+                    let path = args.next()
+                        .ok_or(Error::msg("--metrics-out requires a path"))?;
+
+                    config.metrics_out = Some(PathBuf::from(path));
+                }
+                "--diagram" => {
+                    let path = args.next()
+                        .ok_or(Error::msg("--diagram requires a path"))?;
+
+                    config.diagram_out = Some(PathBuf::from(path));
+                }
+                "--dot" => {
+                    let path = args.next()
+                        .ok_or(Error::msg("--dot requires a path"))?;
+
+                    config.dot_out = Some(PathBuf::from(path));
+                }
+                "--dot-elections" => config.dot_elections = true,
+                "--once" => opts.once = true,
+                "--mock-clock" => mock_clock = true,
+                "--virtual-time" => config.virtual_time = true,
+                "--deterministic" => config.deterministic = true,
+                "--step" => step = true,
+                "--restore" => {
+                    opts.restore = Some(
+                        args.next()
+                            .ok_or(Error::msg("--restore requires a path"))?
+                    );
+                }
+                "--otel-endpoint" => {
+                    otel_endpoint = Some(
+                        args.next()
+                            .ok_or(Error::msg("--otel-endpoint requires a URL"))?
+                    );
+                }
+                "--record-format" => {
+                    record_format = Some(parse_arg(&arg, args.next())?);
+                }
+                "--log-format" => log_format = parse_arg(&arg, args.next())?,
+                "--render" => config.render = Some(parse_arg(&arg, args.next())?),
+                "--log-file" => {
+                    log_file = Some(
+                        args.next()
+                            .ok_or(Error::msg("--log-file requires a path"))?
+                    );
+                }
+                "--labels" => {
+                    config.labels = args.next()
+                        .ok_or(Error::msg("--labels requires a list of names"))?
+                        .split(',')
+                        .map(String::from)
+                        .collect();
+                }
+                "--processing-delay" => {
+                    config.processing = args.next()
+                        .ok_or(Error::msg("--processing-delay requires a list of delays"))?
+                        .split(',')
+                        .map(str::parse)
+                        .collect::<Result<_>>()?;
+                }
+                "--priorities" => {
+                    config.priorities = args.next()
+                        .ok_or(Error::msg("--priorities requires a list of numbers"))?
+                        .split(',')
+                        .map(|p| parse_arg(&arg, Some(p.to_string())))
+                        .collect::<Result<_>>()?;
+                }
+                "--termination" => {
+                    config.termination = parse_arg(&arg, args.next())?;
+                }
+                "--algorithm" => config.algorithm = parse_arg(&arg, args.next())?,
+                "--elect" => config.ranking = parse_arg(&arg, args.next())?,
+                "--reelect-on" => {
+                    config.reelect = parse_arg(&arg, args.next())?;
+                }
+                "--ping-timeout" => {
+                    ping_timeout = Some(parse_duration(&args.next().unwrap_or_default())?);
+                }
+                "--ping-retries" => ping_retries = Some(parse_arg(&arg, args.next())?),
+                "--timeout-backoff" => {
+                    let backoff = args.next()
+                        .ok_or(Error::msg("--timeout-backoff requires a backoff"))?;
+
+                    config.backoff = backoff.parse()?;
+                }
+                "--adaptive-timeout" => {
+                    let k: f64 = parse_arg(&arg, args.next())?;
+
+                    if !(k.is_finite() && k >= 0.0) {
+                        bail!("--adaptive-timeout must be a number of standard deviations");
+                    }
+
+                    config.adaptive_timeout = Some(k);
+                }
+                "--cache-successor" => config.cache_successor = true,
+                "--reconfigure" => config.reconfigure = true,
+                "--respawn" => config.respawn = true,
+                "--send-retries" => {
+                    config.send_retries = parse_arg(&arg, args.next())?;
+                }
+                "--seed" => seed = Some(parse_arg(&arg, args.next())?),
+                "--random" | "--toggles" => opts.random = Some(parse_arg(&arg, args.next())?),
+                "--wait" => {
+                    let wait = args.next().unwrap_or_default();
+                    opts.wait = Some(parse_duration(&wait)?);
+                }
+                "--fail" => opts.fail = Some(parse_arg(&arg, args.next())?),
+                "--max-schedules" => {
+                    opts.max_schedules = parse_arg(&arg, args.next())?;
+
+                    if opts.max_schedules == 0 {
+                        bail!("--max-schedules must be at least 1");
+                    }
+                }
+                "--schedule" => {
+                    opts.schedule = args.next()
+                        .ok_or(Error::msg("--schedule requires a list of picks"))?
+                        .split(',')
+                        .map(|p| parse_arg(&arg, Some(p.to_string())))
+                        .collect::<Result<_>>()?;
+                }
+                "--id" => opts.id = Some(parse_arg(&arg, args.next())?),
+                "--peers" => opts.peers = parse_addrs(&arg, args.next())?,
+                "--nodes" => opts.nodes = parse_addrs(&arg, args.next())?,
+                "--transport" => opts.protocol = parse_arg(&arg, args.next())?,
+                "--loss-rate" => {
+                    opts.loss_rate = parse_arg(&arg, args.next())?;
+
+                    if !(0.0..=1.0).contains(&opts.loss_rate) {
+                        bail!("--loss-rate must be between 0 and 1");
+                    }
+                }
+                "--ring-size" | "--size" => {
+                    opts.ring_size = parse_arg(&arg, args.next())?;
+
+                    if opts.ring_size == 0 {
+                        bail!("{} must be at least 1", arg);
+                    }
+                }
+                "--wait-range" => {
+                    let range = args.next().unwrap_or_default();
+
+                    opts.wait_range = match range.split_once(',') {
+                        Some((min, max)) => (parse_duration(min)?, parse_duration(max)?),
+                        None => bail!("--wait-range requires MIN,MAX"),
+                    };
+
+                    if opts.wait_range.0 > opts.wait_range.1 {
+                        bail!("--wait-range must not start after it ends");
+                    }
+                }
+                "--bias-coordinator" => {
+                    opts.bias = parse_arg(&arg, args.next())?;
+
+                    if !(0.0..=1.0).contains(&opts.bias) {
+                        bail!("--bias-coordinator must be between 0 and 1");
+                    }
+                }
+                "--duration" => {
+                    let duration = args.next().unwrap_or_default();
+                    opts.duration = Some(parse_duration(&duration)?);
+                }
+                "--sim-timeout" => {
+                    let timeout = args.next().unwrap_or_default();
+                    config.sim_timeout = Some(parse_duration(&timeout)?);
+                }
+                "--election-timeout" => {
+                    let timeout = args.next().unwrap_or_default();
+                    config.election_timeout = Some(parse_duration(&timeout)?);
+                }
+                "--channel-capacity" => {
+                    let capacity = parse_arg(&arg, args.next())?;
+
+                    if capacity == 0 {
+                        bail!("--channel-capacity must be at least 1");
+                    }
+
+                    config.channel_capacity = Some(capacity);
+                }
+                "--runtime" => config.runtime = parse_arg(&arg, args.next())?,
+                "--drop-rate" => config.faults.drop_rate = parse_rate(&arg, args.next())?,
+                "--duplicate-rate" => {
+                    config.faults.duplicate_rate = parse_rate(&arg, args.next())?;
                 }
+                "--reorder-rate" => config.faults.reorder_rate = parse_rate(&arg, args.next())?,
+                "--max-delay" => {
+                    let delay = args.next().unwrap_or_default();
+                    config.faults.max_delay = parse_duration(&delay)?;
+                }
+                "--latency" => {
+                    let latency = args.next()
+                        .ok_or(Error::msg("--latency requires a distribution"))?;
+
+                    config.faults.latency = Some(latency.parse()?);
+                }
+                "--link-latency" => {
+                    config.faults.link_latencies = parse_links(&arg, args.next(), str::parse)?;
+                }
+                "--link-drop-rate" => {
+                    config.faults.link_drop_rates = parse_links(&arg, args.next(), |p| {
+                        parse_rate(&arg, Some(p.to_string()))
+                    })?;
+                }
+                _ if arg.starts_with("--") => bail!("Unknown option {}", arg),
+                _ => opts.positional.push(arg),
             }
-        },
-        None => None,
-    };
+        }
 
-    run(in_, out);
-}
+        // The nodes make up the whole ring.
+        if !opts.nodes.is_empty() {
+            opts.ring_size = opts.nodes.len();
+        }
 
-fn run(in_: Option<&Path>, out: Option<&Path>) {
-    // If an output file path was specified, redirect stdout to that.
-    let redirect = match out {
-        Some(out_path) => {
-            let log = OpenOptions::new()
-                .truncate(true)
-                .read(true)
-                .create(true)
-                .write(true)
-                .open(out_path)
-                .unwrap();
-
-            Some(Redirect::stdout(log).unwrap())
-        }
-        None => None,
-    };
+        if opts.protocol != Protocol::default()
+            && opts.nodes.is_empty() && opts.peers.is_empty()
+        {
+            bail!("--transport only applies to a ring of nodes");
+        }
+
+        if opts.loss_rate > 0.0 && opts.protocol != Protocol::Udp {
+            bail!("--loss-rate only applies to --transport udp");
+        }
+
+        let networked = !opts.nodes.is_empty() || !opts.peers.is_empty();
 
-    // Create a channel for each ring member.
-    let chans: [(Sender<Msg>, Receiver<Msg>); RING_SIZE] = (0..RING_SIZE)
-        .map(|_| bounded(1))
-        .collect::<Vec<_>>()
-        .try_into()
-        .unwrap();
-
-    // Create a channel for the simulator.
-    let (sim_s, sim_r) = bounded(1);
-
-    let sim_seq = match in_ {
-        Some(path) => SimSeq::from_file(path),
-        None => Ok(SimSeq::default()),
-    }.unwrap();
-
-    // Spawn a thread for each ring member and one for the controller.
-    // Each ring member receives on its channel and sends on the next's.
-    thread::scope(|scope| {
-        for i in 0..RING_SIZE {
-            let ss: HashMap<usize, Sender<Msg>> = (0..RING_SIZE)
-                .map(|j| {
-                    (j.clone(), chans[j].0.clone())
-                })
-                .filter(|(j, _)| *j != i)
-                .collect::<HashMap<_, _>>();
-
-            let sim_s = sim_s.clone();
-            let r = chans[i].1.clone();
-            let next_id = if i == RING_SIZE - 1 { 0 } else { i + 1 };
-
-            scope.spawn(
-                move |_| RingMember::new(i, ss, sim_s, r, next_id, 0).run()
+        match ping_timeout {
+            Some(timeout) => config.backoff.base = timeout,
+            None if networked && config.backoff == Backoff::default() => {
+                config.backoff.base = NETWORK_PING_TIMEOUT;
+            }
+            None => {}
+        }
+
+        // Every retry waits the backoff's multiplier longer than the last.
+        if let Some(retries) = ping_retries {
+            config.backoff.attempts = retries.saturating_add(1);
+        }
+
+        // Datagrams between the simulator and the nodes can be lost too.
+        if opts.protocol == Protocol::Udp && config.sim_timeout.is_none() {
+            config.sim_timeout = Some(SIM_TIMEOUT);
+        }
+
+        if config.dot_elections && config.dot_out.is_none() {
+            bail!("--dot-elections writes its graphs next to --dot's, so it requires --dot");
+        }
+
+        #[cfg(feature = "tui")]
+        if config.tui && opts.out.is_some() {
+            bail!("--tui shows the ring's output itself, so it can't go to -o");
+        }
+
+        // The other algorithms elect the highest id, without votes to rank
+        // or audit.
+        if config.algorithm != Algorithm::Ring
+            && (config.audit || !config.priorities.is_empty()
+                || config.ranking != Ranking::default()
+                || config.termination != TerminationRule::default())
+        {
+            bail!(
+                "--audit, --elect, --priorities and --termination only apply \
+                to --algorithm ring"
             );
         }
 
-        println!("main: election ring created");
-        let (first_s, sim_r) = (chans[0].0.clone(), sim_r.clone());
-        scope.spawn(move |_| sim_election(sim_seq, first_s, sim_r, 0));
-    })
-    .unwrap();
+        if !config.priorities.is_empty() && config.ranking != Ranking::Priority {
+            bail!("--priorities only apply to --elect priority");
+        }
+
+        if config.pre_vote && config.detection.is_none() && config.lease.is_none() {
+            bail!("--pre-vote only applies to --detect or --lease");
+        }
+
+        if let Some(path) = record {
+            let format = match record_format {
+                Some(format) => format,
+                None if path.ends_with(".bin") => record::Format::Binary,
+                None => record::Format::default(),
+            };
+
+            let recorder = Recorder::create(Path::new(&path), format)?;
+            config.recorder = Some(recorder);
+        }
+
+        // Text goes wherever the rest of the output goes, -o included.
+        match (log_format, log_file) {
+            (event::Format::Text, Some(_)) => {
+                bail!("--log-file only applies to --log-format jsonl");
+            }
+            (event::Format::Text, None) => {}
+            (event::Format::Jsonl, Some(path)) => {
+                config.events = SharedSink::new(JsonLines::create(Path::new(&path))?);
+            }
+            (event::Format::Jsonl, None) => {
+                if config.render.is_some() {
+                    bail!("--render pictures the ring among text, so jsonl needs --log-file");
+                }
+
+                config.events = SharedSink::new(JsonLines::stdout());
+            }
+            (event::Format::Tracing, Some(_)) => {
+                bail!("--log-file only applies to --log-format jsonl");
+            }
+            (event::Format::Tracing, None) => {
+                config.events = SharedSink::new(event::Tracing::default());
+            }
+        }
+
+        // Clients are sent events as --log-format jsonl writes them, whatever
+        // the format of those written here.
+        #[cfg(feature = "ws-server")]
+        if let Some(port) = ws_port {
+            let addr = format!("127.0.0.1:{}", port);
+            let broadcast = ring::ws::Broadcast::serve(&addr, config.events.clone())?;
+            config.events = SharedSink::new(broadcast);
+        }
 
-    println!("main: done");
+        if oracle {
+            let oracle = Oracle::new(config.events.clone());
+            config.events = SharedSink::new(oracle.clone());
+            config.oracle = Some(oracle);
+        }
 
-    match redirect {
-        Some(redirect_) => {
-            redirect_.into_inner();
+        // Heartbeats are sent and missed in real time, as members wait on
+        // each other.
+        if mock_clock {
+            if config.heartbeat.is_some() {
+                bail!("--heartbeat needs the real clock, not --mock-clock");
+            }
+
+            if config.detection.is_some() {
+                bail!("--detect needs the real clock, not --mock-clock");
+            }
+
+            if config.lease.is_some() {
+                bail!("--lease needs the real clock, not --mock-clock");
+            }
+
+            config.clock = SharedClock::new(MockClock::new());
+        }
+
+        // Only virtual time stands still while the run is paused, and only
+        // taking turns has one delivery wait at a time.
+        if step {
+            if !opts.nodes.is_empty() || !opts.peers.is_empty() {
+                bail!("--step only applies to a ring spawned here");
+            }
+
+            config.deterministic = true;
+            config.stepper = Some(Stepper::stdin());
+        }
+
+        if config.virtual_time || config.deterministic {
+            if mock_clock {
+                bail!(
+                    "--virtual-time and --deterministic keep their own clock, \
+                    so they can't take --mock-clock"
+                );
+            }
+
+            if !opts.nodes.is_empty() || !opts.peers.is_empty() {
+                bail!("--virtual-time and --deterministic only apply to a ring spawned here");
+            }
+        }
+
+        // Dropped messages can leave the simulator waiting on the ring
+        // forever.
+        if config.faults.lossy() && config.sim_timeout.is_none() {
+            config.sim_timeout = Some(SIM_TIMEOUT);
+        }
+
+        // Pick a seed if none was given, so that the run can be reproduced.
+        config.seed = seed.unwrap_or_else(|| {
+            SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|d| d.as_nanos() as u64)
+                .unwrap_or_default()
+        });
+
+        if let Some(endpoint) = otel_endpoint {
+            config.tracer = Some(Tracer::new(&endpoint, config.seed)?);
+        }
+
+        Ok(opts)
+    }
+
+    /// Finish building `ring` with the configuration, output file and
+    /// nodes given.
+    fn ring(self, ring: RingBuilder) -> RingBuilder {
+        let ring = match self.nodes.is_empty() {
+            true => ring,
+            false => ring.nodes(self.nodes).protocol(self.protocol),
+        };
+
+        let ring = ring.config(self.config);
+
+        match self.out {
+            Some(out) => ring.output(out),
+            None => ring,
+        }
+    }
+
+    /// The input file, given with `--seq` or as the first argument.
+    fn input(&self) -> Option<&Path> {
+        self.positional.first().or(self.seq.as_ref()).map(Path::new)
+    }
+
+    /// Read the simulation sequence from `path`, generate a random one if
+    /// asked to, or use the one from the configuration file or else the
+    /// default one, applying the election origin if one was given.
+    fn sim_seq(&self, path: Option<&Path>) -> Result<SimSeq> {
+        let sim_seq = match (path, self.random, &self.steps) {
+            (Some(path), _, _) => SimSeq::from_file(path, self.ring_size)?,
+            (None, Some(steps), _) => {
+                let mut rng = Rng::new(self.config.seed);
+                SimSeq::random(
+                    &mut rng, self.ring_size, steps, self.bias, self.wait_range, &self.config,
+                )
+            }
+            (None, None, Some((waits, toggles))) => {
+                SimSeq::new(self.ring_size, toggles.clone(), waits.clone())?
+            }
+            (None, None, None) => {
+                SimSeq::walk_down(self.ring_size, self.wait.unwrap_or(Duration::from_secs(1)))
+            },
+        };
+
+        // Start every election from the same member if one was given.
+        match self.origin {
+            Some(id) => sim_seq.with_origin(self.ring_size, id),
+            None => Ok(sim_seq),
         }
-        None => (),
     }
 }
 
-fn sim_election(
-    seq: SimSeq,
-    first_s: Sender<Msg>,
-    sim_r: Receiver<SimMsg>,
-    coord_id: usize,
-) -> Result<()> {
-    let mut coord_id = coord_id;
-
-    for (id, secs) in seq
-        .toggles
-        .iter()
-        // Append a 0 second wait to the wait sequence
-        // to get all the ids in the zip.
-        .zip(seq.waits.iter())
-    {
-        println!("sim: waiting for {:?}s", *secs);
-        std::thread::sleep(std::time::Duration::new(*secs, 0));
-        first_s.send(Msg::SimToggle { id: *id })?;
-        println!("sim: toggled {}", *id);
-        // Wait for toggle confirmation.
-        let msg = sim_r.recv()?;
-
-        if let SimMsg::ConfirmToggle { id, active } = msg {
-            if id == coord_id && !active {
-                first_s.send(Msg::election())?;
-                println!("sim: election started");
-                // Wait for election results.
-                let msg = sim_r.recv()?;
-
-                if let SimMsg::ElectionResult { id } = msg {
-                    coord_id = id;
+/// Options read from a TOML configuration file.
+struct ConfigFile {
+    /// The file's settings, as the command line options they stand for.
+    args: Vec<String>,
+    /// The waits and toggles of the sequence it holds, if any.
+    steps: Option<(Vec<Duration>, Vec<usize>)>,
+}
+
+impl ConfigFile {
+    /// Read the configuration file at `path`.
+    ///
+    /// Every top-level key sets the option of the same name, as in
+    /// `ring-size = 5` or `verify = true`, with arrays for lists. A
+    /// `[sequence]` table holds the simulation sequence, either as its
+    /// `waits` and `toggles` or as the `file` it's in, relative to the
+    /// configuration file.
+    fn load(path: &Path) -> Result<Self> {
+        let contents = match fs::read_to_string(path) {
+            Ok(c) => c,
+            Err(e) => bail!("Error reading {}: {}", path.display(), e),
+        };
+
+        let mut args = Vec::new();
+        let (mut waits, mut toggles) = (None, None);
+
+        for (key, value) in toml::parse(&contents)? {
+            match (key.as_str(), value) {
+                ("sequence.file", toml::Value::String(file)) => {
+                    let file = path.parent().unwrap_or(Path::new("")).join(file);
+                    args.extend(["--seq".to_string(), file.display().to_string()]);
                 }
+                ("sequence.waits", value) => waits = Some(durations(&key, &value)?),
+                ("sequence.toggles", value) => toggles = Some(numbers(&key, &value)?),
+                (key, value) if key.contains('.') || matches!(value, toml::Value::Table(_)) => {
+                    bail!("Unknown setting {} in {}", key, path.display());
+                }
+                (_, toml::Value::Bool(false)) => {}
+                (key, toml::Value::Bool(true)) => args.push(format!("--{}", key)),
+                (key, value) => args.extend([format!("--{}", key), value.to_string()]),
             }
         }
+
+        let steps = match (waits, toggles) {
+            (Some(waits), Some(toggles)) => Some((waits, toggles)),
+            (None, None) => None,
+            _ => bail!("The [sequence] in {} needs both waits and toggles", path.display()),
+        };
+
+        Ok(Self { args, steps })
     }
+}
 
-    first_s.send(Msg::SimEnd)?;
-    println!("sim: sent end signal");
-    println!("sim: done");
-    Ok(())
+/// Read the array of numbers setting `key`.
+fn numbers<T: TryFrom<i64>>(key: &str, value: &toml::Value) -> Result<Vec<T>> {
+    let items = match value {
+        toml::Value::Array(items) => items,
+        _ => bail!("{} must be an array of numbers", key),
+    };
+
+    items.iter()
+        .map(|item| match item {
+            toml::Value::Integer(n) => T::try_from(*n)
+                .map_err(|_| Error::msg(format!("{} must not hold negative numbers", key))),
+            _ => bail!("{} must be an array of numbers", key),
+        })
+        .collect()
 }
 
-#[derive(Debug)]
-struct RingMember {
-    id: usize,
-    sim_active: bool,
-    ss: HashMap<usize, Sender<Msg>>,
-    sim_s: Sender<SimMsg>,
-    r: Receiver<Msg>,
-    next_id: usize,
-    coord_id: usize,
+/// Read the array of durations setting `key`, in seconds if given as
+/// numbers.
+fn durations(key: &str, value: &toml::Value) -> Result<Vec<Duration>> {
+    let items = match value {
+        toml::Value::Array(items) => items,
+        _ => bail!("{} must be an array of durations", key),
+    };
+
+    items.iter()
+        .map(|item| match item {
+            toml::Value::Integer(secs) => u64::try_from(*secs)
+                .map(Duration::from_secs)
+                .map_err(|_| Error::msg(format!("{} must not hold negative durations", key))),
+            toml::Value::String(s) => parse_duration(s),
+            _ => bail!("{} must be an array of durations", key),
+        })
+        .collect()
 }
 
-impl RingMember {
-    fn new(
-        id: usize, ss: HashMap<usize, Sender<Msg>>, sim_s: Sender<SimMsg>,
-        r: Receiver<Msg>, next_id: usize, coord_id: usize
-    ) -> Self {
-        Self { id, sim_active: true, ss, sim_s, r, next_id, coord_id }
+/// Run a single scenario, from the input file or the default sequence.
+fn run_scenario(opts: Opts) -> Result<()> {
+    // Ctrl-C ends the run, summary and all, rather than kill it.
+    interrupt::catch_ctrl_c();
+
+    if opts.once {
+        return once(opts);
     }
 
-    fn run(&mut self) -> Result<()> {
-        loop {
-            let msg = self.r.recv()?;
-            println!("{}: received {:?}", self.id, msg);
-            let res = self.handle_msg(msg)?;
+    #[cfg(feature = "control-server")]
+    if let Some(port) = opts.control_port {
+        return control(opts, port);
+    }
 
-            if !res {
-                break;
-            }
+    if let Some(path) = &opts.restore {
+        if opts.input().is_some() {
+            bail!("--restore resumes the checkpoint's own sequence");
         }
 
-        println!("{}: done", self.id);
-        Ok(())
+        let checkpoint = Checkpoint::load(Path::new(path))?;
+
+        opts.config.events.print(format_args!(
+            "main: resuming from step {} of {}\n",
+            checkpoint.step + 1, checkpoint.seq.actions.len()
+        ));
+
+        let ring = Ring::builder().size(checkpoint.members.len());
+        opts.ring(ring).run(Driver::Resume(checkpoint))?;
+        return Ok(());
     }
 
-    fn handle_msg(&mut self, msg: Msg) -> Result<bool> {
-        match msg {
-            Msg::Ping { s_id } => {
-                if !self.sim_active {
-                    Ok(true)
-                } else {
-                    self.ss.get(&s_id)
-                        .ok_or(Error::msg("Unknown sender"))?
-                        .send(Msg::Pong)?;
+    if opts.random.is_some() {
+        let seed = opts.config.seed;
+        opts.config.events.print(format_args!("main: random sequence with seed {}\n", seed));
+    }
 
-                    println!("{}: answered ping from {}", self.id, s_id);
-                    Ok(true)
-                }
-            }
-            Msg::Pong => Ok(true),
-            Msg::Election { body } => {
-                self.vote(body)?;
-                Ok(true)
-			}
-            Msg::ElectionResult { id } => {
-                self.update_coord(id)?;
-                Ok(true)
-			}
-            Msg::SimToggle { id } => {
-                self.toggle(id)?;
-                Ok(true)
-			}
-            Msg::SimEnd => {
-                self.ss.get(&self.next_id)
-                    .ok_or(Error::msg("Invalid next member id"))?
-                    .send(msg)?;
-
-                println!("{}: will now stop", self.id);
-                println!("{}: sent stop signal forward", self.id);
-                Ok(false)
-			}
-        }
-    }
-
-    /// Vote for the next coordinator or end the election if that has
-    /// already been done.
-    fn vote(&mut self, mut body: [bool; RING_SIZE]) -> Result<()> {
-        if !self.sim_active && body == [false; RING_SIZE] {
-            self.send(Msg::Election { body })?;
-
-            println!(
-                "{}: received election from sim, but am inactive!", self.id
-            );
+    let sim_seq = opts.sim_seq(opts.input())?;
+    let expect = sim_seq.expect;
+    let ring = Ring::builder().size(opts.ring_size);
+    let summary = opts.ring(ring).run(Driver::Script(sim_seq))?;
 
-            println!("{}: forwarding election", self.id);
-            return Ok(());
-        }
+    expect.check(&summary)
+}
 
-        if !body[self.id] {
-            body[self.id] = true;
-            println!("{}: joined election", self.id);
+/// Drive the ring from the control API served on `port` of localhost,
+/// until asked to `POST /end`.
+#[cfg(feature = "control-server")]
+fn control(mut opts: Opts, port: u16) -> Result<()> {
+    if opts.input().is_some() || opts.random.is_some() || opts.restore.is_some() {
+        bail!("--control-port drives the ring instead of a sequence");
+    }
 
-            let msg = Msg::Election { body };
-            let sent = self.send(msg);
+    if !opts.nodes.is_empty() {
+        bail!("--control-port only applies to a ring spawned here");
+    }
 
-            if sent.is_ok() {
-                println!("{}: forwarding election", self.id);
-                return Ok(());
-            }
-        }
+    // Virtual time would stand still between requests.
+    if opts.config.virtual_time || opts.config.deterministic {
+        bail!(
+            "--control-port runs in real time, without --virtual-time, --deterministic or \
+            --step"
+        );
+    }
 
-        // Elect the ring member with the lowest id who voted.
-        let winner_id = body.iter()
-            .enumerate()
-            .filter(|(_, b)| **b)
-            .map(|(i, _)| i)
-            .min()
-            .unwrap();
-
-        self.sim_force_send(Msg::ElectionResult { id: winner_id })?;
-        println!("{}: election ended", self.id);
-        println!("{}: {} won the election", self.id, winner_id);
-        println!("{}: sent result forward", self.id);
-        Ok(())
-    }
-
-    /// Update the coordinator id based on the election results.
-    fn update_coord(&mut self, id: usize) -> Result<()> {
-        if self.coord_id == id {
-            self.sim_s.send(SimMsg::ElectionResult { id })?;
-            println!("{}: sent result to sim", self.id);
-            return Ok(());
-        }
+    let pause = Pause::default();
+    opts.config.pause = Some(pause.clone());
+    let (actions_s, actions_r) = channel::unbounded();
+
+    let ring_size = opts.ring_size;
+
+    let control = Control {
+        ring_size,
+        origin: opts.origin.unwrap_or(0),
+        actions: actions_s,
+        pause,
+        events: opts.config.events.clone(),
+    };
+
+    let addr = format!("127.0.0.1:{}", port);
+    control.serve(&addr)?;
+    opts.config.events.print(format_args!("main: control API at http://{}\n", addr));
 
-        self.sim_force_send(Msg::ElectionResult { id })?;
-        self.coord_id = id;
+    opts.ring(Ring::builder().size(ring_size)).run(Driver::Interactive(actions_r))?;
+    Ok(())
+}
 
-        println!(
-            "{}: {} won the election", self.id, self.coord_id
+/// Check that a sequence file can be run on a ring of the size given,
+/// without running it, printing which members each of its changes leaves
+/// active.
+fn validate(opts: Opts) -> Result<()> {
+    let path = opts.input().ok_or(Error::msg("Usage: validate INPUTFILE"))?;
+    let seq = SimSeq::from_file(path, opts.ring_size)?;
+    let after = seq.dry_run(opts.ring_size)
+        .map_err(|e| Error::msg(format!("{}, {}", path.display(), e)))?;
+
+    for (i, (action, active)) in seq.actions.iter().zip(&after).enumerate() {
+        let changes = matches!(
+            action,
+            Action::Toggle { .. } | Action::Kill { .. } | Action::Recover { .. }
+            | Action::Crash { .. } | Action::Restart(_) | Action::Join { .. }
+            | Action::Leave { .. }
         );
 
-        println!("{}: sent result forward", self.id);
-        Ok(())
+        if !changes {
+            continue;
+        }
+
+        let active = active.iter()
+            .enumerate()
+            .filter(|(_, a)| **a)
+            .map(|(id, _)| id.to_string())
+            .collect::<Vec<_>>();
+
+        println!("validate: {} ({}) leaves {} active", seq.at(i), action, active.join(", "));
     }
 
-    /// Toggle active/inactive if target is self, else send message forward.
-    fn toggle(&mut self, id: usize) -> Result<()> {
-        if id != self.id {
-            self.sim_force_send(Msg::SimToggle { id })?;
-            println!("{}: sent toggle forward", self.id);
-            return Ok(());
+    println!(
+        "validate: {} holds {} steps for a ring of {}",
+        path.display(), seq.steps(), opts.ring_size
+    );
+
+    Ok(())
+}
+
+/// Print a sequence file, random if asked to and the default sequence
+/// otherwise, or write it to the output file, structured if it's named
+/// `.toml`.
+fn generate(opts: Opts) -> Result<()> {
+    let seq = opts.sim_seq(None)?;
+    let structured = opts.out.as_ref().is_some_and(|out| out.ends_with(".toml"));
+
+    match &opts.out {
+        Some(out) => {
+            match structured {
+                true => fs::write(out, seq.to_toml())?,
+                false => fs::write(out, seq.to_string())?,
+            }
+
+            match opts.random {
+                Some(_) => println!(
+                    "generate: wrote {} steps to {} (seed {})",
+                    seq.steps(), out, opts.config.seed
+                ),
+                None => println!("generate: wrote {} steps to {}", seq.steps(), out),
+            }
         }
+        None => print!("{}", seq),
+    }
 
-        self.sim_active ^= true;
+    Ok(())
+}
 
-        self.sim_s.send(SimMsg::ConfirmToggle {
-            id: self.id,
-            active: self.sim_active
-        })?;
+/// Hold a single election on a fresh ring and print its winner.
+fn once(opts: Opts) -> Result<()> {
+    let origin = opts.origin.unwrap_or(0);
 
-        println!("{}: active = {}", self.id, self.sim_active);
-        println!("{}: sent toggle to sim", self.id);
-        Ok(())
+    if origin >= opts.ring_size {
+        bail!("Election origin {} is not a ring member", origin);
     }
 
-    /// Send a message to the first active member ringwise.
-    fn send(&mut self, msg: Msg) -> Result<()> {
-        let range = (0..RING_SIZE)
-            .skip(self.id + 1)
-            .chain(0..self.id);
+    // Leave the winner as the only output, unless it all goes to a file.
+    let gag = match opts.out {
+        Some(_) => None,
+        None => Gag::stdout().ok(),
+    };
 
-        for i in range {
-            // Ping the next member.
-            self.ss.get(&i)
-                .ok_or(Error::msg("Missing sender"))?
-                .send(Msg::Ping { s_id: self.id })?;
+    let ring = Ring::builder().size(opts.ring_size);
+    let res = opts.ring(ring).run(Driver::Once { origin });
 
-            println!("{}: pinged {}", self.id, i);
+    drop(gag);
+    println!("coordinator: {}", res?.coord_id);
+    Ok(())
+}
+
+/// Drive the ring from a prompt, taking an action a line as a sequence file
+/// has one, such as `toggle 2` or `election 1`, and `election` from the
+/// `--origin`, as well as `status`, `pause`, `resume` and `quit`. An empty
+/// line, pressing Enter alone, pauses the ring or resumes it.
+fn repl(mut opts: Opts) -> Result<()> {
+    if !opts.nodes.is_empty() {
+        bail!("repl only applies to a ring spawned here");
+    }
 
-            // Wait again for a response after handling an unexpected message
-            // if one was received.
-            loop {
-                let res = self.r.recv_timeout(Duration::from_millis(1));
+    interrupt::catch_ctrl_c();
 
-                if !res.is_ok() {
-                    println!("{}: {} is inactive", self.id, i);
-                    break;
-                }
+    // Virtual time would stand still at the prompt.
+    if opts.config.virtual_time || opts.config.deterministic {
+        bail!("repl runs in real time, without --virtual-time, --deterministic or --step");
+    }
 
-                if let Ok(Msg::Pong) = res {
-                    self.ss.get(&i).unwrap().send(msg)?;
-                    println!("{}: {} is active, sending message", self.id, i);
-                    return Ok(());
+    let pause = Pause::default();
+    opts.config.pause = Some(pause.clone());
+
+    let events = opts.config.events.clone();
+    let (ring_size, origin) = (opts.ring_size, opts.origin.unwrap_or(0));
+    let (actions_s, actions_r) = channel::unbounded();
+
+    // Read the prompt on a thread of its own, so that the ring can be
+    // paused while the simulator is busy.
+    thread::spawn(move || {
+        eprint!("> ");
+
+        for line in io::stdin().lock().lines() {
+            let line = match line {
+                Ok(line) => line,
+                Err(_) => break,
+            };
+
+            let line = match line.trim() {
+                "" if pause.is_paused() => "resume",
+                "" => "pause",
+                line => line,
+            };
+
+            let action = match line {
+                "quit" => break,
+                "pause" if pause.is_paused() => {
+                    eprintln!("The ring is paused already");
+                    None
                 }
+                "pause" => {
+                    pause.pause();
+                    events.emit(Source::Main, &Event::Paused);
+                    None
+                }
+                "resume" if !pause.is_paused() => {
+                    eprintln!("The ring isn't paused");
+                    None
+                }
+                "resume" => {
+                    pause.resume();
+                    events.emit(Source::Main, &Event::Resumed);
+                    None
+                }
+                "status" => {
+                    for m in pause.statuses() {
+                        match &m.label {
+                            Some(label) => print!("{} ({})", m.id, label),
+                            None => print!("{}", m.id),
+                        }
+
+                        print!(
+                            ": active = {}, coord_id = {}, state = {:?}",
+                            m.active, m.coord_id, m.state
+                        );
+
+                        match m.down.is_empty() {
+                            true => println!(),
+                            false => println!(", down = {:?}", m.down),
+                        }
+                    }
 
-                self.handle_msg(res.unwrap())?;
+                    None
+                }
+                "election" => Some(Ok(Action::Election { origin })),
+                line => Some(line.parse::<Action>().and_then(|action| action.within(ring_size))),
+            };
+
+            // The simulator is gone once the run has ended.
+            match action {
+                Some(Ok(ref action)) if actions_s.send(action.clone()).is_err() => break,
+                Some(Err(e)) => eprintln!("error: {}", e),
+                _ => {}
             }
+
+            eprint!("> ");
         }
 
-        bail!("No response")
+        // The ring can't be told to stop while it holds every message.
+        pause.resume();
+    });
+
+    opts.ring(Ring::builder().size(ring_size)).run(Driver::Interactive(actions_r))?;
+    Ok(())
+}
+
+/// Run a single ring member as its own process, reaching the other
+/// members and answering the simulator over the transport given.
+fn node(opts: Opts) -> Result<()> {
+    let id = opts.id.ok_or(Error::msg("node requires an --id"))?;
+
+    if opts.peers.is_empty() {
+        bail!("node requires the --peers of the whole ring");
     }
 
-    /// Send a message ringwise, starting from the next member,
-    /// Regardless of whether they are simulating inactivity or not.
-    fn sim_force_send(&self, msg: Msg) -> Result<()> {
-        self.ss.get(&self.next_id)
-            .ok_or(Error::msg("Invalid next member id"))?
-            .send(msg)?;
+    if opts.out.is_some() {
+        bail!("A node prints to stdout, so it can't go to -o");
+    }
 
-        Ok(())
+    let addr = opts.peers.get(id).cloned().unwrap_or_default();
+
+    match opts.protocol {
+        Protocol::Tcp => {
+            let (transport, sim_s) = Tcp::bind(id, opts.peers)?;
+            println!("{}: listening at {}", id, addr);
+            run_node(id, transport, sim_s, opts.config)
+        }
+        Protocol::Udp => {
+            // Each node loses messages by a stream of its own, apart from
+            // the one its member makes random choices by.
+            let seed = opts.config.seed.wrapping_sub(id as u64 + 1);
+            let (transport, sim_s) = Udp::bind(id, opts.peers, opts.loss_rate, seed)?;
+            println!("{}: bound to {}", id, addr);
+            run_node(id, transport, sim_s, opts.config)
+        }
+        #[cfg(unix)]
+        Protocol::Unix => {
+            let (transport, sim_s) = Unix::bind(id, opts.peers)?;
+            println!("{}: listening at {}", id, addr);
+            run_node(id, transport, sim_s, opts.config)
+        }
+        #[cfg(not(unix))]
+        Protocol::Unix => bail!("Unix domain sockets need a Unix system"),
     }
 }
 
-#[derive(Debug)]
-enum Msg {
-    Ping { s_id: usize },
-    Pong,
-    Election { body: [bool; RING_SIZE] },
-    ElectionResult { id: usize },
-    SimToggle { id: usize },
-    SimEnd,
+fn run_node(
+    id: usize, transport: impl Transport + 'static, sim_s: Sender<SimMsg>,
+    config: SimConfig,
+) -> Result<()> {
+    RingMember::builder()
+        .id(id)
+        .transport(transport)
+        .sim(sim_s)
+        .config(config)
+        .build()
+        .run()
 }
 
-impl Msg {
-    fn election() -> Self {
-        Self::Election {
-            body: [false; RING_SIZE],
+/// Run the ring under chaos for the given duration.
+fn chaos(opts: Opts) -> Result<()> {
+    let duration = opts.duration
+        .ok_or(Error::msg("chaos requires a --duration"))?;
+
+    interrupt::catch_ctrl_c();
+
+    let seed = opts.config.seed;
+    opts.config.events.print(format_args!("main: chaos with seed {}\n", seed));
+    let ring = Ring::builder().size(opts.ring_size);
+    opts.ring(ring).run(Driver::Chaos { duration })?;
+    Ok(())
+}
+
+/// Run every scenario file in a directory on a fresh ring, with the
+/// invariants verified, and print a table of the results.
+fn run_suite(mut opts: Opts) -> Result<()> {
+    let dir = opts.positional.first()
+        .ok_or(Error::msg("Usage: run-suite DIR"))?;
+
+    let mut paths = fs::read_dir(dir)?
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .filter(|path| path.is_file())
+        .filter(|path| {
+            !path.file_name()
+                .map(|name| name.to_string_lossy().starts_with('.'))
+                .unwrap_or(true)
+        })
+        .collect::<Vec<_>>();
+
+    paths.sort();
+
+    // A stuck scenario should fail instead of holding up the whole suite.
+    opts.config.verify = true;
+    opts.config.sim_timeout.get_or_insert(SIM_TIMEOUT);
+
+    let mut rows = Vec::with_capacity(paths.len());
+
+    for path in &paths {
+        let name = path.file_name().unwrap().to_string_lossy().to_string();
+
+        // Keep the ring's chatter out of the results table.
+        let gag = Gag::stdout().ok();
+
+        let res = opts.sim_seq(Some(path)).and_then(|seq| {
+            let expect = seq.expect;
+            let summary = Ring::builder()
+                .size(opts.ring_size)
+                .config(opts.config.clone())
+                .run(Driver::Script(seq))?;
+            expect.check(&summary)?;
+            Ok(summary)
+        });
+
+        drop(gag);
+        rows.push((name, res));
+    }
+
+    let width = rows.iter()
+        .map(|(name, _)| name.len())
+        .chain(["scenario".len()])
+        .max()
+        .unwrap_or_default();
+
+    println!("{:width$}  coordinator  elections  result", "scenario");
+    let mut failed = 0;
+
+    for (name, res) in &rows {
+        match res {
+            Ok(summary) => println!(
+                "{:width$}  {:<11}  {:<9}  pass",
+                name, summary.coord_id, summary.elections
+            ),
+            Err(e) => {
+                failed += 1;
+                println!("{:width$}  {:<11}  {:<9}  FAIL: {}", name, "-", "-", e);
+            }
         }
     }
-}
 
-#[derive(Debug)]
-enum SimMsg {
-    ConfirmToggle { id: usize, active: bool },
-    ElectionResult { id: usize },
+    if failed > 0 {
+        bail!("{} of {} scenarios failed", failed, rows.len());
+    }
+
+    Ok(())
 }
 
-/// The `SimSeq` type, which specifies a sequence of alternating waits and
-/// toggles to be performed by the simulator.
-///
-/// From start, the simulator should wait for waits[i] seconds and then toggle
-/// process toggles[i] active/inactive, in this order, for i = 0 to i = n,
-/// such that n is the amount of toggles to be performed.
+/// Take a single ring member down and count the messages of each type the
+/// ring delivers until it has converged again.
 ///
-/// Note that the number of toggles must be equal to the number of waits.
-#[derive(Debug)]
-struct SimSeq {
-    /// Ring member ids to be toggles active/inactive.
-    toggles: Vec<usize>,
-    /// Times in seconds to wait for before each toggle.
-    waits: Vec<u64>,
+/// The count doesn't depend on timing, so long as pings are answered within
+/// their timeout, which is why the analysis waits longer for them.
+fn analyze(mut opts: Opts) -> Result<()> {
+    let id = opts.fail.ok_or(Error::msg("analyze requires --fail ID"))?;
+
+    if opts.config.backoff == Backoff::default() {
+        opts.config.backoff.base = ANALYZE_PING_TIMEOUT;
+    }
+
+    let counts = Arc::new(MessageCounts::default());
+    opts.config.counts = Some(counts.clone());
+
+    let seq = SimSeq::new(opts.ring_size, vec![id], vec![Duration::ZERO])?;
+    let seq = match opts.origin {
+        Some(origin) => seq.with_origin(opts.ring_size, origin)?,
+        None => seq,
+    };
+
+    // Keep the ring's chatter out of the breakdown.
+    let gag = Gag::stdout().ok();
+    let res = Ring::builder()
+        .size(opts.ring_size)
+        .config(opts.config)
+        .run(Driver::Script(seq));
+    drop(gag);
+    let summary = res?;
+
+    println!("ring of {}, member {} down", opts.ring_size, id);
+    println!("coordinator {} after {} elections", summary.coord_id, summary.elections);
+    print!("{}", counts);
+    Ok(())
 }
 
-impl Default for SimSeq {
-    /// Default simulation sequence.
-    ///
-    /// Toggle the coordinator inactive until the last ring member
-    /// is the only one left. Then, toggle its predecessor active before
-    /// toggling the coordinator inactive and then active and so on
-    /// until the first ring member is reached.
-    /// Wait 1 second between toggles.
-    ///
-    /// E.g.: The toggle order for 0 1 2 is 0 1 1 2 2 0 1 1.
-    fn default() -> Self {
-        const NUM_TOGGLES: usize = RING_SIZE - 1 + (RING_SIZE - 1) * 3;
-        let mut toggles = Vec::with_capacity(NUM_TOGGLES);
+/// Follow a sequence under every interleaving of messages, or as many as
+/// asked, on a ring keeping virtual time, checking its invariants in each.
+fn explore(opts: Opts) -> Result<()> {
+    if !opts.nodes.is_empty() {
+        bail!("explore only applies to a ring spawned here");
+    }
 
-        for i in 0..RING_SIZE - 1 {
-            toggles.push(i);
-        }
+    let seq = opts.sim_seq(opts.input())?;
+    let ring = Ring::builder().size(opts.ring_size).config(opts.config);
 
-        for i in (0..RING_SIZE - 1).rev() {
-            toggles.push(i);
-            toggles.push(i + 1);
-            toggles.push(i + 1);
-        }
+    // Keep the ring's chatter out of the report, unless it's to show how
+    // the one schedule given goes.
+    let (gag, max_schedules) = match opts.schedule.is_empty() {
+        true => (Gag::stdout().ok(), opts.max_schedules),
+        false => (None, 1),
+    };
+
+    let res = ring.explore(&seq, opts.schedule, max_schedules);
+    drop(gag);
+
+    println!("explore: {}", res?);
+    Ok(())
+}
+
+/// Parse the value given to a command line option.
+fn parse_arg<T: std::str::FromStr>(opt: &str, value: Option<String>) -> Result<T> {
+    match value.map(|v| v.parse()) {
+        Some(Ok(v)) => Ok(v),
+        _ => bail!("{} requires a valid value", opt),
+    }
+}
+
+/// Parse the probability option `opt` gives, which must be between 0 and 1.
+fn parse_rate(opt: &str, value: Option<String>) -> Result<f64> {
+    let rate: f64 = parse_arg(opt, value)?;
+
+    if !(0.0..=1.0).contains(&rate) {
+        bail!("{} must be between 0 and 1", opt);
+    }
+
+    Ok(rate)
+}
+
+/// Parse a setting per link, such as `0-1=0.5,1-2=0.1` for messages from
+/// member 0 to member 1 and from 1 to 2, each as `parse` does.
+fn parse_links<T>(
+    opt: &str, value: Option<String>, parse: impl Fn(&str) -> Result<T>,
+) -> Result<HashMap<(usize, usize), T>> {
+    let value = value.ok_or(Error::msg(format!("{} requires a list of links", opt)))?;
+    let usage = || Error::msg(format!("{} requires FROM-TO=VALUE,...", opt));
+    let mut links = HashMap::new();
+
+    for link in value.split(',') {
+        let (ids, v) = link.split_once('=').ok_or_else(usage)?;
+        let (from, to) = ids.split_once('-').ok_or_else(usage)?;
+        let from = parse_arg(opt, Some(from.to_string()))?;
+        let to = parse_arg(opt, Some(to.to_string()))?;
+        links.insert((from, to), parse(v)?);
+    }
+
+    Ok(links)
+}
 
-        SimSeq::new(toggles, [1; NUM_TOGGLES].to_vec()).unwrap()
+/// Parse a list of addresses, such as `127.0.0.1:7000,127.0.0.1:7001`.
+fn parse_addrs(opt: &str, value: Option<String>) -> Result<Vec<String>> {
+    match value {
+        Some(v) if !v.is_empty() => Ok(v.split(',').map(String::from).collect()),
+        _ => bail!("{} requires a list of addresses", opt),
     }
 }
 
-impl SimSeq {
-    fn new(toggles: Vec<usize>, waits: Vec<u64>) -> Result<Self> {
-        if toggles.len() != waits.len(){
-            bail!("Number of toggles must be equal to the number of waits");
+/// Replay a recording with the options it was recorded with, or print the
+/// messages delivered in it, in the order they were received, if `--list`.
+fn replay(path: &Path, args: &[String]) -> Result<()> {
+    let records = record::read(path)?;
+
+    if args == ["--list"] {
+        for record in records {
+            println!("{}", record);
         }
 
-        Ok(Self { toggles, waits })
+        return Ok(());
     }
 
-    /// Read the simulation sequence from a file
-    /// Waits on odd lines, and toggles on evens.
-    fn from_file(path: &std::path::Path) -> Result<Self> {
-        let contents;
-        let mut toggles = Vec::new();
-        let mut waits = Vec::new();
+    let opts = Opts::parse(args, false)?;
+    Ring::builder().size(opts.ring_size).config(opts.config).replay(&records)
+}
+
+/// Report the first message at which two recordings diverge, and how many
+/// messages of each type they differ by.
+fn diff(a: &Path, b: &Path) -> Result<()> {
+    let (a, b) = (record::read(a)?, record::read(b)?);
 
-        match fs::read_to_string(path) {
-            Ok(c) => contents = c,
-            Err(e) => bail!("Error reading file: {}", e),
+    let divergence = (0..a.len().max(b.len()))
+        .find(|i| a.get(*i) != b.get(*i));
+
+    let i = match divergence {
+        Some(i) => i,
+        None => {
+            println!("diff: recordings are identical ({} messages)", a.len());
+            return Ok(());
         }
+    };
 
-        for (i, char) in contents.chars().enumerate() {
-            // Skip newlines or whitespaces
-            if char == ' ' || char == '\n' {
-                continue;
-            }
-            // spaces increase i value, so waits are in indexes divisible by 4
-            // which are represented as odd lines
-            else if char.is_numeric() && i % 4 == 0 {
-                waits.push(char.to_digit(10).unwrap() as u64);
-            }
-            // toggles are in indexes divisible by 2 and not 4
-            // which are represented as even lines
-            else if char.is_numeric() && i % 2 == 0 {
-                toggles.push(char.to_digit(10).unwrap() as usize);
+    let show = |record: Option<&Record>| match record {
+        Some(record) => record.to_string(),
+        None => "(end of recording)".to_string(),
+    };
+
+    println!("diff: recordings diverge at message {}", i + 1);
+    println!("  a: {}", show(a.get(i)));
+    println!("  b: {}", show(b.get(i)));
+
+    let mut counts: Vec<(String, [usize; 2])> = Vec::new();
+
+    for (side, records) in [&a, &b].into_iter().enumerate() {
+        for record in records {
+            let kind = record.kind();
+
+            match counts.iter_mut().find(|(k, _)| *k == kind) {
+                Some((_, n)) => n[side] += 1,
+                None => {
+                    let mut n = [0; 2];
+                    n[side] = 1;
+                    counts.push((kind, n));
+                }
             }
         }
+    }
+
+    counts.sort();
+    let width = counts.iter().map(|(k, _)| k.len()).max().unwrap_or_default();
+    println!("{:width$}  {:>5}  {:>5}  {:>6}", "type", "a", "b", "delta");
 
-        Ok(SimSeq::new(toggles, waits).unwrap())
+    for (kind, [n_a, n_b]) in counts {
+        let delta = n_b as i64 - n_a as i64;
+        println!("{:width$}  {:>5}  {:>5}  {:>+6}", kind, n_a, n_b, delta);
     }
+
+    bail!("Recordings differ");
 }
+
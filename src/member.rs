@@ -0,0 +1,1512 @@
+//! A ring member's side of the election protocols, as a state machine:
+//! where the member stands, and what it does with every message it's
+//! handed.
+//!
+//! The machine does nothing of itself. It sends messages, reports to the
+//! simulator and emits events through the [`Effects`] it's stepped with,
+//! and asks them which member around the ring is the next active one,
+//! which is all it needs to know of the ring. A
+//! [`RingMember`](crate::RingMember) steps its machine over its transport
+//! in real or virtual time, finding active members by pinging them and
+//! stepping the machine through whatever else arrives meanwhile. An
+//! [`Outbox`] steps it over a ring whose members are up as it says and
+//! collects everything it does, for a test to hand a member any sequence
+//! of messages and check what came of each.
+
+use std::future::Future;
+use std::pin::Pin;
+
+use anyhow::{bail, Result};
+
+use crate::event::Event;
+use crate::executor;
+use crate::{
+    alive_micros, rank, Algorithm, MemberStatus, Msg, ProcessingDelay, SimConfig,
+    SimMsg, State, TerminationRule,
+};
+
+/// What an [`Effects`] takes its time over, and a machine's step through
+/// a message waits on: boxed, for effects to be stepped with as a trait
+/// object and for a step to come back into another through them.
+pub type Waiting<'a, T> = Pin<Box<dyn Future<Output = Result<T>> + 'a>>;
+
+/// What a member's machine acts through.
+pub trait Effects {
+    /// Send `msg` to ring member `id`.
+    fn send_to(&mut self, id: usize, msg: Msg) -> Waiting<'_, ()>;
+
+    /// Send `msg` to the simulator.
+    fn send_sim(&mut self, msg: SimMsg) -> Result<()>;
+
+    fn emit(&mut self, event: Event);
+
+    /// Find the first active member after `core`'s clockwise, or before it
+    /// unless `clockwise`, stepping `core` through any other message
+    /// received while looking.
+    fn active_toward<'a>(&'a mut self, core: &'a mut Core, clockwise: bool) -> Waiting<'a, usize>;
+
+    /// Wait out the backoff for `count` answers, stepping `core` through
+    /// whatever else is received meanwhile, and return how many came.
+    /// Waiting stops early should the election `core` takes part in end.
+    fn await_answers<'a>(&'a mut self, core: &'a mut Core, count: usize) -> Waiting<'a, usize>;
+
+    /// Whether ring member `id` answers a ping, stepping `core` through any
+    /// other message received while waiting.
+    fn reaches<'a>(&'a mut self, core: &'a mut Core, id: usize) -> Waiting<'a, bool>;
+
+    /// When the member is seen alive, in microseconds.
+    fn alive_micros(&self) -> u64;
+}
+
+/// Where a ring member stands.
+#[derive(Debug, Clone)]
+pub struct Core {
+    id: usize,
+    ring_size: usize,
+    pub(crate) sim_active: bool,
+    next_id: usize,
+    coord_id: usize,
+    pub(crate) state: State,
+    config: SimConfig,
+    /// The last toggle applied to this member, so that resent ones aren't
+    /// applied twice.
+    last_toggle: Option<usize>,
+    /// When this member was last confirmed alive, in microseconds since
+    /// the Unix epoch.
+    pub(crate) last_alive: u64,
+    /// Whether the simulator is waiting on this member for the result of
+    /// the bully election it was asked to start.
+    owes_result: bool,
+    /// The members that challenged this one while it announces itself,
+    /// this one included if the simulator asked it to start an election.
+    late: Vec<usize>,
+    /// Where this member stands in the latest Hirschberg–Sinclair election
+    /// it heard of, if any.
+    candidacy: Option<Candidacy>,
+    /// Whether the simulator told this member to stop.
+    pub(crate) ended: bool,
+    /// How many elections this member took part in, telling each apart.
+    pub(crate) joined: usize,
+    /// Which members this member routes around, having heard they are
+    /// down, by member.
+    down: Vec<bool>,
+    /// Which members left the ring, so are passed by, by member.
+    gone: Vec<bool>,
+    /// How long this member takes over each message from another.
+    pub(crate) processing: ProcessingDelay,
+    /// The latest epoch this member heard an election or its result
+    /// carry. Kept through restarts, as if on disk, lest a restarted
+    /// member take a stale result for a current one.
+    pub(crate) epoch: usize,
+    /// How many renewals of the coordinator's lease this member heard.
+    pub(crate) renewals: usize,
+    /// The epoch and initiator of the latest ring election this member
+    /// voted in, for another started alongside it to be absorbed.
+    voted: Option<(usize, usize)>,
+    /// Whether this member is handing over as coordinator, so stands in no
+    /// election until the one it started to do so ends.
+    stepping_down: bool,
+    /// The epoch and initiator of the ring election this member ended, for
+    /// it to know the result once it comes back around.
+    closing: Option<(usize, usize)>,
+    /// Whether this member restarted and heard of no coordinator since.
+    forgotten: bool,
+}
+
+/// Where a ring member stands in a Hirschberg–Sinclair election.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Candidacy {
+    /// Which of the simulator's elections this is, numbered from 1, so that
+    /// messages still in flight from an earlier one are told apart.
+    election: usize,
+    initiator: usize,
+    stage: Stage,
+}
+
+/// The stages a Hirschberg–Sinclair candidate goes through.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Stage {
+    /// Probing `2^phase` members out each way, with `replies` of the two
+    /// replies back so far.
+    Probing { phase: u32, replies: u8 },
+    /// Beaten by a higher id, and only relaying other candidates' messages.
+    Relaying,
+    /// Won, its probe having made it around the ring.
+    Won,
+}
+
+/// A Hirschberg–Sinclair probe as it arrived, as in [`Msg::Probe`].
+#[derive(Debug, Clone, Copy)]
+struct Probe {
+    id: usize,
+    election: usize,
+    phase: u32,
+    hops: usize,
+    clockwise: bool,
+    initiator: usize,
+}
+
+/// Something a member did, as an [`Outbox`] collects it.
+#[derive(Debug, PartialEq)]
+pub enum Outgoing {
+    Sent { to: usize, msg: Msg },
+    SentSim(SimMsg),
+    Emitted(Event),
+}
+
+/// Effects collecting what a member does, over a ring whose members are
+/// active as `active` says. No answer comes while the member waits on a
+/// bully election, but one can be stepped in afterwards like any message.
+#[derive(Debug, Default)]
+pub struct Outbox {
+    /// Whether each member is active, by member.
+    pub active: Vec<bool>,
+    /// Everything the member did since last taken, in order.
+    pub out: Vec<Outgoing>,
+}
+
+impl Outbox {
+    pub fn new(active: Vec<bool>) -> Self {
+        Self { active, out: Vec::new() }
+    }
+
+    /// Step `core` through `msg`, returning everything it did.
+    pub fn step(&mut self, core: &mut Core, msg: Msg) -> Result<Vec<Outgoing>> {
+        executor::block_on(core.step(self, msg))?;
+        Ok(std::mem::take(&mut self.out))
+    }
+}
+
+impl Effects for Outbox {
+    fn send_to(&mut self, id: usize, msg: Msg) -> Waiting<'_, ()> {
+        self.out.push(Outgoing::Sent { to: id, msg });
+        Box::pin(async { Ok(()) })
+    }
+
+    fn send_sim(&mut self, msg: SimMsg) -> Result<()> {
+        self.out.push(Outgoing::SentSim(msg));
+        Ok(())
+    }
+
+    fn emit(&mut self, event: Event) {
+        self.out.push(Outgoing::Emitted(event));
+    }
+
+    fn active_toward<'a>(&'a mut self, core: &'a mut Core, clockwise: bool) -> Waiting<'a, usize> {
+        let mut range = core.ring().collect::<Vec<_>>();
+
+        if !clockwise {
+            range.reverse();
+        }
+
+        let found = range.into_iter().find(|i| self.active.get(*i) == Some(&true));
+
+        Box::pin(async move {
+            match found {
+                Some(id) => Ok(id),
+                None => bail!("No response"),
+            }
+        })
+    }
+
+    fn await_answers<'a>(&'a mut self, _: &'a mut Core, _: usize) -> Waiting<'a, usize> {
+        Box::pin(async { Ok(0) })
+    }
+
+    fn reaches<'a>(&'a mut self, _: &'a mut Core, id: usize) -> Waiting<'a, bool> {
+        let reached = self.active.get(id) == Some(&true);
+        Box::pin(async move { Ok(reached) })
+    }
+
+    fn alive_micros(&self) -> u64 {
+        0
+    }
+}
+
+impl Core {
+    /// Member `id` of a ring of `ring_size`, following the member an
+    /// election among them all would elect as the coordinator.
+    pub fn new(id: usize, ring_size: usize, config: SimConfig) -> Self {
+        let coord_id = config.elects(&vec![true; ring_size]).unwrap_or_default();
+
+        let state = if id == coord_id {
+            State::Coordinator
+        } else {
+            State::Follower
+        };
+
+        let processing = config.processing.get(id).copied().unwrap_or_default();
+
+        Self {
+            id, ring_size, sim_active: true, next_id: (id + 1) % ring_size,
+            coord_id, state, last_toggle: None, last_alive: alive_micros(&config),
+            config, owes_result: false, late: Vec::new(), candidacy: None, ended: false,
+            joined: 0, down: vec![false; ring_size], gone: vec![false; ring_size], processing,
+            epoch: 0, voted: None, renewals: 0, stepping_down: false, closing: None,
+            forgotten: false,
+        }
+    }
+
+    pub fn id(&self) -> usize {
+        self.id
+    }
+
+    /// The member's view of itself, as reported to the simulator.
+    pub fn status(&self) -> MemberStatus {
+        MemberStatus {
+            id: self.id,
+            label: self.config.labels.get(self.id).cloned(),
+            active: self.sim_active,
+            coord_id: self.coord_id,
+            state: self.state,
+            down: (0..self.ring_size).filter(|i| self.down[*i]).collect(),
+        }
+    }
+
+    /// The other members, clockwise from the next, but for those this
+    /// member routes around and those that left the ring.
+    pub(crate) fn ring(&self) -> impl Iterator<Item = usize> + '_ {
+        (self.id + 1..self.ring_size)
+            .chain(0..self.id)
+            .filter(|i| !self.down[*i] && !self.gone[*i])
+    }
+
+    pub(crate) fn coord_id(&self) -> usize {
+        self.coord_id
+    }
+
+    /// Whether the member watches the coordinator, as the active followers
+    /// of another member do should members find failures themselves or
+    /// hold the coordinator to a lease.
+    pub(crate) fn watches(&self) -> bool {
+        (self.config.detection.is_some() || self.config.lease.is_some())
+            && self.sim_active
+            && self.state == State::Follower
+            && self.coord_id != self.id
+    }
+
+    /// Start an election of the member's own, as if the simulator had
+    /// asked it to, numbered after the latest the member heard of.
+    pub(crate) async fn start_election(&mut self, effects: &mut dyn Effects) -> Result<()> {
+        let election = self.epoch.max(self.candidacy.map_or(0, |c| c.election)) + 1;
+        let msg = Msg::opening(self.config.algorithm, self.id, self.ring_size, election);
+        self.step(effects, msg).await?;
+        Ok(())
+    }
+
+    /// Start an election of the member's own, the coordinator seeming gone,
+    /// or first hold a pre-vote around the ring on whether it is, if asked
+    /// to, for the election to only start should most members agree.
+    pub(crate) async fn suspect(&mut self, effects: &mut dyn Effects) -> Result<()> {
+        if !self.config.pre_vote {
+            return self.start_election(effects).await;
+        }
+
+        effects.emit(Event::PreVoting(self.coord_id));
+        let msg = Msg::PreVote { origin: self.id, coord_id: self.coord_id, agreed: 1, hops: 0 };
+
+        // With no other member to ask, this one is left to decide alone.
+        match self.send(effects, msg).await {
+            Ok(()) => Ok(()),
+            Err(_) => self.start_election(effects).await,
+        }
+    }
+
+    /// Handle `msg`, returning whether to go on handling messages, unless
+    /// told to stop.
+    pub fn step<'a>(&'a mut self, effects: &'a mut dyn Effects, msg: Msg) -> Waiting<'a, bool> {
+        Box::pin(async move {
+            match msg {
+                Msg::Ping { s_id } => {
+                    if !self.sim_active {
+                        Ok(true)
+                    } else {
+                        effects.send_to(s_id, Msg::Pong).await?;
+                        self.last_alive = effects.alive_micros();
+                        effects.emit(Event::AnsweredPing(s_id));
+                        Ok(true)
+                    }
+                }
+                Msg::Pong | Msg::Answer => Ok(true),
+                Msg::Election { body, keys, initiator, epoch } => {
+                    self.vote(effects, body, keys, initiator, epoch).await?;
+                    Ok(true)
+                }
+                Msg::ElectionResult { id, initiator, votes, epoch } => {
+                    self.update_coord(effects, id, initiator, votes, epoch).await?;
+                    Ok(true)
+                }
+                Msg::Candidate { id, initiator } => {
+                    self.nominate(effects, id, initiator).await?;
+                    Ok(true)
+                }
+                Msg::Probe { id, election, phase, hops, clockwise, initiator } => {
+                    let probe = Probe { id, election, phase, hops, clockwise, initiator };
+                    self.probed(effects, probe).await?;
+                    Ok(true)
+                }
+                Msg::Reply { id, election, clockwise } => {
+                    self.replied(effects, id, election, clockwise).await?;
+                    Ok(true)
+                }
+                Msg::BullyElection { s_id, initiator } => {
+                    self.challenged(effects, s_id, initiator).await?;
+                    Ok(true)
+                }
+                Msg::Coordinator { id, initiator } => {
+                    self.announced(effects, id, initiator).await?;
+                    Ok(true)
+                }
+                Msg::MemberDown { id, origin, hops } => {
+                    self.member_down(effects, id, origin, hops).await?;
+                    Ok(true)
+                }
+                Msg::MemberUp { id, hops } => {
+                    self.member_up(effects, id, hops).await?;
+                    Ok(true)
+                }
+                Msg::LeaseRenewal { id, hops } => {
+                    self.renewed(effects, id, hops).await?;
+                    Ok(true)
+                }
+                Msg::PreVote { origin, coord_id, agreed, hops } => {
+                    self.pre_voted(effects, origin, coord_id, agreed, hops).await?;
+                    Ok(true)
+                }
+                Msg::SimToggle { id, hops, seq } => {
+                    self.toggle(effects, id, hops, seq).await?;
+                    Ok(true)
+                }
+                Msg::SimStatus => {
+                    effects.send_sim(SimMsg::Status(self.status()))?;
+
+                    effects.emit(Event::SentStatus);
+                    Ok(true)
+                }
+                Msg::SimEnd => {
+                    effects.emit(Event::Stopping);
+                    self.ended = true;
+                    Ok(false)
+                }
+                Msg::SimLeft { id } => {
+                    self.membership(id, false);
+                    effects.emit(Event::MemberLeft(id));
+                    Ok(true)
+                }
+                Msg::SimJoined { id } => {
+                    self.membership(id, true);
+                    effects.emit(Event::MemberJoined(id));
+                    Ok(true)
+                }
+                Msg::SimSlow { delay } => {
+                    self.processing = delay;
+                    effects.emit(Event::Slowed(delay));
+                    Ok(true)
+                }
+                Msg::SimPriority { priority } => {
+                    self.config.set_priority(self.id, priority);
+                    effects.emit(Event::Reprioritized(priority));
+                    Ok(true)
+                }
+                Msg::SimStepDown { epoch } => {
+                    self.stepping_down = true;
+                    self.state = State::Follower;
+                    effects.emit(Event::HandingOver);
+
+                    let msg = Msg::opening(self.config.algorithm, self.id, self.ring_size, epoch);
+                    self.step(effects, msg).await
+                }
+                Msg::SimRestart => {
+                    self.restart();
+                    effects.emit(Event::Restarted);
+                    Ok(true)
+                }
+            }
+        })
+    }
+
+    /// Vote for the next coordinator or end the election if that has
+    /// already been done.
+    ///
+    /// `initiator` is the member that started the election, carried along
+    /// with it and into its result, as is its `epoch`.
+    async fn vote(
+        &mut self,
+        effects: &mut dyn Effects,
+        mut body: Vec<bool>,
+        mut keys: Vec<u64>,
+        initiator: usize,
+        epoch: usize,
+    ) -> Result<()> {
+        if body.len() != self.ring_size || keys.len() != self.ring_size {
+            effects.emit(Event::MisfitElection { votes: body.len(), keys: keys.len() });
+            return Ok(());
+        }
+
+        self.epoch = self.epoch.max(epoch);
+
+        if initiator == self.id && !body.contains(&true) && !self.stepping_down {
+            self.detected_down(effects);
+        }
+
+        if !self.sim_active && !body.contains(&true) {
+            let sent = self.send(effects, Msg::Election { body, keys, initiator, epoch }).await;
+
+            effects.emit(Event::InactiveForElection);
+
+            if sent.is_ok() {
+                effects.emit(Event::ForwardingElection);
+            } else {
+                effects.emit(Event::NoneToForwardTo);
+            }
+
+            return Ok(());
+        }
+
+        // A member stepping down votes in no election, but passes each on.
+        if self.stepping_down {
+            match self.send(effects, Msg::Election { body, keys, initiator, epoch }).await {
+                Ok(()) => effects.emit(Event::ForwardingElection),
+                Err(_) => effects.emit(Event::NoneToForwardTo),
+            }
+
+            return Ok(());
+        }
+
+        // Of two elections numbered alike, one started by a lower id is
+        // absorbed by any member that voted in the other, and one this
+        // member is asked to start once it voted in another by that one,
+        // which passed it by already. Either way the other goes on to end.
+        if let Some((voted_epoch, voted_for)) = self.voted {
+            let opening = initiator == self.id && !body.contains(&true);
+
+            if !body[self.id] && voted_epoch == epoch && voted_for != initiator
+                && (opening || voted_for > initiator)
+            {
+                effects.emit(Event::ElectionAbsorbed { initiator, epoch, into: voted_for });
+                return Ok(());
+            }
+        }
+
+        if !body[self.id] {
+            body[self.id] = true;
+            self.voted = self.voted.max(Some((epoch, initiator)));
+            keys[self.id] = self.config.ranking_key(self.id).unwrap_or(self.last_alive);
+            self.join(effects);
+
+            if self.config.verify && !self.sim_active {
+                effects.send_sim(SimMsg::Violation {
+                    reason: format!(
+                        "inactive member {} voted in election {:?}",
+                        self.id, body
+                    ),
+                })?;
+            }
+
+            match self.next_active(effects).await {
+                Ok(next) if self.config.termination == TerminationRule::AllVoted
+                    && body[next] =>
+                {
+                    effects.emit(Event::AlreadyVoted(next));
+                }
+                Ok(next) => {
+                    let msg = Msg::Election {
+                        body: body.clone(), keys: keys.clone(), initiator, epoch,
+                    };
+
+                    if effects.send_to(next, msg).await.is_ok() {
+                        effects.emit(Event::ForwardingElection);
+                        return Ok(());
+                    }
+                }
+                Err(_) => (),
+            }
+        }
+
+        let winner_id = rank(&body, &keys).unwrap();
+
+        let votes = self.config.audit.then_some(body);
+        let msg = Msg::ElectionResult { id: winner_id, initiator, votes, epoch };
+        self.closing = Some((epoch, initiator));
+        self.sim_force_send(effects, msg).await?;
+        effects.emit(Event::ElectionEnded { initiator, epoch });
+        effects.emit(Event::CoordinatorElected(winner_id));
+        effects.emit(Event::SentResultForward);
+        Ok(())
+    }
+
+    /// Pass on the candidate of a Chang–Roberts election, putting this
+    /// member forward instead should it have the higher id, or end the
+    /// election once the candidacy has made it around the ring.
+    ///
+    /// The simulator starts an election by nominating `initiator` to
+    /// itself.
+    async fn nominate(
+        &mut self, effects: &mut dyn Effects, candidate: usize, initiator: usize,
+    ) -> Result<()> {
+        let joined = self.state == State::Participant;
+
+        // Only the simulator sends an inactive member anything, so hand the
+        // election to the next active member to start.
+        if !self.sim_active {
+            effects.emit(Event::InactiveForElection);
+
+            match self.next_active(effects).await {
+                Ok(next) => {
+                    effects.send_to(next, Msg::Candidate { id: next, initiator }).await?;
+                    effects.emit(Event::HandedElection(next));
+                }
+                Err(_) => {
+                    effects.emit(Event::NoneToHandTo);
+                }
+            }
+
+            return Ok(());
+        }
+
+        // A member stepping down puts itself forward in no election, so
+        // hands one it was asked to start on as an inactive one does, and
+        // passes every other candidate on as it is.
+        if self.stepping_down {
+            match self.next_active(effects).await {
+                Ok(next) if candidate == self.id => {
+                    effects.send_to(next, Msg::Candidate { id: next, initiator }).await?;
+                    effects.emit(Event::HandedElection(next));
+                }
+                Ok(next) => {
+                    effects.send_to(next, Msg::Candidate { id: candidate, initiator }).await?;
+                    effects.emit(Event::ForwardedCandidate(candidate));
+                }
+                Err(_) => effects.emit(Event::NoneToHandTo),
+            }
+
+            return Ok(());
+        }
+
+        if candidate == self.id && joined {
+            return self.elected(effects, initiator).await;
+        }
+
+        if candidate == self.id && initiator == self.id {
+            self.detected_down(effects);
+        }
+
+        let forward = candidate.max(self.id);
+
+        if !joined {
+            self.join(effects);
+        }
+
+        if let Ok(next) = self.next_active(effects).await {
+            let msg = Msg::Candidate { id: forward, initiator };
+
+            if effects.send_to(next, msg).await.is_ok() {
+                effects.emit(Event::ForwardedCandidate(forward));
+                return Ok(());
+            }
+        }
+
+        // With no other member active, this one is left to win.
+        self.elected(effects, initiator).await
+    }
+
+    /// End a Chang–Roberts or Hirschberg–Sinclair election won by this
+    /// member. A Hirschberg–Sinclair election ends in the epoch it was
+    /// numbered, and a Chang–Roberts one, whose candidates carry no epoch,
+    /// in the latest this member heard of.
+    async fn elected(&mut self, effects: &mut dyn Effects, initiator: usize) -> Result<()> {
+        let epoch = self.candidacy.map_or(self.epoch, |c| c.election);
+
+        self.sim_force_send(effects, Msg::ElectionResult {
+            id: self.id, initiator, votes: None, epoch,
+        }).await?;
+
+        effects.emit(Event::ElectionEnded { initiator, epoch });
+        effects.emit(Event::CoordinatorElected(self.id));
+        effects.emit(Event::SentResultForward);
+        Ok(())
+    }
+
+    /// Relay or answer the probe of a Hirschberg–Sinclair candidate `id`,
+    /// standing as a candidate on first hearing of the election.
+    ///
+    /// The simulator starts an election by probing `initiator` with its own
+    /// id.
+    async fn probed(&mut self, effects: &mut dyn Effects, probe: Probe) -> Result<()> {
+        let Probe { id, election, initiator, .. } = probe;
+
+        // Only the simulator sends an inactive member anything, so hand the
+        // election to the next active member to start.
+        if !self.sim_active {
+            effects.emit(Event::InactiveForElection);
+            return self.hand_probe(effects, election, initiator).await;
+        }
+
+        // A member stepping down stands in no election, so hands one it was
+        // asked to start on as an inactive one does, and relays every other
+        // candidate's probe, swallowing none.
+        if self.stepping_down {
+            return match id == self.id {
+                true => self.hand_probe(effects, election, initiator).await,
+                false => self.relay(effects, probe).await,
+            };
+        }
+
+        let stage = match self.candidacy {
+            Some(c) if c.election > election => {
+                effects.emit(Event::StaleProbe);
+                return Ok(());
+            }
+            Some(c) if c.election == election => c.stage,
+            _ => {
+                if id == self.id && initiator == self.id {
+                    self.detected_down(effects);
+                }
+
+                self.stand(effects, election, initiator).await?;
+
+                // The simulator's own probe only starts the election.
+                if id == self.id {
+                    return Ok(());
+                }
+
+                match self.candidacy {
+                    Some(c) => c.stage,
+                    None => return Ok(()),
+                }
+            }
+        };
+
+        if id == self.id {
+            return match stage {
+                Stage::Probing { .. } => self.won(effects).await,
+                _ => Ok(()),
+            };
+        }
+
+        if id < self.id {
+            effects.emit(Event::SwallowedProbe(id));
+            return Ok(());
+        }
+
+        if let Stage::Probing { .. } = stage {
+            self.set_stage(Stage::Relaying);
+            effects.emit(Event::BeatenBy(id));
+        }
+
+        self.relay(effects, probe).await
+    }
+
+    /// Hand Hirschberg–Sinclair election `election` to the next active
+    /// member to start.
+    async fn hand_probe(
+        &mut self, effects: &mut dyn Effects, election: usize, initiator: usize,
+    ) -> Result<()> {
+        match self.next_active(effects).await {
+            Ok(next) => {
+                let msg = Msg::Probe {
+                    id: next, election, phase: 0, hops: 0, clockwise: true, initiator,
+                };
+
+                effects.send_to(next, msg).await?;
+                effects.emit(Event::HandedElection(next));
+            }
+            Err(_) => {
+                effects.emit(Event::NoneToHandTo);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Pass another candidate's probe on, or reply to it once it has gone
+    /// as far as its phase takes it.
+    async fn relay(&mut self, effects: &mut dyn Effects, probe: Probe) -> Result<()> {
+        let Probe { id, election, phase, hops, clockwise, initiator } = probe;
+
+        if hops + 1 < 1 << phase {
+            let msg = Msg::Probe {
+                id, election, phase, hops: hops + 1, clockwise, initiator,
+            };
+
+            self.send_toward(effects, clockwise, msg).await?;
+        } else {
+            let msg = Msg::Reply { id, election, clockwise: !clockwise };
+            self.send_toward(effects, !clockwise, msg).await?;
+            effects.emit(Event::RepliedToProbe(id));
+        }
+
+        Ok(())
+    }
+
+    /// Relay a Hirschberg–Sinclair reply on to candidate `id`, or if this
+    /// is the candidate, move on to the next phase once probes in both
+    /// directions have been replied to.
+    async fn replied(
+        &mut self, effects: &mut dyn Effects, id: usize, election: usize, clockwise: bool,
+    ) -> Result<()> {
+        // A member may hear a reply it never saw the probe for, should the
+        // ring have looked different on the way out, so relay any reply.
+        if id != self.id {
+            self.send_toward(effects, clockwise, Msg::Reply { id, election, clockwise }).await?;
+            return Ok(());
+        }
+
+        let candidacy = match self.candidacy {
+            Some(c) if c.election == election => c,
+            _ => {
+                effects.emit(Event::StaleReply);
+                return Ok(());
+            }
+        };
+
+        match candidacy.stage {
+            Stage::Probing { phase, replies: 0 } => {
+                self.set_stage(Stage::Probing { phase, replies: 1 });
+                Ok(())
+            }
+            Stage::Probing { phase, .. } => {
+                self.probe(effects, election, phase + 1, candidacy.initiator).await
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Take part in an election.
+    fn join(&mut self, effects: &mut dyn Effects) {
+        self.state = State::Participant;
+        self.joined += 1;
+        effects.emit(Event::Joined);
+    }
+
+    /// Stand as a candidate in Hirschberg–Sinclair election `election`.
+    async fn stand(
+        &mut self, effects: &mut dyn Effects, election: usize, initiator: usize,
+    ) -> Result<()> {
+        self.join(effects);
+        self.probe(effects, election, 0, initiator).await
+    }
+
+    /// Probe `2^phase` members out both ways around the ring, winning at
+    /// once should there be no other active member to probe.
+    async fn probe(
+        &mut self, effects: &mut dyn Effects, election: usize, phase: u32, initiator: usize,
+    ) -> Result<()> {
+        self.candidacy = Some(Candidacy {
+            election,
+            initiator,
+            stage: Stage::Probing { phase, replies: 0 },
+        });
+
+        for clockwise in [true, false] {
+            let msg = Msg::Probe {
+                id: self.id, election, phase, hops: 0, clockwise, initiator,
+            };
+
+            if !self.send_toward(effects, clockwise, msg).await? {
+                return self.won(effects).await;
+            }
+        }
+
+        effects.emit(Event::Probing { hops: 1 << phase });
+        Ok(())
+    }
+
+    /// End the Hirschberg–Sinclair election this member won.
+    async fn won(&mut self, effects: &mut dyn Effects) -> Result<()> {
+        let initiator = match self.candidacy {
+            Some(c) => c.initiator,
+            None => return Ok(()),
+        };
+
+        self.set_stage(Stage::Won);
+        self.elected(effects, initiator).await
+    }
+
+    fn set_stage(&mut self, stage: Stage) {
+        if let Some(c) = &mut self.candidacy {
+            c.stage = stage;
+        }
+    }
+
+    /// Send a message to the first active member clockwise, or the other
+    /// way around the ring, returning whether there was one to send to.
+    async fn send_toward(
+        &mut self, effects: &mut dyn Effects, clockwise: bool, msg: Msg,
+    ) -> Result<bool> {
+        match effects.active_toward(self, clockwise).await {
+            Ok(id) => {
+                effects.send_to(id, msg).await?;
+                Ok(true)
+            }
+            Err(_) => Ok(false),
+        }
+    }
+
+    /// Update the coordinator id based on the election results, unless
+    /// they're of an epoch before the latest this member heard of, so are
+    /// stale.
+    async fn update_coord(
+        &mut self, effects: &mut dyn Effects, id: usize, initiator: usize,
+        votes: Option<Vec<bool>>, epoch: usize,
+    ) -> Result<()> {
+        if epoch < self.epoch {
+            effects.emit(Event::StaleResult { id, epoch, latest: self.epoch });
+            return Ok(());
+        }
+
+        self.epoch = epoch;
+        self.stepping_down = false;
+        self.forgotten = false;
+
+        self.state = if id == self.id {
+            State::Coordinator
+        } else {
+            State::Follower
+        };
+
+        // The result of an election goes all the way around the ring, back
+        // to the member that ended it, the winner unless the ring algorithm
+        // ran, since the members it passes may follow the winner already.
+        let around = match self.config.algorithm {
+            Algorithm::ChangRoberts | Algorithm::HirschbergSinclair => id == self.id,
+            _ => self.closing == Some((epoch, initiator)),
+        };
+
+        if around {
+            self.closing = None;
+            self.coord_id = id;
+            effects.send_sim(SimMsg::ElectionResult { id, initiator, votes, epoch })?;
+            effects.emit(Event::SentResultToSim);
+            return Ok(());
+        }
+
+        self.sim_force_send(effects, Msg::ElectionResult { id, initiator, votes, epoch }).await?;
+        self.coord_id = id;
+
+        effects.emit(Event::CoordinatorElected(self.coord_id));
+
+        effects.emit(Event::SentResultForward);
+        Ok(())
+    }
+
+    /// Take part in the bully election that member `s_id` challenged this
+    /// one to, or that the simulator asked it to start if `s_id` is its own
+    /// id.
+    async fn challenged(
+        &mut self, effects: &mut dyn Effects, s_id: usize, initiator: usize,
+    ) -> Result<()> {
+        let started = s_id == self.id;
+
+        if started && initiator == self.id {
+            if !self.stepping_down {
+                self.detected_down(effects);
+            }
+
+            self.owes_result = true;
+        }
+
+        // An inactive member doesn't answer, nor does one stepping down, but
+        // either hands an election it was asked to start on to the next
+        // active member.
+        if !self.sim_active || self.stepping_down {
+            if !started {
+                return Ok(());
+            }
+
+            if !self.sim_active {
+                effects.emit(Event::InactiveForElection);
+            }
+
+            match self.next_active(effects).await {
+                Ok(next) => {
+                    effects.send_to(next, Msg::BullyElection { s_id: next, initiator }).await?;
+                    effects.emit(Event::HandedElection(next));
+                }
+                Err(_) => {
+                    effects.emit(Event::NoneToHandTo);
+                }
+            }
+
+            return Ok(());
+        }
+
+        if !started {
+            effects.send_to(s_id, Msg::Answer).await?;
+            effects.emit(Event::AnsweredElection(s_id));
+        }
+
+        let announcing = self.state == State::Participant && self.coord_id == self.id;
+
+        match self.state {
+            // A challenger that missed the last announcement only needs to
+            // hear it again, as does the initiator, which the simulator may
+            // be waiting on, unless a higher member came back up since this
+            // one won, which it challenges instead.
+            State::Coordinator if !started && self.higher().is_empty() => {
+                self.tell(effects, s_id, initiator).await
+            }
+            // One challenging this member while it announces itself may have
+            // been passed by the announcement, so it is told again once this
+            // member has won, as is the simulator if it asked this member to
+            // start an election meanwhile.
+            State::Participant if announcing => {
+                self.late.push(s_id);
+                Ok(())
+            }
+            State::Participant => Ok(()),
+            _ => self.bully(effects, initiator).await,
+        }
+    }
+
+    /// Tell a bully election's challenger `s_id` and its initiator that this
+    /// member is the coordinator.
+    async fn tell(
+        &mut self, effects: &mut dyn Effects, s_id: usize, initiator: usize,
+    ) -> Result<()> {
+        let mut told = vec![s_id];
+
+        if initiator != s_id && initiator != self.id {
+            told.push(initiator);
+        }
+
+        for id in told {
+            effects.send_to(id, Msg::Coordinator { id: self.id, initiator }).await?;
+            effects.emit(Event::ToldCoordinator(id));
+        }
+
+        Ok(())
+    }
+
+    /// Challenge every member in the ring with a higher id, and win the
+    /// election unless one of them answers within the backoff.
+    async fn bully(&mut self, effects: &mut dyn Effects, initiator: usize) -> Result<()> {
+        self.join(effects);
+        let higher = self.higher();
+
+        for &id in &higher {
+            effects.send_to(id, Msg::BullyElection { s_id: self.id, initiator }).await?;
+        }
+
+        if !higher.is_empty() {
+            effects.emit(Event::Challenged(higher.clone()));
+        }
+
+        if effects.await_answers(self, higher.len().min(1)).await? == 1 {
+            effects.emit(Event::Answered);
+            return Ok(());
+        }
+
+        // Another member may have won while this one waited.
+        if self.state != State::Participant {
+            return Ok(());
+        }
+
+        self.announce(effects, initiator).await
+    }
+
+    /// The members in the ring with a higher id than this one.
+    fn higher(&self) -> Vec<usize> {
+        (self.id + 1..self.ring_size).filter(|id| !self.gone[*id]).collect()
+    }
+
+    /// Announce this member as the coordinator to every other member in the
+    /// ring, the initiator last, so that the simulator only learns the result once
+    /// the others have acknowledged it.
+    async fn announce(&mut self, effects: &mut dyn Effects, initiator: usize) -> Result<()> {
+        effects.emit(Event::Unanswered);
+        self.coord_id = self.id;
+        self.forgotten = false;
+
+        let others = (0..self.ring_size)
+            .filter(|id| *id != self.id && *id != initiator && !self.gone[*id])
+            .collect::<Vec<_>>();
+
+        for &id in &others {
+            effects.send_to(id, Msg::Coordinator { id: self.id, initiator }).await?;
+        }
+
+        let acks = effects.await_answers(self, others.len()).await?;
+
+        // A higher member that was too slow to answer may have won after
+        // all.
+        if self.state != State::Participant {
+            self.late.clear();
+            return Ok(());
+        }
+
+        if acks < others.len() {
+            effects.emit(Event::Acknowledged { acks, of: others.len() });
+        }
+
+        self.state = State::Coordinator;
+        effects.emit(Event::CoordinatorElected(self.id));
+
+        for id in std::mem::take(&mut self.late) {
+            if id == self.id {
+                self.report(effects, id)?;
+            } else if id != initiator {
+                effects.send_to(id, Msg::Coordinator { id: self.id, initiator }).await?;
+                effects.emit(Event::ToldCoordinator(id));
+            }
+        }
+
+        if initiator == self.id {
+            return self.report(effects, initiator);
+        }
+
+        effects.send_to(initiator, Msg::Coordinator { id: self.id, initiator }).await?;
+        effects.emit(Event::SentResult(initiator));
+        Ok(())
+    }
+
+    /// Follow the coordinator a bully election announced, and pass the
+    /// result on to the simulator if it was waiting on this member for it.
+    ///
+    /// An active member with a higher id than the coordinator, which must
+    /// have been too slow to answer, holds the election again instead,
+    /// unless it's stepping down.
+    async fn announced(
+        &mut self, effects: &mut dyn Effects, id: usize, initiator: usize,
+    ) -> Result<()> {
+        if self.sim_active && !self.stepping_down && id < self.id {
+            effects.emit(Event::Outranks(id));
+
+            return match self.state {
+                State::Participant => Ok(()),
+                _ => self.bully(effects, initiator).await,
+            };
+        }
+
+        self.coord_id = id;
+        self.state = State::Follower;
+        self.stepping_down = false;
+        self.forgotten = false;
+        effects.send_to(id, Msg::Answer).await?;
+        effects.emit(Event::CoordinatorElected(id));
+
+        if self.owes_result {
+            self.report(effects, initiator)?;
+        }
+
+        Ok(())
+    }
+
+    /// Send the simulator the result of the bully election it asked this
+    /// member to start.
+    fn report(&mut self, effects: &mut dyn Effects, initiator: usize) -> Result<()> {
+        self.owes_result = false;
+
+        effects.send_sim(SimMsg::ElectionResult {
+            id: self.coord_id, initiator, votes: None, epoch: self.epoch,
+        })?;
+
+        effects.emit(Event::SentResultToSim);
+        Ok(())
+    }
+
+    /// Toggle active/inactive if target is self, else send message forward.
+    ///
+    /// `hops` counts the members the toggle went through before this one,
+    /// so that a toggle whose target is not in the ring is reported back to
+    /// the simulator once it has gone around, instead of circling forever.
+    async fn toggle(
+        &mut self, effects: &mut dyn Effects, id: usize, hops: usize, seq: usize,
+    ) -> Result<()> {
+        if id != self.id {
+            if hops + 1 >= self.ring_size {
+                effects.send_sim(SimMsg::Unreachable { id })?;
+                effects.emit(Event::ToggleUnreachable(id));
+                return Ok(());
+            }
+
+            self.sim_force_send(effects, Msg::SimToggle { id, hops: hops + 1, seq }).await?;
+            effects.emit(Event::SentToggleForward);
+            return Ok(());
+        }
+
+        // A resent toggle that was already applied only needs to be
+        // acknowledged again.
+        if self.last_toggle != Some(seq) {
+            self.sim_active ^= true;
+            self.last_toggle = Some(seq);
+        }
+
+        effects.send_sim(SimMsg::ConfirmToggle {
+            id: self.id,
+            active: self.sim_active,
+            seq,
+        })?;
+
+        effects.emit(Event::Toggled(self.sim_active));
+        effects.emit(Event::SentToggle);
+
+        // A member that is down hears nothing of the ring, so routes through
+        // every member, and once back up tells the ring to route through it.
+        if self.config.reconfigure {
+            self.down.fill(false);
+
+            if self.sim_active {
+                effects.emit(Event::Rejoined);
+                let _ = self.send(effects, Msg::MemberUp { id: self.id, hops: 0 }).await;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Route around member `id` from now on, it having not answered, and
+    /// tell the rest of the ring through `next`, the next active member if
+    /// there is one. Member `id` is told too, should it only have been
+    /// slow, for it to say otherwise.
+    pub(crate) async fn declare_down(
+        &mut self, effects: &mut dyn Effects, id: usize, next: Option<usize>,
+    ) -> Result<()> {
+        // A scan may have found the member down while this one waited on
+        // another's ping. One that is down itself keeps routing through
+        // every member.
+        if self.down[id] || !self.sim_active {
+            return Ok(());
+        }
+
+        self.down[id] = true;
+        effects.emit(Event::DeclaredDown(id));
+
+        let msg = Msg::MemberDown { id, origin: self.id, hops: 0 };
+        let _ = effects.send_to(id, msg.clone()).await;
+
+        match next {
+            Some(next) => effects.send_to(next, msg).await,
+            None => Ok(()),
+        }
+    }
+
+    /// Route around member `id`, which member `origin` found down, and pass
+    /// the news on around the ring, `hops` members from `origin` so far.
+    /// Should this be the member found down, it tells the ring it's up
+    /// instead.
+    async fn member_down(
+        &mut self, effects: &mut dyn Effects, id: usize, origin: usize, hops: usize,
+    ) -> Result<()> {
+        if !self.sim_active || origin == self.id {
+            return Ok(());
+        }
+
+        if id == self.id {
+            effects.emit(Event::Refuting(origin));
+            let _ = self.send(effects, Msg::MemberUp { id, hops: 0 }).await;
+            return Ok(());
+        }
+
+        if !self.down[id] {
+            self.down[id] = true;
+            effects.emit(Event::MemberDown(id));
+        }
+
+        if hops + 1 < self.ring_size {
+            let _ = self.send(effects, Msg::MemberDown { id, origin, hops: hops + 1 }).await;
+        }
+
+        Ok(())
+    }
+
+    /// Route through member `id` again, which is back up, and pass the news
+    /// on around the ring, `hops` members from `id` so far.
+    async fn member_up(&mut self, effects: &mut dyn Effects, id: usize, hops: usize) -> Result<()> {
+        if !self.sim_active || id == self.id {
+            return Ok(());
+        }
+
+        if self.down[id] {
+            self.down[id] = false;
+            effects.emit(Event::MemberUp(id));
+        }
+
+        if hops + 1 < self.ring_size {
+            let _ = self.send(effects, Msg::MemberUp { id, hops: hops + 1 }).await;
+        }
+
+        Ok(())
+    }
+
+    /// Renew this member's lease as coordinator, sending the renewal around
+    /// the ring.
+    pub(crate) async fn renew_lease(&mut self, effects: &mut dyn Effects) -> Result<()> {
+        effects.emit(Event::RenewingLease);
+        let _ = self.send(effects, Msg::LeaseRenewal { id: self.id, hops: 0 }).await;
+        Ok(())
+    }
+
+    /// Renew the lease of coordinator `id` should this member follow it,
+    /// and pass the renewal on around the ring, `hops` members from the
+    /// coordinator so far.
+    async fn renewed(&mut self, effects: &mut dyn Effects, id: usize, hops: usize) -> Result<()> {
+        if !self.sim_active || id == self.id {
+            return Ok(());
+        }
+
+        if id == self.coord_id {
+            self.renewals += 1;
+            effects.emit(Event::LeaseRenewed(id));
+        }
+
+        if hops + 1 < self.ring_size {
+            let _ = self.send(effects, Msg::LeaseRenewal { id, hops: hops + 1 }).await;
+        }
+
+        Ok(())
+    }
+
+    /// Take part in the pre-vote `origin` holds on whether coordinator
+    /// `coord_id` is gone, `hops` members from it so far, `agreed` of
+    /// which and `origin` found it so, and pass it on around the ring. Once
+    /// back around, `origin` starts an election should most of those asked
+    /// have agreed, unless another was held meanwhile.
+    async fn pre_voted(
+        &mut self, effects: &mut dyn Effects, origin: usize, coord_id: usize, agreed: usize,
+        hops: usize,
+    ) -> Result<()> {
+        if origin == self.id {
+            let polled = hops + 1;
+
+            if coord_id != self.coord_id || self.state != State::Follower {
+                effects.emit(Event::StalePreVote(coord_id));
+                return Ok(());
+            }
+
+            return match agreed * 2 > polled {
+                true => {
+                    effects.emit(Event::PreVoteWon { agreed, polled });
+                    self.start_election(effects).await
+                }
+                false => {
+                    effects.emit(Event::PreVoteLost { agreed, polled });
+                    Ok(())
+                }
+            };
+        }
+
+        if !self.sim_active {
+            return Ok(());
+        }
+
+        // Only a member following the same coordinator, and failing to
+        // reach it itself, agrees it's gone.
+        let agrees = coord_id == self.coord_id && coord_id != self.id
+            && !effects.reaches(self, coord_id).await?;
+
+        effects.emit(Event::PreVoted { origin, agrees });
+
+        if hops + 1 < self.ring_size {
+            let msg = Msg::PreVote {
+                origin, coord_id, agreed: agreed + agrees as usize, hops: hops + 1,
+            };
+
+            let _ = self.send(effects, msg).await;
+        }
+
+        Ok(())
+    }
+
+    /// Pass member `id` by from now on unless it's `present`, whether
+    /// active or not, leaving this one to pass the simulator's messages on
+    /// to itself should every other have left.
+    fn membership(&mut self, id: usize, present: bool) {
+        self.gone[id] = !present;
+
+        self.next_id = (self.id + 1..self.ring_size)
+            .chain(0..self.id)
+            .find(|i| !self.gone[*i])
+            .unwrap_or(self.id);
+    }
+
+    /// Start over as the member first started, taking the one an election
+    /// among them all would elect for the coordinator and routing through
+    /// every member, having forgotten all it knew of the ring but who left
+    /// it. The latest Hirschberg–Sinclair election it heard of is kept, as
+    /// it would be on disk, for those it starts not to be taken for ones
+    /// long over.
+    pub(crate) fn restart(&mut self) {
+        self.coord_id = self.config.elects(&vec![true; self.ring_size]).unwrap_or_default();
+
+        self.state = match self.id == self.coord_id {
+            true => State::Coordinator,
+            false => State::Follower,
+        };
+
+        self.owes_result = false;
+        self.stepping_down = false;
+        self.forgotten = true;
+        self.late.clear();
+        self.down.fill(false);
+    }
+
+    /// Note that this member starts an election having found the
+    /// coordinator down, or knowing none since it restarted.
+    fn detected_down(&self, effects: &mut dyn Effects) {
+        match self.forgotten {
+            true => effects.emit(Event::SeekingCoordinator),
+            false => effects.emit(Event::DetectedDown(self.coord_id)),
+        }
+    }
+
+    /// Take on the state saved in a checkpoint. An election in progress is
+    /// held again from the start, so the member hasn't voted in it yet.
+    pub fn restore(&mut self, status: &MemberStatus) {
+        self.sim_active = status.active;
+        self.coord_id = status.coord_id;
+
+        self.state = match status.state {
+            State::Participant => State::Follower,
+            state => state,
+        };
+    }
+
+    /// Send a message to the first active member ringwise.
+    async fn send(&mut self, effects: &mut dyn Effects, msg: Msg) -> Result<()> {
+        let i = self.next_active(effects).await?;
+        effects.send_to(i, msg).await?;
+        effects.emit(Event::Sent(i));
+        Ok(())
+    }
+
+    /// Find the first active member ringwise.
+    async fn next_active(&mut self, effects: &mut dyn Effects) -> Result<usize> {
+        effects.active_toward(self, true).await
+    }
+
+    /// Send a message ringwise, starting from the next member,
+    /// Regardless of whether they are simulating inactivity or not.
+    async fn sim_force_send(&mut self, effects: &mut dyn Effects, msg: Msg) -> Result<()> {
+        effects.send_to(self.next_id, msg).await
+    }
+}
+
+// Ring members run on threads of their own, outside any loom model.
+#[cfg(all(test, not(loom)))]
+mod tests {
+    use std::collections::VecDeque;
+
+    use super::*;
+    use crate::Ranking;
+
+    /// A ring of `size` members, configured as `config`.
+    fn ring(size: usize, config: SimConfig) -> Vec<Core> {
+        (0..size).map(|id| Core::new(id, size, config.clone())).collect()
+    }
+
+    /// Hold a ring election over `cores` from member 0, handing every
+    /// message on to its target, and return everything the members did
+    /// until the first result was sent.
+    fn elect(cores: &mut [Core]) -> Vec<Outgoing> {
+        let mut outbox = Outbox::new(vec![true; cores.len()]);
+        let mut queue = VecDeque::from([(0, Msg::election(0, cores.len(), 1))]);
+        let mut done = Vec::new();
+
+        while let Some((to, msg)) = queue.pop_front() {
+            for out in outbox.step(&mut cores[to], msg).unwrap() {
+                let ended = matches!(out, Outgoing::Sent { msg: Msg::ElectionResult { .. }, .. });
+
+                if let Outgoing::Sent { to, msg } = &out {
+                    queue.push_back((*to, msg.clone()));
+                }
+
+                done.push(out);
+
+                if ended {
+                    return done;
+                }
+            }
+        }
+
+        panic!("the election never ended: {:?}", done);
+    }
+
+    /// The member the election that did `out` elected.
+    fn winner(out: &[Outgoing]) -> usize {
+        out.iter()
+            .find_map(|out| match out {
+                Outgoing::Sent { msg: Msg::ElectionResult { id, .. }, .. } => Some(*id),
+                _ => None,
+            })
+            .unwrap()
+    }
+
+    /// How many election messages members sent in `out`.
+    fn elections(out: &[Outgoing]) -> usize {
+        out.iter().filter(|out| matches!(out, Outgoing::Sent { msg: Msg::Election { .. }, .. }))
+            .count()
+    }
+
+    #[test]
+    fn an_election_ends_once_all_voted_or_back_at_its_origin() {
+        let rule = |termination| SimConfig { termination, ..SimConfig::default() };
+
+        let origin = elect(&mut ring(3, rule(TerminationRule::ReturnToOrigin)));
+        assert_eq!(elections(&origin), 3);
+        assert!(!origin.contains(&Outgoing::Emitted(Event::AlreadyVoted(0))));
+
+        let all_voted = elect(&mut ring(3, rule(TerminationRule::AllVoted)));
+        assert_eq!(elections(&all_voted), 2);
+        assert!(all_voted.contains(&Outgoing::Emitted(Event::AlreadyVoted(0))));
+
+        assert_eq!(winner(&origin), 0);
+        assert_eq!(winner(&all_voted), 0);
+    }
+
+    #[test]
+    fn the_highest_priority_wins_and_the_lowest_id_among_equals() {
+        let elected = |priorities: &[u64]| {
+            let config = SimConfig { priorities: priorities.to_vec(), ..SimConfig::default() };
+            winner(&elect(&mut ring(priorities.len(), config)))
+        };
+
+        assert_eq!(elected(&[1, 5, 5, 2]), 1);
+        assert_eq!(elected(&[1, 2, 5, 5]), 2);
+        assert_eq!(elected(&[3, 1, 1, 3]), 0);
+        assert_eq!(elected(&[0, 0, 0, 7]), 3);
+        assert_eq!(elected(&[0; 4]), 0);
+    }
+
+    #[test]
+    fn a_recently_alive_higher_id_beats_a_stale_lower_one() {
+        let ranked = |ranking| {
+            let mut cores = ring(3, SimConfig { ranking, ..SimConfig::default() });
+
+            for (core, last_alive) in cores.iter_mut().zip([100, 300, 200]) {
+                core.last_alive = last_alive;
+            }
+
+            winner(&elect(&mut cores))
+        };
+
+        assert_eq!(ranked(Ranking::Recency), 1);
+        assert_eq!(ranked(Ranking::LowestId), 0);
+    }
+
+    #[test]
+    fn a_toggle_around_the_ring_without_its_target_is_reported() {
+        let mut core = Core::new(0, 3, SimConfig::default());
+        let mut outbox = Outbox::new(vec![true; 3]);
+
+        let out = outbox.step(&mut core, Msg::SimToggle { id: 1, hops: 1, seq: 1 }).unwrap();
+        let forwarded = Msg::SimToggle { id: 1, hops: 2, seq: 1 };
+        assert_eq!(out[0], Outgoing::Sent { to: 1, msg: forwarded });
+
+        let out = outbox.step(&mut core, Msg::SimToggle { id: 1, hops: 2, seq: 1 }).unwrap();
+        assert_eq!(out, [
+            Outgoing::SentSim(SimMsg::Unreachable { id: 1 }),
+            Outgoing::Emitted(Event::ToggleUnreachable(1)),
+        ]);
+    }
+
+    #[test]
+    fn an_election_not_fitting_the_ring_is_dropped() {
+        let mut core = Core::new(2, 3, SimConfig::default());
+        let mut outbox = Outbox::new(vec![true; 3]);
+
+        let short = Msg::Election { body: vec![true], keys: vec![0; 3], initiator: 0, epoch: 1 };
+        let out = outbox.step(&mut core, short).unwrap();
+        assert_eq!(out, [Outgoing::Emitted(Event::MisfitElection { votes: 1, keys: 3 })]);
+    }
+}
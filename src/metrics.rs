@@ -0,0 +1,148 @@
+//! A tiny HTTP server exporting a running simulation's metrics for
+//! Prometheus to scrape.
+//!
+//! `GET /metrics` returns, in Prometheus' text format, the elections
+//! started, the messages ring members sent by type, the pings that went
+//! unanswered, the current coordinator and how many members are active.
+//! Every process counts what happens in it, so a node reports only what
+//! its own member sent, and only the simulator knows the coordinator and
+//! the active members.
+
+use std::fmt::Write as _;
+use std::net::TcpStream;
+use std::sync::{Arc, Mutex, MutexGuard};
+
+use anyhow::Result;
+
+use crate::http;
+use crate::Msg;
+
+/// The metrics counted so far.
+#[derive(Debug, Clone)]
+pub struct Exporter {
+    state: Arc<Mutex<Counted>>,
+}
+
+#[derive(Debug, Default)]
+struct Counted {
+    elections: usize,
+    /// How many messages of each type each ring member sent, by member.
+    sent: Vec<(usize, &'static str, usize)>,
+    /// How many pings each ring member gave up on, by member.
+    ping_failures: Vec<(usize, usize)>,
+    coordinator: Option<usize>,
+    active: Option<usize>,
+}
+
+impl Exporter {
+    /// Serve the metrics at `addr`, such as `127.0.0.1:9100`.
+    pub fn serve(addr: &str) -> Result<Self> {
+        let exporter = Self { state: Arc::new(Mutex::new(Counted::default())) };
+        let served = exporter.clone();
+        http::serve(addr, "metrics", move |stream| served.answer(stream))?;
+        Ok(exporter)
+    }
+
+    /// Count an election started by the simulator.
+    pub(crate) fn election(&self) {
+        self.lock().elections += 1;
+    }
+
+    /// Count `msg` as sent by ring member `id`.
+    pub(crate) fn sent(&self, id: usize, msg: &Msg) {
+        let mut state = self.lock();
+        let kind = msg.kind();
+
+        match state.sent.iter_mut().find(|(m, k, _)| *m == id && *k == kind) {
+            Some((_, _, n)) => *n += 1,
+            None => state.sent.push((id, kind, 1)),
+        }
+    }
+
+    /// Count a ping by ring member `id` as unanswered.
+    pub(crate) fn ping_failed(&self, id: usize) {
+        let mut state = self.lock();
+
+        match state.ping_failures.iter_mut().find(|(m, _)| *m == id) {
+            Some((_, n)) => *n += 1,
+            None => state.ping_failures.push((id, 1)),
+        }
+    }
+
+    /// Set the coordinator and how many ring members are active, as the
+    /// simulator knows them.
+    pub(crate) fn ring(&self, coordinator: usize, active: usize) {
+        let mut state = self.lock();
+        state.coordinator = Some(coordinator);
+        state.active = Some(active);
+    }
+
+    fn answer(&self, mut stream: TcpStream) -> Result<()> {
+        let (method, path) = http::request(&stream)?;
+
+        let (status, body) = match (method.as_str(), path.as_str()) {
+            ("GET", "/metrics") => ("200 OK", self.render()),
+            ("GET", _) => ("404 Not Found", "not found\n".to_string()),
+            _ => ("405 Method Not Allowed", "method not allowed\n".to_string()),
+        };
+
+        http::respond(&mut stream, status, "text/plain; version=0.0.4", &body)
+    }
+
+    /// The metrics in Prometheus' text format.
+    fn render(&self) -> String {
+        let state = self.lock();
+        let mut out = String::new();
+
+        let mut sent = state.sent.clone();
+        sent.sort();
+        let mut ping_failures = state.ping_failures.clone();
+        ping_failures.sort();
+
+        family(&mut out, "elections_total", "counter", "Elections started by the simulator.");
+        let _ = writeln!(out, "elections_total {}", state.elections);
+
+        family(
+            &mut out, "messages_sent_total", "counter",
+            "Messages ring members sent one another, by type.",
+        );
+
+        for (id, kind, n) in sent {
+            let _ = writeln!(out, "messages_sent_total{{member=\"{}\",type=\"{}\"}} {}", id, kind, n);
+        }
+
+        family(
+            &mut out, "ping_failures_total", "counter",
+            "Pings that weren't answered within their timeout.",
+        );
+
+        for (id, n) in ping_failures {
+            let _ = writeln!(out, "ping_failures_total{{member=\"{}\"}} {}", id, n);
+        }
+
+        if let Some(coordinator) = state.coordinator {
+            family(
+                &mut out, "current_coordinator_id", "gauge",
+                "The coordinator according to the simulator.",
+            );
+            let _ = writeln!(out, "current_coordinator_id {}", coordinator);
+        }
+
+        if let Some(active) = state.active {
+            family(&mut out, "active_members", "gauge", "Ring members currently active.");
+            let _ = writeln!(out, "active_members {}", active);
+        }
+
+        out
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Counted> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+/// Describe the metric family `name` of type `kind`.
+fn family(out: &mut String, name: &str, kind: &str, help: &str) {
+    let _ = writeln!(out, "# HELP {} {}", name, help);
+    let _ = writeln!(out, "# TYPE {} {}", name, kind);
+}
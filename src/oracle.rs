@@ -0,0 +1,101 @@
+//! An oracle checking the ring's safety as the simulation runs: once an
+//! election is over, no two active members follow different coordinators.
+//!
+//! The oracle only sees the ring through the events its members emit, which
+//! it passes on to the sink it wraps. It keeps every event bearing on the
+//! invariant since it was last checked, to report the exact sequence that
+//! led to a violation. Members it hasn't seen follow any coordinator yet,
+//! such as those run as nodes of their own, aren't checked.
+
+use std::collections::BTreeMap;
+use std::sync::Arc;
+
+use anyhow::{bail, Result};
+
+use crate::clock::SharedClock;
+use crate::event::{Event, SharedSink, Sink, Source};
+use crate::sync::{Mutex, MutexGuard};
+
+/// Watches the events of a simulation on their way to `inner`.
+///
+/// Clones share what was seen, so that the simulator can check the events
+/// the ring members emitted.
+#[derive(Debug, Clone)]
+pub struct Oracle {
+    inner: SharedSink,
+    seen: Arc<Mutex<Seen>>,
+}
+
+#[derive(Debug, Default)]
+struct Seen {
+    /// Whether each member is active and whom it follows, by member.
+    members: BTreeMap<usize, (bool, Option<usize>)>,
+    /// The events bearing on the invariant since it was last checked.
+    trail: Vec<String>,
+}
+
+impl Oracle {
+    pub fn new(inner: SharedSink) -> Self {
+        Self { inner, seen: Arc::new(Mutex::new(Seen::default())) }
+    }
+
+    /// Check that every active member follows the same coordinator, as
+    /// they should once an election is over.
+    pub fn check(&self) -> Result<()> {
+        let mut seen = self.lock();
+
+        let followed = seen.members.iter()
+            .filter(|(_, (active, _))| *active)
+            .filter_map(|(id, (_, coord_id))| coord_id.map(|coord_id| (*id, coord_id)))
+            .collect::<Vec<_>>();
+
+        if followed.windows(2).any(|w| w[0].1 != w[1].1) {
+            let followed = followed.iter()
+                .map(|(id, coord_id)| format!("{} follows {}", id, coord_id))
+                .collect::<Vec<_>>();
+
+            bail!(
+                "oracle: active members follow different coordinators ({}), after:\n{}",
+                followed.join(", "), seen.trail.join("\n")
+            );
+        }
+
+        seen.trail.clear();
+        Ok(())
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Seen> {
+        self.seen.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+impl Sink for Oracle {
+    fn emit(&self, source: Source, event: &Event) {
+        let bears = match (source, event) {
+            (Source::Member(id), Event::Toggled(active)) => {
+                self.lock().members.entry(id).or_insert((true, None)).0 = *active;
+                true
+            }
+            (Source::Member(id), Event::CoordinatorElected(coord_id)) => {
+                self.lock().members.entry(id).or_insert((true, None)).1 = Some(*coord_id);
+                true
+            }
+            (Source::Sim, Event::ElectionStarted { .. } | Event::ElectionWon { .. }) => true,
+            _ => false,
+        };
+
+        if bears {
+            self.lock().trail.push(format!("{}: {}", source, event));
+        }
+
+        self.inner.emit(source, event);
+    }
+
+    fn holds_stdout(&self) -> bool {
+        self.inner.holds_stdout()
+    }
+
+    fn keep_time(&self, clock: &SharedClock) {
+        self.inner.keep_time(clock);
+    }
+}
@@ -0,0 +1,93 @@
+//! Pausing a running ring from outside it, and following what state each
+//! member was last in meanwhile.
+//!
+//! While paused, ring members hold every message delivered to them until
+//! the ring is resumed, and no time spent paused counts towards a timeout,
+//! whether a member's waiting on an answer or the simulator's on the ring.
+
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use crate::sync::{Condvar, Mutex, MutexGuard};
+use crate::MemberStatus;
+
+/// Pauses and resumes a ring. Clones pause the same ring.
+#[derive(Debug, Clone, Default)]
+pub struct Pause {
+    state: Arc<Paused>,
+}
+
+#[derive(Debug, Default)]
+struct Paused {
+    pausing: Mutex<Pausing>,
+    resumed: Condvar,
+}
+
+#[derive(Debug, Default)]
+struct Pausing {
+    /// When the ring was paused, if it is.
+    since: Option<Instant>,
+    /// How long the ring was paused for before then.
+    total: Duration,
+    /// Each ring member's state when it last waited for a message, by id.
+    statuses: Vec<Option<MemberStatus>>,
+}
+
+impl Pause {
+    /// Pause the ring, unless it already is.
+    pub fn pause(&self) {
+        self.lock().since.get_or_insert_with(Instant::now);
+    }
+
+    /// Resume the ring, unless it isn't paused.
+    pub fn resume(&self) {
+        let mut pausing = self.lock();
+
+        if let Some(since) = pausing.since.take() {
+            pausing.total += since.elapsed();
+            self.state.resumed.notify_all();
+        }
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.lock().since.is_some()
+    }
+
+    /// The state each ring member was in when it last waited for a message,
+    /// by id, leaving out those that haven't yet.
+    pub fn statuses(&self) -> Vec<MemberStatus> {
+        self.lock().statuses.iter().flatten().cloned().collect()
+    }
+
+    /// Note the state of a ring member about to wait for a message.
+    pub(crate) fn waiting(&self, status: MemberStatus) {
+        let mut pausing = self.lock();
+        let id = status.id;
+
+        if pausing.statuses.len() <= id {
+            pausing.statuses.resize(id + 1, None);
+        }
+
+        pausing.statuses[id] = Some(status);
+    }
+
+    /// Wait until resumed, if paused.
+    pub(crate) fn hold(&self) {
+        let mut pausing = self.lock();
+
+        while pausing.since.is_some() {
+            pausing = self.state.resumed.wait(pausing).unwrap_or_else(|e| e.into_inner());
+        }
+    }
+
+    /// How long the ring has been paused for in all, so that a wait can
+    /// make up for whatever more of it passed paused.
+    pub(crate) fn paused_for(&self) -> Duration {
+        let pausing = self.lock();
+        pausing.total + pausing.since.map_or(Duration::ZERO, |since| since.elapsed())
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Pausing> {
+        self.state.pausing.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
@@ -0,0 +1,1062 @@
+//! Recordings of the messages delivered during a simulation.
+//!
+//! A recording holds every message delivered to a ring member or to the
+//! simulator, and every time a member gave up waiting for one, in delivery
+//! order, in one of two formats:
+//!
+//! - NDJSON: a header line naming the format and its version, followed by
+//!   one JSON object per delivery.
+//! - Binary: a magic string and the format version, followed by one
+//!   length-prefixed frame per delivery. This is far smaller for long runs.
+//!
+//! Readers tell the formats apart by the binary magic string.
+
+use std::fmt;
+use std::fs::{self, File};
+use std::io::{BufWriter, Write};
+use std::path::Path;
+use std::sync::Arc;
+
+use anyhow::{bail, Error, Result};
+
+use crate::json::Value;
+use crate::sync::{Mutex, MutexGuard};
+use crate::{MemberStatus, Msg, SimMsg, State};
+
+const FORMAT_NAME: &str = "ring-record";
+
+/// Start of every binary recording.
+const MAGIC: &[u8; 8] = b"RINGREC\0";
+
+/// Version of the encoding of `Msg` and `SimMsg`.
+///
+/// Bump this whenever a message variant or its fields change, so that old
+/// recordings are rejected instead of being decoded into garbage.
+pub const FORMAT_VERSION: u64 = 24;
+
+/// How a recording is written.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Format {
+    #[default]
+    Ndjson,
+    Binary,
+}
+
+impl std::str::FromStr for Format {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "ndjson" => Ok(Self::Ndjson),
+            // The name other tools go by for a compact binary encoding.
+            "binary" | "bincode" => Ok(Self::Binary),
+            _ => bail!("Unknown recording format {}", s),
+        }
+    }
+}
+
+/// A single recorded delivery.
+#[derive(Debug, PartialEq)]
+pub enum Record {
+    /// A message delivered to ring member `id`.
+    Member { id: usize, msg: Msg },
+    /// A message delivered to the simulator.
+    Sim { msg: SimMsg },
+    /// Ring member `id` gave up waiting for a message.
+    Timeout { id: usize },
+}
+
+impl fmt::Display for Record {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Record::Member { id, msg } => write!(f, "{}: received {:?}", id, msg),
+            Record::Sim { msg } => write!(f, "sim: received {:?}", msg),
+            Record::Timeout { id } => write!(f, "{}: timed out", id),
+        }
+    }
+}
+
+/// Writes every delivered message to a recording.
+///
+/// Clones share the same file, so deliveries from all threads end up in a
+/// single stream.
+#[derive(Clone)]
+pub struct Recorder {
+    out: Arc<Mutex<BufWriter<File>>>,
+    format: Format,
+}
+
+impl fmt::Debug for Recorder {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Recorder")
+            .field("format", &self.format)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Recorder {
+    /// Create a recording at `path`, writing its header.
+    pub fn create(path: &Path, format: Format) -> Result<Self> {
+        let mut out = BufWriter::new(File::create(path)?);
+
+        match format {
+            Format::Ndjson => {
+                let header = Value::object([
+                    ("format", FORMAT_NAME.into()),
+                    ("version", FORMAT_VERSION.into()),
+                ]);
+
+                writeln!(out, "{}", header)?;
+            }
+            Format::Binary => {
+                out.write_all(MAGIC)?;
+                out.write_all(&(FORMAT_VERSION as u32).to_le_bytes())?;
+            }
+        }
+
+        Ok(Self { out: Arc::new(Mutex::new(out)), format })
+    }
+
+    /// Record a message delivered to ring member `id`.
+    pub fn member(&self, id: usize, msg: &Msg) -> Result<()> {
+        match self.format {
+            Format::Ndjson => self.write_line(
+                Value::object([("to", id.into()), ("msg", msg.to_json())])
+            ),
+            Format::Binary => {
+                let mut frame = vec![0];
+                put_usize(&mut frame, id);
+                msg.encode(&mut frame);
+                self.write_frame(&frame)
+            }
+        }
+    }
+
+    /// Record a message delivered to the simulator.
+    pub fn sim(&self, msg: &SimMsg) -> Result<()> {
+        match self.format {
+            Format::Ndjson => self.write_line(
+                Value::object([("to", "sim".into()), ("msg", msg.to_json())])
+            ),
+            Format::Binary => {
+                let mut frame = vec![1];
+                msg.encode(&mut frame);
+                self.write_frame(&frame)
+            }
+        }
+    }
+
+    /// Record ring member `id` giving up waiting for a message.
+    pub fn timeout(&self, id: usize) -> Result<()> {
+        match self.format {
+            Format::Ndjson => self.write_line(
+                Value::object([("to", id.into()), ("timeout", true.into())])
+            ),
+            Format::Binary => {
+                let mut frame = vec![2];
+                put_usize(&mut frame, id);
+                self.write_frame(&frame)
+            }
+        }
+    }
+
+    fn write_line(&self, line: Value) -> Result<()> {
+        let mut out = self.lock()?;
+        writeln!(out, "{}", line)?;
+        Ok(())
+    }
+
+    fn write_frame(&self, frame: &[u8]) -> Result<()> {
+        let mut out = self.lock()?;
+        out.write_all(&(frame.len() as u32).to_le_bytes())?;
+        out.write_all(frame)?;
+        Ok(())
+    }
+
+    fn lock(&self) -> Result<MutexGuard<'_, BufWriter<File>>> {
+        self.out.lock().map_err(|_| Error::msg("Recorder lock poisoned"))
+    }
+}
+
+/// Read a recording in either format, rejecting it if its format version is
+/// not the one this build writes.
+pub fn read(path: &Path) -> Result<Vec<Record>> {
+    let contents = match fs::read(path) {
+        Ok(c) => c,
+        Err(e) => bail!("Error reading recording: {}", e),
+    };
+
+    match contents.strip_prefix(MAGIC) {
+        Some(rest) => read_binary(rest),
+        None => match String::from_utf8(contents) {
+            Ok(text) => read_ndjson(path, &text),
+            Err(_) => bail!("{} is not a ring recording", path.display()),
+        },
+    }
+}
+
+fn check_version(version: u64) -> Result<()> {
+    if version != FORMAT_VERSION {
+        bail!(
+            "Recording has format version {}, but this build reads version {}",
+            version, FORMAT_VERSION
+        );
+    }
+
+    Ok(())
+}
+
+fn read_binary(mut input: &[u8]) -> Result<Vec<Record>> {
+    let mut input = Decoder { buf: &mut input };
+    check_version(input.u32()? as u64)?;
+    let mut records = Vec::new();
+
+    while !input.buf.is_empty() {
+        let len = input.u32()? as usize;
+        let mut frame = input.take(len)?;
+        let mut frame = Decoder { buf: &mut frame };
+
+        let record = match frame.u8()? {
+            0 => Record::Member { id: frame.usize()?, msg: Msg::decode(&mut frame)? },
+            1 => Record::Sim { msg: SimMsg::decode(&mut frame)? },
+            2 => Record::Timeout { id: frame.usize()? },
+            t => bail!("frame {}: unknown recipient tag {}", records.len() + 1, t),
+        };
+
+        records.push(record);
+    }
+
+    Ok(records)
+}
+
+fn read_ndjson(path: &Path, contents: &str) -> Result<Vec<Record>> {
+    let mut lines = contents.lines().enumerate();
+
+    let header = match lines.next() {
+        Some((_, line)) => Value::parse(line).ok(),
+        None => None,
+    };
+
+    let header = match header {
+        Some(h) if h.get("format").and_then(Value::as_str) == Some(FORMAT_NAME) => h,
+        _ => bail!("{} is not a ring recording", path.display()),
+    };
+
+    match header.get("version").and_then(Value::as_u64) {
+        Some(v) => check_version(v)?,
+        None => bail!("Recording header is missing its format version"),
+    }
+
+    let mut records = Vec::new();
+
+    for (i, line) in lines {
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        let record = Value::parse(line)
+            .and_then(|v| Record::from_json(&v))
+            .map_err(|e| Error::msg(format!("line {}: {}", i + 1, e)))?;
+
+        records.push(record);
+    }
+
+    Ok(records)
+}
+
+impl Record {
+    /// The name of the recorded message's variant, telling messages to the
+    /// simulator apart.
+    pub fn kind(&self) -> String {
+        match self {
+            Record::Member { msg, .. } => msg.kind().to_string(),
+            Record::Sim { msg } => format!("{} (to sim)", msg.kind()),
+            Record::Timeout { .. } => "Timeout".to_string(),
+        }
+    }
+
+    fn from_json(v: &Value) -> Result<Self> {
+        if v.get("timeout").is_some() {
+            let id = v.get("to").and_then(Value::as_u64).ok_or(Error::msg("Invalid recipient"))?;
+            return Ok(Self::Timeout { id: id as usize });
+        }
+
+        let msg = v.get("msg").ok_or(Error::msg("Missing message"))?;
+
+        match v.get("to") {
+            Some(Value::String(s)) if s == "sim" => {
+                Ok(Self::Sim { msg: SimMsg::from_json(msg)? })
+            }
+            Some(to) => {
+                let id = to.as_u64().ok_or(Error::msg("Invalid recipient"))?;
+                Ok(Self::Member { id: id as usize, msg: Msg::from_json(msg)? })
+            }
+            None => bail!("Missing recipient"),
+        }
+    }
+}
+
+impl Msg {
+    pub(crate) fn to_json(&self) -> Value {
+        match self {
+            Msg::Ping { s_id } => Value::object([
+                ("type", "Ping".into()),
+                ("s_id", (*s_id).into()),
+            ]),
+            Msg::Pong => Value::object([("type", "Pong".into())]),
+            Msg::Election { body, keys, initiator, epoch } => Value::object([
+                ("type", "Election".into()),
+                ("body", votes_to_json(body)),
+                ("keys", Value::Array(keys.iter().map(|k| (*k).into()).collect())),
+                ("initiator", (*initiator).into()),
+                ("epoch", (*epoch).into()),
+            ]),
+            Msg::ElectionResult { id, initiator, votes, epoch } => Value::object([
+                ("type", "ElectionResult".into()),
+                ("id", (*id).into()),
+                ("initiator", (*initiator).into()),
+                ("votes", votes.as_deref().map(votes_to_json).unwrap_or(Value::Null)),
+                ("epoch", (*epoch).into()),
+            ]),
+            Msg::Candidate { id, initiator } => Value::object([
+                ("type", "Candidate".into()),
+                ("id", (*id).into()),
+                ("initiator", (*initiator).into()),
+            ]),
+            Msg::Probe { id, election, phase, hops, clockwise, initiator } => {
+                Value::object([
+                    ("type", "Probe".into()),
+                    ("id", (*id).into()),
+                    ("election", (*election).into()),
+                    ("phase", (*phase as usize).into()),
+                    ("hops", (*hops).into()),
+                    ("clockwise", (*clockwise).into()),
+                    ("initiator", (*initiator).into()),
+                ])
+            }
+            Msg::Reply { id, election, clockwise } => Value::object([
+                ("type", "Reply".into()),
+                ("id", (*id).into()),
+                ("election", (*election).into()),
+                ("clockwise", (*clockwise).into()),
+            ]),
+            Msg::BullyElection { s_id, initiator } => Value::object([
+                ("type", "BullyElection".into()),
+                ("s_id", (*s_id).into()),
+                ("initiator", (*initiator).into()),
+            ]),
+            Msg::Answer => Value::object([("type", "Answer".into())]),
+            Msg::Coordinator { id, initiator } => Value::object([
+                ("type", "Coordinator".into()),
+                ("id", (*id).into()),
+                ("initiator", (*initiator).into()),
+            ]),
+            Msg::MemberDown { id, origin, hops } => Value::object([
+                ("type", "MemberDown".into()),
+                ("id", (*id).into()),
+                ("origin", (*origin).into()),
+                ("hops", (*hops).into()),
+            ]),
+            Msg::MemberUp { id, hops } => Value::object([
+                ("type", "MemberUp".into()),
+                ("id", (*id).into()),
+                ("hops", (*hops).into()),
+            ]),
+            Msg::LeaseRenewal { id, hops } => Value::object([
+                ("type", "LeaseRenewal".into()),
+                ("id", (*id).into()),
+                ("hops", (*hops).into()),
+            ]),
+            Msg::PreVote { origin, coord_id, agreed, hops } => Value::object([
+                ("type", "PreVote".into()),
+                ("origin", (*origin).into()),
+                ("coord_id", (*coord_id).into()),
+                ("agreed", (*agreed).into()),
+                ("hops", (*hops).into()),
+            ]),
+            Msg::SimToggle { id, hops, seq } => Value::object([
+                ("type", "SimToggle".into()),
+                ("id", (*id).into()),
+                ("hops", (*hops).into()),
+                ("seq", (*seq).into()),
+            ]),
+            Msg::SimStatus => Value::object([("type", "SimStatus".into())]),
+            Msg::SimEnd => Value::object([("type", "SimEnd".into())]),
+            Msg::SimLeft { id } => Value::object([
+                ("type", "SimLeft".into()),
+                ("id", (*id).into()),
+            ]),
+            Msg::SimJoined { id } => Value::object([
+                ("type", "SimJoined".into()),
+                ("id", (*id).into()),
+            ]),
+            Msg::SimRestart => Value::object([("type", "SimRestart".into())]),
+            Msg::SimSlow { delay } => Value::object([
+                ("type", "SimSlow".into()),
+                ("delay", delay.to_string().into()),
+            ]),
+            Msg::SimPriority { priority } => Value::object([
+                ("type", "SimPriority".into()),
+                ("priority", (*priority).into()),
+            ]),
+            Msg::SimStepDown { epoch } => Value::object([
+                ("type", "SimStepDown".into()),
+                ("epoch", (*epoch).into()),
+            ]),
+        }
+    }
+
+    fn from_json(v: &Value) -> Result<Self> {
+        match field_str(v, "type")? {
+            "Ping" => Ok(Msg::Ping { s_id: field_usize(v, "s_id")? }),
+            "Pong" => Ok(Msg::Pong),
+            "Election" => {
+                let body = v.get("body").ok_or(Error::msg("Missing field body"))?;
+
+                let keys = v.get("keys")
+                    .and_then(Value::as_array)
+                    .ok_or(Error::msg("Missing field keys"))?
+                    .iter()
+                    .map(|k| k.as_u64().ok_or(Error::msg("Invalid ranking key")))
+                    .collect::<Result<Vec<_>>>()?;
+
+                Ok(Msg::Election {
+                    body: votes_from_json(body)?,
+                    keys,
+                    initiator: field_usize(v, "initiator")?,
+                    epoch: field_usize(v, "epoch")?,
+                })
+            }
+            "ElectionResult" => Ok(Msg::ElectionResult {
+                id: field_usize(v, "id")?,
+                initiator: field_usize(v, "initiator")?,
+                votes: optional_votes_from_json(v)?,
+                epoch: field_usize(v, "epoch")?,
+            }),
+            "Candidate" => Ok(Msg::Candidate {
+                id: field_usize(v, "id")?,
+                initiator: field_usize(v, "initiator")?,
+            }),
+            "Probe" => Ok(Msg::Probe {
+                id: field_usize(v, "id")?,
+                election: field_usize(v, "election")?,
+                phase: field_usize(v, "phase")? as u32,
+                hops: field_usize(v, "hops")?,
+                clockwise: field_bool(v, "clockwise")?,
+                initiator: field_usize(v, "initiator")?,
+            }),
+            "Reply" => Ok(Msg::Reply {
+                id: field_usize(v, "id")?,
+                election: field_usize(v, "election")?,
+                clockwise: field_bool(v, "clockwise")?,
+            }),
+            "BullyElection" => Ok(Msg::BullyElection {
+                s_id: field_usize(v, "s_id")?,
+                initiator: field_usize(v, "initiator")?,
+            }),
+            "Answer" => Ok(Msg::Answer),
+            "Coordinator" => Ok(Msg::Coordinator {
+                id: field_usize(v, "id")?,
+                initiator: field_usize(v, "initiator")?,
+            }),
+            "MemberDown" => Ok(Msg::MemberDown {
+                id: field_usize(v, "id")?,
+                origin: field_usize(v, "origin")?,
+                hops: field_usize(v, "hops")?,
+            }),
+            "MemberUp" => Ok(Msg::MemberUp {
+                id: field_usize(v, "id")?,
+                hops: field_usize(v, "hops")?,
+            }),
+            "LeaseRenewal" => Ok(Msg::LeaseRenewal {
+                id: field_usize(v, "id")?,
+                hops: field_usize(v, "hops")?,
+            }),
+            "PreVote" => Ok(Msg::PreVote {
+                origin: field_usize(v, "origin")?,
+                coord_id: field_usize(v, "coord_id")?,
+                agreed: field_usize(v, "agreed")?,
+                hops: field_usize(v, "hops")?,
+            }),
+            "SimToggle" => Ok(Msg::SimToggle {
+                id: field_usize(v, "id")?,
+                hops: field_usize(v, "hops")?,
+                seq: field_usize(v, "seq")?,
+            }),
+            "SimStatus" => Ok(Msg::SimStatus),
+            "SimEnd" => Ok(Msg::SimEnd),
+            "SimLeft" => Ok(Msg::SimLeft { id: field_usize(v, "id")? }),
+            "SimJoined" => Ok(Msg::SimJoined { id: field_usize(v, "id")? }),
+            "SimRestart" => Ok(Msg::SimRestart),
+            "SimSlow" => Ok(Msg::SimSlow { delay: field_str(v, "delay")?.parse()? }),
+            "SimPriority" => Ok(Msg::SimPriority {
+                priority: v.get("priority")
+                    .and_then(Value::as_u64)
+                    .ok_or(Error::msg("Missing field priority"))?,
+            }),
+            "SimStepDown" => Ok(Msg::SimStepDown { epoch: field_usize(v, "epoch")? }),
+            t => bail!("Unknown message type {}", t),
+        }
+    }
+}
+
+impl SimMsg {
+    pub(crate) fn to_json(&self) -> Value {
+        match self {
+            SimMsg::ConfirmToggle { id, active, seq } => Value::object([
+                ("type", "ConfirmToggle".into()),
+                ("id", (*id).into()),
+                ("active", (*active).into()),
+                ("seq", (*seq).into()),
+            ]),
+            SimMsg::Unreachable { id } => Value::object([
+                ("type", "Unreachable".into()),
+                ("id", (*id).into()),
+            ]),
+            SimMsg::ElectionResult { id, initiator, votes, epoch } => Value::object([
+                ("type", "ElectionResult".into()),
+                ("id", (*id).into()),
+                ("initiator", (*initiator).into()),
+                ("votes", votes.as_deref().map(votes_to_json).unwrap_or(Value::Null)),
+                ("epoch", (*epoch).into()),
+            ]),
+            SimMsg::Status(status) => Value::object([
+                ("type", "Status".into()),
+                ("id", status.id.into()),
+                ("label", match &status.label {
+                    Some(label) => label.as_str().into(),
+                    None => Value::Null,
+                }),
+                ("active", status.active.into()),
+                ("coord_id", status.coord_id.into()),
+                ("state", format!("{:?}", status.state).into()),
+                ("down", Value::Array(status.down.iter().map(|id| (*id).into()).collect())),
+            ]),
+            SimMsg::Violation { reason } => Value::object([
+                ("type", "Violation".into()),
+                ("reason", reason.as_str().into()),
+            ]),
+            SimMsg::Heartbeat { id } => Value::object([
+                ("type", "Heartbeat".into()),
+                ("id", (*id).into()),
+            ]),
+            SimMsg::Crashed { id, reason } => Value::object([
+                ("type", "Crashed".into()),
+                ("id", (*id).into()),
+                ("reason", reason.as_str().into()),
+            ]),
+            SimMsg::Respawned { id, reason } => Value::object([
+                ("type", "Respawned".into()),
+                ("id", (*id).into()),
+                ("reason", reason.as_str().into()),
+            ]),
+        }
+    }
+
+    fn from_json(v: &Value) -> Result<Self> {
+        match field_str(v, "type")? {
+            "ConfirmToggle" => Ok(SimMsg::ConfirmToggle {
+                id: field_usize(v, "id")?,
+                active: field_bool(v, "active")?,
+                seq: field_usize(v, "seq")?,
+            }),
+            "Unreachable" => Ok(SimMsg::Unreachable {
+                id: field_usize(v, "id")?,
+            }),
+            "ElectionResult" => Ok(SimMsg::ElectionResult {
+                id: field_usize(v, "id")?,
+                initiator: field_usize(v, "initiator")?,
+                votes: optional_votes_from_json(v)?,
+                epoch: field_usize(v, "epoch")?,
+            }),
+            "Status" => {
+                let state = match field_str(v, "state")? {
+                    "Follower" => State::Follower,
+                    "Participant" => State::Participant,
+                    "Coordinator" => State::Coordinator,
+                    s => bail!("Unknown member state {}", s),
+                };
+
+                Ok(SimMsg::Status(MemberStatus {
+                    id: field_usize(v, "id")?,
+                    label: v.get("label")
+                        .and_then(Value::as_str)
+                        .map(String::from),
+                    active: field_bool(v, "active")?,
+                    coord_id: field_usize(v, "coord_id")?,
+                    state,
+                    down: v.get("down")
+                        .and_then(Value::as_array)
+                        .ok_or(Error::msg("Missing field down"))?
+                        .iter()
+                        .map(|id| id.as_u64().map(|n| n as usize))
+                        .collect::<Option<Vec<_>>>()
+                        .ok_or(Error::msg("Invalid member in down"))?,
+                }))
+            }
+            "Violation" => Ok(SimMsg::Violation {
+                reason: field_str(v, "reason")?.to_string(),
+            }),
+            "Heartbeat" => Ok(SimMsg::Heartbeat { id: field_usize(v, "id")? }),
+            "Crashed" => Ok(SimMsg::Crashed {
+                id: field_usize(v, "id")?,
+                reason: field_str(v, "reason")?.to_string(),
+            }),
+            "Respawned" => Ok(SimMsg::Respawned {
+                id: field_usize(v, "id")?,
+                reason: field_str(v, "reason")?.to_string(),
+            }),
+            t => bail!("Unknown simulator message type {}", t),
+        }
+    }
+}
+
+fn votes_to_json(votes: &[bool]) -> Value {
+    Value::Array(votes.iter().map(|b| (*b).into()).collect())
+}
+
+fn votes_from_json(v: &Value) -> Result<Vec<bool>> {
+    v.as_array()
+        .ok_or(Error::msg("Invalid votes"))?
+        .iter()
+        .map(|b| b.as_bool().ok_or(Error::msg("Invalid vote")))
+        .collect()
+}
+
+/// The votes an election result may carry for auditing.
+fn optional_votes_from_json(v: &Value) -> Result<Option<Vec<bool>>> {
+    match v.get("votes") {
+        None | Some(Value::Null) => Ok(None),
+        Some(votes) => votes_from_json(votes).map(Some),
+    }
+}
+
+pub fn field_str<'a>(v: &'a Value, key: &str) -> Result<&'a str> {
+    v.get(key)
+        .and_then(Value::as_str)
+        .ok_or(Error::msg(format!("Missing field {}", key)))
+}
+
+pub fn field_usize(v: &Value, key: &str) -> Result<usize> {
+    v.get(key)
+        .and_then(Value::as_u64)
+        .map(|n| n as usize)
+        .ok_or(Error::msg(format!("Missing field {}", key)))
+}
+
+pub fn field_bool(v: &Value, key: &str) -> Result<bool> {
+    v.get(key)
+        .and_then(Value::as_bool)
+        .ok_or(Error::msg(format!("Missing field {}", key)))
+}
+
+impl Msg {
+    fn encode(&self, out: &mut Vec<u8>) {
+        match self {
+            Msg::Ping { s_id } => {
+                out.push(0);
+                put_usize(out, *s_id);
+            }
+            Msg::Pong => out.push(1),
+            Msg::Election { body, keys, initiator, epoch } => {
+                out.push(2);
+                put_votes(out, body);
+                put_usize(out, keys.len());
+                keys.iter().for_each(|k| out.extend(k.to_le_bytes()));
+                put_usize(out, *initiator);
+                put_usize(out, *epoch);
+            }
+            Msg::ElectionResult { id, initiator, votes, epoch } => {
+                out.push(3);
+                put_usize(out, *id);
+                put_usize(out, *initiator);
+                put_optional_votes(out, votes);
+                put_usize(out, *epoch);
+            }
+            Msg::SimToggle { id, hops, seq } => {
+                out.push(4);
+                put_usize(out, *id);
+                put_usize(out, *hops);
+                put_usize(out, *seq);
+            }
+            Msg::SimStatus => out.push(5),
+            Msg::SimEnd => out.push(6),
+            Msg::BullyElection { s_id, initiator } => {
+                out.push(7);
+                put_usize(out, *s_id);
+                put_usize(out, *initiator);
+            }
+            Msg::Answer => out.push(8),
+            Msg::Coordinator { id, initiator } => {
+                out.push(9);
+                put_usize(out, *id);
+                put_usize(out, *initiator);
+            }
+            Msg::Candidate { id, initiator } => {
+                out.push(10);
+                put_usize(out, *id);
+                put_usize(out, *initiator);
+            }
+            Msg::Probe { id, election, phase, hops, clockwise, initiator } => {
+                out.push(11);
+                put_usize(out, *id);
+                put_usize(out, *election);
+                out.extend(phase.to_le_bytes());
+                put_usize(out, *hops);
+                out.push(*clockwise as u8);
+                put_usize(out, *initiator);
+            }
+            Msg::Reply { id, election, clockwise } => {
+                out.push(12);
+                put_usize(out, *id);
+                put_usize(out, *election);
+                out.push(*clockwise as u8);
+            }
+            Msg::MemberDown { id, origin, hops } => {
+                out.push(13);
+                put_usize(out, *id);
+                put_usize(out, *origin);
+                put_usize(out, *hops);
+            }
+            Msg::MemberUp { id, hops } => {
+                out.push(14);
+                put_usize(out, *id);
+                put_usize(out, *hops);
+            }
+            Msg::LeaseRenewal { id, hops } => {
+                out.push(20);
+                put_usize(out, *id);
+                put_usize(out, *hops);
+            }
+            Msg::PreVote { origin, coord_id, agreed, hops } => {
+                out.push(22);
+                put_usize(out, *origin);
+                put_usize(out, *coord_id);
+                put_usize(out, *agreed);
+                put_usize(out, *hops);
+            }
+            Msg::SimLeft { id } => {
+                out.push(15);
+                put_usize(out, *id);
+            }
+            Msg::SimJoined { id } => {
+                out.push(16);
+                put_usize(out, *id);
+            }
+            Msg::SimRestart => out.push(17),
+            Msg::SimSlow { delay } => {
+                out.push(18);
+                put_str(out, &delay.to_string());
+            }
+            Msg::SimPriority { priority } => {
+                out.push(19);
+                out.extend(priority.to_le_bytes());
+            }
+            Msg::SimStepDown { epoch } => {
+                out.push(21);
+                put_usize(out, *epoch);
+            }
+        }
+    }
+
+    fn decode(input: &mut Decoder) -> Result<Self> {
+        match input.u8()? {
+            0 => Ok(Msg::Ping { s_id: input.usize()? }),
+            1 => Ok(Msg::Pong),
+            2 => Ok(Msg::Election {
+                body: input.votes()?,
+                keys: input.keys()?,
+                initiator: input.usize()?,
+                epoch: input.usize()?,
+            }),
+            3 => Ok(Msg::ElectionResult {
+                id: input.usize()?,
+                initiator: input.usize()?,
+                votes: input.optional_votes()?,
+                epoch: input.usize()?,
+            }),
+            4 => Ok(Msg::SimToggle {
+                id: input.usize()?,
+                hops: input.usize()?,
+                seq: input.usize()?,
+            }),
+            5 => Ok(Msg::SimStatus),
+            6 => Ok(Msg::SimEnd),
+            7 => Ok(Msg::BullyElection {
+                s_id: input.usize()?,
+                initiator: input.usize()?,
+            }),
+            8 => Ok(Msg::Answer),
+            9 => Ok(Msg::Coordinator {
+                id: input.usize()?,
+                initiator: input.usize()?,
+            }),
+            10 => Ok(Msg::Candidate {
+                id: input.usize()?,
+                initiator: input.usize()?,
+            }),
+            11 => Ok(Msg::Probe {
+                id: input.usize()?,
+                election: input.usize()?,
+                phase: input.u32()?,
+                hops: input.usize()?,
+                clockwise: input.u8()? != 0,
+                initiator: input.usize()?,
+            }),
+            12 => Ok(Msg::Reply {
+                id: input.usize()?,
+                election: input.usize()?,
+                clockwise: input.u8()? != 0,
+            }),
+            13 => Ok(Msg::MemberDown {
+                id: input.usize()?,
+                origin: input.usize()?,
+                hops: input.usize()?,
+            }),
+            14 => Ok(Msg::MemberUp {
+                id: input.usize()?,
+                hops: input.usize()?,
+            }),
+            15 => Ok(Msg::SimLeft { id: input.usize()? }),
+            16 => Ok(Msg::SimJoined { id: input.usize()? }),
+            17 => Ok(Msg::SimRestart),
+            18 => Ok(Msg::SimSlow { delay: input.str()?.parse()? }),
+            19 => Ok(Msg::SimPriority { priority: input.u64()? }),
+            20 => Ok(Msg::LeaseRenewal {
+                id: input.usize()?,
+                hops: input.usize()?,
+            }),
+            21 => Ok(Msg::SimStepDown { epoch: input.usize()? }),
+            22 => Ok(Msg::PreVote {
+                origin: input.usize()?,
+                coord_id: input.usize()?,
+                agreed: input.usize()?,
+                hops: input.usize()?,
+            }),
+            t => bail!("Unknown message tag {}", t),
+        }
+    }
+}
+
+impl SimMsg {
+    fn encode(&self, out: &mut Vec<u8>) {
+        match self {
+            SimMsg::ConfirmToggle { id, active, seq } => {
+                out.push(0);
+                put_usize(out, *id);
+                out.push(*active as u8);
+                put_usize(out, *seq);
+            }
+            SimMsg::Unreachable { id } => {
+                out.push(1);
+                put_usize(out, *id);
+            }
+            SimMsg::ElectionResult { id, initiator, votes, epoch } => {
+                out.push(2);
+                put_usize(out, *id);
+                put_usize(out, *initiator);
+                put_optional_votes(out, votes);
+                put_usize(out, *epoch);
+            }
+            SimMsg::Status(status) => {
+                out.push(3);
+                put_usize(out, status.id);
+
+                match &status.label {
+                    Some(label) => {
+                        out.push(1);
+                        put_str(out, label);
+                    }
+                    None => out.push(0),
+                }
+
+                out.push(status.active as u8);
+                put_usize(out, status.coord_id);
+
+                out.push(match status.state {
+                    State::Follower => 0,
+                    State::Participant => 1,
+                    State::Coordinator => 2,
+                });
+
+                put_usize(out, status.down.len());
+                status.down.iter().for_each(|id| put_usize(out, *id));
+            }
+            SimMsg::Violation { reason } => {
+                out.push(4);
+                put_str(out, reason);
+            }
+            SimMsg::Crashed { id, reason } => {
+                out.push(5);
+                put_usize(out, *id);
+                put_str(out, reason);
+            }
+            SimMsg::Heartbeat { id } => {
+                out.push(6);
+                put_usize(out, *id);
+            }
+            SimMsg::Respawned { id, reason } => {
+                out.push(7);
+                put_usize(out, *id);
+                put_str(out, reason);
+            }
+        }
+    }
+
+    fn decode(input: &mut Decoder) -> Result<Self> {
+        match input.u8()? {
+            0 => Ok(SimMsg::ConfirmToggle {
+                id: input.usize()?,
+                active: input.u8()? != 0,
+                seq: input.usize()?,
+            }),
+            1 => Ok(SimMsg::Unreachable { id: input.usize()? }),
+            2 => Ok(SimMsg::ElectionResult {
+                id: input.usize()?,
+                initiator: input.usize()?,
+                votes: input.optional_votes()?,
+                epoch: input.usize()?,
+            }),
+            3 => {
+                let id = input.usize()?;
+
+                let label = match input.u8()? {
+                    0 => None,
+                    _ => Some(input.str()?),
+                };
+
+                let active = input.u8()? != 0;
+                let coord_id = input.usize()?;
+
+                let state = match input.u8()? {
+                    0 => State::Follower,
+                    1 => State::Participant,
+                    2 => State::Coordinator,
+                    s => bail!("Unknown member state tag {}", s),
+                };
+
+                let down = (0..input.usize()?)
+                    .map(|_| input.usize())
+                    .collect::<Result<Vec<_>>>()?;
+
+                Ok(SimMsg::Status(MemberStatus { id, label, active, coord_id, state, down }))
+            }
+            4 => Ok(SimMsg::Violation { reason: input.str()? }),
+            5 => Ok(SimMsg::Crashed { id: input.usize()?, reason: input.str()? }),
+            6 => Ok(SimMsg::Heartbeat { id: input.usize()? }),
+            7 => Ok(SimMsg::Respawned { id: input.usize()?, reason: input.str()? }),
+            t => bail!("Unknown simulator message tag {}", t),
+        }
+    }
+}
+
+impl Msg {
+    /// The message in the binary encoding, as a transport sends it.
+    pub(crate) fn to_bytes(&self) -> Vec<u8> {
+        let mut out = Vec::new();
+        self.encode(&mut out);
+        out
+    }
+
+    pub(crate) fn from_bytes(mut bytes: &[u8]) -> Result<Self> {
+        Self::decode(&mut Decoder { buf: &mut bytes })
+    }
+}
+
+impl SimMsg {
+    /// The message in the binary encoding, as a transport sends it.
+    pub(crate) fn to_bytes(&self) -> Vec<u8> {
+        let mut out = Vec::new();
+        self.encode(&mut out);
+        out
+    }
+
+    pub(crate) fn from_bytes(mut bytes: &[u8]) -> Result<Self> {
+        Self::decode(&mut Decoder { buf: &mut bytes })
+    }
+}
+
+fn put_usize(out: &mut Vec<u8>, n: usize) {
+    out.extend((n as u32).to_le_bytes());
+}
+
+fn put_votes(out: &mut Vec<u8>, votes: &[bool]) {
+    put_usize(out, votes.len());
+    out.extend(votes.iter().map(|b| *b as u8));
+}
+
+fn put_optional_votes(out: &mut Vec<u8>, votes: &Option<Vec<bool>>) {
+    match votes {
+        Some(votes) => {
+            out.push(1);
+            put_votes(out, votes);
+        }
+        None => out.push(0),
+    }
+}
+
+fn put_str(out: &mut Vec<u8>, s: &str) {
+    put_usize(out, s.len());
+    out.extend(s.as_bytes());
+}
+
+/// Reads the binary encoding back, front to back.
+struct Decoder<'a, 'b> {
+    buf: &'b mut &'a [u8],
+}
+
+impl<'a> Decoder<'a, '_> {
+    fn take(&mut self, n: usize) -> Result<&'a [u8]> {
+        if self.buf.len() < n {
+            bail!("Recording ends in the middle of a frame");
+        }
+
+        let (head, tail) = self.buf.split_at(n);
+        *self.buf = tail;
+        Ok(head)
+    }
+
+    fn u8(&mut self) -> Result<u8> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u32(&mut self) -> Result<u32> {
+        let bytes = self.take(4)?;
+        Ok(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+    }
+
+    fn usize(&mut self) -> Result<usize> {
+        Ok(self.u32()? as usize)
+    }
+
+    fn votes(&mut self) -> Result<Vec<bool>> {
+        let n = self.usize()?;
+        Ok(self.take(n)?.iter().map(|b| *b != 0).collect())
+    }
+
+    fn u64(&mut self) -> Result<u64> {
+        let bytes = self.take(8)?;
+        Ok(u64::from_le_bytes(bytes.try_into()?))
+    }
+
+    fn keys(&mut self) -> Result<Vec<u64>> {
+        let n = self.usize()?;
+        (0..n).map(|_| self.u64()).collect()
+    }
+
+    fn optional_votes(&mut self) -> Result<Option<Vec<bool>>> {
+        match self.u8()? {
+            0 => Ok(None),
+            _ => self.votes().map(Some),
+        }
+    }
+
+    fn str(&mut self) -> Result<String> {
+        let n = self.usize()?;
+
+        match String::from_utf8(self.take(n)?.to_vec()) {
+            Ok(s) => Ok(s),
+            Err(_) => bail!("Invalid text in recording"),
+        }
+    }
+}
@@ -0,0 +1,114 @@
+//! Pictures of the ring printed among the rest of the output, for plain
+//! terminal logs to show what state the ring is in as it changes.
+//!
+//! Once the ring is created, and whenever a member is toggled or crashes,
+//! or the simulator learns who won an election, the ring is printed on a
+//! line of its own, clockwise from the first member: `[0*] -> (1) -> [2]`,
+//! with the coordinator starred and those down in parentheses.
+
+use std::fmt;
+
+use anyhow::{bail, Error, Result};
+
+use crate::clock::SharedClock;
+use crate::event::{Event, SharedSink, Sink, Source};
+use crate::sync::Mutex;
+
+/// How the ring is pictured after every change.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Render {
+    /// On a line of plain text.
+    Ascii,
+}
+
+impl std::str::FromStr for Render {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "ascii" => Ok(Self::Ascii),
+            _ => bail!("Unknown rendering {}", s),
+        }
+    }
+}
+
+/// The ring as pictured.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Ring {
+    /// Whether each member is up, by id.
+    active: Vec<bool>,
+    /// The coordinator according to the simulator.
+    coord_id: usize,
+}
+
+impl fmt::Display for Ring {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (id, active) in self.active.iter().enumerate() {
+            if id > 0 {
+                write!(f, " -> ")?;
+            }
+
+            let star = match id == self.coord_id {
+                true => "*",
+                false => "",
+            };
+
+            match active {
+                true => write!(f, "[{}{}]", id, star)?,
+                false => write!(f, "({}{})", id, star)?,
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Emits every event to an inner sink, printing the ring after those that
+/// change it.
+#[derive(Debug)]
+pub(crate) struct Ascii {
+    inner: SharedSink,
+    ring: Mutex<Ring>,
+}
+
+impl Ascii {
+    /// Picture a ring whose members start out as `active` says, by id,
+    /// with `coord_id` as the coordinator.
+    pub(crate) fn new(inner: SharedSink, active: Vec<bool>, coord_id: usize) -> Self {
+        Self { inner, ring: Mutex::new(Ring { active, coord_id }) }
+    }
+}
+
+impl Sink for Ascii {
+    fn emit(&self, source: Source, event: &Event) {
+        self.inner.emit(source, event);
+
+        let mut ring = self.ring.lock().unwrap_or_else(|e| e.into_inner());
+        let before = ring.clone();
+
+        match (source, event) {
+            (Source::Member(id), Event::Toggled(active)) if id < ring.active.len() => {
+                ring.active[id] = *active;
+            }
+            (Source::Member(id), Event::Crashed(_)) if id < ring.active.len() => {
+                ring.active[id] = false;
+            }
+            (Source::Sim, Event::ElectionWon { id, .. }) => ring.coord_id = *id,
+            _ => {}
+        }
+
+        // Printed under the lock, so pictures come out in the order the
+        // changes were made. The ring is pictured once to start with, too.
+        if *ring != before || (source, event) == (Source::Main, &Event::RingCreated) {
+            self.inner.print(format_args!("ring: {}\n", *ring));
+        }
+    }
+
+    fn holds_stdout(&self) -> bool {
+        self.inner.holds_stdout()
+    }
+
+    fn keep_time(&self, clock: &SharedClock) {
+        self.inner.keep_time(clock);
+    }
+}
@@ -0,0 +1,263 @@
+//! Replaying a recording: running every ring member again on the messages
+//! it was delivered, in the order it was delivered them, so that it does
+//! and prints what it did in the recorded run.
+//!
+//! Members take turns at their deliveries in the recording's order, each
+//! keeping its turn until it asks for its next, so that what they print
+//! comes in the same order every replay. Waiting takes no time: a member
+//! gives up waiting whenever the recording has it give up. Only the members
+//! are run again, not the simulator, whose part in the run is in its
+//! messages to them.
+//!
+//! A replay follows the recording only as far as the members do what they
+//! did, which takes the options the run was recorded with, its `--seed`
+//! included, and a ranking that doesn't depend on when members were seen
+//! alive. A member asking for a message it wasn't delivered, or stopping
+//! short of one it was, fails the replay. Nor can a run be replayed that
+//! had a member leave the ring and join it again.
+
+use std::cell::Cell;
+use std::sync::Arc;
+
+use anyhow::{bail, Result};
+use crossbeam::thread;
+
+use crate::clock::{MockClock, SharedClock};
+use crate::event::{Event, Source};
+use crate::record::Record;
+use crate::sync::channel::unbounded;
+use crate::sync::{Condvar, Mutex};
+use crate::transport::Transport;
+use crate::{Msg, RingBuilder, RingMember, SimConfig};
+
+/// A delivery to the member with the given id, or its giving up waiting for
+/// one.
+type Delivery = (usize, Option<Msg>);
+
+/// Whose turn it is at the recording's deliveries.
+#[derive(Debug, Default)]
+struct Turns {
+    turn: Mutex<Turn>,
+    changed: Condvar,
+}
+
+#[derive(Debug, Default)]
+struct Turn {
+    /// The delivery whose member may take it, or is handling it.
+    next: usize,
+    /// A member that stopped short of its deliveries, holding up those of
+    /// everyone else after them.
+    diverged: Option<usize>,
+}
+
+impl Turns {
+    /// Wait for delivery `i`'s turn.
+    fn wait(&self, i: usize) -> Result<()> {
+        let mut turn = self.turn.lock().unwrap_or_else(|e| e.into_inner());
+
+        loop {
+            if let Some(id) = turn.diverged {
+                bail!("Replay diverges from the recording at member {}", id);
+            }
+
+            if turn.next == i {
+                return Ok(());
+            }
+
+            turn = self.changed.wait(turn).unwrap_or_else(|e| e.into_inner());
+        }
+    }
+
+    fn update(&self, f: impl FnOnce(&mut Turn)) {
+        f(&mut self.turn.lock().unwrap_or_else(|e| e.into_inner()));
+        self.changed.notify_all();
+    }
+}
+
+/// Reaches a ring member with its recorded deliveries, and nobody else.
+#[derive(Debug)]
+struct Recorded {
+    id: usize,
+    ring_size: usize,
+    deliveries: Arc<Vec<Delivery>>,
+    /// Where this member's deliveries are among everyone's.
+    mine: Vec<usize>,
+    /// How many of them the member has taken.
+    taken: Cell<usize>,
+    /// Whether the member is handling the last delivery it took, and so
+    /// holding the turn.
+    holding: Cell<bool>,
+    turns: Arc<Turns>,
+}
+
+impl Recorded {
+    /// Take the member's next delivery once it's its turn, having passed
+    /// the turn on from the last.
+    fn take(&self) -> Result<Option<Msg>> {
+        self.pass();
+
+        let i = match self.mine.get(self.taken.get()) {
+            Some(i) => *i,
+            // A member told to stop may still wait on another, as it did
+            // when recorded, until the ring stops around it.
+            None if self.stopped() => bail!("Member {} was told to stop", self.id),
+            None => {
+                self.turns.update(|turn| {
+                    turn.diverged.get_or_insert(self.id);
+                });
+                bail!("The recording ends while member {} waits for a message", self.id);
+            }
+        };
+
+        self.turns.wait(i)?;
+        self.taken.set(self.taken.get() + 1);
+        self.holding.set(true);
+
+        Ok(self.deliveries[i].1.clone())
+    }
+
+    /// Whether the last delivery the member took told it to stop.
+    fn stopped(&self) -> bool {
+        let last = self.taken.get().checked_sub(1).map(|n| self.mine[n]);
+        matches!(last.map(|i| &self.deliveries[i].1), Some(Some(Msg::SimEnd)))
+    }
+
+    fn pass(&self) {
+        if self.holding.replace(false) {
+            self.turns.update(|turn| turn.next += 1);
+        }
+    }
+}
+
+impl Drop for Recorded {
+    fn drop(&mut self) {
+        self.pass();
+
+        if self.taken.get() < self.mine.len() {
+            self.turns.update(|turn| {
+                turn.diverged.get_or_insert(self.id);
+            });
+        }
+    }
+}
+
+impl Transport for Recorded {
+    fn ring_size(&self) -> usize {
+        self.ring_size
+    }
+
+    /// Messages sent are those the recording delivers to their recipients.
+    fn send_to(&self, _: usize, _: Msg) -> Result<()> {
+        Ok(())
+    }
+
+    fn recv(&self) -> Result<Msg> {
+        match self.take()? {
+            Some(msg) => Ok(msg),
+            None => bail!("Member {} waits for a message it gave up on when recorded", self.id),
+        }
+    }
+
+    fn recv_timeout(&self, _: std::time::Duration) -> Result<Option<Msg>> {
+        self.take()
+    }
+}
+
+impl RingBuilder {
+    /// Run every ring member again on its deliveries in `records`, in the
+    /// order they were made, and fail should a member stray from them.
+    pub fn replay(self, records: &[Record]) -> Result<()> {
+        if !self.nodes.is_empty() {
+            bail!("Only a ring spawned here can be replayed");
+        }
+
+        let deliveries = records.iter()
+            .filter_map(|record| match record {
+                Record::Member { id, msg } => Some((*id, Some(msg.clone()))),
+                Record::Timeout { id } => Some((*id, None)),
+                Record::Sim { .. } => None,
+            })
+            .collect::<Vec<_>>();
+
+        if let Some((id, _)) = deliveries.iter().find(|(id, _)| *id >= self.size) {
+            bail!("The recording delivers to member {}, beyond a ring of {}", id, self.size);
+        }
+
+        // The simulator tells a member nothing once it's told it to stop,
+        // unless the member joined the ring again, and so ran twice over.
+        for (i, (id, msg)) in deliveries.iter().enumerate() {
+            let rejoined = matches!(msg, Some(Msg::SimEnd))
+                && deliveries[i + 1..].iter()
+                    .any(|(to, msg)| to == id && msg.as_ref().is_some_and(Msg::is_sim));
+
+            if rejoined {
+                bail!(
+                    "The recording has member {} join the ring again, which can't be replayed",
+                    id
+                );
+            }
+        }
+
+        let deliveries = Arc::new(deliveries);
+        let turns = Arc::new(Turns::default());
+
+        // What a member waited on is in the recording, so no time passes.
+        let config = SimConfig {
+            clock: SharedClock::new(MockClock::new()),
+            ..self.config
+        };
+
+        // Members report to a simulator that isn't there, and never hear
+        // back from it but through the recording.
+        let (sim_s, _sim_r) = unbounded();
+
+        let res = thread::scope(|scope| {
+            for id in 0..self.size {
+                let mine = (0..deliveries.len())
+                    .filter(|i| deliveries[*i].0 == id)
+                    .collect::<Vec<_>>();
+
+                // A member never delivered anything never ran.
+                if mine.is_empty() {
+                    continue;
+                }
+
+                let transport = Recorded {
+                    id,
+                    ring_size: self.size,
+                    deliveries: deliveries.clone(),
+                    mine,
+                    taken: Cell::new(0),
+                    holding: Cell::new(false),
+                    turns: turns.clone(),
+                };
+
+                let mut member = RingMember::builder()
+                    .id(id)
+                    .transport(transport)
+                    .sim(sim_s.clone())
+                    .config(config.clone())
+                    .build();
+
+                let events = config.events.clone();
+
+                scope.spawn(move |_| {
+                    if let Err(e) = member.run() {
+                        events.emit(Source::Member(id), &Event::Stopped(e.to_string()));
+                    }
+                });
+            }
+        });
+
+        if res.is_err() {
+            bail!("A ring member panicked during the replay");
+        }
+
+        let turn = turns.turn.lock().unwrap_or_else(|e| e.into_inner());
+
+        match turn.diverged {
+            Some(id) => bail!("Replay diverges from the recording at member {}", id),
+            None => Ok(()),
+        }
+    }
+}
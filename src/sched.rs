@@ -0,0 +1,534 @@
+//! A discrete-event scheduler keeping virtual time for a whole simulation.
+//!
+//! Every thread taking part in the simulation, each ring member and the
+//! simulator, waits through the scheduler, whether for a message or for
+//! time to pass. Virtual time only moves once every one of them is waiting
+//! with nothing left to receive, and then jumps straight to the earliest
+//! deadline among them, so receive timeouts pass as instantly as sleeps do
+//! and a run takes only as long as handling its messages does.
+//!
+//! A deterministic scheduler also has them take turns: only one runs at a
+//! time, and whenever it waits, the next turn goes to one of those with a
+//! message to receive or a deadline due, chosen by the seed. Two runs with
+//! the same seed then interleave exactly alike.
+//!
+//! An exploring scheduler chooses the turns as a script says instead, and
+//! notes how many it could have chosen from, so that repeated runs can go
+//! through every interleaving in turn. It fingerprints the simulation at
+//! every choice by what each participant has received so far, and stops
+//! branching once it reaches a state an earlier run already went through.
+
+use std::cell::Cell;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashSet;
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use anyhow::{Error, Result};
+use crate::clock::Clock;
+use crate::rng::Rng;
+use crate::sync::channel::{unbounded, Receiver, Select, Sender, TryRecvError};
+use crate::sync::{thread, Mutex, MutexGuard};
+
+thread_local! {
+    /// The participant the current thread takes part as, if any.
+    static PART: Cell<Option<usize>> = const { Cell::new(None) };
+}
+
+/// The scheduler. Clones schedule the same simulation.
+#[derive(Clone)]
+pub struct Scheduler {
+    start: Instant,
+    state: Arc<Mutex<State>>,
+}
+
+struct State {
+    /// How much virtual time has passed.
+    now: Duration,
+    parts: Vec<Part>,
+    /// What picks whose turn is next, if participants take turns.
+    turns: Option<Turns>,
+}
+
+enum Turns {
+    Seeded(Rng),
+    Scripted(Script),
+}
+
+/// The turns an exploring scheduler takes.
+struct Script {
+    /// Which of those ready to go next to pick at each of the first
+    /// choices, the first of them after that.
+    prefix: Vec<usize>,
+    /// What was picked at each choice so far, and out of how many.
+    taken: Vec<(usize, usize)>,
+    /// The fingerprints of the states every run went through, shared
+    /// between runs.
+    visited: Arc<Mutex<HashSet<u64>>>,
+    /// Whether the run reached a state already visited, so that nothing
+    /// after it is worth exploring.
+    pruned: bool,
+}
+
+struct Part {
+    status: Status,
+    /// How many messages are waiting to be received.
+    pending: Box<dyn Fn() -> usize + Send>,
+    /// A hash of everything received so far, while exploring.
+    history: u64,
+    /// Bumped on every wait, so that a stale wake-up is told apart.
+    wait: u64,
+    wake_s: Sender<u64>,
+    wake_r: Receiver<u64>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Status {
+    Running,
+    /// Waiting until `deadline`, if any, or for a message, if `receiving`.
+    Waiting { deadline: Option<Duration>, receiving: bool },
+    Done,
+}
+
+impl fmt::Debug for Scheduler {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Scheduler").finish_non_exhaustive()
+    }
+}
+
+impl Default for Scheduler {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Scheduler {
+    pub fn new() -> Self {
+        Self::with_turns(None)
+    }
+
+    /// A scheduler having participants take turns, in an order picked by
+    /// `seed`.
+    pub fn deterministic(seed: u64) -> Self {
+        Self::with_turns(Some(Turns::Seeded(Rng::new(seed))))
+    }
+
+    /// A scheduler having participants take turns as `prefix` says, and
+    /// the first of them ready after that, skipping over the states in
+    /// `visited` and adding those it goes through.
+    pub(crate) fn exploring(prefix: Vec<usize>, visited: Arc<Mutex<HashSet<u64>>>) -> Self {
+        Self::with_turns(Some(Turns::Scripted(Script {
+            prefix, taken: Vec::new(), visited, pruned: false,
+        })))
+    }
+
+    /// What an exploring scheduler picked at each choice so far, and out of
+    /// how many, leaving out those past a state already visited.
+    pub(crate) fn taken(&self) -> Vec<(usize, usize)> {
+        match &self.lock().turns {
+            Some(Turns::Scripted(script)) => script.taken.clone(),
+            _ => Vec::new(),
+        }
+    }
+
+    /// Note `what` as received by the current thread's participant, for an
+    /// exploring scheduler to tell states apart by.
+    pub(crate) fn received(&self, what: &impl fmt::Debug) {
+        let part = match PART.with(Cell::get) {
+            Some(part) => part,
+            None => return,
+        };
+
+        let mut state = self.lock();
+
+        if let Some(Turns::Scripted(_)) = state.turns {
+            let mut hasher = DefaultHasher::new();
+            state.parts[part].history.hash(&mut hasher);
+            format!("{:?}", what).hash(&mut hasher);
+            state.parts[part].history = hasher.finish();
+        }
+    }
+
+    fn with_turns(turns: Option<Turns>) -> Self {
+        Self {
+            start: Instant::now(),
+            state: Arc::new(Mutex::new(State {
+                now: Duration::ZERO, parts: Vec::new(), turns,
+            })),
+        }
+    }
+
+    /// Add a participant, told how many messages are waiting for it by
+    /// `pending`, and return its number. It counts as running until it
+    /// first waits, so that time doesn't pass before it has started, or,
+    /// if participants take turns, waits for its first.
+    pub(crate) fn join(&self, pending: impl Fn() -> usize + Send + 'static) -> usize {
+        let mut state = self.lock();
+        let (wake_s, wake_r) = unbounded();
+
+        let status = match state.turns {
+            Some(_) => Status::Waiting { deadline: Some(state.now), receiving: false },
+            None => Status::Running,
+        };
+
+        state.parts.push(Part {
+            status,
+            pending: Box::new(pending),
+            history: 0,
+            wait: 0,
+            wake_s,
+            wake_r,
+        });
+
+        state.parts.len() - 1
+    }
+
+    /// Take part as participant `part` on the current thread until the
+    /// returned guard is dropped, once it's the participant's turn if they
+    /// take turns.
+    pub(crate) fn enter(&self, part: usize) -> Leave {
+        PART.with(|p| p.set(Some(part)));
+
+        let turn = {
+            let mut state = self.lock();
+            state.settle();
+            state.turns.as_ref().map(|_| state.parts[part].wake_r.clone())
+        };
+
+        if let Some(wake) = turn {
+            await_wake(&wake, 0);
+        }
+
+        Leave { sched: self.clone(), part }
+    }
+
+    /// Wait as the current thread's participant until one of `receivers`
+    /// may have a message or `deadline` has passed, returning whether it
+    /// hasn't. A thread not taking part waits in real time.
+    pub(crate) fn wait_until<T>(
+        &self, receivers: &[&Receiver<T>], deadline: Option<Instant>,
+    ) -> bool {
+        let part = match PART.with(Cell::get) {
+            Some(part) => part,
+            None => return wait_real(receivers, deadline),
+        };
+
+        let deadline = deadline.map(|d| d.saturating_duration_since(self.start));
+        let (wait, wake) = match self.begin(part, deadline, true) {
+            Some(begun) => begun,
+            None => return false,
+        };
+
+        // A turn comes with a message or a deadline passed, so the caller
+        // looks for the former and waits again to find out the latter.
+        if self.takes_turns() {
+            await_wake(&wake, wait);
+            return true;
+        }
+
+        let mut sel = Select::new();
+
+        for r in receivers {
+            sel.recv(r);
+        }
+
+        let woken = sel.recv(&wake);
+
+        loop {
+            let i = sel.ready();
+
+            if i != woken {
+                self.resume(part);
+                return true;
+            }
+
+            match wake.try_recv() {
+                Ok(w) if w == wait => return false,
+                _ => continue,
+            }
+        }
+    }
+
+    /// Wait as the current thread's participant until `deadline` passes,
+    /// or return `None` if it has already passed, returning the number of
+    /// the wait and where its wake-up comes.
+    fn begin(
+        &self, part: usize, deadline: Option<Duration>, receiving: bool,
+    ) -> Option<(u64, Receiver<u64>)> {
+        let mut state = self.lock();
+
+        if deadline.is_some_and(|d| d <= state.now) {
+            return None;
+        }
+
+        let p = &mut state.parts[part];
+        p.wait += 1;
+        p.status = Status::Waiting { deadline, receiving };
+        let begun = (p.wait, p.wake_r.clone());
+
+        state.settle();
+        Some(begun)
+    }
+
+    /// How much virtual time has passed, in microseconds.
+    pub(crate) fn micros(&self) -> u64 {
+        self.lock().now.as_micros() as u64
+    }
+
+    /// Count participant `part` as running again after a message came.
+    fn resume(&self, part: usize) {
+        let mut state = self.lock();
+        state.parts[part].status = Status::Running;
+    }
+
+    fn takes_turns(&self) -> bool {
+        self.lock().turns.is_some()
+    }
+
+    fn lock(&self) -> MutexGuard<'_, State> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+impl State {
+    /// Let virtual time pass to the earliest deadline, and wake whoever
+    /// waits on it, if every participant is waiting with nothing to
+    /// receive. If participants take turns, hand the next turn out instead
+    /// once nobody's running.
+    fn settle(&mut self) {
+        if self.turns.is_some() {
+            return self.take_turns();
+        }
+
+        let busy = self.parts.iter().any(|p| match p.status {
+            Status::Running => true,
+            Status::Waiting { receiving, .. } => receiving && (p.pending)() > 0,
+            Status::Done => false,
+        });
+
+        if busy {
+            return;
+        }
+
+        if !self.pass_time() {
+            return;
+        }
+
+        for p in &mut self.parts {
+            if let Status::Waiting { deadline: Some(d), .. } = p.status {
+                if d <= self.now {
+                    p.status = Status::Running;
+                    let _ = p.wake_s.send(p.wait);
+                }
+            }
+        }
+    }
+
+    /// Give the next turn to a participant with a message to receive or a
+    /// deadline due, letting time pass to the earliest deadline if there's
+    /// none, unless one is still running.
+    fn take_turns(&mut self) {
+        if self.parts.iter().any(|p| p.status == Status::Running) {
+            return;
+        }
+
+        let mut ready = self.ready();
+
+        if ready.is_empty() && self.pass_time() {
+            ready = self.ready();
+        }
+
+        if ready.is_empty() {
+            return;
+        }
+
+        let fingerprint = match self.turns {
+            Some(Turns::Scripted(_)) => self.fingerprint(),
+            _ => 0,
+        };
+
+        let pick = match &mut self.turns {
+            Some(Turns::Seeded(rng)) => rng.below(ready.len() as u64) as usize,
+            Some(Turns::Scripted(script)) => script.pick(ready.len(), fingerprint),
+            None => return,
+        };
+
+        let p = &mut self.parts[ready[pick]];
+        p.status = Status::Running;
+        let _ = p.wake_s.send(p.wait);
+    }
+
+    /// A hash of the state of the simulation, as far as the scheduler can
+    /// tell: the time, and what every participant has received and is
+    /// doing.
+    fn fingerprint(&self) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        self.now.hash(&mut hasher);
+
+        for p in &self.parts {
+            p.history.hash(&mut hasher);
+            format!("{:?}", p.status).hash(&mut hasher);
+            (p.pending)().hash(&mut hasher);
+        }
+
+        hasher.finish()
+    }
+
+    /// The participants waiting that have a message to receive or a
+    /// deadline due, in order.
+    fn ready(&self) -> Vec<usize> {
+        self.parts.iter()
+            .enumerate()
+            .filter(|(_, p)| match p.status {
+                Status::Waiting { deadline, receiving } => {
+                    receiving && (p.pending)() > 0
+                        || deadline.is_some_and(|d| d <= self.now)
+                }
+                _ => false,
+            })
+            .map(|(i, _)| i)
+            .collect()
+    }
+
+    /// Let virtual time pass to the earliest deadline, returning whether
+    /// there was one.
+    fn pass_time(&mut self) -> bool {
+        let next = self.parts.iter()
+            .filter_map(|p| match p.status {
+                Status::Waiting { deadline, .. } => deadline,
+                _ => None,
+            })
+            .min();
+
+        // Everyone waiting forever is a deadlock, as it would be in real
+        // time.
+        match next {
+            Some(next) => {
+                self.now = self.now.max(next);
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+impl Script {
+    /// Pick which of the `ready` participants goes next, in the state with
+    /// `fingerprint`.
+    fn pick(&mut self, ready: usize, fingerprint: u64) -> usize {
+        if let Some(pick) = self.prefix.get(self.taken.len()) {
+            let pick = (*pick).min(ready - 1);
+            self.taken.push((pick, ready));
+            return pick;
+        }
+
+        // Whatever follows a state visited before was or will be explored
+        // from there.
+        if self.pruned {
+            return 0;
+        }
+
+        if !self.visited.lock().unwrap_or_else(|e| e.into_inner()).insert(fingerprint) {
+            self.pruned = true;
+            return 0;
+        }
+
+        self.taken.push((0, ready));
+        0
+    }
+}
+
+/// Marks a participant done when dropped.
+pub(crate) struct Leave {
+    sched: Scheduler,
+    part: usize,
+}
+
+impl Drop for Leave {
+    fn drop(&mut self) {
+        PART.with(|p| p.set(None));
+        let mut state = self.sched.lock();
+        let part = &mut state.parts[self.part];
+        part.status = Status::Done;
+        // Let go of whatever the participant received on, so that sending
+        // to it fails as it would without the scheduler.
+        part.pending = Box::new(|| 0);
+        state.settle();
+    }
+}
+
+impl Clock for Scheduler {
+    fn now(&self) -> Instant {
+        self.start + self.lock().now
+    }
+
+    /// Wait until `duration` has passed in virtual time. A thread not
+    /// taking part sleeps in real time.
+    fn sleep(&self, duration: Duration) {
+        let part = match PART.with(Cell::get) {
+            Some(part) => part,
+            None => return thread::sleep(duration),
+        };
+
+        let deadline = self.lock().now + duration;
+
+        if let Some((wait, wake)) = self.begin(part, Some(deadline), false) {
+            await_wake(&wake, wait);
+        }
+    }
+
+    fn ring_now(&self) -> Instant {
+        self.now()
+    }
+}
+
+/// Receive the next message on `r`, waiting on `sched` until `deadline`, if
+/// given, and returning `None` once it has passed.
+pub(crate) fn recv_until<T: fmt::Debug>(
+    sched: &Scheduler, r: &Receiver<T>, deadline: Option<Instant>,
+) -> Result<Option<T>> {
+    loop {
+        match r.try_recv() {
+            Ok(msg) => {
+                sched.received(&msg);
+                return Ok(Some(msg));
+            }
+            Err(TryRecvError::Disconnected) => return Err(Error::msg("Channel disconnected")),
+            Err(TryRecvError::Empty) => {}
+        }
+
+        if !sched.wait_until(&[r], deadline) {
+            sched.received(&"timeout");
+            return Ok(None);
+        }
+    }
+}
+
+/// Wait for the wake-up ending wait number `wait`, skipping stale ones.
+fn await_wake(wake: &Receiver<u64>, wait: u64) {
+    while let Ok(w) = wake.recv() {
+        if w == wait {
+            return;
+        }
+    }
+}
+
+/// Wait in real time until one of `receivers` may have a message or
+/// `deadline` has passed, returning whether it hasn't.
+pub(crate) fn wait_real<T>(receivers: &[&Receiver<T>], deadline: Option<Instant>) -> bool {
+    let mut sel = Select::new();
+
+    for r in receivers {
+        sel.recv(r);
+    }
+
+    match deadline {
+        Some(deadline) => sel.ready_deadline(deadline).is_ok(),
+        None => {
+            sel.ready();
+            true
+        }
+    }
+}
@@ -0,0 +1,115 @@
+//! A tiny HTTP server reporting on a running simulation.
+//!
+//! `GET /status` returns the latest snapshot of the ring and `GET /metrics`
+//! the messages delivered and elections held so far, both as JSON. The
+//! simulator publishes a snapshot as the ring starts and a fresh one after
+//! every step.
+
+use std::net::TcpStream;
+use std::sync::{Arc, Mutex};
+
+use anyhow::Result;
+
+use crate::http::{self, error};
+use crate::json::Value;
+use crate::{MessageCounts, RingSnapshot};
+
+/// What the simulator last published about the ring.
+#[derive(Debug, Clone)]
+pub struct Board {
+    state: Arc<Mutex<Published>>,
+    counts: Arc<MessageCounts>,
+}
+
+#[derive(Debug, Default)]
+struct Published {
+    snapshot: Option<RingSnapshot>,
+    initiated: Vec<usize>,
+}
+
+impl Board {
+    /// Serve the board at `addr`, such as `127.0.0.1:8080`, reporting the
+    /// messages counted in `counts`.
+    pub fn serve(addr: &str, counts: Arc<MessageCounts>) -> Result<Self> {
+        let board = Self {
+            state: Arc::new(Mutex::new(Published::default())),
+            counts,
+        };
+
+        let served = board.clone();
+        http::serve(addr, "status", move |stream| served.answer(stream))?;
+        Ok(board)
+    }
+
+    /// Publish the latest snapshot of the ring and how many elections each
+    /// member was asked to start.
+    pub fn publish(&self, snapshot: RingSnapshot, initiated: Vec<usize>) {
+        if let Ok(mut state) = self.state.lock() {
+            state.snapshot = Some(snapshot);
+            state.initiated = initiated;
+        }
+    }
+
+    fn answer(&self, mut stream: TcpStream) -> Result<()> {
+        let (method, path) = http::request(&stream)?;
+
+        let (status, body) = match (method.as_str(), path.as_str()) {
+            ("GET", "/status") => ("200 OK", self.status()),
+            ("GET", "/metrics") => ("200 OK", self.metrics()),
+            ("GET", _) => ("404 Not Found", error("not found")),
+            _ => ("405 Method Not Allowed", error("method not allowed")),
+        };
+
+        http::respond(&mut stream, status, "application/json", &body.to_string())
+    }
+
+    fn status(&self) -> Value {
+        let state = match self.state.lock() {
+            Ok(state) => state,
+            Err(_) => return error("status lock poisoned"),
+        };
+
+        let snapshot = match &state.snapshot {
+            Some(snapshot) => snapshot,
+            None => return Value::Null,
+        };
+
+        let members = snapshot.members.iter()
+            .map(|m| Value::object([
+                ("id", m.id.into()),
+                ("label", match &m.label {
+                    Some(label) => label.as_str().into(),
+                    None => Value::Null,
+                }),
+                ("active", m.active.into()),
+                ("coord_id", m.coord_id.into()),
+                ("state", format!("{:?}", m.state).into()),
+                ("down", Value::Array(m.down.iter().map(|id| (*id).into()).collect())),
+            ]))
+            .collect();
+
+        Value::object([
+            ("coordinator", snapshot.coord_id.into()),
+            ("members", Value::Array(members)),
+        ])
+    }
+
+    fn metrics(&self) -> Value {
+        let initiated = match self.state.lock() {
+            Ok(state) => state.initiated.clone(),
+            Err(_) => return error("status lock poisoned"),
+        };
+
+        let messages = self.counts.totals()
+            .into_iter()
+            .map(|(name, n)| (name.to_string(), n.into()))
+            .collect();
+
+        Value::object([
+            ("messages", Value::Object(messages)),
+            ("elections", initiated.iter().sum::<usize>().into()),
+            ("initiated", Value::Array(initiated.into_iter().map(Value::from).collect())),
+        ])
+    }
+}
+
@@ -0,0 +1,119 @@
+//! Single-stepping a run: pausing before every message delivered to a ring
+//! member, showing it and what state every member is in, until told to go
+//! on, so that an election can be walked through message by message.
+//!
+//! What the stepper shows goes to stderr, out of the way of the run's own
+//! output wherever that goes. It reads a line before each delivery: an
+//! empty one or `step` delivers the message, and `continue`, or the end of
+//! the input, lets the run go on to the end without pausing again.
+
+use std::fmt;
+use std::io::{self, BufRead, BufReader};
+use std::sync::Arc;
+
+use anyhow::Result;
+
+use crate::sync::{Mutex, MutexGuard};
+use crate::{MemberStatus, Msg};
+
+/// Pauses a run before every delivery. Clones pause the same run.
+#[derive(Clone)]
+pub struct Stepper {
+    state: Arc<Mutex<Stepping>>,
+}
+
+struct Stepping {
+    input: Box<dyn BufRead + Send>,
+    /// Each ring member's state when it last waited for a message, by id.
+    statuses: Vec<Option<MemberStatus>>,
+    /// How many messages were delivered so far.
+    steps: usize,
+    /// Whether to keep pausing, until told to continue.
+    stepping: bool,
+}
+
+impl fmt::Debug for Stepper {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Stepper").finish_non_exhaustive()
+    }
+}
+
+impl Stepper {
+    /// A stepper reading what to do from stdin.
+    pub fn stdin() -> Self {
+        Self::new(BufReader::new(io::stdin()))
+    }
+
+    /// A stepper reading what to do from `input`, a line at a time.
+    pub fn new(input: impl BufRead + Send + 'static) -> Self {
+        let stepping = Stepping {
+            input: Box::new(input),
+            statuses: Vec::new(),
+            steps: 0,
+            stepping: true,
+        };
+
+        Self { state: Arc::new(Mutex::new(stepping)) }
+    }
+
+    /// Note the state of a ring member about to wait for a message.
+    pub(crate) fn waiting(&self, status: MemberStatus) {
+        let mut state = self.lock();
+        let id = status.id;
+
+        if state.statuses.len() <= id {
+            state.statuses.resize(id + 1, None);
+        }
+
+        state.statuses[id] = Some(status);
+    }
+
+    /// Pause before delivering `msg` to ring member `id`, until told to go
+    /// on.
+    pub(crate) fn deliver(&self, id: usize, msg: &Msg) -> Result<()> {
+        let mut state = self.lock();
+        state.steps += 1;
+
+        if !state.stepping {
+            return Ok(());
+        }
+
+        eprintln!("step {}: {} is delivered {:?}", state.steps, id, msg);
+
+        for status in state.statuses.iter().flatten() {
+            match &status.label {
+                Some(label) => eprint!("  {} ({})", status.id, label),
+                None => eprint!("  {}", status.id),
+            }
+
+            eprintln!(
+                ": active = {}, coord_id = {}, state = {:?}",
+                status.active, status.coord_id, status.state
+            );
+        }
+
+        loop {
+            eprint!("[Enter or step to deliver it, continue to run to the end] ");
+            let mut line = String::new();
+
+            if state.input.read_line(&mut line)? == 0 {
+                eprintln!();
+                state.stepping = false;
+                return Ok(());
+            }
+
+            match line.trim() {
+                "" | "step" => return Ok(()),
+                "continue" => {
+                    state.stepping = false;
+                    return Ok(());
+                }
+                other => eprintln!("Unknown command {}", other),
+            }
+        }
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Stepping> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
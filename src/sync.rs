@@ -0,0 +1,417 @@
+//! The primitives ring members synchronize through: locks, atomics,
+//! sleeps and channels. The ring takes them from here rather than from
+//! `std` and crossbeam, so that building with `--cfg loom` swaps them for
+//! those of [loom](https://docs.rs/loom), whose model checker runs a test
+//! under every interleaving of its threads.
+//!
+//! Loom has no channels of crossbeam's kind, so under loom they're queues
+//! behind one of its locks, doing what the ring uses crossbeam's for. Nor
+//! does time pass under loom: a sleep yields to another thread, and a
+//! receive with a deadline times out whenever nothing is waiting to be
+//! received, so that a reply comes either side of its sender's timeout in
+//! one interleaving or another.
+//!
+//! `Arc` is always `std`'s, which unlike loom's holds trait objects such as
+//! the shared sinks, and so are the threads a ring is run on and those
+//! talking over sockets. A loom model spawns ring members over
+//! [`Channels`](crate::transport::Channels) on threads of its own, as this
+//! module's test does, run by `RUSTFLAGS="--cfg loom" cargo test --lib`;
+//! the crate's other tests run outside loom.
+//!
+//! Even two members interleave in more ways than loom gets through
+//! quickly, so the test bounds preemptions at two.
+
+#[cfg(not(loom))]
+pub use std::sync::atomic::{AtomicUsize, Ordering};
+#[cfg(not(loom))]
+pub use std::sync::{Condvar, Mutex, MutexGuard};
+
+#[cfg(loom)]
+pub use loom::sync::atomic::{AtomicUsize, Ordering};
+#[cfg(loom)]
+pub use loom::sync::{Condvar, Mutex, MutexGuard};
+
+pub mod thread {
+    #[cfg(not(loom))]
+    pub use std::thread::sleep;
+
+    /// Let another thread run, as no time passes under loom.
+    #[cfg(loom)]
+    pub fn sleep(_: std::time::Duration) {
+        loom::thread::yield_now();
+    }
+}
+
+#[cfg(not(loom))]
+pub mod channel {
+    pub use crossbeam::channel::{
+        bounded, unbounded, Receiver, RecvTimeoutError, Select, Sender, TryRecvError,
+    };
+}
+
+#[cfg(loom)]
+pub mod channel {
+    use std::collections::VecDeque;
+    use std::fmt;
+    use std::sync::Arc;
+    use std::time::{Duration, Instant};
+
+    pub use crossbeam::channel::{
+        ReadyTimeoutError, RecvError, RecvTimeoutError, SendError, TryRecvError, TrySendError,
+    };
+    use loom::sync::{Condvar, Mutex, MutexGuard};
+
+    struct Chan<T> {
+        queue: Mutex<Queue<T>>,
+        /// Notified of every change to the queue.
+        changed: Condvar,
+        capacity: Option<usize>,
+    }
+
+    struct Queue<T> {
+        msgs: VecDeque<T>,
+        senders: usize,
+        receivers: usize,
+        /// The selects waiting on the channel, notified along with
+        /// `changed`.
+        selects: Vec<Arc<Signal>>,
+    }
+
+    impl<T> Chan<T> {
+        fn lock(&self) -> MutexGuard<'_, Queue<T>> {
+            self.queue.lock().unwrap_or_else(|e| e.into_inner())
+        }
+
+        fn wait<'a>(&self, queue: MutexGuard<'a, Queue<T>>) -> MutexGuard<'a, Queue<T>> {
+            self.changed.wait(queue).unwrap_or_else(|e| e.into_inner())
+        }
+
+        /// Change the queue and let everyone waiting on it know.
+        fn change<R>(&self, f: impl FnOnce(&mut Queue<T>) -> R) -> R {
+            let mut queue = self.lock();
+            let res = f(&mut queue);
+            queue.selects.iter().for_each(|s| s.notify());
+            self.changed.notify_all();
+            res
+        }
+    }
+
+    impl<T> Queue<T> {
+        fn ready(&self) -> bool {
+            !self.msgs.is_empty() || self.senders == 0
+        }
+    }
+
+    /// Wakes a select up.
+    #[derive(Default)]
+    struct Signal {
+        set: Mutex<bool>,
+        cond: Condvar,
+    }
+
+    impl Signal {
+        fn notify(&self) {
+            *self.set.lock().unwrap_or_else(|e| e.into_inner()) = true;
+            self.cond.notify_all();
+        }
+
+        /// Wait until notified since last woken.
+        fn wait(&self) {
+            let mut set = self.set.lock().unwrap_or_else(|e| e.into_inner());
+
+            while !*set {
+                set = self.cond.wait(set).unwrap_or_else(|e| e.into_inner());
+            }
+
+            *set = false;
+        }
+    }
+
+    pub struct Sender<T>(Arc<Chan<T>>);
+
+    pub struct Receiver<T>(Arc<Chan<T>>);
+
+    /// Create a channel holding any number of messages.
+    pub fn unbounded<T>() -> (Sender<T>, Receiver<T>) {
+        channel(None)
+    }
+
+    /// Create a channel holding up to `capacity` messages, and at least
+    /// one, as a rendezvous is beyond a queue.
+    pub fn bounded<T>(capacity: usize) -> (Sender<T>, Receiver<T>) {
+        channel(Some(capacity.max(1)))
+    }
+
+    fn channel<T>(capacity: Option<usize>) -> (Sender<T>, Receiver<T>) {
+        let queue = Queue { msgs: VecDeque::new(), senders: 1, receivers: 1, selects: vec![] };
+        let chan = Chan { queue: Mutex::new(queue), changed: Condvar::new(), capacity };
+        let chan = Arc::new(chan);
+        (Sender(chan.clone()), Receiver(chan))
+    }
+
+    impl<T> Sender<T> {
+        /// Send `msg`, waiting for room while the channel is full.
+        pub fn send(&self, msg: T) -> Result<(), SendError<T>> {
+            let mut queue = self.0.lock();
+
+            while queue.receivers > 0 && self.full(&queue) {
+                queue = self.0.wait(queue);
+            }
+
+            drop(queue);
+            self.try_send(msg).map_err(|e| SendError(e.into_inner()))
+        }
+
+        pub fn try_send(&self, msg: T) -> Result<(), TrySendError<T>> {
+            self.0.change(|queue| {
+                if queue.receivers == 0 {
+                    Err(TrySendError::Disconnected(msg))
+                } else if self.full(queue) {
+                    Err(TrySendError::Full(msg))
+                } else {
+                    queue.msgs.push_back(msg);
+                    Ok(())
+                }
+            })
+        }
+
+        fn full(&self, queue: &Queue<T>) -> bool {
+            self.0.capacity.is_some_and(|c| queue.msgs.len() >= c)
+        }
+    }
+
+    impl<T> Receiver<T> {
+        pub fn try_recv(&self) -> Result<T, TryRecvError> {
+            self.0.change(|queue| match queue.msgs.pop_front() {
+                Some(msg) => Ok(msg),
+                None if queue.senders == 0 => Err(TryRecvError::Disconnected),
+                None => Err(TryRecvError::Empty),
+            })
+        }
+
+        pub fn recv(&self) -> Result<T, RecvError> {
+            let mut queue = self.0.lock();
+
+            while !queue.ready() {
+                queue = self.0.wait(queue);
+            }
+
+            drop(queue);
+            self.try_recv().map_err(|_| RecvError)
+        }
+
+        pub fn recv_timeout(&self, _: Duration) -> Result<T, RecvTimeoutError> {
+            self.try_recv().map_err(|e| match e {
+                TryRecvError::Empty => RecvTimeoutError::Timeout,
+                TryRecvError::Disconnected => RecvTimeoutError::Disconnected,
+            })
+        }
+
+        pub fn recv_deadline(&self, _: Instant) -> Result<T, RecvTimeoutError> {
+            self.recv_timeout(Duration::ZERO)
+        }
+
+        pub fn len(&self) -> usize {
+            self.0.lock().msgs.len()
+        }
+
+        pub fn is_empty(&self) -> bool {
+            self.len() == 0
+        }
+    }
+
+    impl<T> IntoIterator for Receiver<T> {
+        type Item = T;
+        type IntoIter = IntoIter<T>;
+
+        fn into_iter(self) -> IntoIter<T> {
+            IntoIter(self)
+        }
+    }
+
+    /// Receives until every sender is gone.
+    pub struct IntoIter<T>(Receiver<T>);
+
+    impl<T> Iterator for IntoIter<T> {
+        type Item = T;
+
+        fn next(&mut self) -> Option<T> {
+            self.0.recv().ok()
+        }
+    }
+
+    impl<T> Clone for Sender<T> {
+        fn clone(&self) -> Self {
+            self.0.change(|queue| queue.senders += 1);
+            Self(self.0.clone())
+        }
+    }
+
+    impl<T> Clone for Receiver<T> {
+        fn clone(&self) -> Self {
+            self.0.change(|queue| queue.receivers += 1);
+            Self(self.0.clone())
+        }
+    }
+
+    impl<T> Drop for Sender<T> {
+        fn drop(&mut self) {
+            self.0.change(|queue| queue.senders -= 1);
+        }
+    }
+
+    impl<T> Drop for Receiver<T> {
+        fn drop(&mut self) {
+            self.0.change(|queue| queue.receivers -= 1);
+        }
+    }
+
+    impl<T> fmt::Debug for Sender<T> {
+        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            f.pad("Sender { .. }")
+        }
+    }
+
+    impl<T> fmt::Debug for Receiver<T> {
+        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            f.pad("Receiver { .. }")
+        }
+    }
+
+    /// A channel a `Select` waits on.
+    trait Watched {
+        fn ready(&self) -> bool;
+
+        /// Have `signal` notified of changes to the channel, or no longer
+        /// unless `watch`.
+        fn watch(&self, signal: &Arc<Signal>, watch: bool);
+    }
+
+    impl<T> Watched for Receiver<T> {
+        fn ready(&self) -> bool {
+            self.0.lock().ready()
+        }
+
+        fn watch(&self, signal: &Arc<Signal>, watch: bool) {
+            let selects = &mut self.0.lock().selects;
+
+            if watch {
+                selects.push(signal.clone());
+            } else {
+                selects.retain(|s| !Arc::ptr_eq(s, signal));
+            }
+        }
+    }
+
+    /// Waits until one of several receivers has a message.
+    #[derive(Default)]
+    pub struct Select<'a> {
+        receivers: Vec<&'a dyn Watched>,
+    }
+
+    impl<'a> Select<'a> {
+        pub fn new() -> Self {
+            Self::default()
+        }
+
+        /// Wait on `r` too, returning its index among those waited on.
+        pub fn recv<T>(&mut self, r: &'a Receiver<T>) -> usize {
+            self.receivers.push(r);
+            self.receivers.len() - 1
+        }
+
+        /// Wait until one of the receivers is ready, returning its index.
+        pub fn ready(&mut self) -> usize {
+            let signal = Arc::new(Signal::default());
+            self.receivers.iter().for_each(|r| r.watch(&signal, true));
+
+            let i = loop {
+                match self.receivers.iter().position(|r| r.ready()) {
+                    Some(i) => break i,
+                    None => signal.wait(),
+                }
+            };
+
+            self.receivers.iter().for_each(|r| r.watch(&signal, false));
+            i
+        }
+
+        pub fn ready_deadline(&mut self, _: Instant) -> Result<usize, ReadyTimeoutError> {
+            self.receivers.iter().position(|r| r.ready()).ok_or(ReadyTimeoutError)
+        }
+    }
+}
+
+#[cfg(all(test, loom))]
+mod tests {
+    use std::sync::atomic::{self, AtomicBool};
+    use std::sync::Arc;
+
+    use super::{channel, Mutex};
+    use crate::golden::Collect;
+    use crate::transport::{Channels, Link};
+    use crate::{Algorithm, Msg, RingMember, SharedSink, SimConfig, SimMsg};
+
+    /// The stack each member's loom thread is given, in bytes.
+    const MEMBER_STACK: usize = 1 << 20;
+
+    /// Member 0 pings member 1 before passing it the election, and its
+    /// pong comes either before the ping times out or after, when member 0
+    /// has given up on it and elected itself. Either way the election ends
+    /// in a result.
+    #[test]
+    fn an_election_ends_whichever_side_of_the_ping_timeout_the_pong_comes() {
+        // Whether member 0 found member 1 down in some interleaving, and up
+        // in some other.
+        static FOUND: [AtomicBool; 2] = [AtomicBool::new(false), AtomicBool::new(false)];
+
+        let mut model = loom::model::Builder::new();
+        model.preemption_bound = Some(2);
+
+        model.check(|| {
+            let (links, rs): (Vec<_>, Vec<_>) =
+                (0..2).map(|_| Link::new(Algorithm::Ring, None)).unzip();
+            let (sim_s, sim_r) = channel::unbounded();
+            let lines = Arc::new(Mutex::new(Vec::new()));
+            let events = SharedSink::new(Collect(lines.clone()));
+            let config = SimConfig { events, ..SimConfig::default() };
+
+            let members = rs.into_iter().enumerate().map(|(id, r)| {
+                let peers = links.iter().cloned().enumerate().filter(|(i, _)| *i != id);
+                let mut member = RingMember::builder()
+                    .id(id)
+                    .transport(Channels::new(peers.collect(), r))
+                    .sim(sim_s.clone())
+                    .config(config.clone())
+                    .build();
+
+                // Polling a member's task goes deeper than loom's little
+                // default stack allows.
+                loom::thread::Builder::new()
+                    .stack_size(MEMBER_STACK)
+                    .spawn(move || member.run())
+                    .unwrap()
+            }).collect::<Vec<_>>();
+
+            links[0].send(Msg::election(0, 2, 1)).unwrap();
+
+            let winner = loop {
+                if let SimMsg::ElectionResult { id, .. } = sim_r.recv().unwrap() {
+                    break id;
+                }
+            };
+
+            assert!(winner < 2, "{} won a ring of 2", winner);
+
+            for (link, member) in links.iter().zip(members) {
+                link.send(Msg::SimEnd).unwrap();
+                member.join().unwrap().unwrap();
+            }
+
+            let up = lines.lock().unwrap().iter().any(|line| line == "0: 1 is active");
+            FOUND[up as usize].store(true, atomic::Ordering::Relaxed);
+        });
+
+        assert!(FOUND.iter().all(|found| found.load(atomic::Ordering::Relaxed)));
+    }
+}
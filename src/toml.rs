@@ -0,0 +1,405 @@
+//! A minimal TOML reader, covering what configuration and sequence files
+//! need: keys set to strings, integers, floats, booleans, arrays or inline
+//! tables of them, grouped under `[table]` headers, and arrays of tables
+//! under `[[array]]` headers.
+//!
+//! Multi-line strings, dates and nested table headers aren't read.
+
+use std::fmt;
+use std::iter::Peekable;
+use std::str::Chars;
+
+use anyhow::{bail, Error, Result};
+
+use crate::json;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    String(String),
+    Integer(i64),
+    Float(f64),
+    Bool(bool),
+    Array(Vec<Value>),
+    /// Table members, in the order they were written.
+    Table(Vec<(String, Value)>),
+}
+
+impl Value {
+    /// The value as JSON, for reading alike whichever of the two it was
+    /// written in.
+    pub(crate) fn to_json(&self) -> json::Value {
+        match self {
+            Self::String(s) => json::Value::String(s.clone()),
+            Self::Integer(n) => json::Value::Number(*n as f64),
+            Self::Float(n) => json::Value::Number(*n),
+            Self::Bool(b) => json::Value::Bool(*b),
+            Self::Array(items) => json::Value::Array(items.iter().map(Self::to_json).collect()),
+            Self::Table(members) => json::Value::Object(
+                members.iter().map(|(k, v)| (k.clone(), v.to_json())).collect()
+            ),
+        }
+    }
+}
+
+impl fmt::Display for Value {
+    /// The value as given on the command line: strings bare and arrays
+    /// comma-separated.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::String(s) => write!(f, "{}", s),
+            Self::Integer(n) => write!(f, "{}", n),
+            Self::Float(n) => write!(f, "{}", n),
+            Self::Bool(b) => write!(f, "{}", b),
+            Self::Array(items) => {
+                for (i, item) in items.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ",")?;
+                    }
+
+                    write!(f, "{}", item)?;
+                }
+
+                Ok(())
+            }
+            Self::Table(members) => {
+                for (i, (k, v)) in members.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ",")?;
+                    }
+
+                    write!(f, "{}={}", k, v)?;
+                }
+
+                Ok(())
+            }
+        }
+    }
+}
+
+/// Read a TOML document into its keys and values, in the order they were
+/// written. Keys under a table are prefixed with its name and a dot, as in
+/// `sequence.waits`, while an array of tables is set as an array under its
+/// name.
+pub fn parse(s: &str) -> Result<Vec<(String, Value)>> {
+    let mut parser = Parser { chars: s.chars().peekable(), line: 1 };
+    let mut entries: Vec<(String, Value)> = Vec::new();
+    // The table keys are being set in, and whether it's the last of an
+    // array of them.
+    let mut table = (String::new(), false);
+
+    loop {
+        parser.skip_blank();
+
+        if parser.chars.peek().is_none() {
+            return Ok(entries);
+        }
+
+        if let Some('[') = parser.chars.peek() {
+            parser.chars.next();
+            let array = parser.chars.next_if_eq(&'[').is_some();
+            table = (parser.key()?, array);
+            parser.expect(']')?;
+
+            if array {
+                parser.expect(']')?;
+                let tables = entry(&mut entries, &table.0, || Value::Array(Vec::new()));
+
+                match tables {
+                    Value::Array(tables) => tables.push(Value::Table(Vec::new())),
+                    _ => bail!("line {}: {} is not an array of tables", parser.line, table.0),
+                }
+            }
+
+            parser.end_of_line()?;
+            continue;
+        }
+
+        let key = parser.key()?;
+        parser.expect('=')?;
+        let value = parser.value()?;
+        parser.end_of_line()?;
+
+        let members = match &table {
+            (name, true) => match entry(&mut entries, name, || Value::Array(Vec::new())) {
+                Value::Array(tables) => match tables.last_mut() {
+                    Some(Value::Table(members)) => members,
+                    _ => bail!("line {}: {} is not an array of tables", parser.line, name),
+                },
+                _ => bail!("line {}: {} is not an array of tables", parser.line, name),
+            },
+            (name, false) => {
+                let key = match name.is_empty() {
+                    true => key,
+                    false => format!("{}.{}", name, key),
+                };
+
+                set(&mut entries, key, value, parser.line)?;
+                continue;
+            }
+        };
+
+        set(members, key, value, parser.line)?;
+    }
+}
+
+/// The value set for `key`, set to `default()` first if it's not.
+fn entry<'a>(
+    entries: &'a mut Vec<(String, Value)>, key: &str, default: impl FnOnce() -> Value,
+) -> &'a mut Value {
+    let i = match entries.iter().position(|(k, _)| k == key) {
+        Some(i) => i,
+        None => {
+            entries.push((key.to_string(), default()));
+            entries.len() - 1
+        }
+    };
+
+    &mut entries[i].1
+}
+
+/// Set `key` to `value`, unless it was already set.
+fn set(entries: &mut Vec<(String, Value)>, key: String, value: Value, line: usize) -> Result<()> {
+    if entries.iter().any(|(k, _)| *k == key) {
+        bail!("line {}: {} is set twice", line, key);
+    }
+
+    entries.push((key, value));
+    Ok(())
+}
+
+struct Parser<'a> {
+    chars: Peekable<Chars<'a>>,
+    /// The line being read, to point at errors.
+    line: usize,
+}
+
+impl Parser<'_> {
+    fn next(&mut self) -> Option<char> {
+        let c = self.chars.next();
+
+        if c == Some('\n') {
+            self.line += 1;
+        }
+
+        c
+    }
+
+    /// Skip spaces and tabs, but not the end of the line.
+    fn skip_ws(&mut self) {
+        while let Some(' ' | '\t') = self.chars.peek() {
+            self.chars.next();
+        }
+    }
+
+    /// Skip whitespace, newlines and comments.
+    fn skip_blank(&mut self) {
+        loop {
+            match self.chars.peek() {
+                Some(c) if c.is_whitespace() => {
+                    self.next();
+                }
+                Some('#') => self.skip_comment(),
+                _ => return,
+            }
+        }
+    }
+
+    fn skip_comment(&mut self) {
+        while let Some(c) = self.chars.peek() {
+            if *c == '\n' {
+                break;
+            }
+
+            self.chars.next();
+        }
+    }
+
+    fn expect(&mut self, expected: char) -> Result<()> {
+        self.skip_ws();
+
+        match self.next() {
+            Some(c) if c == expected => Ok(()),
+            Some(c) => bail!("line {}: expected '{}', found '{}'", self.line, expected, c),
+            None => bail!("line {}: expected '{}', found end of input", self.line, expected),
+        }
+    }
+
+    /// Expect nothing but a comment before the end of the line.
+    fn end_of_line(&mut self) -> Result<()> {
+        self.skip_ws();
+
+        match self.chars.peek() {
+            None | Some('\n') => Ok(()),
+            Some('\r') => Ok(()),
+            Some('#') => {
+                self.skip_comment();
+                Ok(())
+            }
+            Some(c) => bail!("line {}: unexpected '{}' after value", self.line, c),
+        }
+    }
+
+    /// A bare or quoted key, dotted keys joined back with dots.
+    fn key(&mut self) -> Result<String> {
+        let mut parts = Vec::new();
+
+        loop {
+            self.skip_ws();
+
+            let part = match self.chars.peek() {
+                Some('"') => self.basic_string()?,
+                Some('\'') => self.literal_string()?,
+                _ => {
+                    let mut part = String::new();
+
+                    while let Some(c) = self.chars.peek().copied() {
+                        if !(c.is_ascii_alphanumeric() || c == '-' || c == '_') {
+                            break;
+                        }
+
+                        part.push(c);
+                        self.chars.next();
+                    }
+
+                    if part.is_empty() {
+                        bail!("line {}: expected a key", self.line);
+                    }
+
+                    part
+                }
+            };
+
+            parts.push(part);
+            self.skip_ws();
+
+            match self.chars.peek() {
+                Some('.') => {
+                    self.chars.next();
+                }
+                _ => return Ok(parts.join(".")),
+            }
+        }
+    }
+
+    fn value(&mut self) -> Result<Value> {
+        self.skip_ws();
+
+        match self.chars.peek().copied() {
+            Some('"') => Ok(Value::String(self.basic_string()?)),
+            Some('\'') => Ok(Value::String(self.literal_string()?)),
+            Some('[') => self.array(),
+            Some('{') => self.inline_table(),
+            Some(c) if c.is_ascii_alphanumeric() || c == '+' || c == '-' => self.scalar(),
+            Some(c) => bail!("line {}: unexpected '{}'", self.line, c),
+            None => bail!("line {}: expected a value, found end of input", self.line),
+        }
+    }
+
+    /// A boolean or a number.
+    fn scalar(&mut self) -> Result<Value> {
+        let mut word = String::new();
+
+        while let Some(c) = self.chars.peek().copied() {
+            if !(c.is_ascii_alphanumeric() || "+-._".contains(c)) {
+                break;
+            }
+
+            word.push(c);
+            self.chars.next();
+        }
+
+        let number = word.replace('_', "");
+
+        let value = match word.as_str() {
+            "true" => Some(Value::Bool(true)),
+            "false" => Some(Value::Bool(false)),
+            _ if number.contains(['.', 'e', 'E']) => number.parse().ok().map(Value::Float),
+            _ => number.parse().ok().map(Value::Integer),
+        };
+
+        value.ok_or_else(|| Error::msg(format!("line {}: invalid value {}", self.line, word)))
+    }
+
+    fn basic_string(&mut self) -> Result<String> {
+        self.expect('"')?;
+        let mut s = String::new();
+
+        loop {
+            match self.chars.next() {
+                Some('"') => return Ok(s),
+                Some('\\') => match self.chars.next() {
+                    Some('n') => s.push('\n'),
+                    Some('r') => s.push('\r'),
+                    Some('t') => s.push('\t'),
+                    Some(c @ ('"' | '\\')) => s.push(c),
+                    Some(c) => bail!("line {}: invalid escape \\{}", self.line, c),
+                    None => bail!("line {}: unterminated string", self.line),
+                },
+                Some('\n') | None => bail!("line {}: unterminated string", self.line),
+                Some(c) => s.push(c),
+            }
+        }
+    }
+
+    fn literal_string(&mut self) -> Result<String> {
+        self.expect('\'')?;
+        let mut s = String::new();
+
+        loop {
+            match self.chars.next() {
+                Some('\'') => return Ok(s),
+                Some('\n') | None => bail!("line {}: unterminated string", self.line),
+                Some(c) => s.push(c),
+            }
+        }
+    }
+
+    /// An inline table, on a single line.
+    fn inline_table(&mut self) -> Result<Value> {
+        self.expect('{')?;
+        let mut members = Vec::new();
+        self.skip_ws();
+
+        if self.chars.next_if_eq(&'}').is_some() {
+            return Ok(Value::Table(members));
+        }
+
+        loop {
+            let key = self.key()?;
+            self.expect('=')?;
+            let value = self.value()?;
+            set(&mut members, key, value, self.line)?;
+            self.skip_ws();
+
+            match self.next() {
+                Some(',') => continue,
+                Some('}') => return Ok(Value::Table(members)),
+                _ => bail!("line {}: expected ',' or '}}' in inline table", self.line),
+            }
+        }
+    }
+
+    /// An array, which may span lines and hold comments between its items.
+    fn array(&mut self) -> Result<Value> {
+        self.expect('[')?;
+        let mut items = Vec::new();
+
+        loop {
+            self.skip_blank();
+
+            if let Some(']') = self.chars.peek() {
+                self.chars.next();
+                return Ok(Value::Array(items));
+            }
+
+            items.push(self.value()?);
+            self.skip_blank();
+
+            match self.next() {
+                Some(',') => continue,
+                Some(']') => return Ok(Value::Array(items)),
+                _ => bail!("line {}: expected ',' or ']' in array", self.line),
+            }
+        }
+    }
+}
@@ -0,0 +1,342 @@
+//! Election traces, exported to an OpenTelemetry collector.
+//!
+//! The whole run is a root span with one child span per election, which
+//! has one child span per ring member taking part, from the first message
+//! delivered to it during the election to the last. Every such message is
+//! an event on its member's span, and every message delivered between
+//! elections one on the root span. The spans are sent in one OTLP/HTTP JSON
+//! request once the run is over.
+
+use std::fmt;
+use std::io::{Read, Write};
+use std::net::TcpStream;
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use anyhow::{bail, Error, Result};
+
+use crate::json::Value;
+use crate::rng::Rng;
+use crate::sync::{Mutex, MutexGuard};
+use crate::Msg;
+
+/// Port OTLP/HTTP collectors listen on unless told otherwise.
+const DEFAULT_PORT: u16 = 4318;
+
+/// Collects the spans of a run for an OTLP collector.
+///
+/// Clones share the same spans, so that ring members can add events to the
+/// election the simulator started.
+#[derive(Clone)]
+pub struct Tracer {
+    endpoint: Endpoint,
+    state: Arc<Mutex<State>>,
+}
+
+impl fmt::Debug for Tracer {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Tracer")
+            .field("endpoint", &self.endpoint)
+            .finish_non_exhaustive()
+    }
+}
+
+#[derive(Debug)]
+struct State {
+    rng: Rng,
+    trace_id: u128,
+    root: Span,
+    /// The election in progress, if any.
+    election: Option<Span>,
+    /// The spans of the members taking part in the election in progress.
+    members: Vec<(usize, Span)>,
+    finished: Vec<Span>,
+}
+
+#[derive(Debug)]
+struct Span {
+    id: u64,
+    parent: Option<u64>,
+    name: &'static str,
+    start: u128,
+    end: u128,
+    attributes: Vec<(&'static str, Value)>,
+    events: Vec<Event>,
+}
+
+#[derive(Debug)]
+struct Event {
+    time: u128,
+    name: &'static str,
+    attributes: Vec<(&'static str, Value)>,
+}
+
+impl Tracer {
+    /// Start tracing a run, to be exported to the collector at `endpoint`,
+    /// such as `http://localhost:4318`.
+    pub fn new(endpoint: &str, seed: u64) -> Result<Self> {
+        let endpoint = endpoint.parse()?;
+        let mut rng = Rng::new(seed);
+        let trace_id = (rng.next_u64() as u128) << 64 | rng.next_u64() as u128;
+        let root = Span::new(&mut rng, None, "run");
+
+        let state = State {
+            rng, trace_id, root, election: None, members: Vec::new(),
+            finished: Vec::new(),
+        };
+
+        Ok(Self { endpoint, state: Arc::new(Mutex::new(state)) })
+    }
+
+    /// Open the span of an election started by ring member `initiator`.
+    pub fn start_election(&self, initiator: usize) -> Result<()> {
+        let mut state = self.lock()?;
+        let parent = state.root.id;
+        let mut span = Span::new(&mut state.rng, Some(parent), "election");
+        span.attributes.push(("initiator", initiator.into()));
+
+        if let Some(unfinished) = state.election.replace(span) {
+            state.finish(unfinished);
+        }
+
+        Ok(())
+    }
+
+    /// Close the span of the election in progress, with its winner if it
+    /// had one.
+    pub fn end_election(&self, winner: Option<usize>) -> Result<()> {
+        let mut state = self.lock()?;
+
+        if let Some(mut span) = state.election.take() {
+            let winner = match winner {
+                Some(id) => id.into(),
+                None => Value::Null,
+            };
+
+            span.attributes.push(("winner", winner));
+            state.finish(span);
+        }
+
+        Ok(())
+    }
+
+    /// Add the delivery of `msg` to ring member `to` to its span in the
+    /// election in progress, or to the run if there is none.
+    pub fn message(&self, to: usize, msg: &Msg) -> Result<()> {
+        let mut state = self.lock()?;
+        let state = &mut *state;
+        let time = now();
+
+        let span = match &state.election {
+            Some(election) => {
+                let i = match state.members.iter().position(|(id, _)| *id == to) {
+                    Some(i) => i,
+                    None => {
+                        let mut span = Span::new(&mut state.rng, Some(election.id), "member");
+                        span.attributes.push(("id", to.into()));
+                        state.members.push((to, span));
+                        state.members.len() - 1
+                    }
+                };
+
+                let span = &mut state.members[i].1;
+                span.end = time;
+                span
+            }
+            None => &mut state.root,
+        };
+
+        span.events.push(Event {
+            time,
+            name: "message",
+            attributes: vec![("to", to.into()), ("type", msg.kind().into())],
+        });
+
+        Ok(())
+    }
+
+    /// End the run and send every span to the collector.
+    pub fn export(&self) -> Result<()> {
+        let body = {
+            let mut state = self.lock()?;
+
+            if let Some(span) = state.election.take() {
+                state.finish(span);
+            }
+
+            state.root.end = now();
+            let trace_id = format!("{:032x}", state.trace_id);
+
+            let spans = state.finished.iter()
+                .chain([&state.root])
+                .map(|span| span.to_json(&trace_id))
+                .collect();
+
+            Value::object([(
+                "resourceSpans",
+                Value::Array(vec![Value::object([
+                    ("resource", Value::object([(
+                        "attributes",
+                        Value::Array(vec![attribute("service.name", &"ring".into())]),
+                    )])),
+                    ("scopeSpans", Value::Array(vec![Value::object([
+                        ("scope", Value::object([("name", "ring".into())])),
+                        ("spans", Value::Array(spans)),
+                    ])])),
+                ])]),
+            )])
+        };
+
+        self.endpoint.post(&body.to_string())
+    }
+
+    fn lock(&self) -> Result<MutexGuard<'_, State>> {
+        self.state.lock().map_err(|_| Error::msg("Tracer lock poisoned"))
+    }
+}
+
+impl State {
+    /// End the span of an election, along with those of its members, which
+    /// end with the last message delivered to them.
+    fn finish(&mut self, election: Span) {
+        self.finished.push(election.end());
+        self.finished.extend(self.members.drain(..).map(|(_, span)| span));
+    }
+}
+
+impl Span {
+    fn new(rng: &mut Rng, parent: Option<u64>, name: &'static str) -> Self {
+        Self {
+            id: rng.next_u64(),
+            parent,
+            name,
+            start: now(),
+            end: 0,
+            attributes: Vec::new(),
+            events: Vec::new(),
+        }
+    }
+
+    fn end(mut self) -> Self {
+        self.end = now();
+        self
+    }
+
+    fn to_json(&self, trace_id: &str) -> Value {
+        let parent = match self.parent {
+            Some(id) => format!("{:016x}", id),
+            None => String::new(),
+        };
+
+        let events = self.events.iter()
+            .map(|event| Value::object([
+                ("timeUnixNano", event.time.to_string().into()),
+                ("name", event.name.into()),
+                ("attributes", attributes(&event.attributes)),
+            ]))
+            .collect();
+
+        Value::object([
+            ("traceId", trace_id.into()),
+            ("spanId", format!("{:016x}", self.id).into()),
+            ("parentSpanId", parent.into()),
+            ("name", self.name.into()),
+            // SPAN_KIND_INTERNAL
+            ("kind", 1usize.into()),
+            ("startTimeUnixNano", self.start.to_string().into()),
+            ("endTimeUnixNano", self.end.to_string().into()),
+            ("attributes", attributes(&self.attributes)),
+            ("events", Value::Array(events)),
+        ])
+    }
+}
+
+fn attributes(attributes: &[(&str, Value)]) -> Value {
+    Value::Array(attributes.iter().map(|(k, v)| attribute(k, v)).collect())
+}
+
+/// An OTLP key-value pair, whose values are tagged with their type.
+fn attribute(key: &str, value: &Value) -> Value {
+    let value = match value {
+        Value::Number(n) => Value::object([("intValue", n.to_string().into())]),
+        Value::String(s) => Value::object([("stringValue", s.as_str().into())]),
+        Value::Bool(b) => Value::object([("boolValue", (*b).into())]),
+        _ => Value::object([]),
+    };
+
+    Value::object([("key", key.into()), ("value", value)])
+}
+
+fn now() -> u128 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_nanos())
+        .unwrap_or_default()
+}
+
+/// Where an OTLP/HTTP collector accepts traces.
+#[derive(Debug, Clone)]
+struct Endpoint {
+    host: String,
+    port: u16,
+    path: String,
+}
+
+impl std::str::FromStr for Endpoint {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        if s.starts_with("https://") {
+            bail!("Only plain http:// OTLP endpoints are supported");
+        }
+
+        let s = s.strip_prefix("http://").unwrap_or(s);
+
+        let (authority, path) = match s.find('/') {
+            Some(i) if i + 1 < s.len() => s.split_at(i),
+            Some(i) => (&s[..i], "/v1/traces"),
+            None => (s, "/v1/traces"),
+        };
+
+        let (host, port) = match authority.rsplit_once(':') {
+            Some((host, port)) => match port.parse() {
+                Ok(port) => (host, port),
+                Err(_) => bail!("Invalid port in OTLP endpoint {}", s),
+            },
+            None => (authority, DEFAULT_PORT),
+        };
+
+        if host.is_empty() {
+            bail!("OTLP endpoint {} has no host", s);
+        }
+
+        Ok(Self { host: host.to_string(), port, path: path.to_string() })
+    }
+}
+
+impl Endpoint {
+    fn post(&self, body: &str) -> Result<()> {
+        let mut stream = TcpStream::connect((self.host.as_str(), self.port))?;
+
+        write!(
+            stream,
+            "POST {} HTTP/1.1\r\nHost: {}:{}\r\n\
+            Content-Type: application/json\r\nContent-Length: {}\r\n\
+            Connection: close\r\n\r\n{}",
+            self.path, self.host, self.port, body.len(), body
+        )?;
+
+        let mut response = String::new();
+        stream.read_to_string(&mut response)?;
+
+        let status = response.split_whitespace()
+            .nth(1)
+            .and_then(|code| code.parse::<u16>().ok());
+
+        match status {
+            Some(code) if (200..300).contains(&code) => Ok(()),
+            Some(code) => bail!("OTLP collector answered with status {}", code),
+            None => bail!("OTLP collector sent an invalid response"),
+        }
+    }
+}
@@ -0,0 +1,842 @@
+//! How ring members reach one another.
+//!
+//! A ring member only ever sends to another member by id and receives
+//! whatever was sent to it, so it can be given any [`Transport`] that does
+//! that. The ring is run over [`Channels`], one link of in-process channels
+//! per member, unless its members run as separate processes over [`Tcp`],
+//! [`Udp`] or, on one machine, Unix domain sockets.
+//!
+//! Over TCP and Unix sockets, every message is sent as a frame: its length as four
+//! little-endian bytes, then the message in the recording's binary
+//! encoding. A connection opens with a single byte saying whether a member
+//! or the simulator is on the other end. The simulator's connection to a
+//! member also carries the member's messages for the simulator back.
+//!
+//! Over UDP, every message is a datagram of its own, opening with the same
+//! byte, and may be lost. The simulator greets every member with a datagram
+//! holding that byte alone, which the member answers with an empty one, and
+//! a member sends its messages for the simulator to wherever the
+//! simulator last sent from.
+
+use std::collections::HashMap;
+use std::fmt;
+#[cfg(unix)]
+use std::fs;
+use std::io::{self, ErrorKind, Read, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream, ToSocketAddrs, UdpSocket};
+#[cfg(unix)]
+use std::os::unix::fs::FileTypeExt;
+#[cfg(unix)]
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+use anyhow::{bail, Error, Result};
+
+use crate::clock::Clock;
+use crate::rng::Rng;
+use crate::sched::{self, Scheduler};
+use crate::sync;
+use crate::sync::channel::{bounded, unbounded, Receiver, Sender, TryRecvError};
+use crate::record::FORMAT_VERSION;
+use crate::{Algorithm, Msg, SimMsg};
+
+/// Opens a connection from another ring member.
+const FROM_MEMBER: u8 = 0;
+
+/// Opens a connection from the simulator.
+const FROM_SIM: u8 = 1;
+
+/// How long the greeting [`hello`] makes is.
+const HELLO_LEN: usize = 9;
+
+/// How long to keep trying to reach a member the simulator is to drive,
+/// so that it can be started along with the members.
+const DIAL_TIMEOUT: Duration = Duration::from_secs(5);
+
+const DIAL_RETRY_DELAY: Duration = Duration::from_millis(100);
+
+/// The largest datagram a UDP member takes.
+const MAX_DATAGRAM: usize = 65_507;
+
+/// How members running as their own processes reach one another.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Protocol {
+    #[default]
+    Tcp,
+    Udp,
+    /// Unix domain sockets, with every member's address a socket path.
+    Unix,
+}
+
+impl std::str::FromStr for Protocol {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "tcp" => Ok(Self::Tcp),
+            "udp" => Ok(Self::Udp),
+            "unix" => Ok(Self::Unix),
+            _ => bail!("Unknown transport {}", s),
+        }
+    }
+}
+
+impl Protocol {
+    /// Connect the simulator to the members listening at `addrs`, as
+    /// [`Streams::dial`] does.
+    pub fn dial(
+        self, addrs: &[String], links: Vec<Link<Receiver<Msg>>>, sim_s: Sender<SimMsg>,
+    ) -> Result<Vec<JoinHandle<()>>> {
+        match self {
+            Self::Tcp => Tcp::dial(addrs, links, sim_s),
+            Self::Udp => Udp::dial(addrs, links, sim_s),
+            #[cfg(unix)]
+            Self::Unix => Unix::dial(addrs, links, sim_s),
+            #[cfg(not(unix))]
+            Self::Unix => bail!("Unix domain sockets need a Unix system"),
+        }
+    }
+}
+
+pub trait Transport: fmt::Debug + Send {
+    /// How many members the ring has, this one included.
+    fn ring_size(&self) -> usize;
+
+    /// Send a message to ring member `id`.
+    fn send_to(&self, id: usize, msg: Msg) -> Result<()>;
+
+    /// Receive the next message.
+    fn recv(&self) -> Result<Msg>;
+
+    /// Like `recv`, but give up after `timeout`, returning `None`.
+    fn recv_timeout(&self, timeout: Duration) -> Result<Option<Msg>>;
+}
+
+/// The two channels a ring member is reached through: one for the election
+/// itself and one for the simulator's control messages, so that control
+/// messages are never held up behind election traffic.
+#[derive(Debug, Clone)]
+pub struct Link<T> {
+    data: T,
+    control: T,
+}
+
+impl Link<Sender<Msg>> {
+    /// Create a link, returning its sending and receiving ends.
+    ///
+    /// The election channel holds `capacity` messages, a single one unless
+    /// given, unless `algorithm` has members message several others at
+    /// once, which could leave two of them stuck sending to each other.
+    pub fn new(algorithm: Algorithm, capacity: Option<usize>) -> (Self, Link<Receiver<Msg>>) {
+        let (data_s, data_r) = match algorithm {
+            Algorithm::Ring | Algorithm::ChangRoberts => bounded(capacity.unwrap_or(1)),
+            Algorithm::Bully | Algorithm::HirschbergSinclair => unbounded(),
+        };
+        let (control_s, control_r) = unbounded();
+
+        (
+            Link { data: data_s, control: control_s },
+            Link { data: data_r, control: control_r },
+        )
+    }
+
+    /// Send a message on the channel it belongs on.
+    pub fn send(&self, msg: Msg) -> Result<()> {
+        if msg.is_sim() {
+            self.control.send(msg)?;
+        } else {
+            self.data.send(msg)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// A member's links to the others, by id, shared with the simulator to add
+/// those joining the ring to and take those leaving it out of.
+pub(crate) type Roster = Arc<sync::Mutex<HashMap<usize, Link<Sender<Msg>>>>>;
+
+/// A member's links to every other member, by id, and the one it receives
+/// on.
+///
+/// A message to a member that left the ring is lost, as if dropped.
+#[derive(Debug)]
+pub struct Channels {
+    peers: Roster,
+    ring_size: usize,
+    link: Link<Receiver<Msg>>,
+    /// What the member waits through to receive, if not in real time.
+    sched: Option<Scheduler>,
+}
+
+impl Channels {
+    pub fn new(
+        peers: HashMap<usize, Link<Sender<Msg>>>, link: Link<Receiver<Msg>>,
+    ) -> Self {
+        let ring_size = peers.len() + 1;
+        Self::shared(Arc::new(sync::Mutex::new(peers)), ring_size, link)
+    }
+
+    /// Reach the other members of a ring of `ring_size` through `peers`, as
+    /// they join and leave it.
+    pub(crate) fn shared(peers: Roster, ring_size: usize, link: Link<Receiver<Msg>>) -> Self {
+        Self { peers, ring_size, link, sched: None }
+    }
+
+    /// Wait to receive through `sched`, in its virtual time.
+    pub(crate) fn scheduled(self, sched: Scheduler) -> Self {
+        Self { sched: Some(sched), ..self }
+    }
+}
+
+impl Transport for Channels {
+    fn ring_size(&self) -> usize {
+        self.ring_size
+    }
+
+    fn send_to(&self, id: usize, msg: Msg) -> Result<()> {
+        let peer = |id| self.peers.lock().unwrap_or_else(|e| e.into_inner()).get(&id).cloned();
+
+        match peer(id) {
+            Some(link) => match link.send(msg) {
+                // The member may have left while the message was on its way.
+                Err(_) if peer(id).is_none() => Ok(()),
+                res => res,
+            },
+            None if id < self.ring_size => Ok(()),
+            None => bail!("Unknown ring member {}", id),
+        }
+    }
+
+    fn recv(&self) -> Result<Msg> {
+        match &self.sched {
+            Some(sched) => recv_until(Some(sched), &self.link, None)?
+                .ok_or(Error::msg("Woken without a message")),
+            None => recv(&self.link),
+        }
+    }
+
+    fn recv_timeout(&self, timeout: Duration) -> Result<Option<Msg>> {
+        match &self.sched {
+            Some(sched) => recv_until(Some(sched), &self.link, Some(sched.now() + timeout)),
+            None => recv_timeout(&self.link, timeout),
+        }
+    }
+}
+
+impl Link<Receiver<Msg>> {
+    /// How many messages are waiting on the link.
+    pub(crate) fn len(&self) -> usize {
+        self.control.len() + self.data.len()
+    }
+}
+
+/// Receive the next message on `link`, waiting on `sched`, if given, or in
+/// real time until `deadline`, if given, and returning `None` once it has
+/// passed. The simulator's control messages have priority, so that, e.g.,
+/// a toggle applies before a concurrent vote.
+fn recv_until(
+    sched: Option<&Scheduler>, link: &Link<Receiver<Msg>>, deadline: Option<Instant>,
+) -> Result<Option<Msg>> {
+    loop {
+        for r in [&link.control, &link.data] {
+            match r.try_recv() {
+                Ok(msg) => {
+                    if let Some(sched) = sched {
+                        sched.received(&msg);
+                    }
+
+                    return Ok(Some(msg));
+                }
+                Err(TryRecvError::Disconnected) => bail!("Channel disconnected"),
+                Err(TryRecvError::Empty) => {}
+            }
+        }
+
+        let receivers = [&link.control, &link.data];
+        let woken = match sched {
+            Some(sched) => sched.wait_until(&receivers, deadline),
+            None => sched::wait_real(&receivers, deadline),
+        };
+
+        if !woken {
+            if let Some(sched) = sched {
+                sched.received(&"timeout");
+            }
+
+            return Ok(None);
+        }
+    }
+}
+
+/// Receive the next message on `link`, waiting in real time.
+fn recv(link: &Link<Receiver<Msg>>) -> Result<Msg> {
+    recv_until(None, link, None)?.ok_or(Error::msg("Woken without a message"))
+}
+
+/// Like `recv`, but give up after `timeout`, returning `None`.
+fn recv_timeout(link: &Link<Receiver<Msg>>, timeout: Duration) -> Result<Option<Msg>> {
+    recv_until(None, link, Some(Instant::now() + timeout))
+}
+
+/// A kind of stream socket members can be reached over.
+pub trait Socket: Read + Write + fmt::Debug + Send + Sized + 'static {
+    fn connect(addr: &str) -> io::Result<Self>;
+
+    fn try_clone(&self) -> io::Result<Self>;
+
+    /// Listen at `addr`, returning what accepts the next connection.
+    fn listen(addr: &str) -> io::Result<Acceptor<Self>>;
+}
+
+type Acceptor<S> = Box<dyn FnMut() -> io::Result<S> + Send>;
+
+impl Socket for TcpStream {
+    fn connect(addr: &str) -> io::Result<Self> {
+        let stream = TcpStream::connect(addr)?;
+        stream.set_nodelay(true)?;
+        Ok(stream)
+    }
+
+    fn try_clone(&self) -> io::Result<Self> {
+        TcpStream::try_clone(self)
+    }
+
+    fn listen(addr: &str) -> io::Result<Acceptor<Self>> {
+        let listener = TcpListener::bind(addr)?;
+        Ok(Box::new(move || listener.accept().map(|(stream, _)| stream)))
+    }
+}
+
+#[cfg(unix)]
+impl Socket for UnixStream {
+    fn connect(addr: &str) -> io::Result<Self> {
+        UnixStream::connect(addr)
+    }
+
+    fn try_clone(&self) -> io::Result<Self> {
+        UnixStream::try_clone(self)
+    }
+
+    /// Listen at path `addr`, taking the place of the socket a member that
+    /// was killed left behind.
+    fn listen(addr: &str) -> io::Result<Acceptor<Self>> {
+        if fs::metadata(addr).map(|m| m.file_type().is_socket()).unwrap_or(false) {
+            fs::remove_file(addr)?;
+        }
+
+        let listener = UnixListener::bind(addr)?;
+        Ok(Box::new(move || listener.accept().map(|(stream, _)| stream)))
+    }
+}
+
+/// A member's connections to the other members, each running as its own
+/// process and listening at its address.
+///
+/// A message to a member that can't be reached is lost, as if dropped, so
+/// that a member whose process is gone looks inactive to the others.
+#[derive(Debug)]
+pub struct Streams<S> {
+    id: usize,
+    /// Every member's address, by id.
+    addrs: Vec<String>,
+    conns: Mutex<HashMap<usize, S>>,
+    link: Link<Receiver<Msg>>,
+}
+
+impl<S: Socket> Streams<S> {
+    /// Listen for the other members and the simulator at member `id`'s
+    /// address among `addrs`, returning the transport and where to send
+    /// messages for whichever simulator connected last.
+    pub fn bind(id: usize, addrs: Vec<String>) -> Result<(Self, Sender<SimMsg>)> {
+        let addr = addrs.get(id)
+            .ok_or(Error::msg(format!("No address for ring member {}", id)))?;
+
+        let mut accept_next = S::listen(addr)?;
+        let ring_size = addrs.len();
+        let (link_s, link) = unbounded_link();
+        let sim = Arc::new(Mutex::new(None::<S>));
+        let (sim_s, sim_r) = unbounded::<SimMsg>();
+
+        let to_sim = sim.clone();
+
+        // Messages for the simulator are lost until one connects.
+        thread::spawn(move || {
+            for msg in sim_r {
+                if let Ok(mut sim) = to_sim.lock() {
+                    if let Some(stream) = sim.as_mut() {
+                        if write_frame(stream, &msg.to_bytes()).is_err() {
+                            *sim = None;
+                        }
+                    }
+                }
+            }
+        });
+
+        thread::spawn(move || {
+            while let Ok(stream) = accept_next() {
+                let link_s = link_s.clone();
+                let sim = sim.clone();
+
+                thread::spawn(move || {
+                    if let Err(e) = accept(stream, link_s, sim, ring_size) {
+                        tracing::warn!("{}: dropped connection: {}", id, e);
+                    }
+                });
+            }
+        });
+
+        let conns = Mutex::new(HashMap::new());
+        Ok((Self { id, addrs, conns, link }, sim_s))
+    }
+
+    /// Connect the simulator to the members listening at `addrs`, by id,
+    /// forwarding what it sends on each of `links` to that member and what
+    /// the members send back to `sim_s`.
+    ///
+    /// Returns the threads forwarding the simulator's messages, which end
+    /// once the links' sending ends are dropped and everything sent on
+    /// them has been passed on. A member hanging up before then, such as
+    /// by its process being killed, is reported to the simulator as a
+    /// crash.
+    pub fn dial(
+        addrs: &[String], links: Vec<Link<Receiver<Msg>>>, sim_s: Sender<SimMsg>,
+    ) -> Result<Vec<JoinHandle<()>>> {
+        let mut forwarders = Vec::new();
+
+        for (id, (addr, link)) in addrs.iter().zip(links).enumerate() {
+            let mut stream = connect::<S>(addr, DIAL_TIMEOUT)?;
+            stream.write_all(&hello(FROM_SIM, addrs.len()))?;
+            let mut replies = stream.try_clone()?;
+            let sim_s = sim_s.clone();
+
+            forwarders.push(thread::spawn(move || {
+                while let Ok(msg) = recv(&link) {
+                    if write_frame(&mut stream, &msg.to_bytes()).is_err() {
+                        return;
+                    }
+                }
+            }));
+
+            thread::spawn(move || {
+                let reason = loop {
+                    let frame = match read_frame(&mut replies) {
+                        Ok(Some(frame)) => frame,
+                        Ok(None) => break "hung up".to_string(),
+                        Err(e) => break e.to_string(),
+                    };
+
+                    let msg = match SimMsg::from_bytes(&frame) {
+                        Ok(msg) => msg,
+                        Err(e) => break e.to_string(),
+                    };
+
+                    if sim_s.send(msg).is_err() {
+                        return;
+                    }
+                };
+
+                // The simulator is gone by the time a member told to stop
+                // hangs up.
+                let _ = sim_s.send(SimMsg::Crashed { id, reason });
+            });
+        }
+
+        Ok(forwarders)
+    }
+}
+
+impl<S: Socket> Transport for Streams<S> {
+    fn ring_size(&self) -> usize {
+        self.addrs.len()
+    }
+
+    fn send_to(&self, id: usize, msg: Msg) -> Result<()> {
+        let addr = self.addrs.get(id)
+            .ok_or(Error::msg(format!("Unknown ring member {}", id)))?;
+
+        let mut conns = self.conns.lock()
+            .map_err(|_| Error::msg("Connection lock poisoned"))?;
+
+        let frame = msg.to_bytes();
+
+        // A connection that broke may be to a member since restarted, so
+        // try a fresh one once.
+        if let Some(stream) = conns.get_mut(&id) {
+            if write_frame(stream, &frame).is_ok() {
+                return Ok(());
+            }
+
+            conns.remove(&id);
+        }
+
+        let sent = connect::<S>(addr, Duration::ZERO).and_then(|mut stream| {
+            stream.write_all(&hello(FROM_MEMBER, self.addrs.len()))?;
+            write_frame(&mut stream, &frame)?;
+            Ok(stream)
+        });
+
+        match sent {
+            Ok(stream) => {
+                conns.insert(id, stream);
+            }
+            Err(e) => tracing::warn!("{}: could not reach {}: {}", self.id, id, e),
+        }
+
+        Ok(())
+    }
+
+    fn recv(&self) -> Result<Msg> {
+        recv(&self.link)
+    }
+
+    fn recv_timeout(&self, timeout: Duration) -> Result<Option<Msg>> {
+        recv_timeout(&self.link, timeout)
+    }
+}
+
+/// A member reached over TCP, listening at a `host:port` address.
+pub type Tcp = Streams<TcpStream>;
+
+/// A member reached over a Unix domain socket, listening at a path, so that
+/// every member runs as a process of its own on the one machine.
+#[cfg(unix)]
+pub type Unix = Streams<UnixStream>;
+
+/// A member's socket for datagrams to and from the other members, each
+/// running as its own process and bound to its address.
+#[derive(Debug)]
+pub struct Udp {
+    id: usize,
+    /// Every member's address, by id.
+    addrs: Vec<String>,
+    socket: UdpSocket,
+    /// Probability of losing a message to another member on purpose, on
+    /// top of whatever the network loses.
+    loss_rate: f64,
+    rng: Mutex<Rng>,
+    link: Link<Receiver<Msg>>,
+}
+
+impl Udp {
+    /// Bind member `id`'s address among `addrs`, returning the transport
+    /// and where to send messages for the simulator. Messages to other
+    /// members are lost with probability `loss_rate`, by the stream of
+    /// random choices `seed` starts.
+    pub fn bind(
+        id: usize, addrs: Vec<String>, loss_rate: f64, seed: u64,
+    ) -> Result<(Self, Sender<SimMsg>)> {
+        let addr = addrs.get(id)
+            .ok_or(Error::msg(format!("No address for ring member {}", id)))?;
+
+        let socket = UdpSocket::bind(addr)?;
+        let ring_size = addrs.len();
+        let (link_s, link) = unbounded_link();
+        let sim = Arc::new(Mutex::new(None::<SocketAddr>));
+        let (sim_s, sim_r) = unbounded::<SimMsg>();
+
+        let replies = socket.try_clone()?;
+        let to_sim = sim.clone();
+
+        // Messages for the simulator are lost until it has sent one.
+        thread::spawn(move || {
+            for msg in sim_r {
+                let addr = to_sim.lock().ok().and_then(|sim| *sim);
+
+                if let Some(addr) = addr {
+                    let _ = replies.send_to(&msg.to_bytes(), addr);
+                }
+            }
+        });
+
+        let incoming = socket.try_clone()?;
+
+        thread::spawn(move || {
+            let mut buf = vec![0; MAX_DATAGRAM];
+
+            while let Ok((len, from)) = incoming.recv_from(&mut buf) {
+                let msg = match check_hello(&buf[..len], ring_size) {
+                    Ok((FROM_SIM, msg)) => {
+                        if let Ok(mut sim) = sim.lock() {
+                            *sim = Some(from);
+                        }
+
+                        if msg.is_empty() {
+                            let _ = incoming.send_to(&[], from);
+                            continue;
+                        }
+
+                        msg
+                    }
+                    Ok((FROM_MEMBER, msg)) => msg,
+                    Ok(_) => continue,
+                    Err(e) => {
+                        tracing::warn!("{}: dropped datagram from {}: {}", id, from, e);
+                        continue;
+                    }
+                };
+
+                match Msg::from_bytes(msg) {
+                    Ok(msg) => {
+                        if link_s.send(msg).is_err() {
+                            return;
+                        }
+                    }
+                    Err(e) => tracing::warn!("{}: dropped datagram from {}: {}", id, from, e),
+                }
+            }
+        });
+
+        let rng = Mutex::new(Rng::new(seed));
+        Ok((Self { id, addrs, socket, loss_rate, rng, link }, sim_s))
+    }
+
+    /// Let the simulator send to the members bound to `addrs`, by id, what
+    /// it sends on each of `links`, passing what the members send back on
+    /// to `sim_s`, and return the threads forwarding the simulator's
+    /// messages, as [`Streams::dial`] does.
+    pub fn dial(
+        addrs: &[String], links: Vec<Link<Receiver<Msg>>>, sim_s: Sender<SimMsg>,
+    ) -> Result<Vec<JoinHandle<()>>> {
+        let socket = UdpSocket::bind("0.0.0.0:0")?;
+
+        // Make sure every member knows where to send its messages for the
+        // simulator before the simulator is left waiting on them.
+        socket.set_read_timeout(Some(DIAL_RETRY_DELAY))?;
+
+        for addr in addrs {
+            greet(&socket, addr, addrs.len())?;
+        }
+
+        socket.set_read_timeout(None)?;
+        let mut forwarders = Vec::new();
+
+        for (addr, link) in addrs.iter().cloned().zip(links) {
+            let socket = socket.try_clone()?;
+            let ring_size = addrs.len();
+
+            forwarders.push(thread::spawn(move || {
+                while let Ok(msg) = recv(&link) {
+                    let mut datagram = hello(FROM_SIM, ring_size);
+                    datagram.extend(msg.to_bytes());
+
+                    if socket.send_to(&datagram, &addr).is_err() {
+                        return;
+                    }
+                }
+            }));
+        }
+
+        thread::spawn(move || {
+            let mut buf = vec![0; MAX_DATAGRAM];
+
+            while let Ok(len) = socket.recv(&mut buf) {
+                // A member may answer a greeting more than once.
+                if len == 0 {
+                    continue;
+                }
+
+                match SimMsg::from_bytes(&buf[..len]) {
+                    Ok(msg) => {
+                        if sim_s.send(msg).is_err() {
+                            return;
+                        }
+                    }
+                    Err(e) => tracing::warn!("sim: dropped datagram: {}", e),
+                }
+            }
+        });
+
+        Ok(forwarders)
+    }
+}
+
+impl Transport for Udp {
+    fn ring_size(&self) -> usize {
+        self.addrs.len()
+    }
+
+    fn send_to(&self, id: usize, msg: Msg) -> Result<()> {
+        let addr = self.addrs.get(id)
+            .ok_or(Error::msg(format!("Unknown ring member {}", id)))?;
+
+        let lost = self.rng.lock()
+            .map_err(|_| Error::msg("Random number generator lock poisoned"))?
+            .chance(self.loss_rate);
+
+        if lost {
+            tracing::info!("{}: lost {:?} to {}", self.id, msg, id);
+            return Ok(());
+        }
+
+        let mut datagram = hello(FROM_MEMBER, self.addrs.len());
+        datagram.extend(msg.to_bytes());
+
+        // A member that isn't bound yet, or any more, just misses out.
+        if let Err(e) = self.socket.send_to(&datagram, addr) {
+            tracing::warn!("{}: could not reach {}: {}", self.id, id, e);
+        }
+
+        Ok(())
+    }
+
+    fn recv(&self) -> Result<Msg> {
+        recv(&self.link)
+    }
+
+    fn recv_timeout(&self, timeout: Duration) -> Result<Option<Msg>> {
+        recv_timeout(&self.link, timeout)
+    }
+}
+
+/// Create a link whose election channel, like its control one, holds any
+/// number of messages, as the sender can't be held up on the other side of
+/// a socket, or while its receiver waits out virtual time.
+pub(crate) fn unbounded_link() -> (Link<Sender<Msg>>, Link<Receiver<Msg>>) {
+    let (data_s, data_r) = unbounded();
+    let (control_s, control_r) = unbounded();
+
+    (
+        Link { data: data_s, control: control_s },
+        Link { data: data_r, control: control_r },
+    )
+}
+
+/// Take the messages sent over a connection another member or the
+/// simulator opened, keeping the simulator's to send its messages back on.
+fn accept<S: Socket>(
+    mut stream: S, link_s: Link<Sender<Msg>>, sim: Arc<Mutex<Option<S>>>, ring_size: usize,
+) -> Result<()> {
+    let mut greeting = [0; HELLO_LEN];
+    stream.read_exact(&mut greeting)?;
+
+    match check_hello(&greeting, ring_size)?.0 {
+        FROM_MEMBER => {}
+        FROM_SIM => {
+            let replies = stream.try_clone()?;
+            *sim.lock().map_err(|_| Error::msg("Simulator lock poisoned"))? = Some(replies);
+        }
+        b => bail!("Unknown connection from {}", b),
+    }
+
+    while let Some(frame) = read_frame(&mut stream)? {
+        link_s.send(Msg::from_bytes(&frame)?)?;
+    }
+
+    Ok(())
+}
+
+/// The greeting that opens a connection, or starts a datagram, from
+/// `role` in a ring of `ring_size`: the role, then the version of the
+/// encoding of the messages that follow and the ring's size, so that a
+/// member can turn away a peer it would misread.
+fn hello(role: u8, ring_size: usize) -> Vec<u8> {
+    let mut hello = vec![role];
+    hello.extend((FORMAT_VERSION as u32).to_le_bytes());
+    hello.extend((ring_size as u32).to_le_bytes());
+    hello
+}
+
+/// Split the greeting [`hello`] makes off the start of `bytes`, returning
+/// the role it gives and what follows it, unless it's from a peer speaking
+/// another version of the encoding or in a ring that isn't `ring_size`.
+fn check_hello(bytes: &[u8], ring_size: usize) -> Result<(u8, &[u8])> {
+    if bytes.len() < HELLO_LEN {
+        bail!("Greeting of {} bytes is too short", bytes.len());
+    }
+
+    let (greeting, rest) = bytes.split_at(HELLO_LEN);
+    let field = |i: usize| u32::from_le_bytes(greeting[i..i + 4].try_into().unwrap()) as u64;
+
+    if field(1) != FORMAT_VERSION {
+        bail!(
+            "Peer speaks version {} of the message encoding, not {}",
+            field(1), FORMAT_VERSION
+        );
+    }
+
+    if field(5) != ring_size as u64 {
+        bail!("Peer is in a ring of {}, not {}", field(5), ring_size);
+    }
+
+    Ok((greeting[0], rest))
+}
+
+/// Greet the member bound to `addr` in a ring of `ring_size` until it
+/// answers, for up to [`DIAL_TIMEOUT`], should nothing be bound there
+/// yet.
+fn greet(socket: &UdpSocket, addr: &str, ring_size: usize) -> Result<()> {
+    let resolved = addr.to_socket_addrs()?.collect::<Vec<_>>();
+    let start = Instant::now();
+    let mut buf = [0; 1];
+
+    while start.elapsed() < DIAL_TIMEOUT {
+        socket.send_to(&hello(FROM_SIM, ring_size), addr)?;
+
+        // Errors only say that nothing is bound at `addr` yet.
+        if let Ok((0, from)) = socket.recv_from(&mut buf) {
+            if resolved.contains(&from) {
+                return Ok(());
+            }
+        }
+    }
+
+    bail!("Could not reach {}", addr)
+}
+
+/// Connect to `addr`, trying again for up to `timeout` should nothing
+/// listen there yet.
+fn connect<S: Socket>(addr: &str, timeout: Duration) -> Result<S> {
+    let start = Instant::now();
+
+    loop {
+        match S::connect(addr) {
+            Ok(stream) => return Ok(stream),
+            Err(e) if start.elapsed() >= timeout => {
+                bail!("Could not connect to {}: {}", addr, e);
+            }
+            Err(_) => thread::sleep(DIAL_RETRY_DELAY),
+        }
+    }
+}
+
+fn write_frame(stream: &mut impl Write, frame: &[u8]) -> Result<()> {
+    stream.write_all(&(frame.len() as u32).to_le_bytes())?;
+    stream.write_all(frame)?;
+    Ok(())
+}
+
+/// Read the next frame, or `None` once the other end has hung up.
+fn read_frame(stream: &mut impl Read) -> Result<Option<Vec<u8>>> {
+    let mut len = [0; 4];
+
+    match stream.read_exact(&mut len) {
+        Ok(()) => {}
+        Err(e) if e.kind() == ErrorKind::UnexpectedEof => return Ok(None),
+        Err(e) => return Err(e.into()),
+    }
+
+    let mut frame = vec![0; u32::from_le_bytes(len) as usize];
+    stream.read_exact(&mut frame)?;
+    Ok(Some(frame))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn a_peer_speaking_another_version_or_in_another_ring_is_turned_away() {
+        let mut greeting = hello(FROM_MEMBER, 3);
+        greeting.push(7);
+        assert_eq!(check_hello(&greeting, 3).unwrap(), (FROM_MEMBER, &[7][..]));
+
+        assert!(check_hello(&greeting, 4).is_err());
+        assert!(check_hello(&greeting[..HELLO_LEN - 1], 3).is_err());
+
+        greeting[1] ^= 1;
+        assert!(check_hello(&greeting, 3).is_err());
+    }
+}
@@ -0,0 +1,344 @@
+//! A live dashboard of a running simulation, drawn in the terminal.
+//!
+//! While the dashboard is up, everything the ring prints is captured and
+//! shown as a scrolling event log under the ring's members. They're drawn
+//! clockwise around a circle, with the coordinator highlighted, those down
+//! dimmed and how many messages wait to be delivered to each, and listed
+//! again with the state each is in. The members are redrawn from the
+//! snapshot the simulator publishes after every step, kept up meanwhile
+//! with the toggles and elections members report. The dashboard is drawn
+//! on stderr, so it needs stderr to be a terminal.
+
+use std::collections::VecDeque;
+use std::f64::consts::PI;
+use std::io::{self, IsTerminal, Read, Write};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+use anyhow::{Error, Result};
+use crossbeam::channel::{bounded, Sender};
+use gag::BufferRedirect;
+
+use crate::clock::SharedClock;
+use crate::event::{Event, SharedSink, Sink, Source};
+use crate::sync::channel::Receiver;
+use crate::transport::Link;
+use crate::{Msg, RingSnapshot, State};
+
+/// How often the dashboard is redrawn.
+const REFRESH: Duration = Duration::from_millis(100);
+
+/// How many of the latest events the log shows.
+const LOG_LINES: usize = 15;
+
+/// The terminal width assumed when `COLUMNS` isn't set.
+const DEFAULT_COLUMNS: usize = 80;
+
+/// The most rows the circle of members is drawn across, however many
+/// members there are.
+const CIRCLE_ROWS: usize = 17;
+
+/// A dashboard being drawn. Clones draw on the same one.
+#[derive(Debug, Clone)]
+pub struct Dashboard {
+    shown: Arc<Mutex<Shown>>,
+    stop: Sender<()>,
+    drawer: Arc<Mutex<Option<JoinHandle<()>>>>,
+}
+
+#[derive(Debug, Default)]
+struct Shown {
+    /// The latest snapshot, kept up with what members reported since.
+    snapshot: Option<RingSnapshot>,
+    /// Every member's link, by id, to count the messages waiting on it.
+    links: Vec<Link<Receiver<Msg>>>,
+}
+
+impl Dashboard {
+    /// Start drawing the dashboard, unless stderr isn't a terminal to draw
+    /// it on.
+    pub fn start() -> Result<Option<Self>> {
+        if !io::stderr().is_terminal() {
+            return Ok(None);
+        }
+
+        let mut output = BufferRedirect::stdout()?;
+        let shown = Arc::new(Mutex::new(Shown::default()));
+        let (stop, stopped) = bounded(1);
+        let drawn = shown.clone();
+
+        let drawer = thread::spawn(move || {
+            let mut log = Log::default();
+
+            loop {
+                let last = stopped.recv_timeout(REFRESH).is_ok();
+                log.read(&mut output);
+
+                let (snapshot, queued) = match drawn.lock() {
+                    Ok(shown) => {
+                        let queued = shown.links.iter().map(Link::len).collect();
+                        (shown.snapshot.clone(), queued)
+                    }
+                    Err(_) => (None, Vec::new()),
+                };
+
+                if let Err(e) = draw(snapshot.as_ref(), &queued, &log) {
+                    tracing::warn!("tui: could not draw the dashboard: {}", e);
+                    return;
+                }
+
+                if last {
+                    return;
+                }
+            }
+        });
+
+        Ok(Some(Self {
+            shown,
+            stop,
+            drawer: Arc::new(Mutex::new(Some(drawer))),
+        }))
+    }
+
+    /// Show `snapshot` from the next redraw on.
+    pub fn publish(&self, snapshot: RingSnapshot) {
+        if let Ok(mut shown) = self.shown.lock() {
+            shown.snapshot = Some(snapshot);
+        }
+    }
+
+    /// Show how many messages wait on each of `links`, those of the
+    /// members by id.
+    pub(crate) fn count(&self, links: Vec<Link<Receiver<Msg>>>) {
+        if let Ok(mut shown) = self.shown.lock() {
+            shown.links = links;
+        }
+    }
+
+    /// A sink keeping the dashboard up with the events emitted to it, and
+    /// emitting them on to `inner`.
+    pub(crate) fn watch(&self, inner: SharedSink) -> Watch {
+        Watch { dashboard: self.clone(), inner }
+    }
+
+    /// Take what `event` says about the ring into the snapshot shown.
+    fn follow(&self, source: Source, event: &Event) {
+        let mut shown = match self.shown.lock() {
+            Ok(shown) => shown,
+            Err(_) => return,
+        };
+
+        let snapshot = match &mut shown.snapshot {
+            Some(snapshot) => snapshot,
+            None => return,
+        };
+
+        let member = match source {
+            Source::Member(id) => snapshot.members.get_mut(id),
+            _ => None,
+        };
+
+        match (member, event) {
+            (Some(m), Event::Toggled(active)) => m.active = *active,
+            (Some(m), Event::CoordinatorElected(coord_id)) => {
+                m.coord_id = *coord_id;
+
+                m.state = match m.id == *coord_id {
+                    true => State::Coordinator,
+                    false => State::Follower,
+                };
+            }
+            (None, Event::ElectionWon { id, .. }) if source == Source::Sim => {
+                snapshot.coord_id = *id;
+            }
+            _ => {}
+        }
+    }
+
+    /// Draw the dashboard one last time and hand stdout back.
+    pub fn close(&self) -> Result<()> {
+        let drawer = self.drawer.lock()
+            .map_err(|_| Error::msg("Dashboard lock poisoned"))?
+            .take();
+
+        if let Some(drawer) = drawer {
+            let _ = self.stop.send(());
+
+            drawer.join()
+                .map_err(|_| Error::msg("Dashboard thread panicked"))?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Keeps a dashboard up with the events emitted to it.
+#[derive(Debug)]
+pub(crate) struct Watch {
+    dashboard: Dashboard,
+    inner: SharedSink,
+}
+
+impl Sink for Watch {
+    fn emit(&self, source: Source, event: &Event) {
+        self.dashboard.follow(source, event);
+        self.inner.emit(source, event);
+    }
+
+    fn holds_stdout(&self) -> bool {
+        self.inner.holds_stdout()
+    }
+
+    fn keep_time(&self, clock: &SharedClock) {
+        self.inner.keep_time(clock);
+    }
+}
+
+/// The latest lines printed while the dashboard is up.
+#[derive(Debug, Default)]
+struct Log {
+    lines: VecDeque<String>,
+    /// The end of a line still being printed.
+    partial: String,
+}
+
+impl Log {
+    fn read(&mut self, output: &mut impl Read) {
+        let mut bytes = Vec::new();
+
+        if output.read_to_end(&mut bytes).is_err() {
+            return;
+        }
+
+        self.partial.push_str(&String::from_utf8_lossy(&bytes));
+
+        while let Some(i) = self.partial.find('\n') {
+            let line = self.partial[..i].to_string();
+            self.partial.drain(..=i);
+
+            if self.lines.len() == LOG_LINES {
+                self.lines.pop_front();
+            }
+
+            self.lines.push_back(line);
+        }
+    }
+}
+
+/// How a member is drawn: the coordinator highlighted and those down dimmed.
+fn style(active: bool, state: State) -> &'static str {
+    match (active, state) {
+        (true, State::Coordinator) => "\x1b[7m",
+        (false, _) => "\x1b[2m",
+        _ => "",
+    }
+}
+
+/// The lines of the snapshot's members drawn clockwise around a circle from
+/// its top, each as its id and how many messages wait for it, if any.
+fn circle(snapshot: &RingSnapshot, queued: &[usize]) -> Vec<String> {
+    let n = snapshot.members.len();
+
+    // Characters are about twice as tall as they're wide.
+    let ry = (n / 2).clamp(2, CIRCLE_ROWS / 2);
+    let rx = ry * 2 + 1;
+
+    // Each cell's character and the style of whatever member it shows.
+    let mut cells = vec![vec![(' ', ""); rx * 2 + 8]; ry * 2 + 1];
+
+    for (i, m) in snapshot.members.iter().enumerate() {
+        let angle = 2.0 * PI * i as f64 / n as f64 - PI / 2.0;
+        let row = (ry as f64 + ry as f64 * angle.sin()).round() as usize;
+        let col = (rx as f64 + rx as f64 * angle.cos()).round() as usize;
+
+        let label = match queued.get(i) {
+            Some(0) | None => format!("[{}]", m.id),
+            Some(q) => format!("[{}]+{}", m.id, q),
+        };
+
+        for (cell, c) in cells[row][col..].iter_mut().zip(label.chars()) {
+            *cell = (c, style(m.active, m.state));
+        }
+    }
+
+    cells.into_iter()
+        .map(|row| {
+            let end = row.iter().rposition(|cell| *cell != (' ', "")).map_or(0, |i| i + 1);
+            let mut line = String::new();
+            let mut current = "";
+
+            for (c, style) in &row[..end] {
+                if *style != current {
+                    line.push_str("\x1b[0m");
+                    line.push_str(style);
+                    current = style;
+                }
+
+                line.push(*c);
+            }
+
+            line + "\x1b[0m"
+        })
+        .collect()
+}
+
+fn draw(snapshot: Option<&RingSnapshot>, queued: &[usize], log: &Log) -> Result<()> {
+    let columns = std::env::var("COLUMNS")
+        .ok()
+        .and_then(|c| c.parse().ok())
+        .unwrap_or(DEFAULT_COLUMNS);
+
+    let rule = "-".repeat(columns);
+
+    // Clear the screen and draw from its top left corner.
+    let mut frame = String::from("\x1b[H\x1b[2J");
+
+    match snapshot {
+        Some(snapshot) => {
+            frame.push_str(&format!("ring: coordinator {}\r\n", snapshot.coord_id));
+            frame.push_str(&rule);
+            frame.push_str("\r\n");
+
+            for line in circle(snapshot, queued) {
+                frame.push_str(&line);
+                frame.push_str("\r\n");
+            }
+
+            frame.push_str(&rule);
+            frame.push_str("\r\n");
+
+            for m in &snapshot.members {
+                let name = match &m.label {
+                    Some(label) => format!("{} ({})", m.id, label),
+                    None => m.id.to_string(),
+                };
+
+                let line = format!(
+                    "{:<16} {:<5} {:<12} follows {}",
+                    name,
+                    if m.active { "up" } else { "down" },
+                    format!("{:?}", m.state),
+                    m.coord_id,
+                );
+
+                frame.push_str(&format!("{}{}\x1b[0m\r\n", style(m.active, m.state), line));
+            }
+        }
+        None => frame.push_str("ring: waiting for the first snapshot\r\n"),
+    }
+
+    frame.push_str(&rule);
+    frame.push_str("\r\n");
+
+    for line in &log.lines {
+        let line = line.chars().take(columns).collect::<String>();
+        frame.push_str(&line);
+        frame.push_str("\r\n");
+    }
+
+    let mut stderr = io::stderr().lock();
+    stderr.write_all(frame.as_bytes())?;
+    stderr.flush()?;
+    Ok(())
+}
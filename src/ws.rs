@@ -0,0 +1,243 @@
+//! A tiny WebSocket server streaming a running simulation's events, for a
+//! browser page to animate the ring, its elections and its failures as
+//! they happen.
+//!
+//! Every client that connects, at any path, is sent each event emitted
+//! from then on as a text message of its own, holding the JSON object
+//! [`JsonLines`] writes for it. Whatever clients send is ignored, and a
+//! client too slow to take an event in is dropped rather than let hold the
+//! ring up.
+
+use std::io::{self, BufRead, BufReader, Write};
+use std::net::TcpStream;
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::{bail, Error, Result};
+
+use crate::clock::SharedClock;
+use crate::event::{Event, JsonLines, SharedSink, Sink, Source};
+use crate::http;
+use crate::sync::Mutex;
+
+/// What a server appends to a client's key to accept its handshake.
+const HANDSHAKE_GUID: &str = "258EAFA5-E914-47DA-95CA-C5AB0DC85B11";
+
+/// How long sending an event to a client may take before it's dropped.
+const WRITE_TIMEOUT: Duration = Duration::from_millis(250);
+
+/// Emits every event to an inner sink, and to every client connected.
+#[derive(Debug)]
+pub struct Broadcast {
+    inner: SharedSink,
+    json: JsonLines,
+}
+
+impl Broadcast {
+    /// Serve the events emitted at `addr`, such as `127.0.0.1:9001`,
+    /// emitting them to `inner` as well.
+    pub fn serve(addr: &str, inner: SharedSink) -> Result<Self> {
+        let clients = Arc::new(Mutex::new(Vec::new()));
+        let accepted = clients.clone();
+
+        http::serve(addr, "ws", move |stream| {
+            let stream = handshake(stream)?;
+            accepted.lock().unwrap_or_else(|e| e.into_inner()).push(stream);
+            Ok(())
+        })?;
+
+        let frames = Frames { clients, line: Vec::new() };
+        Ok(Self { inner, json: JsonLines::new(Box::new(frames)) })
+    }
+}
+
+impl Sink for Broadcast {
+    fn emit(&self, source: Source, event: &Event) {
+        self.inner.emit(source, event);
+        self.json.emit(source, event);
+    }
+
+    fn holds_stdout(&self) -> bool {
+        self.inner.holds_stdout()
+    }
+
+    fn keep_time(&self, clock: &SharedClock) {
+        self.inner.keep_time(clock);
+    }
+}
+
+/// Sends every line written to it to every client, as a message of its own.
+struct Frames {
+    clients: Arc<Mutex<Vec<TcpStream>>>,
+    /// What was written of the line being sent.
+    line: Vec<u8>,
+}
+
+impl Frames {
+    fn send(&mut self) {
+        let len = self.line.len();
+
+        // A final text frame, its length in as few bytes as will hold it.
+        let mut frame = vec![0x81];
+
+        match len {
+            0..=125 => frame.push(len as u8),
+            126..=0xffff => {
+                frame.push(126);
+                frame.extend_from_slice(&(len as u16).to_be_bytes());
+            }
+            _ => {
+                frame.push(127);
+                frame.extend_from_slice(&(len as u64).to_be_bytes());
+            }
+        }
+
+        frame.append(&mut self.line);
+
+        let mut clients = self.clients.lock().unwrap_or_else(|e| e.into_inner());
+        clients.retain_mut(|client| client.write_all(&frame).is_ok());
+    }
+}
+
+impl Write for Frames {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        for byte in buf {
+            match byte {
+                b'\n' => self.send(),
+                byte => self.line.push(*byte),
+            }
+        }
+
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Take a client's opening handshake, and accept it.
+fn handshake(mut stream: TcpStream) -> Result<TcpStream> {
+    let mut reader = BufReader::new(&stream);
+    let mut key = None;
+
+    loop {
+        let mut line = String::new();
+
+        if reader.read_line(&mut line)? == 0 {
+            bail!("connection closed during the handshake");
+        }
+
+        match line.trim_end().split_once(':') {
+            Some((name, value)) if name.eq_ignore_ascii_case("sec-websocket-key") => {
+                key = Some(value.trim().to_string());
+            }
+            Some(_) => {}
+            None if line.trim_end().is_empty() => break,
+            None => {}
+        }
+    }
+
+    let key = match key {
+        Some(key) => key,
+        None => {
+            let body = "Expected a WebSocket handshake";
+            http::respond(&mut stream, "400 Bad Request", "text/plain", body)?;
+
+            return Err(Error::msg("not a WebSocket handshake"));
+        }
+    };
+
+    let accept = base64(&sha1(format!("{}{}", key, HANDSHAKE_GUID).as_bytes()));
+
+    write!(
+        stream,
+        "HTTP/1.1 101 Switching Protocols\r\nUpgrade: websocket\r\nConnection: Upgrade\r\n\
+        Sec-WebSocket-Accept: {}\r\n\r\n",
+        accept
+    )?;
+
+    stream.set_write_timeout(Some(WRITE_TIMEOUT))?;
+    Ok(stream)
+}
+
+/// The SHA-1 digest of `data`, which is all the handshake needs it for.
+fn sha1(data: &[u8]) -> [u8; 20] {
+    let mut h: [u32; 5] = [0x67452301, 0xefcdab89, 0x98badcfe, 0x10325476, 0xc3d2e1f0];
+
+    // Padded to a whole number of blocks, ending with its length in bits.
+    let mut msg = data.to_vec();
+    msg.push(0x80);
+
+    while msg.len() % 64 != 56 {
+        msg.push(0);
+    }
+
+    msg.extend_from_slice(&(data.len() as u64 * 8).to_be_bytes());
+
+    for block in msg.chunks(64) {
+        let mut w = [0u32; 80];
+
+        for (i, word) in block.chunks(4).enumerate() {
+            w[i] = u32::from_be_bytes([word[0], word[1], word[2], word[3]]);
+        }
+
+        for i in 16..80 {
+            w[i] = (w[i - 3] ^ w[i - 8] ^ w[i - 14] ^ w[i - 16]).rotate_left(1);
+        }
+
+        let [mut a, mut b, mut c, mut d, mut e] = h;
+
+        for (i, word) in w.iter().enumerate() {
+            let (f, k) = match i {
+                0..=19 => ((b & c) | (!b & d), 0x5a827999),
+                20..=39 => (b ^ c ^ d, 0x6ed9eba1),
+                40..=59 => ((b & c) | (b & d) | (c & d), 0x8f1bbcdc),
+                _ => (b ^ c ^ d, 0xca62c1d6),
+            };
+
+            let t = a.rotate_left(5)
+                .wrapping_add(f)
+                .wrapping_add(e)
+                .wrapping_add(k)
+                .wrapping_add(*word);
+
+            (e, d, c, b, a) = (d, c, b.rotate_left(30), a, t);
+        }
+
+        for (h, v) in h.iter_mut().zip([a, b, c, d, e]) {
+            *h = h.wrapping_add(v);
+        }
+    }
+
+    let mut digest = [0; 20];
+
+    for (i, h) in h.iter().enumerate() {
+        digest[i * 4..i * 4 + 4].copy_from_slice(&h.to_be_bytes());
+    }
+
+    digest
+}
+
+/// `data` in standard, padded base64.
+fn base64(data: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] =
+        b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+    let mut out = String::new();
+
+    for chunk in data.chunks(3) {
+        let bytes = [0, chunk[0], *chunk.get(1).unwrap_or(&0), *chunk.get(2).unwrap_or(&0)];
+        let n = u32::from_be_bytes(bytes);
+
+        // Every byte of the chunk takes up a digit and a bit more.
+        for i in 0..4 {
+            match i <= chunk.len() {
+                true => out.push(ALPHABET[(n >> (18 - 6 * i) & 63) as usize] as char),
+                false => out.push('='),
+            }
+        }
+    }
+
+    out
+}
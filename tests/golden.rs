@@ -0,0 +1,49 @@
+//! Every election algorithm following the same sequence, checked against
+//! the trace it was checked in with. Set `RING_BLESS` to write the traces
+//! over once a change to them is meant.
+
+// The ring runs on threads of its own, outside any loom model.
+#![cfg(not(loom))]
+
+use std::path::{Path, PathBuf};
+
+use ring::{Algorithm, Ring, SimConfig, SimSeq};
+
+/// Where the file `name` the tests read is kept.
+fn fixture(name: &str) -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("tests").join(name)
+}
+
+/// Check the trace of `algorithm` following the kill-coordinator sequence
+/// against the golden one named `name`.
+fn check(algorithm: Algorithm, name: &str) {
+    let seq = SimSeq::from_file(&fixture("kill-coordinator.txt"), 5).unwrap();
+    let mut config = SimConfig::default();
+    config.algorithm = algorithm;
+
+    let trace = Ring::builder().size(5).config(config).trace(&seq).unwrap();
+
+    if let Err(e) = trace.check(&fixture(&format!("golden/{}.golden", name))) {
+        panic!("{:#}", e);
+    }
+}
+
+#[test]
+fn ring() {
+    check(Algorithm::Ring, "ring");
+}
+
+#[test]
+fn bully() {
+    check(Algorithm::Bully, "bully");
+}
+
+#[test]
+fn chang_roberts() {
+    check(Algorithm::ChangRoberts, "chang-roberts");
+}
+
+#[test]
+fn hirschberg_sinclair() {
+    check(Algorithm::HirschbergSinclair, "hirschberg-sinclair");
+}